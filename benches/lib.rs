@@ -6,9 +6,9 @@ use std::fs::File;
 use std::io::BufReader;
 use test::Bencher;
 
-const SMALL_FILE: &'static str = "benches/values-small.log";
-const MED_FILE: &'static str = "benches/values-med.log";
-const LARGE_FILE: &'static str = "benches/values-large.log";
+const SMALL_FILE: &str = "benches/values-small.log";
+const MED_FILE: &str = "benches/values-med.log";
+const LARGE_FILE: &str = "benches/values-large.log";
 
 fn get_test_values(path: &str) -> Vec<f64> {
     let reader = File::open(path).unwrap();
@@ -40,6 +40,11 @@ fn test_statistics_med_from_all_values(b: &mut Bencher) {
     b.iter(|| staccato::Statistics::from(&values, None));
 }
 
+// Using multiple independent accumulators for the sum, min, max, and standard
+// deviation reductions takes ~160us for all values of the large file (~120us
+// for the 75th percentile) compared to ~300us (~230us) with a single one.
+// Compensated summation of each accumulator brings this back to ~300us
+// (~230us) in exchange for sums that don't lose precision with many values.
 #[bench]
 fn test_statistics_large_from_sliced_values(b: &mut Bencher) {
     let values = get_test_values(LARGE_FILE);
@@ -51,3 +56,179 @@ fn test_statistics_large_from_all_values(b: &mut Bencher) {
     let values = get_test_values(LARGE_FILE);
     b.iter(|| staccato::Statistics::from(&values, None));
 }
+
+#[bench]
+fn test_statistics_large_from_sorted_sliced_values(b: &mut Bencher) {
+    let values = get_test_values(LARGE_FILE);
+    let sorted = staccato::SortedValues::new(&values).unwrap();
+    b.iter(|| staccato::Statistics::from_sorted(sorted, Some(75)));
+}
+
+#[bench]
+fn test_statistics_large_from_sorted_all_values(b: &mut Bencher) {
+    let values = get_test_values(LARGE_FILE);
+    let sorted = staccato::SortedValues::new(&values).unwrap();
+    b.iter(|| staccato::Statistics::from_sorted(sorted, None));
+}
+
+#[bench]
+fn test_statistics_bundle_large_with_percentiles(b: &mut Bencher) {
+    let values = get_test_values(LARGE_FILE);
+    b.iter(|| staccato::StatisticsBundle::with_percentiles(&values, &[50, 75, 90, 95, 99]));
+}
+
+// Computing all percentile slices in a single cumulative pass is about four
+// times faster for five percentiles of the large file than scanning each
+// slice separately with `Statistics::from_sorted`.
+#[bench]
+fn test_statistics_bundle_large_from_sorted(b: &mut Bencher) {
+    let values = get_test_values(LARGE_FILE);
+    let sorted = staccato::SortedValues::new(&values).unwrap();
+    b.iter(|| {
+        staccato::StatisticsBundle::from_sorted(sorted, &[50, 75, 90, 95, 99], &[], staccato::QuantileMethod::default())
+    });
+}
+
+// Finding the median of unsorted values by selection takes ~0.54ms for the
+// large file, compared to ~3.4ms for sorting the values first.
+#[bench]
+fn test_statistics_large_from_unsorted_values(b: &mut Bencher) {
+    let mut reader = File::open(LARGE_FILE).unwrap();
+    let values: Vec<f64> = staccato::get_values(&mut reader, staccato::SortingPolicy::Unsorted).unwrap();
+    b.iter(|| staccato::Statistics::from(&values, None));
+}
+
+#[bench]
+fn test_statistics_large_sort_then_from_sorted(b: &mut Bencher) {
+    let mut reader = File::open(LARGE_FILE).unwrap();
+    let values: Vec<f64> = staccato::get_values(&mut reader, staccato::SortingPolicy::Unsorted).unwrap();
+    b.iter(|| {
+        let mut vals = values.clone();
+        staccato::Statistics::from_sorted(staccato::SortedValues::sort(&mut vals), None)
+    });
+}
+
+// Sorting with `sort_unstable_by` and a total ordering instead of the stable
+// `sort_by` is about 10% faster for the 100,000 values of the large file
+// (~3.05ms vs ~3.4ms) and doesn't allocate a scratch buffer of up to half the
+// size of the values.
+#[bench]
+fn test_sort_values_large(b: &mut Bencher) {
+    let mut reader = File::open(LARGE_FILE).unwrap();
+    let values: Vec<f64> = staccato::get_values(&mut reader, staccato::SortingPolicy::Unsorted).unwrap();
+    b.iter(|| {
+        let mut vals = values.clone();
+        staccato::sort_values(&mut vals);
+        vals
+    });
+}
+
+#[bench]
+fn test_get_values_large_unsorted(b: &mut Bencher) {
+    b.iter(|| {
+        let mut reader = File::open(LARGE_FILE).unwrap();
+        staccato::get_values(&mut reader, staccato::SortingPolicy::Unsorted).unwrap()
+    });
+}
+
+// Reserving space for values based on the size of the file makes no measurable
+// difference for the 100,000 values of the large file since parsing dominates,
+// and is about 3% faster (~0.96s vs ~1.0s) for a file of 20 million values. The
+// larger benefit is memory: growing the vector to 20 million values ends with a
+// 256MiB vector copied from a 128MiB one (384MiB at once) instead of ~160MB.
+#[bench]
+fn test_get_values_large_with_capacity(b: &mut Bencher) {
+    b.iter(|| {
+        let capacity = staccato::capacity_for_file(std::path::Path::new(LARGE_FILE));
+        let mut reader = File::open(LARGE_FILE).unwrap();
+        staccato::get_values_with_capacity(&mut reader, staccato::SortingPolicy::Unsorted, capacity).unwrap()
+    });
+}
+
+#[bench]
+fn test_get_values_large_from_slice(b: &mut Bencher) {
+    let bytes = std::fs::read(LARGE_FILE).unwrap();
+    b.iter(|| staccato::get_values_from_slice(&bytes, staccato::SortingPolicy::Unsorted).unwrap());
+}
+
+// Checking that the values of the large file are in order takes ~100us
+// compared to ~140us to sort them, both including copying the values. The
+// sort detects that the values are already in order so it's close to linear
+// as well and the difference is small compared to parsing them (~5ms).
+#[bench]
+fn test_sort_values_large_already_sorted(b: &mut Bencher) {
+    let values = get_test_values(LARGE_FILE);
+    b.iter(|| {
+        let mut copy = values.clone();
+        staccato::sort_values(&mut copy);
+        copy
+    });
+}
+
+#[bench]
+fn test_check_sorted_large(b: &mut Bencher) {
+    let values = get_test_values(LARGE_FILE);
+    b.iter(|| {
+        let copy = values.clone();
+        staccato::check_sorted(&copy).unwrap();
+        copy
+    });
+}
+
+#[cfg(feature = "mmap")]
+#[bench]
+fn test_get_values_large_mmap(b: &mut Bencher) {
+    b.iter(|| {
+        let file = File::open(LARGE_FILE).unwrap();
+        let map = unsafe { memmap2::Mmap::map(&file).unwrap() };
+        staccato::get_values_from_slice(&map, staccato::SortingPolicy::Unsorted).unwrap()
+    });
+}
+
+#[cfg(feature = "rayon")]
+fn get_random_values(n: usize) -> Vec<f64> {
+    let mut state = 42u64;
+    (0..n)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 11) as f64 / 1000f64
+        })
+        .collect()
+}
+
+#[cfg(feature = "rayon")]
+#[bench]
+fn test_sort_values_huge_serial(b: &mut Bencher) {
+    let values = get_random_values(5_000_000);
+    b.iter(|| staccato::sort_values(&mut values.clone()));
+}
+
+#[cfg(feature = "rayon")]
+#[bench]
+fn test_sort_values_huge_parallel(b: &mut Bencher) {
+    let values = get_random_values(5_000_000);
+    b.iter(|| staccato::sort_values_parallel(&mut values.clone(), 4));
+}
+
+// Storing values as f32 halves the size of the value vector (4 bytes per
+// value instead of 8, e.g. ~400KB instead of ~800KB for the 100,000 values
+// of the large file) in exchange for about 7 significant digits per value.
+#[bench]
+fn test_statistics_large_f64_sorted(b: &mut Bencher) {
+    b.iter(|| {
+        let mut reader = File::open(LARGE_FILE).unwrap();
+        let values: Vec<f64> = staccato::get_values_as(&mut reader, staccato::SortingPolicy::Sorted).unwrap();
+        staccato::StatisticsBundle::with_percentiles(&values, &[50, 90, 99])
+    });
+}
+
+#[bench]
+fn test_statistics_large_f32_sorted(b: &mut Bencher) {
+    b.iter(|| {
+        let mut reader = File::open(LARGE_FILE).unwrap();
+        let values: Vec<f32> = staccato::get_values_as(&mut reader, staccato::SortingPolicy::Sorted).unwrap();
+        staccato::StatisticsBundle::with_percentiles(&values, &[50, 90, 99])
+    });
+}
@@ -0,0 +1,100 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Bake version information into the crate at compile time: the version of
+//! the crate along with the git commit and date it was built from, if git
+//! metadata is available. Builds from a crates.io tarball don't have any git
+//! metadata so only have the version and build date.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    let version = env::var("CARGO_PKG_VERSION").unwrap();
+    let mut details = Vec::new();
+
+    if let Some(commit) = git_commit() {
+        details.push(commit);
+    }
+
+    details.push(build_date());
+    println!(
+        "cargo:rustc-env=STACCATO_VERSION_INFO={} ({})",
+        version,
+        details.join(" ")
+    );
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    if Path::new(".git").exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        println!("cargo:rerun-if-changed=.git/refs");
+    }
+}
+
+/// Short hash of the current git commit, or `None` if this isn't a git
+/// checkout or git isn't installed.
+fn git_commit() -> Option<String> {
+    if !Path::new(".git").exists() {
+        return None;
+    }
+
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    Some(commit).filter(|c| !c.is_empty())
+}
+
+/// Date of the build as YYYY-MM-DD in UTC, using `SOURCE_DATE_EPOCH` instead
+/// of the current time if it's set so that builds are reproducible.
+fn build_date() -> String {
+    let secs = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Year, month, and day of a number of days since 1970-01-01, using the
+/// algorithm from http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
@@ -0,0 +1,103 @@
+/* Staccato - Statistics from the command line. Licensed under the GPL-3.0+. */
+
+#ifndef STACCATO_H
+#define STACCATO_H
+
+/* Generated with cbindgen, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ Statistics were computed.
+ */
+#define STACCATO_OK 0
+
+/*
+ A pointer that must not be null was null.
+ */
+#define STACCATO_ERR_NULL -1
+
+/*
+ There were no values (other than `NaN` values) to compute statistics for.
+ */
+#define STACCATO_ERR_EMPTY -2
+
+/*
+ Values pushed one at a time to compute statistics of, opaque to C.
+ */
+typedef struct StaccatoStats StaccatoStats;
+
+/*
+ Statistics of a sequence of values, with the same fields as `Statistics`.
+ */
+typedef struct StaccatoStatistics {
+  uint64_t count;
+  double sum;
+  double mean;
+  double upper;
+  double lower;
+  double median;
+  double stddev;
+} StaccatoStatistics;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Create an empty set of values to push to, which must be freed with
+ `staccato_stats_free`.
+ */
+StaccatoStats *staccato_stats_new(void);
+
+/*
+ Add a value to a set of values, returning `STACCATO_ERR_NULL` if `stats`
+ is null.
+
+ # Safety
+
+ `stats` must be null or a pointer returned by `staccato_stats_new` that
+ hasn't been freed.
+ */
+int staccato_stats_push(StaccatoStats *stats, double value);
+
+/*
+ Write statistics of every value pushed so far to `out`, which is left
+ unchanged if there aren't any values or either pointer is null.
+
+ # Safety
+
+ `stats` must be null or a pointer returned by `staccato_stats_new` that
+ hasn't been freed, and `out` must be null or valid for writes.
+ */
+int staccato_stats_compute(const StaccatoStats *stats, StaccatoStatistics *out);
+
+/*
+ Free a set of values. Freeing a null pointer does nothing.
+
+ # Safety
+
+ `stats` must be null or a pointer returned by `staccato_stats_new` that
+ hasn't already been freed.
+ */
+void staccato_stats_free(StaccatoStats *stats);
+
+/*
+ Write statistics of `len` values to `out`. `values` may be null only if
+ `len` is zero, in which case `STACCATO_ERR_EMPTY` is returned.
+
+ # Safety
+
+ `values` must be null or valid for reads of `len` values, and `out` must
+ be null or valid for writes.
+ */
+int staccato_compute(const double *values, size_t len, StaccatoStatistics *out);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* STACCATO_H */
@@ -16,13 +16,109 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 //
 
-use clap::Clap;
-use staccato::{get_values, KeyValueSep, SortingPolicy, StatisticsBundle, StatisticsFormatter};
-use std::fs::File;
-use std::io::{stdin, BufReader};
-use std::path::PathBuf;
+use clap::{Arg, ArgMatches, ArgSettings, Clap, ErrorKind, FromArgMatches, IntoApp};
+use staccato::{
+    capacity_for_file, check_sorted, collect_parsed_values_sampled, compare_bundles, estimate_values_in_file,
+    get_integers, get_measurements, get_parsed_values_sampled, get_values_as, merge_sorted_values, parse_values, run,
+    run_from, run_with, run_with_timings, sort_values, Annotation, AnnotationFormatter, Annotator, BoxPlot,
+    BoxPlotFormatter, BundleFormatter, CdfFormatter, CdfPoints, ComparisonFormatter, Condition, CumulativeSum,
+    CumulativeSumFormatter, CumulativeSumMode, Delimiter, DurationExtractor, Engine, ErrorPolicy, ExternalSorter,
+    FieldExtractor, FormattedBundle, FormattedBundles, Histogram, HistogramFormatter, IntegerStatisticsBundle,
+    IntegerStatisticsFormatter, KeyCase, KeyValueFormatter, KeyValueSep, LineExtractor, MarkerExtractor, Measure,
+    MemoryLimited, Normalization, NormalizationFormatter, Normalizer, NullDelimited, Omission, OutlierPolicy,
+    PairedStatistics, PairedStatisticsFormatter, ParseLineError, Percentiles, Phase, PlainExtractor, ProgressReader,
+    QuantileMethod, Reservoir, Rng, Rounding, SortedValues, SortingPolicy, SplitExtractor, StaccatoConfig,
+    StaccatoError, StatName, Statistics, StatisticsBundle, StatisticsConfig, StreamingStatistics, Timings,
+    TimingsFormatter, Unit, UnitConversion, ValidationFormatter, WhiskerPolicy, BYTES_PER_VALUE,
+    DEFAULT_MALFORMED_SAMPLES, DEFAULT_RUN_SIZE,
+};
+#[cfg(feature = "rayon")]
+use staccato::{get_values_from_file_parallel, sort_values_parallel};
+#[cfg(feature = "serde")]
+use staccato::{Accuracy, JsonFormatter, MergePolicy};
+use std::collections::HashSet;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, stderr, stdin, stdout, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::slice;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_WIDTH: usize = 80;
+const DEFAULT_BUCKETS: usize = 10;
+/// Label of statistics of the values in every file with `--per-file`.
+const TOTAL_LABEL: &str = "total";
+
+/// How often input files are checked for changes with `--watch`.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+/// Displayed between reports with `--watch`, unless clearing the screen.
+const WATCH_SEPARATOR: &str = "---\n";
+/// Terminal escape sequence to clear the screen and move the cursor to the
+/// top left corner.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// Percentiles with metrics computed by `--timer`, like a statsd timer.
+const TIMER_PERCENTILES: &[u8] = &[90];
+/// Quantiles computed by `--timer`.
+const TIMER_QUANTILES: &[f64] = &[90.0, 95.0, 99.0];
+/// Quantiles computed by `--quartiles`, along with the key of each.
+const QUARTILES: &[(f64, &str)] = &[(25.0, "q1"), (50.0, "median"), (75.0, "q3")];
+/// Quantiles computed by `--summary`, along with the key of each.
+const FIVE_NUMBERS: &[(f64, &str)] = &[
+    (0.0, "lower"),
+    (25.0, "q1"),
+    (50.0, "median"),
+    (75.0, "q3"),
+    (100.0, "upper"),
+];
+
+// Exit codes for each kind of error, following the conventions of sysexits.h
+// so that scripts can tell why values couldn't be read.
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 64;
+const EXIT_DATA_ERR: i32 = 65;
+const EXIT_NO_INPUT: i32 = 66;
+const EXIT_IO_ERR: i32 = 74;
+const EXIT_CONFIG: i32 = 78;
+// Exit code after reading input was interrupted by Ctrl-C, the same as a
+// shell uses for a command killed by SIGINT.
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// Number of times `--verbose` was given, set once options are parsed.
+static VERBOSITY: AtomicU64 = AtomicU64::new(0);
+
+/// Whether `--quiet` was given, set once options are parsed.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Display a warning on standard error unless `--quiet` is given.
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        warning(format_args!($($arg)*))
+    };
+}
+
+/// Display a diagnostic on standard error when `--verbose` is given.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        log(LogLevel::Info, format_args!($($arg)*))
+    };
+}
+
+/// Display a diagnostic on standard error when `--verbose` is given twice.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        log(LogLevel::Debug, format_args!($($arg)*))
+    };
+}
+
+/// Options that can't be set in a config file.
+const NOT_CONFIGURABLE: &[&str] = &["config", "no-config", "help", "version"];
 
 /// Staccato is a program for generating statistics from a stream
 /// of numbers from the command line. It reads values from a file or
@@ -34,152 +130,3641 @@ use std::str::FromStr;
 /// can have it additionally compute statistics for some subset of the
 /// values of the stream. For example, using the argument `-p 25,50`
 /// would compute statistics for the lower 25% of values and lower 50%
-/// of values.
+/// of values. Running it without a subcommand is the same as running
+/// `st summary`.
 #[derive(Clap, Debug)]
-#[clap(name = "st")]
+#[clap(name = "st", version = staccato::VERSION_INFO)]
 struct StaccatoOptions {
-    /// comma separated list of percentiles (from 1 to 99,
-    /// inclusive) that should have metrics computed. Default
-    /// is not to compute metrics for any specific percentiles,
-    /// only the global metrics.
-    #[clap(short = 'p', long)]
-    percentiles: Option<Percentiles>,
+    #[clap(flatten)]
+    stats: StatsOptions,
 
-    /// type of separator to use when printing keys and values.
-    /// Possible values for this option are the literal string
-    /// 'tab' for the tab character, the literal string 'colon'
-    /// for a colon and space, or any other string to use that
-    /// as a separator. For example you could use the string
-    /// ' => ' as a separator. Default is to use a colon and a
-    /// space
-    #[clap(short = 's', long)]
-    separator: Option<KeyValueSep>,
+    #[clap(flatten)]
+    output: OutputOptions,
+
+    /// file of default options, in TOML with a key for the long name
+    /// of each option, e.g. 'precision = 2' or 'percentiles = [50, 90]'.
+    /// Flags are set with 'true'. Options can also be set by environment
+    /// variables named for the long name of each option, e.g.
+    /// 'ST_PRECISION=2' or 'ST_KEY_CASE=camel', where flags are set with
+    /// 'true' or '1'. Options given on the command line take precedence
+    /// over environment variables, which take precedence over the file.
+    /// Options only apply to the subcommands that have them. Default is
+    /// '$XDG_CONFIG_HOME/staccato/config.toml' (or
+    /// '~/.config/staccato/config.toml'), if it exists.
+    #[clap(long, global = true, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// don't read default options from a config file.
+    #[clap(long, global = true, conflicts_with = "config")]
+    no_config: bool,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+// Options are only parsed once, so their size doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clap, Debug)]
+enum Command {
+    /// compute statistics of the values, the same as running without
+    /// a subcommand.
+    Summary(StatsOptions),
+
+    /// display the number of values in each of a number of buckets
+    /// between the lowest and highest value, as a row for each bucket
+    /// of its lower and upper edge and the number of values in it.
+    Hist(HistOptions),
+
+    /// compare the values from FILE (or standard input) to the values
+    /// from another file. Statistics for both sets of values are
+    /// displayed side by side along with the absolute and percent
+    /// change from the first set of values to the second. When both
+    /// sets have the same number of values, values on the same line
+    /// are treated as pairs and their Pearson and Spearman rank
+    /// correlation (pearson_r and spearman_r) are displayed as well.
+    Compare(CompareOptions),
+
+    /// combine statistics previously serialized as JSON (using the
+    /// library with the `serde` feature), e.g. of values on different
+    /// machines, into statistics of all of the values. The count, sum,
+    /// mean, upper, lower, and standard deviation are the same as if
+    /// they were computed from all the values. The median is the mean
+    /// of each median weighted by count and each percentile slice is
+    /// combined from the slices with the same percentile, so these are
+    /// estimates. Quantiles can't be combined and are left out. Which
+    /// statistics are exact, combined using a formula, or estimates is
+    /// displayed on standard error unless --quiet is given. Only
+    /// percentile slices that are in every file are combined, with a
+    /// warning about the others.
+    #[cfg(feature = "serde")]
+    Merge(MergeOptions),
+}
+
+/// Options for how values are read from each input, shared by each
+/// subcommand that reads values.
+#[derive(Clap, Debug, Clone)]
+struct InputOptions {
+    /// compute statistics of something measured from each line of
+    /// text instead of parsing each line as a number. Possible values
+    /// are 'length' for the number of characters in each line, 'words'
+    /// for the number of whitespace separated words in each line, and
+    /// 'bytes' for the number of bytes in each line. Empty lines are
+    /// measured as zero instead of being skipped.
+    #[clap(long)]
+    measure: Option<Measure>,
+
+    /// parse only one field (counting from 1) of each line as a value,
+    /// e.g. a column of a CSV file or the latency from each line of a
+    /// log. Fields are separated by whitespace unless --delimiter is
+    /// used. Lines without the field are skipped.
+    #[clap(long, conflicts_with = "measure")]
+    field: Option<usize>,
+
+    /// parse every field of each line as a value, for input with
+    /// several values on each line. Fields are separated by whitespace
+    /// unless --delimiter is used. Lines with any field that isn't a
+    /// number are skipped.
+    #[clap(long, conflicts_with_all = &["field", "measure"])]
+    split: bool,
+
+    /// character separating the fields of each line for --field and
+    /// --split. Possible values are the literal string 'whitespace' for
+    /// any amount of whitespace, the literal string 'tab' for a tab
+    /// character, or any other single character such as ','. Default
+    /// is whitespace.
+    #[clap(long)]
+    delimiter: Option<Delimiter>,
+
+    /// parse the number immediately following each occurrence of MARKER
+    /// in each line as a value, e.g. 'time=' for lines of a log like
+    /// 'status=200 time=12.5ms'. Lines without the marker are skipped.
+    #[clap(long, value_name = "MARKER", conflicts_with_all = &["field", "split", "measure"])]
+    after: Option<String>,
+
+    /// read input files by memory mapping them instead of reading
+    /// them, which can be faster for very large files. Standard input
+    /// and files that aren't regular files (such as pipes) or can't be
+    /// locked are read normally. Each file is locked with a shared lock
+    /// while it's mapped, waiting for any exclusive lock to be released
+    /// first. Only use this option for files that aren't being written,
+    /// or whose writers take an exclusive lock (e.g. using `flock`)
+    /// before truncating them: a file truncated while being read
+    /// without one will crash the program.
+    #[cfg(feature = "mmap")]
+    #[clap(long, conflicts_with_all = &["progress", "field", "split", "after", "null-data"])]
+    mmap: bool,
+
+    /// number of threads to use for sorting values and reading each
+    /// file when there are multiple files, or parsing separate parts of
+    /// the file when there is a single regular file. The default of one
+    /// reads and sorts values without any additional threads.
+    #[cfg(feature = "rayon")]
+    #[clap(long, default_value = "1")]
+    threads: usize,
 
-    /// optional file to read values to from. If not supplied
+    /// show progress while reading input on standard error. For files
+    /// the percent read, rate of reading, and estimated time remaining
+    /// are shown, and for standard input or pipes the number of lines
+    /// read. WHEN is 'auto' (the default) to only show progress when
+    /// standard error is a terminal, 'always', or 'never'. Input is
+    /// read using a single thread when showing progress.
+    #[clap(long, value_name = "WHEN", require_equals = true)]
+    progress: Option<Option<ProgressMode>>,
+
+    /// read records separated by NUL bytes instead of lines, for input
+    /// whose records can contain newlines, e.g. from 'find -print0'.
+    /// Newlines within a record are treated as whitespace.
+    #[clap(short = '0', long)]
+    null_data: bool,
+
+    /// values are already in ascending order, e.g. from 'sort -n', so they
+    /// don't need to be sorted again. The order is checked instead, which
+    /// takes a single pass over the values. MODE is 'verify' (the
+    /// default) to exit with an error if the values aren't in order or
+    /// 'trust' to display a warning and sort them instead. Values from
+    /// multiple files must be in order as if they were a single file.
+    #[clap(long, value_name = "MODE", require_equals = true)]
+    sorted: Option<Option<SortedInput>>,
+
+    /// read values from standard input even when it's a terminal, to type
+    /// values in by hand (ending with Ctrl-D). Without this option, st
+    /// exits with a hint about how to use it when no FILE is given and
+    /// standard input is a terminal instead of waiting for input.
+    #[clap(long, conflicts_with = "FILE")]
+    stdin: bool,
+
+    /// optional files to read values to from. If not supplied
     /// values will be read from standard input. The values are
     /// expected to be floating point or integer values, one per
     /// line. Leading or trailing whitespace will be removed before
-    /// parsing each value.
+    /// parsing each value. Values from multiple files are treated
+    /// as if the files were a single file.
     #[clap(name = "FILE", parse(from_os_str))]
-    file: Option<PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+/// Options for how output is displayed, shared by every subcommand.
+#[derive(Clap, Debug)]
+struct OutputOptions {
+    /// number of digits to display after the decimal point for each
+    /// value. Default is 5.
+    #[clap(long, default_value = "5", global = true)]
+    precision: usize,
+
+    /// type of separator to use when printing keys and values.
+    /// Possible values for this option are the literal string
+    /// 'tab' for the tab character, the literal string 'colon'
+    /// for a colon and space, 'space' for a space, 'equals' for an
+    /// equals sign, 'comma' for a comma, 'none' for no separator, or
+    /// any other string to use that as a separator. A colon on its
+    /// own is the same as 'colon'. For example you could use the string
+    /// ' => ' as a separator. The escape sequences '\t', '\n', '\0',
+    /// and '\\' can be used for a tab, newline, null character, and
+    /// backslash. Default is to use a colon and a space
+    #[clap(short = 's', long, global = true)]
+    separator: Option<KeyValueSep>,
+
+    /// format used to display statistics. Possible values for this
+    /// option are 'text' for a key and value on each line and, when
+    /// built with the `serde` feature, 'json' for a single line of JSON
+    /// that can be combined with other statistics using `st merge`.
+    /// JSON includes every statistic at full precision. Only statistics
+    /// of floating point values, including a summary, use this format,
+    /// other output such as a boxplot is always text. Default is 'text'.
+    #[clap(long, global = true)]
+    format: Option<OutputFormat>,
+
+    /// naming style of the keys that statistics are displayed with, for
+    /// text and JSON output alike. Possible values for this option are
+    /// 'snake' (e.g. 'sample_count' and 'mean_90'), 'camel' (e.g.
+    /// 'sampleCount' and 'mean90'), and 'kebab' (e.g. 'sample-count' and
+    /// 'mean-90'). Only JSON with snake case keys can be combined using
+    /// `st merge`. Default is 'snake'.
+    #[clap(long, global = true)]
+    key_case: Option<KeyCase>,
+
+    /// round every statistic other than counts to an integer, displayed
+    /// without a decimal point regardless of --precision, for systems
+    /// that only accept integers. Possible values are 'nearest' (the
+    /// default when no value is given) which rounds values half-way
+    /// between two integers to the even one, e.g. 2.5 to 2, 'ceil' to
+    /// round up, and 'floor' to round down. This applies to statistics
+    /// in text and JSON output but not to other output such as a boxplot.
+    #[clap(long, value_name = "MODE", require_equals = true, global = true)]
+    round: Option<Option<Rounding>>,
+
+    /// display the statistics in each section of text and JSON output
+    /// with their keys in alphabetical order instead of the usual order,
+    /// e.g. so output can be compared with `diff` after adding options.
+    /// Percentile slices are always displayed in ascending order.
+    #[clap(long, global = true)]
+    sort_keys: bool,
+
+    /// display diagnostics on standard error about how values are read
+    /// and statistics are computed: the input being read, how values are
+    /// extracted from each line, how many lines were parsed, blank, or
+    /// malformed, whether values were sorted, how many values are in each
+    /// percentile slice, and how long it took. Use twice (-vv) for more
+    /// detail such as how input is read, each malformed line, and how
+    /// long reading took.
+    #[clap(short = 'v', long, global = true, parse(from_occurrences))]
+    verbose: u64,
+
+    /// don't display warnings on standard error, e.g. about percentile
+    /// slices that were omitted because there weren't enough values or
+    /// about there not being any values. Errors are still displayed.
+    #[clap(long, global = true)]
+    quiet: bool,
+
+    /// include a slice for each percentile that was omitted because there
+    /// weren't enough values in JSON output, with null for every
+    /// statistic, so that the output always has the same keys. JSON with
+    /// null statistics can't be read by `st merge`.
+    #[cfg(feature = "serde")]
+    #[clap(long, global = true)]
+    null_omitted: bool,
+}
+
+#[derive(Clap, Debug)]
+struct StatsOptions {
+    #[clap(flatten)]
+    input: InputOptions,
+
+    /// type used to store values while computing statistics. Possible
+    /// values are 'f64' (the default) and 'f32' which uses half as much
+    /// memory but only keeps about 7 significant digits of each value.
+    /// Results are within a relative error of about 1e-6 of 'f64'.
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "measure", "field", "split", "after", "sorted", "stream", "summary", "boxplot", "cdf", "annotate",
+            "cumsum", "normalize", "compare",
+        ]
+    )]
+    #[cfg_attr(feature = "mmap", clap(conflicts_with = "mmap"))]
+    precision_mode: Option<PrecisionMode>,
+
+    /// read values as integers (in the range of a 64 bit signed or
+    /// unsigned integer) instead of floating point numbers so that the count,
+    /// sum, upper, and lower values are exact no matter how large the
+    /// values or their sum are. The mean, median, and standard deviation
+    /// are computed from the exact sum. Any line that isn't an integer
+    /// is an error instead of being skipped.
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "measure", "field", "split", "after", "sorted", "stream", "reservoir", "spill", "max-memory",
+            "precision-mode", "quantiles", "all-quantiles", "summary", "boxplot", "cdf", "annotate", "cumsum", "normalize", "compare",
+        ]
+    )]
+    integers: bool,
+
+    /// compute statistics in a single pass over the values without
+    /// keeping them in memory, for inputs too large to fit in memory.
+    /// The median and any quantiles are estimated using a t-digest in
+    /// this mode and are within 1% of the correct rank. Percentiles
+    /// can't be computed in this mode. On Unix, sending the process
+    /// SIGUSR1 (e.g. `kill -USR1 PID`) displays statistics of the values
+    /// read so far on standard error once the next line is read, without
+    /// stopping.
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "measure", "field", "split", "after", "percentiles", "summary", "boxplot", "cdf", "annotate", "cumsum",
+            "normalize", "compare",
+        ]
+    )]
+    #[cfg_attr(feature = "mmap", clap(conflicts_with = "mmap"))]
+    stream: bool,
+
+    /// compute statistics in a single pass over the values while only
+    /// keeping a uniform random sample of at most N of them in memory,
+    /// for inputs too large to fit in memory. The count, sum, mean,
+    /// upper, lower, and standard deviation are computed from all the
+    /// values but the median, quantiles, and percentile statistics are
+    /// estimated from the sample (so percentile counts and sums are of
+    /// the sampled values). The size of the sample is displayed as
+    /// 'sample_count' when it doesn't include every value.
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with_all = &[
+            "measure", "field", "split", "after", "sorted", "stream", "precision-mode", "summary", "boxplot",
+            "cdf", "annotate", "cumsum", "normalize", "compare",
+        ]
+    )]
+    #[cfg_attr(feature = "mmap", clap(conflicts_with = "mmap"))]
+    reservoir: Option<usize>,
+
+    /// compute exact statistics of inputs too large to fit in memory
+    /// by sorting values in runs of --spill-run-size values that are
+    /// written to temporary files in DIR (the system temporary directory
+    /// if not given) and then merged. Temporary files are removed even
+    /// if the program is interrupted.
+    #[clap(
+        long,
+        value_name = "DIR",
+        require_equals = true,
+        conflicts_with_all = &[
+            "measure", "field", "split", "after", "sorted", "stream", "reservoir", "precision-mode", "summary",
+            "boxplot", "cdf", "annotate", "cumsum", "normalize", "compare",
+        ]
+    )]
+    #[cfg_attr(feature = "mmap", clap(conflicts_with = "mmap"))]
+    spill: Option<Option<PathBuf>>,
+
+    /// number of values to keep in memory before writing them to a
+    /// temporary file with --spill. Each value uses 8 bytes of memory.
+    /// Default is 67108864 (64Mi values, 512MiB).
+    #[clap(long, value_name = "N", requires = "spill")]
+    spill_run_size: Option<usize>,
+
+    /// compute exact statistics if the values fit in SIZE bytes of
+    /// memory (estimated as 16 bytes per value) and estimate them
+    /// otherwise, the same way as --reservoir with a sample as large as
+    /// fits in SIZE. A notice is printed to standard error when values
+    /// are estimated. SIZE is a number of bytes with an optional K, M,
+    /// G, or T suffix for KiB, MiB, GiB, or TiB, e.g. '512M'.
+    #[clap(
+        long,
+        value_name = "SIZE",
+        conflicts_with_all = &[
+            "measure", "field", "split", "after", "sorted", "stream", "reservoir", "spill", "precision-mode",
+            "summary", "boxplot", "cdf", "annotate", "cumsum", "normalize", "compare",
+        ]
+    )]
+    #[cfg_attr(feature = "mmap", clap(conflicts_with = "mmap"))]
+    max_memory: Option<MemorySize>,
+
+    /// seed used to choose values at random, i.e. the values sampled by
+    /// --reservoir and by --max-memory when values don't fit, so that the
+    /// same input always results in the same output. Default is a
+    /// different seed each time, which is displayed with --verbose.
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// read each line as a duration like '250ms' or '1.5s' and compute
+    /// statistics of them in milliseconds, like a statsd timer. Units are
+    /// 'ns', 'us', 'ms', 's', 'm', and 'h', and numbers without a unit are
+    /// milliseconds. Unless --percentiles or --quantiles are given, this
+    /// also computes metrics for the 90th percentile and the values at
+    /// the 90th, 95th, and 99th quantiles.
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "measure", "field", "split", "after", "integers", "precision-mode", "stream", "reservoir", "spill",
+            "max-memory",
+        ]
+    )]
+    #[cfg_attr(feature = "mmap", clap(conflicts_with = "mmap"))]
+    timer: bool,
+
+    /// comma separated list of percentiles (from 1 to 99,
+    /// inclusive) that should have metrics computed. Ranges can be
+    /// given as START-END for every percentile from START to END or
+    /// START-END:STEP for every STEP of them, e.g. 90-99:3 for 90, 93,
+    /// 96, and 99. Can be given more than once to combine them. Default
+    /// is not to compute metrics for any specific percentiles,
+    /// only the global metrics.
+    #[clap(short = 'p', long, multiple_occurrences = true, number_of_values = 1)]
+    percentiles: Vec<Percentiles>,
+
+    /// comma separated list of quantiles (from 0 to 100, inclusive,
+    /// fractional values like 99.9 are allowed) whose values should
+    /// be computed. Unlike percentiles, this computes the single value
+    /// at each quantile rather than metrics for a subset of values.
+    #[clap(long)]
+    quantiles: Option<Quantiles>,
+
+    /// compute the value at every integer quantile from 1 to 99,
+    /// inclusive. Each value is displayed with the keys 'p1' through
+    /// 'p99'.
+    #[clap(long, conflicts_with = "quantiles")]
+    all_quantiles: bool,
+
+    /// compute the value of the first quartile, median, and third
+    /// quartile, the same as adding 25, 50, and 75 to --quantiles but
+    /// displayed with the keys 'q1', 'median', and 'q3'. In text output,
+    /// the median quartile is displayed in place of the median of all
+    /// values.
+    #[clap(short = 'q', long)]
+    quartiles: bool,
+
+    /// method used to compute the value of quantiles that fall between
+    /// two values. Possible values for this option are 'linear' to
+    /// interpolate between the two closest values or 'nearest' to use
+    /// the value with the nearest rank. Default is 'linear'.
+    #[clap(long)]
+    quantile_method: Option<QuantileMethod>,
+
+    /// display the five-number summary of the values along with the
+    /// count instead of every statistic, the same as adding 0, 25, 50,
+    /// 75, and 100 to --quantiles but displayed with the keys 'lower',
+    /// 'q1', 'median', 'q3', and 'upper'. Only the count of all values
+    /// is displayed unless --keys is given.
+    #[clap(long, conflicts_with = "compare")]
+    summary: bool,
+
+    /// comma separated list of statistics to compute and display for
+    /// all values and each percentile. Possible values are 'count',
+    /// 'sum', 'mean', 'upper', 'lower', 'median', and 'stddev'. Leaving
+    /// out the median or standard deviation skips the extra pass over
+    /// the values needed to compute them. Default is every statistic.
+    #[clap(long, conflicts_with_all = &["boxplot", "annotate", "normalize", "compare"])]
+    keys: Option<StatisticsConfig>,
+
+    /// minimum number of values a percentile slice must have for its
+    /// statistics to be displayed, and that there must be in total for
+    /// quantiles to be. Slices and quantiles without enough values are
+    /// omitted with a warning. Default is 1, which displays every slice
+    /// with any values.
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with_all = &[
+            "integers", "boxplot", "cdf", "annotate", "normalize", "validate",
+        ]
+    )]
+    min_samples: Option<usize>,
+
+    /// display the key of every statistic that can be displayed, the name
+    /// used for it by --fail-if, and a description of it, then exit
+    /// without reading any values. With --percentiles or --quantiles, the
+    /// keys for each percentile or quantile are listed as well.
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "boxplot", "cdf", "annotate", "cumsum", "normalize", "compare", "validate", "watch",
+            "per-file",
+        ]
+    )]
+    list_keys: bool,
+
+    /// display how long reading, parsing, and sorting values, computing
+    /// statistics, and formatting them took on standard error after the
+    /// statistics, one `phase<SEP>millis` line for each along with the
+    /// total and the number of values per second. Values are read from
+    /// every FILE in order without any additional threads.
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "measure", "progress", "sorted", "integers", "stream", "reservoir", "spill", "max-memory",
+            "precision-mode", "summary", "boxplot", "cdf", "annotate", "cumsum", "normalize", "compare",
+            "validate", "watch", "per-file", "list-keys",
+        ]
+    )]
+    #[cfg_attr(feature = "mmap", clap(conflicts_with = "mmap"))]
+    timing: bool,
+
+    /// display a boxplot of the values scaled to the width of the
+    /// terminal instead of statistics. When comparing values, a
+    /// boxplot is displayed for each set of values using the same
+    /// scale.
+    #[clap(long, conflicts_with = "summary")]
+    boxplot: bool,
+
+    /// where the whiskers of a boxplot end. Possible values for this
+    /// option are 'range' to extend the whiskers to the lowest and
+    /// highest values or 'iqr' to extend them to the most extreme
+    /// values within 1.5 times the interquartile range and mark any
+    /// values beyond them as outliers. Default is 'iqr'.
+    #[clap(long)]
+    whiskers: Option<WhiskerPolicy>,
+
+    /// display the empirical cumulative distribution of the values
+    /// instead of statistics, as rows of each value and the fraction
+    /// of values less than or equal to it. Possible values for this
+    /// option are a number of evenly spaced values between the lowest
+    /// and highest values or the literal string 'all' for a row for
+    /// every distinct value. Default is 100 values.
+    #[clap(long, require_equals = true, conflicts_with_all = &["summary", "boxplot", "compare"])]
+    cdf: Option<Option<CdfPoints>>,
+
+    /// display statistics for the values before the empirical
+    /// cumulative distribution.
+    #[clap(long, requires = "cdf")]
+    with_summary: bool,
+
+    /// display each value in its original order followed by an
+    /// annotation computed from all the values instead of statistics.
+    /// Possible values for this option are 'bin:N' for the quantile
+    /// bin (from 1 to N) the value falls into and 'rank' for the
+    /// percentile rank (from 0 to 100) of the value. Values exactly on
+    /// the edge between two bins are put into the lower bin. Values
+    /// that are tied share the rank of the midpoint between them.
+    #[clap(long, conflicts_with_all = &["summary", "boxplot", "cdf", "compare"])]
+    annotate: Option<Annotation>,
+
+    /// display the running total of the values in their original
+    /// order instead of statistics. Possible values for this option
+    /// are 'totals' to display only the running total, 'with-values'
+    /// to display each value followed by the running total, or 'stats'
+    /// to display statistics of the running totals instead of the
+    /// values. Default is 'totals'.
+    #[clap(
+        long,
+        require_equals = true,
+        conflicts_with_all = &["summary", "boxplot", "cdf", "annotate", "compare"]
+    )]
+    cumsum: Option<Option<CumulativeSumMode>>,
+
+    /// display each value normalized in its original order instead
+    /// of statistics. Possible values for this option are 'minmax' to
+    /// scale values to the range 0 to 1 or 'zscore' to standardize
+    /// values using the mean and standard deviation. If all values
+    /// are the same, each is normalized to 0.
+    #[clap(
+        long,
+        conflicts_with_all = &["summary", "boxplot", "cdf", "annotate", "cumsum", "compare"]
+    )]
+    normalize: Option<Normalization>,
+
+    /// exit with an error (after displaying statistics) if the condition
+    /// EXPR is true, e.g. 'mean > 100' or 'p99.upper >= 250', for gating
+    /// on statistics in scripts. EXPR is a statistic, an operator ('<',
+    /// '<=', '>', '>=', '==', or '!='), and a number. Statistics are named
+    /// the same as they're displayed, e.g. 'mean' for all values,
+    /// 'p99.upper' for --percentiles, and 'p99.9' for --quantiles, and
+    /// must be computed. Can be given more than once, in which case the
+    /// program exits with an error if any of the conditions are true.
+    #[clap(
+        long,
+        value_name = "EXPR",
+        multiple_occurrences = true,
+        number_of_values = 1,
+        conflicts_with_all = &["integers", "boxplot", "cdf", "annotate", "cumsum", "normalize", "compare"]
+    )]
+    fail_if: Vec<Condition>,
+
+    /// only check that each line of input is a value instead of computing
+    /// statistics, displaying the number of lines that were values, blank,
+    /// or malformed and the line number, byte offset, and content of the
+    /// first few malformed lines. Exits with an error if any lines were
+    /// malformed. Each file is checked separately.
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "measure", "integers", "stream", "reservoir", "spill", "max-memory", "precision-mode", "summary",
+            "boxplot", "cdf", "annotate", "cumsum", "normalize", "compare", "fail-if",
+        ]
+    )]
+    #[cfg_attr(feature = "mmap", clap(conflicts_with = "mmap"))]
+    validate: bool,
+
+    /// exit with an error if any line of input isn't a value instead of
+    /// skipping it, displaying the number of malformed lines and the line
+    /// number, byte offset, and content of the first few of them.
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "measure", "integers", "stream", "reservoir", "spill", "max-memory", "precision-mode", "validate",
+            "timing",
+        ]
+    )]
+    #[cfg_attr(feature = "mmap", clap(conflicts_with = "mmap"))]
+    strict: bool,
+
+    /// number of malformed lines to display with --validate or --strict.
+    /// Long lines are shortened and control characters in them are
+    /// escaped. Only these lines are kept, no matter how many lines are
+    /// malformed. Default is 5.
+    #[clap(long, value_name = "N")]
+    malformed_samples: Option<usize>,
+
+    /// keep watching the input files after displaying statistics and
+    /// display them again each time any of the files change, including
+    /// when a file is replaced by renaming another file over it, until
+    /// interrupted (e.g. with Ctrl-C). Reports are separated by a line of
+    /// dashes unless --clear is used. At least one FILE must be given.
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "measure", "progress", "sorted", "integers", "stream", "reservoir", "spill", "max-memory",
+            "precision-mode", "summary", "boxplot", "cdf", "annotate", "cumsum", "normalize", "compare", "fail-if",
+            "validate",
+        ]
+    )]
+    #[cfg_attr(feature = "mmap", clap(conflicts_with = "mmap"))]
+    watch: bool,
+
+    /// clear the screen before each report when using --watch instead of
+    /// separating reports with a line of dashes, if standard output is a
+    /// terminal.
+    #[clap(long, requires = "watch")]
+    clear: bool,
+
+    /// display statistics of the values in each file separately, labeled
+    /// with the path of the file, followed by statistics of the values in
+    /// all of them labeled 'total'. For JSON output a single object is
+    /// displayed with each label as a key.
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "integers", "stream", "reservoir", "spill", "max-memory", "precision-mode", "summary", "boxplot",
+            "cdf", "annotate", "cumsum", "normalize", "compare", "fail-if", "validate", "watch",
+        ]
+    )]
+    per_file: bool,
+
+    /// label statistics of each file with --per-file using only the name
+    /// of the file instead of its path.
+    #[clap(long, requires = "per-file")]
+    short_labels: bool,
+
+    /// write the statistics of each file with --per-file (and of all of
+    /// them) to a separate file in DIR instead of displaying them, named
+    /// after the label with an extension for the --format, e.g.
+    /// 'DIR/total.txt'. DIR is created if it doesn't exist. Characters that
+    /// aren't safe in file names are replaced with '_' and names that would
+    /// collide get a numeric suffix. DIR/index.txt lists each label and the
+    /// name of the file its statistics were written to.
+    #[clap(long, value_name = "DIR", parse(from_os_str), requires = "per-file")]
+    output_dir: Option<PathBuf>,
+
+    /// prefix the key of every statistic with TEXT and a period, e.g.
+    /// '--label=api' displays 'api.mean', so that statistics from several
+    /// runs can be combined without their keys colliding. Characters
+    /// other than letters, digits, '-', '_', and '.' are replaced with '_'.
+    /// The key case applies to each key after the label but not to the
+    /// label itself. For JSON output the statistics are displayed as an
+    /// object with TEXT as its only key instead. Without TEXT, the name
+    /// of the file (without its extension) is used when there is exactly
+    /// one FILE, e.g. 'st FILE --label', since an argument right after
+    /// --label is taken as TEXT.
+    #[clap(
+        long,
+        value_name = "TEXT",
+        conflicts_with_all = &[
+            "boxplot", "cdf", "annotate", "normalize", "compare", "validate", "per-file",
+        ]
+    )]
+    label: Option<Option<String>>,
+
+    /// unit of the values being read, e.g. 's' for seconds. Units of time
+    /// are 'ns', 'us', 'ms', 's', 'm', and 'h' and units of size are 'B',
+    /// 'KB', 'MB', 'GB' (powers of 1000) and 'KiB', 'MiB', 'GiB' (powers
+    /// of 1024). The unit is displayed with the key 'unit' before the
+    /// statistics. Values are always in 'ms' with --timer.
+    #[clap(
+        long,
+        value_name = "UNIT",
+        conflicts_with_all = &[
+            "timer", "integers", "boxplot", "cdf", "annotate", "cumsum", "normalize", "compare", "validate",
+        ]
+    )]
+    unit_in: Option<Unit>,
+
+    /// unit to display statistics in, converted from the unit given by
+    /// --unit-in which must be the same kind of unit, e.g. 'ms' to display
+    /// values read in seconds as milliseconds. Every statistic other than
+    /// counts is converted. Conditions given by --fail-if are checked
+    /// against statistics in the original unit.
+    #[clap(
+        long,
+        value_name = "UNIT",
+        conflicts_with_all = &[
+            "integers", "boxplot", "cdf", "annotate", "cumsum", "normalize", "compare", "validate",
+        ]
+    )]
+    unit_out: Option<Unit>,
+
+    /// deprecated, use `st compare --to FILE` instead. Optional file of
+    /// values to compare against the values from FILE (or standard
+    /// input), which is done by the compare subcommand with the same
+    /// percentiles, quantiles, --threshold, and --boxplot.
+    #[clap(
+        long,
+        value_name = "FILE",
+        parse(from_os_str),
+        conflicts_with_all = &["timer", "all-quantiles", "quartiles", "min-samples", "strict"]
+    )]
+    compare: Option<PathBuf>,
+
+    /// deprecated, use `st compare --threshold` instead. Percent increase
+    /// from the first set of values to the second above which a
+    /// statistic is highlighted as a regression when comparing values
+    /// with --compare.
+    #[clap(long, requires = "compare")]
+    threshold: Option<f64>,
+}
+
+#[derive(Clap, Debug)]
+struct HistOptions {
+    #[clap(flatten)]
+    input: InputOptions,
+
+    /// number of evenly spaced buckets from the lowest to the highest
+    /// value. Default is 10.
+    #[clap(long, value_name = "N")]
+    buckets: Option<usize>,
+
+    /// space the edges of the buckets evenly on a log scale from the
+    /// lowest positive value to the highest value instead, for values
+    /// spanning several orders of magnitude such as latencies. Values
+    /// that aren't positive are counted as underflow.
+    #[clap(long)]
+    log: bool,
+
+    /// comma separated list of the edges of each bucket instead of
+    /// choosing them from the values, e.g. '0,10,100,1000'. Edges must
+    /// be increasing. Values below the lowest edge or above the highest
+    /// edge are counted as underflow and overflow.
+    #[clap(long, conflicts_with_all = &["buckets", "log"])]
+    edges: Option<Edges>,
+
+    /// count values below the lowest edge in the first bucket and values
+    /// above the highest edge in the last bucket instead of counting
+    /// them as underflow and overflow.
+    #[clap(long)]
+    clamp: bool,
 }
 
-#[derive(Default, PartialEq, Debug)]
-struct Percentiles {
-    value: Vec<u8>,
+#[derive(Clap, Debug)]
+struct CompareOptions {
+    #[clap(flatten)]
+    input: InputOptions,
+
+    /// file of values to compare against the values from FILE (or
+    /// standard input).
+    #[clap(long, value_name = "FILE", parse(from_os_str), required = true)]
+    to: PathBuf,
+
+    /// comma separated list of percentiles (from 1 to 99,
+    /// inclusive) that should have metrics computed and compared.
+    /// Ranges and repeated occurrences are combined the same way as
+    /// for computing statistics.
+    #[clap(short = 'p', long, multiple_occurrences = true, number_of_values = 1)]
+    percentiles: Vec<Percentiles>,
+
+    /// comma separated list of quantiles (from 0 to 100, inclusive)
+    /// whose values should be computed and compared.
+    #[clap(long)]
+    quantiles: Option<Quantiles>,
+
+    /// method used to compute the value of quantiles that fall between
+    /// two values, 'linear' or 'nearest'. Default is 'linear'.
+    #[clap(long)]
+    quantile_method: Option<QuantileMethod>,
+
+    /// percent increase from the first set of values to the second
+    /// above which a statistic is highlighted as a regression.
+    /// Highlighting is only done when writing to a terminal.
+    #[clap(long)]
+    threshold: Option<f64>,
+
+    /// display a boxplot of each set of values using the same scale
+    /// instead of statistics.
+    #[clap(long)]
+    boxplot: bool,
+
+    /// where the whiskers of a boxplot end, 'range' or 'iqr'. Default
+    /// is 'iqr'.
+    #[clap(long)]
+    whiskers: Option<WhiskerPolicy>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Clap, Debug)]
+struct MergeOptions {
+    /// fail if a percentile slice isn't in every file instead of
+    /// leaving it out of the combined statistics with a warning.
+    #[clap(long)]
+    strict: bool,
+
+    /// files of statistics serialized as JSON to combine.
+    #[clap(name = "FILE", parse(from_os_str), required = true)]
+    files: Vec<PathBuf>,
+}
+
+#[derive(Default, Clone, PartialEq, Debug)]
+struct Quantiles {
+    value: Vec<f64>,
 }
 
-impl FromStr for Percentiles {
+impl FromStr for Quantiles {
     type Err = String;
 
     fn from_str(val: &str) -> Result<Self, Self::Err> {
         let mut out = Vec::new();
-        for p in val.split(",") {
-            match p.parse::<u8>() {
-                Ok(i) if i > 0 && i < 100 => {
+        for q in val.split(',') {
+            match q.parse::<f64>() {
+                Ok(i) if (0f64..=100f64).contains(&i) => {
                     out.push(i);
                 }
                 _ => {
-                    return Err(format!("Invalid percentile value {}", p));
+                    return Err(format!("Invalid quantile value {}", q));
                 }
             };
         }
 
-        return Ok(Percentiles { value: out });
+        Ok(Quantiles { value: out })
     }
 }
 
-fn main() {
-    let opts: StaccatoOptions = StaccatoOptions::parse();
-    let percents = opts.percentiles.unwrap_or(Percentiles::default());
-    let separator = opts.separator.unwrap_or(KeyValueSep::default());
-    let sorting = if !percents.value.is_empty() {
-        SortingPolicy::Sorted
-    } else {
-        SortingPolicy::Unsorted
-    };
+impl Quantiles {
+    fn all() -> Quantiles {
+        Quantiles {
+            value: (1..100).map(f64::from).collect(),
+        }
+    }
 
-    let line_result = if let Some(f) = opts.file {
-        // If we've been given a file argument, try to open it and read
-        // values out of it. If we can't for any reason, just give up and
-        // exit now.
-        match File::open(f) {
-            Ok(handle) => get_values(&mut BufReader::new(handle), sorting),
-            Err(e) => {
-                eprintln!("error: Cannot open file: {}", e);
-                process::exit(1);
+    /// Add each of the named quantiles (e.g. the quartiles) after these
+    /// quantiles, unless they're already included.
+    fn with_named(mut self, named: &[(f64, &str)]) -> Quantiles {
+        for &(q, _) in named {
+            if !self.value.contains(&q) {
+                self.value.push(q);
             }
         }
-    } else {
-        // Let the user know we're just going to block on stdin before doing
-        // it since sometimes people run commands without arguments just
-        // expecting them to display help.
-        eprintln!(concat!(
-            "notice: waiting for input from stdin. If this isn't what you ",
-            "want, try running with the `--help` option"
-        ));
 
-        get_values(&mut BufReader::new(stdin()), sorting)
-    };
+        self
+    }
+}
 
-    let lines = match line_result {
-        Ok(v) => v,
-        Err(e) => {
-            eprintln!("error: Could not parse values: {}", e);
-            process::exit(1);
+/// Edges of the buckets of a histogram, parsed from a comma separated list.
+#[derive(Debug, Clone, PartialEq)]
+struct Edges {
+    value: Vec<f64>,
+}
+
+impl FromStr for Edges {
+    type Err = String;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        let value = val
+            .split(',')
+            .map(|e| e.parse::<f64>().map_err(|_| format!("Invalid bucket edge {}", e)))
+            .collect::<Result<Vec<f64>, String>>()?;
+
+        if Histogram::with_edges(&value).is_none() {
+            return Err(format!(
+                "Invalid bucket edges {}, at least two increasing edges are required",
+                val
+            ));
         }
-    };
 
-    let stats = StatisticsBundle::with_percentiles(&lines, &percents.value);
-    if let Some(v) = stats {
-        print!("{}", StatisticsFormatter::with_sep(&v, separator));
-    } else {
-        eprintln!("warning: No values to compute stats for");
+        Ok(Edges { value })
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::Percentiles;
-    use std::str::FromStr;
+/// Number of bytes, parsed from a number with an optional binary unit suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MemorySize {
+    bytes: usize,
+}
 
-    #[test]
-    fn test_parse_percentiles_err_not_in_range() {
-        let percents = "75,90,100,110";
-        let res = Percentiles::from_str(percents);
+impl FromStr for MemorySize {
+    type Err = String;
 
-        assert!(res.is_err());
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        let (num, shift) = match val.char_indices().last() {
+            Some((i, 'K')) | Some((i, 'k')) => (&val[..i], 10),
+            Some((i, 'M')) | Some((i, 'm')) => (&val[..i], 20),
+            Some((i, 'G')) | Some((i, 'g')) => (&val[..i], 30),
+            Some((i, 'T')) | Some((i, 't')) => (&val[..i], 40),
+            _ => (val, 0),
+        };
+
+        num.parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_mul(1 << shift))
+            .map(|bytes| MemorySize { bytes })
+            .ok_or_else(|| format!("Invalid memory size {}", val))
     }
+}
 
-    #[test]
-    fn test_parse_percentiles_lower_bound() {
-        let percents = "0,50,75";
-        let res = Percentiles::from_str(percents);
+/// Type used to store values while computing statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum PrecisionMode {
+    #[default]
+    F64,
+    F32,
+}
 
-        assert!(res.is_err());
+impl FromStr for PrecisionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PrecisionMode, Self::Err> {
+        if "f64" == s {
+            Ok(PrecisionMode::F64)
+        } else if "f32" == s {
+            Ok(PrecisionMode::F32)
+        } else {
+            Err(format!("Invalid precision mode {}", s))
+        }
     }
+}
 
-    #[test]
-    fn test_parse_percentiles_upper_bound() {
-        let percents = "50,75,100";
-        let res = Percentiles::from_str(percents);
+/// Format used to display statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum OutputFormat {
+    #[default]
+    Text,
+    #[cfg(feature = "serde")]
+    Json,
+}
 
-        assert!(res.is_err());
+impl OutputOptions {
+    /// How statistics should be rounded, if at all.
+    fn rounding(&self) -> Option<Rounding> {
+        self.round.map(Option::unwrap_or_default)
     }
+}
 
-    #[test]
-    fn test_parse_percentiles_err_not_a_number() {
-        let percents = "75,banana";
-        let res = Percentiles::from_str(percents);
+impl OutputFormat {
+    fn formatter(
+        self,
+        output: &OutputOptions,
+        config: StatisticsConfig,
+        label: Option<&str>,
+        unit: Option<UnitConversion>,
+    ) -> Box<dyn BundleFormatter> {
+        let key_case = output.key_case.unwrap_or_default();
+        let rounding = output.rounding();
+        match self {
+            OutputFormat::Text => {
+                let mut formatter = KeyValueFormatter::with_sep(output.separator.clone().unwrap_or_default())
+                    .precision(output.precision)
+                    .config(config)
+                    .key_case(key_case)
+                    .sort_keys(output.sort_keys);
+                if let Some(l) = label {
+                    formatter = formatter.label(l);
+                }
+                if let Some(u) = unit {
+                    formatter = formatter.unit(u);
+                }
+                if let Some(r) = rounding {
+                    formatter = formatter.rounding(r);
+                }
 
-        assert!(res.is_err());
+                Box::new(formatter)
+            }
+            #[cfg(feature = "serde")]
+            OutputFormat::Json => {
+                let mut formatter = JsonFormatter::new()
+                    .key_case(key_case)
+                    .omitted_as_null(output.null_omitted)
+                    .sort_keys(output.sort_keys);
+                if let Some(l) = label {
+                    formatter = formatter.label(l);
+                }
+                if let Some(u) = unit {
+                    formatter = formatter.unit(u);
+                }
+                if let Some(r) = rounding {
+                    formatter = formatter.rounding(r);
+                }
+
+                Box::new(formatter)
+            }
+        }
     }
 
-    #[test]
-    fn test_parse_percentiles_ok() {
-        let percents = "75,90,95,98";
-        let res = Percentiles::from_str(percents);
+    /// Extension of files that statistics in this format are written to.
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "txt",
+            #[cfg(feature = "serde")]
+            OutputFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, Self::Err> {
+        if "text" == s {
+            return Ok(OutputFormat::Text);
+        }
+
+        #[cfg(feature = "serde")]
+        if "json" == s {
+            return Ok(OutputFormat::Json);
+        }
+
+        Err(format!("Invalid output format {}", s))
+    }
+}
+
+/// What to do when values that should already be sorted aren't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum SortedInput {
+    #[default]
+    Verify,
+    Trust,
+}
+
+impl FromStr for SortedInput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SortedInput, Self::Err> {
+        if "verify" == s {
+            Ok(SortedInput::Verify)
+        } else if "trust" == s {
+            Ok(SortedInput::Trust)
+        } else {
+            Err(format!("Invalid sorted input mode {}", s))
+        }
+    }
+}
+
+/// When to show progress while reading input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ProgressMode {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ProgressMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ProgressMode, Self::Err> {
+        if "auto" == s {
+            Ok(ProgressMode::Auto)
+        } else if "always" == s {
+            Ok(ProgressMode::Always)
+        } else if "never" == s {
+            Ok(ProgressMode::Never)
+        } else {
+            Err(format!("Invalid progress mode {}", s))
+        }
+    }
+}
+
+impl ProgressMode {
+    fn enabled(self) -> bool {
+        match self {
+            ProgressMode::Auto => stderr().is_terminal(),
+            ProgressMode::Always => true,
+            ProgressMode::Never => false,
+        }
+    }
+}
+
+/// Error to display before exiting with an exit code for its cause.
+#[derive(Debug)]
+struct CliError {
+    message: String,
+    code: i32,
+}
+
+impl CliError {
+    fn new<S: Into<String>>(code: i32, message: S) -> CliError {
+        CliError {
+            message: message.into(),
+            code,
+        }
+    }
+
+    /// Error reading values from a file, or standard input if there is no file.
+    fn parse(file: Option<&PathBuf>, e: StaccatoError) -> CliError {
+        let label = input_label(file.map(slice::from_ref).unwrap_or_default());
+        Self::new(exit_code(&e), format!("Could not parse values from {}: {}", label, e))
+    }
+
+    fn exit(&self) -> ! {
+        eprintln!("error: {}", self);
+        process::exit(self.code)
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.message.fmt(f)
+    }
+}
+
+/// Level of detail of a diagnostic, each requiring `--verbose` to be given
+/// one more time to be displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LogLevel {
+    Info = 1,
+    Debug = 2,
+}
+
+impl LogLevel {
+    fn prefix(self) -> &'static str {
+        match self {
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+}
+
+/// Line to display for a diagnostic at a level, or `None` if `--verbose`
+/// wasn't given enough times to display it.
+fn log_line(verbosity: u64, level: LogLevel, message: fmt::Arguments) -> Option<String> {
+    if verbosity >= level as u64 {
+        Some(format!("{}: {}\n", level.prefix(), message))
+    } else {
+        None
+    }
+}
+
+fn warning(message: fmt::Arguments) {
+    if !QUIET.load(Ordering::Relaxed) {
+        eprintln!("warning: {}", message);
+    }
+}
+
+fn log(level: LogLevel, message: fmt::Arguments) {
+    if let Some(line) = log_line(VERBOSITY.load(Ordering::Relaxed), level, message) {
+        // Diagnostics are best effort and never stop values being read.
+        let _ = stderr().lock().write_all(line.as_bytes());
+    }
+}
+
+/// Write output to standard output, exiting quietly if whatever was reading
+/// it has gone away, e.g. when output is piped to `head`.
+fn print_output<T: fmt::Display>(output: T) {
+    let mut out = stdout().lock();
+    if let Err(e) = write!(out, "{}", output).and_then(|_| out.flush()) {
+        if e.kind() == io::ErrorKind::BrokenPipe {
+            process::exit(0);
+        }
+
+        CliError::new(EXIT_IO_ERR, format!("Could not write output: {}", e)).exit();
+    }
+}
+
+/// Write a bundle to standard output, warning first if the sum of any of
+/// the statistics overflowed since it'll be displayed as infinite.
+fn print_bundle<F: BundleFormatter + ?Sized>(formatter: &F, bundle: &StatisticsBundle) {
+    if bundle.overflowed() {
+        warn!("sum of values overflowed to infinity, mean is computed without it");
+    }
+
+    for stats in bundle.percentile_stats() {
+        if let Some(p) = stats.percentile() {
+            info!("percentile {} slice contains {} values", p, stats.count());
+        }
+    }
+
+    let percentiles: Vec<(String, Omission)> = bundle
+        .omitted_percentiles()
+        .iter()
+        .map(|o| (o.percentile().to_string(), o.reason()))
+        .collect();
+    let quantiles: Vec<(String, Omission)> = bundle
+        .omitted_quantiles()
+        .iter()
+        .map(|o| (o.key(), o.reason()))
+        .collect();
 
-        assert!(res.is_ok());
+    for line in omitted_warning("percentile slices", &percentiles)
+        .into_iter()
+        .chain(omitted_warning("quantiles", &quantiles))
+    {
+        warn!("{}", line);
+    }
+
+    print_output(FormattedBundle::new(formatter, bundle));
+}
+
+/// Update streaming statistics with values from a reader the same way as
+/// `StreamingStatistics::push_reader`, calling `interim` with the statistics
+/// so far whenever a report is requested (by SIGUSR1) between values.
+fn push_streamed<F: Fn(&StreamingStatistics)>(
+    stats: &mut StreamingStatistics,
+    reader: &mut dyn BufRead,
+    interim: F,
+) -> Result<(), StaccatoError> {
+    for res in parse_values(reader) {
+        if report::requested() {
+            interim(stats);
+        }
+
+        match res {
+            Ok(v) => stats.push(v),
+            Err(ParseLineError::Invalid { .. }) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Warning listing what was omitted from a bundle (e.g. percentile slices)
+/// by key for each reason it was, or `None` if nothing was.
+fn omitted_warning(what: &str, omitted: &[(String, Omission)]) -> Option<String> {
+    let mut reasons: Vec<Omission> = Vec::new();
+    for &(_, r) in omitted {
+        if !reasons.contains(&r) {
+            reasons.push(r);
+        }
+    }
+
+    let parts: Vec<String> = reasons
+        .into_iter()
+        .map(|r| {
+            let keys: Vec<&str> = omitted
+                .iter()
+                .filter(|(_, o)| *o == r)
+                .map(|(k, _)| k.as_str())
+                .collect();
+            format!("{} ({})", keys.join(","), r)
+        })
+        .collect();
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(format!("omitted {} {}", what, parts.join(", ")))
+    }
+}
+
+/// Exit with an error if any of the conditions are true for a bundle, after
+/// displaying each one that is, or if any of them are for a statistic that
+/// wasn't computed.
+fn check_conditions(bundle: &StatisticsBundle, conditions: &[Condition], precision: usize) {
+    let mut failed = false;
+    for cond in conditions {
+        match cond.actual(bundle) {
+            Ok(actual) if cond.comparison().holds(actual, cond.value()) => {
+                eprintln!("failed: {} ({} is {:.*})", cond, cond.stat(), precision, actual);
+                failed = true;
+            }
+            Ok(_) => {}
+            Err(e) => CliError::new(EXIT_USAGE, format!("Cannot check condition '{}': {}", cond, e)).exit(),
+        }
+    }
+
+    if failed {
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+fn exit_code(e: &StaccatoError) -> i32 {
+    match e {
+        StaccatoError::Io(_) => EXIT_IO_ERR,
+        StaccatoError::Utf8 { .. }
+        | StaccatoError::ParseFailure { .. }
+        | StaccatoError::Malformed { .. }
+        | StaccatoError::EmptyInput
+        | StaccatoError::NotSorted { .. } => EXIT_DATA_ERR,
+        StaccatoError::InvalidPercentile { .. }
+        | StaccatoError::InvalidPercentileRange { .. }
+        | StaccatoError::InvalidSeparator
+        | StaccatoError::InvalidEscape { .. } => EXIT_USAGE,
+    }
+}
+
+fn sorting_policy(sorted: bool) -> SortingPolicy {
+    if sorted {
+        SortingPolicy::Sorted
+    } else {
+        SortingPolicy::Unsorted
+    }
+}
+
+/// Compute statistics of all inputs using an external sort. Any temporary
+/// files are removed before this returns, even if there's an error.
+fn spill_bundle(
+    files: &[PathBuf],
+    reading: ReadOptions,
+    dir: PathBuf,
+    run_size: usize,
+    percentiles: &[u8],
+    quantiles: &[f64],
+    method: QuantileMethod,
+) -> Result<Option<StatisticsBundle>, CliError> {
+    if !dir.is_dir() {
+        let message = format!("Temporary directory {} does not exist", dir.display());
+        return Err(CliError::new(EXIT_USAGE, message));
+    }
+
+    let mut sorter = ExternalSorter::new(dir, run_size);
+    for file in each_input(files) {
+        open_input(file, reading).and_then(|mut r| sorter.push_reader(&mut r).map_err(|e| CliError::parse(file, e)))?;
+    }
+
+    sorter.bundle(percentiles, quantiles, method).map_err(|e| {
+        CliError::new(
+            exit_code(&e),
+            format!("Could not sort values using temporary files: {}", e),
+        )
+    })
+}
+
+/// Compute statistics of all inputs, exactly if they fit in `max_bytes` and
+/// estimated from a sample otherwise. When all inputs are regular files the
+/// number of values is estimated before reading them so that values that
+/// clearly won't fit aren't kept in memory first.
+fn limited_bundle(
+    files: &[PathBuf],
+    reading: ReadOptions,
+    max_bytes: usize,
+    seed: u64,
+    percentiles: &[u8],
+    quantiles: &[f64],
+    method: QuantileMethod,
+) -> Result<Option<StatisticsBundle>, CliError> {
+    let estimate = each_input(files).into_iter().try_fold(0usize, |total, file| {
+        file.and_then(|p| estimate_values_in_file(p).ok().flatten())
+            .map(|n| total.saturating_add(n))
+    });
+
+    let rng = Rng::new(seed);
+    let mut engine = match estimate {
+        Some(n) if n > max_bytes / BYTES_PER_VALUE => MemoryLimited::approximate_with_rng(max_bytes, rng),
+        _ => MemoryLimited::with_rng(max_bytes, rng),
+    };
+
+    for file in each_input(files) {
+        open_input(file, reading).and_then(|mut r| engine.push_reader(&mut r).map_err(|e| CliError::parse(file, e)))?;
+    }
+
+    if engine.is_approximate() {
+        eprintln!(
+            "notice: Values do not fit in --max-memory, estimating statistics from a sample of {} values",
+            engine.max_values()
+        );
+    }
+
+    Ok(engine.bundle(percentiles, quantiles, method))
+}
+
+/// Combine statistics bundles serialized as JSON in each of the files,
+/// along with the percentiles of slices that were left out of the result
+/// because they weren't in every file.
+#[cfg(feature = "serde")]
+fn merge_bundles(files: &[PathBuf], policy: MergePolicy) -> Result<(StatisticsBundle, Vec<u8>), CliError> {
+    let mut merged: Option<StatisticsBundle> = None;
+    let mut requested: Vec<u8> = Vec::new();
+    for f in files {
+        let reader = File::open(f)
+            .map_err(|e| CliError::new(EXIT_NO_INPUT, format!("Cannot open file {}: {}", f.display(), e)))?;
+        let bundle: StatisticsBundle = serde_json::from_reader(BufReader::new(reader)).map_err(|e| {
+            let message = format!("Could not parse statistics from {}: {}", f.display(), e);
+            CliError::new(EXIT_DATA_ERR, message)
+        })?;
+
+        requested.extend(bundle.percentiles());
+        merged = Some(match merged {
+            Some(m) => m.merge(&bundle, policy).map_err(|e| {
+                let message = format!("Could not merge statistics from {}: {}", f.display(), e);
+                CliError::new(EXIT_DATA_ERR, message)
+            })?,
+            None => bundle,
+        });
+    }
+
+    let merged = merged.ok_or_else(|| CliError::new(EXIT_FAILURE, "No statistics to merge"))?;
+    requested.sort_unstable();
+    requested.dedup();
+    requested.retain(|&p| {
+        merged.percentile_for(p).is_none() && !merged.omitted_percentiles().iter().any(|o| o.percentile() == p)
+    });
+    Ok((merged, requested))
+}
+
+/// Lines describing which statistics of merged statistics are exact and
+/// which are only estimates, one for each kind of accuracy.
+#[cfg(feature = "serde")]
+fn accuracy_lines(bundle: &StatisticsBundle) -> Vec<String> {
+    let global = bundle.global_stats();
+    let percentiles: Vec<String> = bundle.percentiles().map(|p| p.to_string()).collect();
+    let mut lines = Vec::new();
+    for accuracy in [Accuracy::Exact, Accuracy::Formula, Accuracy::Estimate] {
+        let mut names: Vec<String> = StatName::ALL
+            .iter()
+            .filter(|&&n| global.accuracy(n) == accuracy)
+            .map(|n| n.to_string())
+            .collect();
+        let slices = bundle.percentile_stats().first();
+        if !percentiles.is_empty() && slices.map(|s| s.accuracy(StatName::Count)) == Some(accuracy) {
+            names.push(format!("percentile slices {}", percentiles.join(",")));
+        }
+
+        if !names.is_empty() {
+            lines.push(format!("{}: {}", accuracy, names.join(", ")));
+        }
+    }
+
+    lines
+}
+
+fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+        ^ u64::from(process::id())
+}
+
+/// Seed for choosing values at random, either given by --seed or a random
+/// one that's logged so that the output can be reproduced.
+fn sampling_seed(seed: Option<u64>) -> u64 {
+    let seed = seed.unwrap_or_else(random_seed);
+    info!("choosing values at random with seed {}", seed);
+    seed
+}
+
+/// How values are produced from each line of input instead of parsing the
+/// whole line as a number.
+#[derive(Debug, Clone)]
+enum InputExtractor {
+    Field(FieldExtractor),
+    Split(SplitExtractor),
+    Marker(MarkerExtractor),
+    Duration(DurationExtractor),
+}
+
+impl InputExtractor {
+    fn from(opts: &InputOptions) -> Result<Option<InputExtractor>, CliError> {
+        let delimiter = opts.delimiter.unwrap_or_default();
+        if let Some(field) = opts.field {
+            FieldExtractor::new(field)
+                .map(|e| Some(InputExtractor::Field(e.delimiter(delimiter))))
+                .ok_or_else(|| CliError::new(EXIT_USAGE, "Invalid field 0, fields are counted from 1".to_string()))
+        } else if opts.split {
+            Ok(Some(InputExtractor::Split(SplitExtractor::new().delimiter(delimiter))))
+        } else if opts.delimiter.is_some() {
+            let message = "The --delimiter option can only be used with --field or --split".to_string();
+            Err(CliError::new(EXIT_USAGE, message))
+        } else if let Some(marker) = &opts.after {
+            MarkerExtractor::new(marker)
+                .map(|e| Some(InputExtractor::Marker(e)))
+                .ok_or_else(|| CliError::new(EXIT_USAGE, "Marker must not be empty".to_string()))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl fmt::Display for InputExtractor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputExtractor::Field(e) => write!(f, "parsing field {} of each line", e.field()),
+            InputExtractor::Split(_) => write!(f, "parsing every field of each line"),
+            InputExtractor::Marker(e) => write!(f, "parsing the number after '{}' in each line", e.marker()),
+            InputExtractor::Duration(_) => write!(f, "parsing each line as a duration in milliseconds"),
+        }
+    }
+}
+
+impl LineExtractor for InputExtractor {
+    fn extract(&self, line: &str, out: &mut Vec<f64>) -> bool {
+        match self {
+            InputExtractor::Field(e) => e.extract(line, out),
+            InputExtractor::Split(e) => e.extract(line, out),
+            InputExtractor::Marker(e) => e.extract(line, out),
+            InputExtractor::Duration(e) => e.extract(line, out),
+        }
+    }
+}
+
+/// How values should be read from each input.
+#[derive(Debug, Clone, Copy)]
+struct ReadOptions<'a> {
+    measure: Option<Measure>,
+    extractor: Option<&'a InputExtractor>,
+    progress: bool,
+    null_data: bool,
+    presorted: Option<SortedInput>,
+    force_stdin: bool,
+    errors: ErrorPolicy,
+    malformed_samples: usize,
+    #[cfg(feature = "mmap")]
+    mmap: bool,
+    #[cfg(feature = "rayon")]
+    threads: usize,
+}
+
+impl<'a> ReadOptions<'a> {
+    fn from(opts: &InputOptions, extractor: Option<&'a InputExtractor>) -> ReadOptions<'a> {
+        let delimiter = opts.delimiter.unwrap_or_default();
+        match (opts.measure, extractor) {
+            (Some(m), _) => info!("measuring the {} of each line", measure_name(m)),
+            (None, Some(e @ InputExtractor::Marker(_))) => info!("{}", e),
+            (None, Some(e)) => info!("{} separated by {}", e, delimiter),
+            (None, None) => info!("parsing each line as a number"),
+        }
+
+        let reading = ReadOptions {
+            measure: opts.measure,
+            extractor,
+            progress: opts.progress.is_some_and(|p| p.unwrap_or_default().enabled()),
+            null_data: opts.null_data,
+            presorted: opts.sorted.map(Option::unwrap_or_default),
+            force_stdin: opts.stdin,
+            errors: ErrorPolicy::Skip,
+            malformed_samples: DEFAULT_MALFORMED_SAMPLES,
+            #[cfg(feature = "mmap")]
+            mmap: opts.mmap,
+            #[cfg(feature = "rayon")]
+            threads: opts.threads,
+        };
+
+        debug!("{:?}", reading);
+        reading
+    }
+}
+
+fn measure_name(measure: Measure) -> &'static str {
+    match measure {
+        Measure::Length => "length",
+        Measure::Words => "number of words",
+        Measure::Bytes => "number of bytes",
+    }
+}
+
+#[cfg(feature = "mmap")]
+mod mmap {
+    use super::ReadOptions;
+    use memmap2::Mmap;
+    use staccato::{get_measurements_from_slice, get_values_from_slice, StaccatoError};
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+
+    /// Read values from a regular file by memory mapping it, or `None` if the
+    /// file isn't a regular file (e.g. a pipe) and should be read normally.
+    pub fn read(path: &Path, sorted: bool, reading: ReadOptions) -> Option<Result<Vec<f64>, StaccatoError>> {
+        let file = File::open(path).ok()?;
+        file.metadata().ok().filter(|m| m.is_file())?;
+
+        // Hold a shared lock for as long as the file is mapped (it's released
+        // when the file is closed, after the map is dropped) so that writers
+        // that lock the file before truncating it wait until it's been read.
+        // Files that can't be locked are read normally instead.
+        file.lock_shared().ok()?;
+        let len = file.metadata().ok()?.len();
+        if len == 0 {
+            return None;
+        }
+
+        // SAFETY: The mapping is only valid while the file isn't modified by
+        // another process. Writers that take an exclusive lock can't truncate
+        // the file while it's mapped, but the lock is only advisory: truncating
+        // the file without it will cause the process to be killed by SIGBUS
+        // rather than reading invalid values. Any other change is detected below
+        // by comparing the file length after reading. This is why mapping files
+        // is opt-in via `--mmap`.
+        let map = match unsafe { Mmap::map(&file) } {
+            Ok(m) => m,
+            Err(e) => return Some(Err(e.into())),
+        };
+
+        let sorting = super::sorting_policy(sorted);
+        let res = match reading.measure {
+            Some(m) => get_measurements_from_slice(&map, m, sorting),
+            None => get_values_from_slice(&map, sorting),
+        };
+
+        if file.metadata().map(|m| m.len()).ok() != Some(len) {
+            return Some(Err(io::Error::other("file was modified while being read").into()));
+        }
+
+        Some(res)
+    }
+}
+
+fn open_input(file: Option<&PathBuf>, reading: ReadOptions) -> Result<Box<dyn BufRead>, CliError> {
+    if let Some(f) = file {
+        let handle = File::open(f)
+            .map_err(|e| CliError::new(EXIT_NO_INPUT, format!("Cannot open file {}: {}", f.display(), e)))?;
+        let total = handle.metadata().ok().filter(|m| m.is_file()).map(|m| m.len());
+        info!("reading values from {}", f.display());
+        return Ok(input_reader(handle, &f.display().to_string(), total, reading));
+    }
+
+    // People often run commands without arguments expecting them to
+    // display help, so don't block waiting for them to type values unless
+    // they asked to.
+    if !reading.force_stdin && stdin().is_terminal() {
+        return Err(CliError::new(
+            EXIT_USAGE,
+            concat!(
+                "No FILE given and standard input is a terminal. Pipe values to st, give a FILE to ",
+                "read them from, or use --stdin to type them in. Try running with the `--help` option ",
+                "for more information",
+            ),
+        ));
+    }
+
+    // Let the user know we're just going to block on stdin before doing
+    // it in case this isn't what they expected.
+    eprintln!(concat!(
+        "notice: waiting for input from stdin. If this isn't what you ",
+        "want, try running with the `--help` option"
+    ));
+
+    info!("reading values from stdin");
+    Ok(input_reader(stdin(), "stdin", None, reading))
+}
+
+/// Buffered reader of lines (or NUL separated records) from a file or
+/// standard input, showing progress if required.
+fn input_reader<R: Read + 'static>(
+    reader: R,
+    label: &str,
+    total: Option<u64>,
+    reading: ReadOptions,
+) -> Box<dyn BufRead> {
+    // Records are turned into lines before showing progress so that records
+    // are counted instead of any newlines in them.
+    let reader = interrupt::interruptible(reader);
+    let reader: Box<dyn Read> = if reading.null_data {
+        Box::new(NullDelimited::new(reader))
+    } else {
+        reader
+    };
+
+    if reading.progress {
+        Box::new(BufReader::new(ProgressReader::new(reader, stderr(), label, total)))
+    } else {
+        Box::new(BufReader::new(reader))
+    }
+}
+
+/// Each file to read values from, or `None` for standard input if there are
+/// no files.
+fn each_input(files: &[PathBuf]) -> Vec<Option<&PathBuf>> {
+    if files.is_empty() {
+        vec![None]
+    } else {
+        files.iter().map(Some).collect()
+    }
+}
+
+/// Read values from a single file, or standard input if there is no file.
+fn read_input(file: Option<&PathBuf>, sorted: bool, reading: ReadOptions) -> Result<Vec<f64>, CliError> {
+    #[cfg(feature = "mmap")]
+    let mapped = file
+        .filter(|_| reading.mmap)
+        .and_then(|f| mmap::read(f, sorted, reading));
+    #[cfg(not(feature = "mmap"))]
+    let mapped = None;
+
+    let label = input_label(file.map_or(&[], slice::from_ref));
+    let res = match mapped {
+        Some(res) => {
+            info!("reading values from {} by memory mapping it", label);
+            res
+        }
+        None => {
+            // Space for values in a regular file is reserved up front based on
+            // its size so that the values aren't copied as they're read.
+            let capacity = file.map_or(0, |f| capacity_for_file(f));
+            debug!("reserved space for {} values", capacity);
+            get_values_or_measurements(
+                &mut open_input(file, reading)?,
+                sorting_policy(sorted),
+                reading,
+                capacity,
+                &label,
+            )
+        }
+    };
+
+    res.map_err(|e| CliError::parse(file, e))
+}
+
+/// Read integer values from each of the files in order (or standard input if
+/// there are no files) as if they were a single file.
+fn read_integers(files: &[PathBuf], sorted: bool, reading: ReadOptions) -> Result<Vec<i128>, CliError> {
+    let mut vals = Vec::new();
+    for file in each_input(files) {
+        let mut reader = open_input(file, reading)?;
+        vals.extend(get_integers(&mut reader, SortingPolicy::Unsorted).map_err(|e| CliError::parse(file, e))?);
+    }
+
+    if sorted {
+        vals.sort_unstable();
+    }
+
+    Ok(vals)
+}
+
+/// Read values from each of the files in order (or standard input if there
+/// are no files) as if they were a single file.
+fn read_inputs(files: &[PathBuf], sorted: bool, reading: ReadOptions) -> Result<Vec<f64>, CliError> {
+    #[cfg(feature = "rayon")]
+    if reading.threads > 1 && !reading.progress {
+        return read_inputs_parallel(files, sorted, reading);
+    }
+
+    if files.len() <= 1 {
+        return read_input(files.first(), sorted, reading);
+    }
+
+    let mut vals = Vec::new();
+    for f in files {
+        vals.extend(read_input(Some(f), false, reading)?);
+    }
+
+    if sorted {
+        sort_values(&mut vals);
+    }
+
+    Ok(vals)
+}
+
+/// Read values from each of the files on a separate thread, sorting the
+/// values from each file on the same thread if required and merging them
+/// afterwards. The result is identical to `read_inputs` with a single thread.
+#[cfg(feature = "rayon")]
+fn read_inputs_parallel(files: &[PathBuf], sorted: bool, reading: ReadOptions) -> Result<Vec<f64>, CliError> {
+    use rayon::prelude::*;
+
+    if files.len() <= 1 {
+        // Values from a single regular file are parsed in chunks on each thread
+        // unless they're being measured, extracted from fields, separated by NUL
+        // bytes, checked strictly, or memory mapped.
+        let chunked = reading.measure.is_none()
+            && reading.extractor.is_none()
+            && !reading.null_data
+            && reading.errors == ErrorPolicy::Skip;
+        #[cfg(feature = "mmap")]
+        let chunked = chunked && !reading.mmap;
+
+        // Pipes and errors fall back to reading the file serially below, which
+        // reports errors the same way as without threads.
+        if let Some(f) = files.first().filter(|_| chunked) {
+            if let Ok(Some(vals)) = get_values_from_file_parallel(f, reading.threads, sorting_policy(sorted)) {
+                return Ok(vals);
+            }
+        }
+
+        // Values are sorted after reading them when using multiple threads
+        // instead of the serial sort done while reading them.
+        let mut vals = read_input(files.first(), false, reading)?;
+        if sorted {
+            sort_values_parallel(&mut vals, reading.threads);
+        }
+
+        return Ok(vals);
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(reading.threads)
+        .build()
+        .map_err(|e| CliError::new(EXIT_FAILURE, format!("Could not start threads: {}", e)))?;
+
+    let per_file = pool.install(|| {
+        files
+            .par_iter()
+            .map(|f| read_input(Some(f), sorted, reading))
+            .collect::<Result<Vec<Vec<f64>>, CliError>>()
+    })?;
+
+    if sorted {
+        Ok(merge_sorted_values(&per_file))
+    } else {
+        Ok(per_file.concat())
+    }
+}
+
+/// Read values from each of the files in order (or standard input if there
+/// are no files) as `f32` values to use less memory.
+fn read_values_f32(files: &[PathBuf], sorted: bool, reading: ReadOptions) -> Vec<f32> {
+    let mut vals = Vec::new();
+    for file in each_input(files) {
+        let res = open_input(file, reading).and_then(|mut r| {
+            get_values_as::<f32, _>(&mut r, SortingPolicy::Unsorted).map_err(|e| CliError::parse(file, e))
+        });
+
+        match res {
+            Ok(v) => vals.extend(v),
+            Err(e) => e.exit(),
+        }
+    }
+
+    if sorted {
+        #[cfg(feature = "rayon")]
+        sort_values_parallel(&mut vals, reading.threads);
+        #[cfg(not(feature = "rayon"))]
+        sort_values(&mut vals);
+    }
+
+    vals
+}
+
+fn read_values(files: &[PathBuf], sorting: SortingPolicy, reading: ReadOptions) -> Vec<f64> {
+    let start = Instant::now();
+    let presorted = reading.presorted.filter(|_| sorting == SortingPolicy::Sorted);
+    let mut vals = match read_inputs(files, sorting == SortingPolicy::Sorted && presorted.is_none(), reading) {
+        Ok(v) => v,
+        Err(e) => e.exit(),
+    };
+
+    debug!("read {} values in {:?}", vals.len(), start.elapsed());
+    if let Some(mode) = presorted {
+        check_presorted(&mut vals, mode, &input_label(files));
+    } else if sorting == SortingPolicy::Sorted {
+        info!("sorted {} values", vals.len());
+    } else {
+        info!(
+            "did not sort {} values, no statistics required them to be sorted",
+            vals.len()
+        );
+    }
+
+    vals
+}
+
+/// Check that values from input that should already be sorted are, exiting
+/// with an error or sorting them (after a warning) depending on the mode if
+/// they aren't.
+fn check_presorted(vals: &mut [f64], mode: SortedInput, label: &str) {
+    match check_sorted(vals) {
+        Ok(()) => info!(
+            "did not sort {} values from {}, they are already sorted",
+            vals.len(),
+            label
+        ),
+        Err(e) if mode == SortedInput::Trust => {
+            warn!("Sorting values from {} since they are not in order: {}", label, e);
+            sort_values(vals);
+        }
+        Err(e) => CliError::new(exit_code(&e), format!("Values from {} are not sorted: {}", label, e)).exit(),
+    }
+}
+
+fn get_values_or_measurements<T: Read>(
+    reader: &mut T,
+    sorting: SortingPolicy,
+    reading: ReadOptions,
+    capacity: usize,
+    label: &str,
+) -> Result<Vec<f64>, StaccatoError> {
+    let parsed = match (reading.measure, reading.extractor) {
+        (Some(m), _) => {
+            let vals = get_measurements(reader, m, sorting)?;
+            info!("measured {} lines from {}", vals.len(), label);
+            return Ok(vals);
+        }
+        (None, Some(e)) => get_parsed_values_sampled(reader, sorting, e, 0, reading.malformed_samples)?,
+        (None, None) => {
+            get_parsed_values_sampled(reader, sorting, PlainExtractor, capacity, reading.malformed_samples)?
+        }
+    };
+
+    info!(
+        "read {} lines from {}: {} parsed, {} blank, {} malformed",
+        parsed.total_lines(),
+        label,
+        parsed.valid(),
+        parsed.blank(),
+        parsed.malformed()
+    );
+    for m in parsed.first_errors() {
+        debug!(
+            "malformed line {} of {} (byte {}): {}",
+            m.line(),
+            label,
+            m.offset(),
+            m.content()
+        );
+    }
+
+    reading.errors.apply(parsed)
+}
+
+fn input_label(files: &[PathBuf]) -> String {
+    if files.is_empty() {
+        "stdin".to_string()
+    } else {
+        files
+            .iter()
+            .map(|f| f.display().to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse::<usize>().ok())
+        .filter(|&c| c > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Parse options from the command line, using options from environment
+/// variables and then the config file (if there is one) for any that aren't
+/// given on the command line.
+fn parse_options() -> StaccatoOptions {
+    let args: Vec<OsString> = env::args_os().collect();
+    let matches = StaccatoOptions::into_app().get_matches_from(&args);
+    let opts = StaccatoOptions::from_arg_matches(&matches);
+    let command = matches.subcommand_name();
+    let given = matches.subcommand().map_or(&matches, |(_, m)| m);
+    let index = default_args_index(&args, command);
+
+    let config = match (opts.no_config, &opts.config) {
+        (true, _) => None,
+        (false, Some(path)) => Some((path.clone(), true)),
+        (false, None) => default_config_path().map(|path| (path, false)),
+    };
+
+    let config = match config {
+        Some((path, required)) => read_config(&path, required).and_then(|table| config_options(&path, &table, command)),
+        None => Ok(Vec::new()),
+    };
+
+    let defaults = config
+        .and_then(|config| Ok(vec![env_options(command)?, config]))
+        .and_then(|layers| default_args(layers, &args, index, given))
+        .unwrap_or_else(|e| e.exit());
+    if defaults.is_empty() {
+        return opts;
+    }
+
+    let args = with_default_args(&args, &defaults, index);
+    StaccatoOptions::from_arg_matches(&StaccatoOptions::into_app().get_matches_from(args))
+}
+
+/// Option from somewhere other than the command line (e.g. a config file)
+/// as command line arguments.
+#[derive(Debug, Clone, PartialEq)]
+struct DefaultOption {
+    name: String,
+    args: Vec<String>,
+}
+
+/// Path of the config file to use when one isn't given, in the XDG config
+/// directory.
+fn default_config_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".config")))?;
+
+    Some(dir.join("staccato").join("config.toml"))
+}
+
+/// Arguments that options can be given for when running a subcommand (or
+/// no subcommand), including global arguments.
+fn command_args(command: Option<&str>) -> Vec<Arg<'static>> {
+    let app = StaccatoOptions::into_app();
+    match command.and_then(|c| app.find_subcommand(c)) {
+        Some(sub) => sub
+            .get_arguments()
+            .chain(OutputOptions::into_app().get_arguments())
+            .cloned()
+            .collect(),
+        None => app.get_arguments().cloned().collect(),
+    }
+}
+
+/// If there is an option with a long name for any subcommand (or no
+/// subcommand) that can be given somewhere other than the command line.
+fn is_configurable(key: &str) -> bool {
+    let app = StaccatoOptions::into_app();
+    !NOT_CONFIGURABLE.contains(&key)
+        && app
+            .get_arguments()
+            .chain(app.get_subcommands().flat_map(|s| s.get_arguments()))
+            .any(|a| a.get_long() == Some(key))
+}
+
+/// Index of the argument that default options are inserted before, just
+/// after the subcommand if there is one.
+fn default_args_index(args: &[OsString], command: Option<&str>) -> usize {
+    command
+        .and_then(|c| args.iter().skip(1).position(|a| a == c))
+        .map_or(1, |i| i + 2)
+}
+
+/// Read a config file as a TOML table. A file that doesn't exist is the
+/// same as an empty one unless it's required.
+fn read_config(path: &Path, required: bool) -> Result<toml::Table, CliError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => return Ok(toml::Table::new()),
+        Err(e) => {
+            let code = if e.kind() == io::ErrorKind::NotFound {
+                EXIT_NO_INPUT
+            } else {
+                EXIT_IO_ERR
+            };
+
+            return Err(CliError::new(
+                code,
+                format!("Could not read config file {}: {}", path.display(), e),
+            ));
+        }
+    };
+
+    contents
+        .parse()
+        .map_err(|e| CliError::new(EXIT_CONFIG, format!("Invalid config file {}: {}", path.display(), e)))
+}
+
+/// Options from a config file, with a key for the long name of each option.
+/// Options that the subcommand (or no subcommand) doesn't have are left out.
+fn config_options(path: &Path, table: &toml::Table, command: Option<&str>) -> Result<Vec<DefaultOption>, CliError> {
+    let args = command_args(command);
+    let mut out = Vec::new();
+
+    for (key, value) in table {
+        let invalid = |msg: String| {
+            CliError::new(
+                EXIT_CONFIG,
+                format!("Invalid option '{}' in config file {}: {}", key, path.display(), msg),
+            )
+        };
+
+        if !is_configurable(key) {
+            return Err(invalid("unknown option".to_string()));
+        }
+
+        let arg = match args.iter().find(|a| a.get_long() == Some(key.as_str())) {
+            Some(arg) => arg,
+            None => continue,
+        };
+
+        let args = option_args(key, value, arg.is_set(ArgSettings::TakesValue)).map_err(invalid)?;
+        check_option_args(&args, command).map_err(invalid)?;
+        out.push(DefaultOption {
+            name: arg.get_name().to_string(),
+            args,
+        });
+    }
+
+    Ok(out)
+}
+
+/// Options from environment variables, named for the long name of each
+/// option with an `ST_` prefix, e.g. `ST_PRECISION` or `ST_KEY_CASE`, for
+/// the options that the subcommand (or no subcommand) has.
+fn env_options(command: Option<&str>) -> Result<Vec<DefaultOption>, CliError> {
+    let mut out = Vec::new();
+
+    for arg in command_args(command) {
+        let key = match arg.get_long() {
+            Some(key) if !NOT_CONFIGURABLE.contains(&key) => key,
+            _ => continue,
+        };
+
+        let var = env_var_name(key);
+        let value = match env::var(&var) {
+            Ok(v) => v,
+            Err(env::VarError::NotPresent) => continue,
+            Err(e) => {
+                return Err(CliError::new(
+                    EXIT_USAGE,
+                    format!("Invalid environment variable {}: {}", var, e),
+                ))
+            }
+        };
+
+        let invalid = |msg: String| CliError::new(EXIT_USAGE, format!("Invalid environment variable {}: {}", var, msg));
+        let args = env_option_args(key, &value, arg.is_set(ArgSettings::TakesValue)).map_err(invalid)?;
+        check_option_args(&args, command).map_err(invalid)?;
+        out.push(DefaultOption {
+            name: arg.get_name().to_string(),
+            args,
+        });
+    }
+
+    Ok(out)
+}
+
+/// Name of the environment variable for an option, e.g. `ST_KEY_CASE` for
+/// `--key-case`.
+fn env_var_name(key: &str) -> String {
+    format!("ST_{}", key.to_uppercase().replace('-', "_"))
+}
+
+/// Command line arguments for a single option from a config file, e.g.
+/// `--precision=2` for `precision = 2`. Arrays are joined with commas, e.g.
+/// `--percentiles=50,90` for `percentiles = [50, 90]`.
+fn option_args(key: &str, value: &toml::Value, takes_value: bool) -> Result<Vec<String>, String> {
+    use toml::Value;
+
+    let scalar = |value: &Value| match value {
+        Value::String(v) => Ok(v.clone()),
+        Value::Integer(v) => Ok(v.to_string()),
+        Value::Float(v) => Ok(v.to_string()),
+        _ => Err(format!("unsupported {} value", value.type_str())),
+    };
+
+    match value {
+        Value::Boolean(true) => Ok(vec![format!("--{}", key)]),
+        Value::Boolean(false) => Ok(Vec::new()),
+        _ if !takes_value => Err(format!("expected true or false, got {}", value.type_str())),
+        Value::Array(vals) => {
+            let vals = vals.iter().map(scalar).collect::<Result<Vec<String>, String>>()?;
+            Ok(vec![format!("--{}={}", key, vals.join(","))])
+        }
+        v => Ok(vec![format!("--{}={}", key, scalar(v)?)]),
+    }
+}
+
+/// Command line arguments for a single option from an environment variable,
+/// e.g. `--precision=2` for `ST_PRECISION=2`. Flags are set by `true` or `1`
+/// and not set by `false`, `0`, or an empty value.
+fn env_option_args(key: &str, value: &str, takes_value: bool) -> Result<Vec<String>, String> {
+    match value {
+        "true" | "1" if !takes_value => Ok(vec![format!("--{}", key)]),
+        "false" | "0" | "" if !takes_value => Ok(Vec::new()),
+        _ if !takes_value => Err(format!("expected true or false, got '{}'", value)),
+        _ => Ok(vec![format!("--{}={}", key, value)]),
+    }
+}
+
+/// Check that the arguments for a single option are valid on their own,
+/// the same way as if they were given on the command line (to a subcommand,
+/// if there is one). Arguments that are required, e.g. files, aren't.
+fn check_option_args(args: &[String], command: Option<&str>) -> Result<(), String> {
+    if args.is_empty() {
+        return Ok(());
+    }
+
+    let mut all = vec!["st".to_string()];
+    all.extend(command.map(String::from));
+    all.extend(args.iter().cloned());
+    match StaccatoOptions::into_app().try_get_matches_from(all) {
+        Err(e) if e.kind != ErrorKind::MissingRequiredArgument => Err(clap_message(&e)),
+        _ => Ok(()),
+    }
+}
+
+/// Arguments for default options from each layer, highest precedence first,
+/// leaving out those that are given on the command line, that conflict with
+/// one that is, or that are given by a layer with higher precedence.
+fn default_args(
+    layers: Vec<Vec<DefaultOption>>,
+    args: &[OsString],
+    index: usize,
+    given: &ArgMatches,
+) -> Result<Vec<String>, CliError> {
+    let app = StaccatoOptions::into_app();
+    let mut out: Vec<String> = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+
+    for layer in layers {
+        let higher = out.clone();
+        for option in layer {
+            if option.args.is_empty() || given.occurrences_of(&option.name) > 0 || names.contains(&option.name) {
+                continue;
+            }
+
+            let mut candidate = higher.clone();
+            candidate.extend(option.args.iter().cloned());
+            match app
+                .clone()
+                .try_get_matches_from(with_default_args(args, &candidate, index))
+            {
+                Err(e) if e.kind == ErrorKind::ArgumentConflict => continue,
+                _ => {
+                    names.push(option.name);
+                    out.extend(option.args);
+                }
+            }
+        }
+    }
+
+    app.try_get_matches_from(with_default_args(args, &out, index))
+        .map_err(|e| CliError::new(EXIT_CONFIG, format!("Invalid default options: {}", clap_message(&e))))
+        .map(|_| out)
+}
+
+/// Command line arguments with default options inserted before the argument
+/// at `index`, i.e. before those given on the command line for `st` itself or
+/// a subcommand.
+fn with_default_args(args: &[OsString], defaults: &[String], index: usize) -> Vec<OsString> {
+    let mut out: Vec<OsString> = args.iter().take(index).cloned().collect();
+    out.extend(defaults.iter().map(OsString::from));
+    out.extend(args.iter().skip(index).cloned());
+    out
+}
+
+/// First line of a clap error, without the "error: " prefix or usage.
+fn clap_message(e: &clap::Error) -> String {
+    let message = e.to_string();
+    let line = message.lines().next().unwrap_or_default();
+    line.trim_start_matches("error: ").to_string()
+}
+
+fn main() {
+    let start = Instant::now();
+    let opts = parse_options();
+    VERBOSITY.store(opts.output.verbose, Ordering::Relaxed);
+    QUIET.store(opts.output.quiet, Ordering::Relaxed);
+    interrupt::install();
+
+    match opts.command {
+        None => run_summary(&opts.stats, &opts.output),
+        Some(Command::Summary(stats)) => run_summary(&stats, &opts.output),
+        Some(Command::Hist(hist)) => run_hist(&hist, &opts.output),
+        Some(Command::Compare(compare)) => run_compare(&compare, &opts.output),
+        #[cfg(feature = "serde")]
+        Some(Command::Merge(merge)) => run_merge(&merge, &opts.output),
+    }
+
+    info!("finished in {:?}", start.elapsed());
+
+    if interrupt::truncated() {
+        warn!("input was interrupted, only values read before then were used");
+        process::exit(EXIT_INTERRUPTED);
+    }
+}
+
+/// Formatter for statistics using the output options.
+fn bundle_formatter(
+    output: &OutputOptions,
+    keys: StatisticsConfig,
+    label: Option<&str>,
+    unit: Option<UnitConversion>,
+) -> Box<dyn BundleFormatter> {
+    output.format.unwrap_or_default().formatter(output, keys, label, unit)
+}
+
+/// Conversion of statistics from the unit of the values to the unit they
+/// should be displayed in, if either is known.
+fn unit_conversion(unit_in: Option<Unit>, unit_out: Option<Unit>) -> Result<Option<UnitConversion>, CliError> {
+    match (unit_in, unit_out) {
+        (None, None) => Ok(None),
+        (None, Some(_)) => Err(CliError::new(EXIT_USAGE, "The --unit-out option requires --unit-in")),
+        (Some(from), to) => UnitConversion::new(from, to.unwrap_or(from)).map(Some).ok_or_else(|| {
+            CliError::new(
+                EXIT_USAGE,
+                format!("Values in {} can't be displayed in {}", from, to.unwrap_or(from)),
+            )
+        }),
+    }
+}
+
+/// Label to prefix keys with, either given or the name of the only file.
+fn key_label(label: &Option<Option<String>>, files: &[PathBuf]) -> Result<Option<String>, CliError> {
+    match (label, files) {
+        (None, _) => Ok(None),
+        (Some(Some(l)), _) => Ok(Some(l.clone())),
+        (Some(None), [f]) => Ok(Some(
+            f.file_stem().unwrap_or(f.as_os_str()).to_string_lossy().into_owned(),
+        )),
+        (Some(None), _) => Err(CliError::new(
+            EXIT_USAGE,
+            "The --label option requires TEXT unless there is exactly one FILE",
+        )),
+    }
+}
+
+/// Table of the key of each statistic that would be displayed, the name of
+/// it for --fail-if, and its description, for --list-keys. Without any
+/// percentiles or quantiles, the form of their keys is listed instead.
+fn key_list(config: &StaccatoConfig, key_case: KeyCase) -> String {
+    let (keys, percents, quantiles) = (config.statistics(), config.percentiles().as_slice(), config.quantiles());
+    const SORTS: &str = " (sorts the values)";
+    let note = |name: StatName| {
+        if name.needs_extra_pass() {
+            " (extra pass over the values)"
+        } else {
+            ""
+        }
+    };
+    let names: Vec<StatName> = StatName::ALL.iter().copied().filter(|&n| keys.includes(n)).collect();
+
+    let mut rows = vec![("key".to_string(), "condition".to_string(), "description".to_string())];
+    for &name in &names {
+        let desc = format!("{}{}", name.description(), note(name));
+        rows.push((key_case.apply(name.as_str()).into_owned(), name.to_string(), desc));
+    }
+
+    rows.push((
+        key_case.apply("sample_count").into_owned(),
+        "-".to_string(),
+        "number of values statistics were estimated from (only with --reservoir)".to_string(),
+    ));
+
+    if quantiles.is_empty() {
+        rows.push((
+            "pQ".to_string(),
+            "pQ".to_string(),
+            "value at quantile Q (with --quantiles)".to_string(),
+        ));
+    }
+    for q in quantiles {
+        let stat = format!("p{}", q);
+        let key = match config.quantile_names().iter().find(|(p, _)| p == q) {
+            Some((_, name)) => name.clone(),
+            None => stat.clone(),
+        };
+        rows.push((key, stat, format!("value at quantile {}{}", q, SORTS)));
+    }
+
+    if percents.is_empty() {
+        rows.push((
+            "STAT_P".to_string(),
+            "pP.STAT".to_string(),
+            "statistic of the lowest P percent of values (with --percentiles)".to_string(),
+        ));
+    }
+    for p in percents {
+        for &name in &names {
+            let desc = format!("{} in the lowest {} percent{}", name.description(), p, SORTS);
+            let key = key_case.apply(&format!("{}_{}", name, p)).into_owned();
+            rows.push((key, format!("p{}.{}", p, name), desc));
+        }
+    }
+
+    let key_width = rows.iter().map(|r| r.0.chars().count()).max().unwrap_or(0);
+    let stat_width = rows.iter().map(|r| r.1.chars().count()).max().unwrap_or(0);
+    rows.iter()
+        .map(|(key, stat, desc)| {
+            format!(
+                "{:<kw$}  {:<sw$}  {}\n",
+                key,
+                stat,
+                desc,
+                kw = key_width,
+                sw = stat_width
+            )
+        })
+        .collect()
+}
+
+/// Display statistics of the values, or something else computed from them
+/// such as a boxplot, depending on the options.
+fn run_summary(opts: &StatsOptions, output: &OutputOptions) {
+    if let Some(to) = &opts.compare {
+        warn!(
+            "--compare is deprecated, use 'st compare --to {}' instead",
+            to.display()
+        );
+        let compare = CompareOptions {
+            input: opts.input.clone(),
+            to: to.clone(),
+            percentiles: opts.percentiles.clone(),
+            quantiles: opts.quantiles.clone(),
+            quantile_method: opts.quantile_method,
+            threshold: opts.threshold,
+            boxplot: opts.boxplot,
+            whiskers: opts.whiskers,
+        };
+        return run_compare(&compare, output);
+    }
+    let extractor = if opts.timer {
+        Some(InputExtractor::Duration(DurationExtractor))
+    } else {
+        InputExtractor::from(&opts.input).unwrap_or_else(|e| e.exit())
+    };
+    let timer_defaults = opts.timer
+        && opts.percentiles.is_empty()
+        && opts.quantiles.is_none()
+        && !opts.all_quantiles
+        && !opts.quartiles
+        && !opts.summary;
+    let percents = if timer_defaults {
+        Percentiles::new(TIMER_PERCENTILES).expect("valid timer percentiles")
+    } else {
+        Percentiles::merged(&opts.percentiles)
+    };
+    let mut quantiles = if opts.all_quantiles {
+        Quantiles::all()
+    } else if timer_defaults {
+        Quantiles {
+            value: TIMER_QUANTILES.to_vec(),
+        }
+    } else {
+        opts.quantiles.clone().unwrap_or_default()
+    };
+    if opts.quartiles {
+        quantiles = quantiles.with_named(QUARTILES);
+    }
+    if opts.summary {
+        quantiles = quantiles.with_named(FIVE_NUMBERS);
+    }
+    let method = opts.quantile_method.unwrap_or_default();
+    let separator = output.separator.clone().unwrap_or_default();
+    let keys = match opts.keys {
+        Some(k) => k,
+        None if opts.summary => StatisticsConfig::none().with_count(true),
+        None => StatisticsConfig::default(),
+    };
+    let key_case = output.key_case.unwrap_or_default();
+    let mut config = StaccatoConfig::new()
+        .with_percentiles(percents.clone())
+        .with_quantiles(&quantiles.value)
+        .with_quantile_method(method)
+        .with_statistics(keys)
+        .with_error_policy(if opts.strict {
+            ErrorPolicy::Fail
+        } else {
+            ErrorPolicy::Skip
+        })
+        .with_malformed_samples(opts.malformed_samples)
+        .with_min_samples(opts.min_samples);
+    if opts.quartiles {
+        for &(q, name) in QUARTILES {
+            config = config.with_quantile_name(q, name);
+        }
+    }
+    if opts.summary {
+        for &(q, name) in FIVE_NUMBERS {
+            config = config.with_quantile_name(q, name);
+        }
+    }
+    let reading = ReadOptions {
+        errors: config.error_policy(),
+        malformed_samples: config.malformed_samples(),
+        ..ReadOptions::from(&opts.input, extractor.as_ref())
+    };
+    let label = key_label(&opts.label, &opts.input.files).unwrap_or_else(|e| e.exit());
+    let unit_in = match opts.unit_in {
+        None if opts.timer && opts.unit_out.is_some() => Some(Unit::Milliseconds),
+        u => u,
+    };
+    let unit = unit_conversion(unit_in, opts.unit_out).unwrap_or_else(|e| e.exit());
+    let formatter = bundle_formatter(output, keys, label.as_deref(), unit);
+
+    if opts.list_keys {
+        print_output(key_list(&config, key_case));
+        return;
+    }
+
+    if opts.validate {
+        validate_inputs(&opts.input.files, reading, &separator);
+        return;
+    }
+
+    if opts.watch {
+        let clear = opts.clear && stdout().is_terminal();
+        watch_inputs(&opts.input.files, &config, reading, &*formatter, clear);
+        return;
+    }
+
+    if opts.per_file {
+        let sorted = config.sorting_policy().is_sorted();
+        let bundles = per_file_bundles(&opts.input.files, &config, sorted, reading, opts.short_labels);
+        let labeled: Vec<(&str, &StatisticsBundle)> = bundles.iter().map(|(l, v)| (l.as_str(), v)).collect();
+        if let Some(dir) = &opts.output_dir {
+            let extension = output.format.unwrap_or_default().extension();
+            write_per_file(dir, &labeled, &*formatter, extension, &separator).unwrap_or_else(|e| e.exit());
+        } else if !labeled.is_empty() {
+            print_output(FormattedBundles::new(&*formatter, &labeled));
+        }
+
+        return;
+    }
+
+    if opts.integers {
+        match read_integers(&opts.input.files, !percents.is_empty(), reading) {
+            Ok(vals) => match IntegerStatisticsBundle::with_percentiles(&vals, percents.as_slice()) {
+                Some(v) => {
+                    let mut formatter = IntegerStatisticsFormatter::with_sep(&v, separator)
+                        .precision(output.precision)
+                        .config(keys)
+                        .key_case(key_case);
+                    if let Some(l) = &label {
+                        formatter = formatter.label(l);
+                    }
+                    if let Some(r) = output.rounding() {
+                        formatter = formatter.rounding(r);
+                    }
+
+                    print_output(formatter)
+                }
+                None => warn!("{}", StaccatoError::EmptyInput),
+            },
+            Err(e) => e.exit(),
+        }
+
+        return;
+    }
+
+    if display_values(opts, output, &config, reading, &*formatter) {
+        return;
+    }
+
+    if opts.timing {
+        let mut timings = Timings::new();
+        match timed_bundle(&opts.input.files, &config, reading, &mut timings) {
+            Ok(Some(v)) => {
+                timings.time(Phase::Format, || print_bundle(&*formatter, &v));
+                eprint!("{}", TimingsFormatter::with_sep(&timings, separator));
+                check_conditions(&v, &opts.fail_if, output.precision);
+            }
+            Ok(None) => warn!("{}", StaccatoError::EmptyInput),
+            Err(e) => e.exit(),
+        }
+
+        return;
+    }
+
+    match computed_bundle(opts, &config, reading, &*formatter) {
+        Ok(Some(v)) => {
+            print_bundle(&*formatter, &v);
+            check_conditions(&v, &opts.fail_if, output.precision);
+        }
+        Ok(None) => warn!("{}", StaccatoError::EmptyInput),
+        Err(e) => e.exit(),
+    }
+}
+
+/// Display something computed from the values other than their statistics
+/// (e.g. a boxplot) if the options ask for one, returning whether anything
+/// was displayed.
+fn display_values(
+    opts: &StatsOptions,
+    output: &OutputOptions,
+    config: &StaccatoConfig,
+    reading: ReadOptions,
+    formatter: &dyn BundleFormatter,
+) -> bool {
+    let separator = output.separator.clone().unwrap_or_default();
+    let method = config.quantile_method();
+
+    let cumsum = opts.cumsum.map(|m| m.unwrap_or_default());
+    if let Some(mode) = cumsum.filter(|&m| m != CumulativeSumMode::Stats) {
+        let lines = read_values(&opts.input.files, sorting_policy(false), reading);
+        print_output(
+            CumulativeSumFormatter::with_sep(&lines, separator)
+                .with_values(mode == CumulativeSumMode::WithValues)
+                .precision(output.precision),
+        );
+    } else if let Some(normalization) = opts.normalize {
+        let lines = read_values(&opts.input.files, sorting_policy(false), reading);
+        if let Some(stats) = Statistics::from(&lines, None) {
+            let normalizer = Normalizer::new(normalization, &stats);
+            print_output(NormalizationFormatter::new(&lines, &normalizer).precision(output.precision));
+        } else {
+            warn!("{}", StaccatoError::EmptyInput);
+        }
+    } else if let Some(annotation) = opts.annotate {
+        let lines = read_values(&opts.input.files, sorting_policy(false), reading);
+        let mut sorted_lines = lines.clone();
+        sort_values(&mut sorted_lines);
+
+        let annotator = Annotator::new(annotation, &sorted_lines, method);
+        print_output(AnnotationFormatter::with_sep(&lines, &annotator, separator).precision(output.precision));
+    } else if let Some(points) = opts.cdf {
+        let lines = read_values(&opts.input.files, sorting_policy(true), reading);
+        if opts.with_summary {
+            if let Some(v) = config.bundle(&lines) {
+                print_bundle(formatter, &v);
+            }
+        }
+
+        if lines.is_empty() {
+            warn!("{}", StaccatoError::EmptyInput);
+        } else {
+            let points = points.unwrap_or_default();
+            print_output(CdfFormatter::with_sep(&lines, points, separator).precision(output.precision));
+        }
+    } else if opts.boxplot {
+        let whiskers = opts.whiskers.unwrap_or_default();
+        print_boxplots(&opts.input.files, None, whiskers, method, reading, output.precision);
+    } else {
+        return false;
+    }
+
+    true
+}
+
+/// Compute statistics of the values, estimated if the options ask for it
+/// and otherwise from every value in memory the same way as the `run_from`
+/// library function. `None` is returned if there aren't any values.
+fn computed_bundle(
+    opts: &StatsOptions,
+    config: &StaccatoConfig,
+    reading: ReadOptions,
+    formatter: &dyn BundleFormatter,
+) -> Result<Option<StatisticsBundle>, CliError> {
+    let files = &opts.input.files;
+    let (percents, quantiles, method) = (config.percentiles(), config.quantiles(), config.quantile_method());
+
+    if opts.stream {
+        let mut stats = StreamingStatistics::new();
+        let interim = |s: &StreamingStatistics| match s.bundle(quantiles) {
+            Some(v) => eprint!("{}", FormattedBundle::new(formatter, &config.finish(v))),
+            None => warn!("{}", StaccatoError::EmptyInput),
+        };
+
+        report::install();
+        for file in each_input(files) {
+            open_input(file, reading)
+                .and_then(|mut r| push_streamed(&mut stats, &mut r, interim).map_err(|e| CliError::parse(file, e)))?;
+        }
+
+        return Ok(stats.bundle(quantiles).map(|b| config.finish(b)));
+    }
+
+    if let Some(size) = opts.reservoir {
+        let mut reservoir = Reservoir::with_rng(size, Rng::new(sampling_seed(opts.seed)));
+        for file in each_input(files) {
+            open_input(file, reading)
+                .and_then(|mut r| reservoir.push_reader(&mut r).map_err(|e| CliError::parse(file, e)))?;
+        }
+
+        let bundle = reservoir.bundle(percents.as_slice(), quantiles, method);
+        return Ok(bundle.map(|b| config.finish(b)));
+    }
+
+    if let Some(dir) = &opts.spill {
+        let dir = dir.clone().unwrap_or_else(env::temp_dir);
+        let run_size = opts.spill_run_size.unwrap_or(DEFAULT_RUN_SIZE);
+        let bundle = spill_bundle(files, reading, dir, run_size, percents.as_slice(), quantiles, method)?;
+        return Ok(bundle.map(|b| config.finish(b)));
+    }
+
+    if let Some(size) = opts.max_memory {
+        let seed = sampling_seed(opts.seed);
+        let bundle = limited_bundle(files, reading, size.bytes, seed, percents.as_slice(), quantiles, method)?;
+        return Ok(bundle.map(|b| config.finish(b)));
+    }
+
+    if opts.precision_mode == Some(PrecisionMode::F32) {
+        let lines = read_values_f32(files, config.sorting_policy().is_sorted(), reading);
+        return Ok(config.bundle(&lines));
+    }
+
+    let totals = opts.cumsum.map(|m| m.unwrap_or_default()) == Some(CumulativeSumMode::Stats);
+    let res = run_from(config, |sorting| {
+        if !totals {
+            return Ok(read_values(files, sorting, reading));
+        }
+
+        // Totals need to be computed from values in their original order
+        // and then sorted if required, instead of sorting the values.
+        let vals = read_values(files, sorting_policy(false), reading);
+        let mut totals: Vec<f64> = CumulativeSum::new(vals.into_iter()).collect();
+        if sorting.is_sorted() {
+            sort_values(&mut totals);
+        }
+
+        Ok(totals)
+    });
+
+    match res {
+        Ok(v) => Ok(Some(v)),
+        Err(StaccatoError::EmptyInput) => Ok(None),
+        Err(e) => {
+            let message = format!("Could not parse values from {}: {}", input_label(files), e);
+            Err(CliError::new(exit_code(&e), message))
+        }
+    }
+}
+
+/// Display how many lines of each input were values, blank, or malformed
+/// instead of computing statistics, exiting with an error if any lines were
+/// malformed.
+fn validate_inputs(files: &[PathBuf], reading: ReadOptions, separator: &KeyValueSep) {
+    let mut malformed = false;
+    for file in each_input(files) {
+        let parsed = open_input(file, reading)
+            .and_then(|r| {
+                match reading.extractor {
+                    Some(e) => collect_parsed_values_sampled(r, e, reading.malformed_samples),
+                    None => collect_parsed_values_sampled(r, PlainExtractor, reading.malformed_samples),
+                }
+                .map_err(|e| CliError::parse(file, e))
+            })
+            .unwrap_or_else(|e| e.exit());
+
+        if let Some(f) = file.filter(|_| files.len() > 1) {
+            print_output(format!("file{}{}\n", separator, f.display()));
+        }
+
+        print_output(ValidationFormatter::with_sep(&parsed, separator.clone()));
+        malformed |= parsed.malformed() > 0;
+    }
+
+    if malformed {
+        process::exit(EXIT_DATA_ERR);
+    }
+}
+
+/// Label of the statistics of a single input with `--per-file`.
+fn per_file_label(file: Option<&PathBuf>, short: bool) -> String {
+    match file {
+        Some(f) if short => f.file_name().unwrap_or(f.as_os_str()).to_string_lossy().into_owned(),
+        Some(f) => f.display().to_string(),
+        None => "stdin".to_string(),
+    }
+}
+
+/// Statistics of the values in each of the files followed by statistics of
+/// all of them, each with its label. Values are only read once, statistics
+/// of all of them are computed by combining the values from each file. No
+/// statistics are returned if there aren't any values.
+fn per_file_bundles(
+    files: &[PathBuf],
+    config: &StaccatoConfig,
+    sorted: bool,
+    reading: ReadOptions,
+    short_labels: bool,
+) -> Vec<(String, StatisticsBundle)> {
+    let inputs = each_input(files);
+    let mut labels: Vec<String> = inputs.iter().map(|f| per_file_label(*f, short_labels)).collect();
+    labels.push(TOTAL_LABEL.to_string());
+
+    let mut seen = HashSet::new();
+    if let Some(label) = labels.iter().find(|l| !seen.insert(l.as_str())) {
+        let message = format!("Statistics of more than one input would be labeled '{}'", label);
+        CliError::new(EXIT_USAGE, message).exit();
+    }
+
+    let presorted = reading.presorted.filter(|_| sorted);
+    let mut per_file = inputs
+        .iter()
+        .map(|f| read_input(*f, sorted && presorted.is_none(), reading))
+        .collect::<Result<Vec<Vec<f64>>, CliError>>()
+        .unwrap_or_else(|e| e.exit());
+
+    if let Some(mode) = presorted {
+        for (vals, label) in per_file.iter_mut().zip(labels.iter()) {
+            check_presorted(vals, mode, label);
+        }
+    }
+
+    let total = if sorted {
+        merge_sorted_values(&per_file)
+    } else {
+        per_file.concat()
+    };
+
+    let mut bundles = Vec::with_capacity(labels.len());
+    for (label, vals) in labels.into_iter().zip(per_file.iter().chain(Some(&total))) {
+        match config.bundle(vals) {
+            Some(v) => bundles.push((label, v)),
+            None => warn!("{} for {}", StaccatoError::EmptyInput, label),
+        }
+    }
+
+    bundles
+}
+
+/// Name of the index of files written by `--output-dir`, which is never used
+/// for the statistics of an input.
+const INDEX_FILE: &str = "index";
+
+/// Write each of the labeled bundles to its own file in a directory (which
+/// is created if needed) along with an index of the label and name of each
+/// file, one `label<SEP>name` line for each.
+fn write_per_file(
+    dir: &Path,
+    bundles: &[(&str, &StatisticsBundle)],
+    formatter: &dyn BundleFormatter,
+    extension: &str,
+    separator: &KeyValueSep,
+) -> Result<(), CliError> {
+    let write = |path: &Path, contents: String| {
+        fs::write(path, contents)
+            .map_err(|e| CliError::new(EXIT_IO_ERR, format!("Cannot write file {}: {}", path.display(), e)))
+    };
+
+    fs::create_dir_all(dir)
+        .map_err(|e| CliError::new(EXIT_IO_ERR, format!("Cannot create directory {}: {}", dir.display(), e)))?;
+
+    let labels: Vec<&str> = bundles.iter().map(|(l, _)| *l).collect();
+    let mut index = String::new();
+    for ((label, bundle), name) in bundles.iter().zip(unique_file_names(&labels, extension)) {
+        write(&dir.join(&name), FormattedBundle::new(formatter, bundle).to_string())?;
+        index.push_str(&format!("{}{}{}\n", label, separator, name));
+    }
+
+    write(&dir.join(format!("{}.txt", INDEX_FILE)), index)?;
+    info!("wrote statistics of {} inputs to {}", bundles.len(), dir.display());
+    Ok(())
+}
+
+/// Name of a file for each label that's safe to create in a directory, with
+/// an extension. Names that would collide (ignoring case, for file systems
+/// that do) get a suffix of '-2', '-3', and so on in the order of the labels.
+fn unique_file_names(labels: &[&str], extension: &str) -> Vec<String> {
+    let mut taken: HashSet<String> = HashSet::new();
+    taken.insert(INDEX_FILE.to_string());
+
+    labels
+        .iter()
+        .map(|label| {
+            let base = safe_file_name(label);
+            let mut name = base.clone();
+            let mut n = 1;
+            while !taken.insert(name.to_lowercase()) {
+                n += 1;
+                name = format!("{}-{}", base, n);
+            }
+
+            format!("{}.{}", name, extension)
+        })
+        .collect()
+}
+
+/// Maximum length in bytes of a file name before its suffix and extension,
+/// well under the limit of most file systems.
+const MAX_FILE_NAME: usize = 200;
+
+/// Label turned into a name that's safe to create a file with: path
+/// separators and control characters (including NUL) are replaced with '_',
+/// as are leading dots so that the file isn't hidden (or '.' or '..'). Long
+/// labels are shortened.
+fn safe_file_name(label: &str) -> String {
+    let mut name = String::with_capacity(label.len());
+    let mut leading = true;
+    for c in label.chars() {
+        if name.len() + c.len_utf8() > MAX_FILE_NAME {
+            break;
+        }
+
+        leading = leading && c == '.';
+        if leading || c == '/' || c == '\\' || c.is_control() {
+            name.push('_');
+        } else {
+            name.push(c);
+        }
+    }
+
+    if name.is_empty() {
+        name.push('_');
+    }
+
+    name
+}
+
+/// Modification time, size, and (on Unix) inode of a file, which change when
+/// the file is written to or another file is renamed over it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    #[cfg(unix)]
+    inode: u64,
+}
+
+impl FileStamp {
+    /// Stamp of the file at a path, or `None` if it doesn't currently exist
+    /// (e.g. between being removed and replaced).
+    fn of(path: &Path) -> Option<FileStamp> {
+        #[cfg(unix)]
+        use std::os::unix::fs::MetadataExt;
+
+        let meta = fs::metadata(path).ok()?;
+        Some(FileStamp {
+            modified: meta.modified().ok(),
+            len: meta.len(),
+            #[cfg(unix)]
+            inode: meta.ino(),
+        })
+    }
+}
+
+/// Stop reading input when interrupted (e.g. by Ctrl-C) so that statistics
+/// of the values read so far are still displayed, and stop watching inputs
+/// after the current report instead of being killed part way through
+/// displaying it. An interrupt at any other time, or a second interrupt,
+/// exits immediately. Only SIGINT is handled this way: SIGTERM keeps its
+/// default behavior of terminating without displaying anything.
+#[cfg(unix)]
+mod interrupt {
+    use std::io::{self, Read};
+    use std::mem;
+    use std::ptr;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+    static TRUNCATED: AtomicBool = AtomicBool::new(false);
+    /// Number of readers (or watches) that stop when interrupted.
+    static DEFERRED: AtomicUsize = AtomicUsize::new(0);
+
+    extern "C" fn handle(sig: libc::c_int) {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) || DEFERRED.load(Ordering::SeqCst) == 0 {
+            // SAFETY: Exiting without running any destructors or flushing
+            // output is safe to do from a signal handler.
+            unsafe { libc::_exit(128 + sig) }
+        }
+    }
+
+    pub fn install() {
+        // SAFETY: The handler only uses atomics and exits, which is safe to
+        // do from a signal handler. Reads aren't restarted after the signal
+        // so that a reader blocked waiting for input can stop.
+        unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGINT, &action, ptr::null_mut());
+        }
+    }
+
+    pub fn interrupted() -> bool {
+        INTERRUPTED.load(Ordering::SeqCst)
+    }
+
+    /// Whether any reader stopped before the end of its input because of
+    /// an interrupt.
+    pub fn truncated() -> bool {
+        TRUNCATED.load(Ordering::SeqCst)
+    }
+
+    /// Handle interrupts by stopping instead of exiting until dropped.
+    pub struct Deferred(());
+
+    pub fn defer() -> Deferred {
+        DEFERRED.fetch_add(1, Ordering::SeqCst);
+        Deferred(())
+    }
+
+    impl Drop for Deferred {
+        fn drop(&mut self) {
+            DEFERRED.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Reader that ends early, as if it had reached the end of its input,
+    /// once interrupted.
+    struct Interruptible<R> {
+        inner: R,
+        _deferred: Deferred,
+    }
+
+    impl<R: Read> Read for Interruptible<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !interrupted() {
+                match self.inner.read(buf) {
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted && interrupted() => {}
+                    res => return res,
+                }
+            }
+
+            TRUNCATED.store(true, Ordering::SeqCst);
+            Ok(0)
+        }
+    }
+
+    pub fn interruptible<R: Read + 'static>(reader: R) -> Box<dyn Read> {
+        Box::new(Interruptible {
+            inner: reader,
+            _deferred: defer(),
+        })
+    }
+}
+
+#[cfg(not(unix))]
+mod interrupt {
+    use std::io::Read;
+
+    pub fn install() {}
+
+    pub fn interrupted() -> bool {
+        false
+    }
+
+    pub fn truncated() -> bool {
+        false
+    }
+
+    pub struct Deferred(());
+
+    pub fn defer() -> Deferred {
+        Deferred(())
+    }
+
+    pub fn interruptible<R: Read + 'static>(reader: R) -> Box<dyn Read> {
+        Box::new(reader)
+    }
+}
+
+/// Requests for statistics of the values read so far while streaming them,
+/// made by sending SIGUSR1 the same way as for `dd`. The handler only sets a
+/// flag that's checked between values so that statistics are never read
+/// while they're being updated.
+#[cfg(unix)]
+mod report {
+    use std::mem;
+    use std::ptr;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn handle(_: libc::c_int) {
+        REQUESTED.store(true, Ordering::SeqCst);
+    }
+
+    pub fn install() {
+        // SAFETY: The handler only stores to an atomic, which is safe to do
+        // from a signal handler. Reads are restarted after the signal so that
+        // reading continues.
+        unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGUSR1, &action, ptr::null_mut());
+        }
+    }
+
+    /// Whether a report was requested since the last time this was called.
+    pub fn requested() -> bool {
+        REQUESTED.swap(false, Ordering::SeqCst)
+    }
+}
+
+#[cfg(not(unix))]
+mod report {
+    pub fn install() {}
+
+    pub fn requested() -> bool {
+        false
+    }
+}
+
+/// Display statistics of the values in each of the files every time any of
+/// them change until interrupted.
+fn watch_inputs(
+    files: &[PathBuf],
+    config: &StaccatoConfig,
+    reading: ReadOptions,
+    formatter: &dyn BundleFormatter,
+    clear: bool,
+) {
+    if files.is_empty() {
+        CliError::new(EXIT_USAGE, "The --watch option requires at least one FILE").exit();
+    }
+
+    if let Some(f) = files.iter().find(|f| !f.exists()) {
+        CliError::new(
+            EXIT_NO_INPUT,
+            format!("Cannot watch file {}: it does not exist", f.display()),
+        )
+        .exit();
+    }
+
+    let _deferred = interrupt::defer();
+    info!("watching {} for changes", input_label(files));
+
+    let mut last: Option<Vec<Option<FileStamp>>> = None;
+    while !interrupt::interrupted() {
+        let stamps: Vec<Option<FileStamp>> = files.iter().map(|f| FileStamp::of(f)).collect();
+        // Files that are missing are likely being replaced, so wait for them
+        // to exist again instead of displaying an error.
+        if last.as_ref() != Some(&stamps) && stamps.iter().all(Option::is_some) {
+            if clear {
+                print_output(CLEAR_SCREEN);
+            } else if last.is_some() {
+                print_output(WATCH_SEPARATOR);
+            }
+
+            match watched_bundle(files, config, reading) {
+                Ok(v) => print_bundle(formatter, &v),
+                Err(e) => warn!("{}", e),
+            }
+
+            last = Some(stamps);
+        }
+
+        thread::sleep(WATCH_INTERVAL);
+    }
+
+    info!("stopped watching {}", input_label(files));
+}
+
+/// Compute statistics of the values in each of the files as if they were a
+/// single file, the same way as the `run` library function.
+fn watched_bundle(
+    files: &[PathBuf],
+    config: &StaccatoConfig,
+    reading: ReadOptions,
+) -> Result<StatisticsBundle, CliError> {
+    let mut reader = chained_input(files, reading)?;
+    match reading.extractor {
+        Some(e) => run_with(config, &mut reader, e),
+        None => run(config, &mut reader),
+    }
+    .map_err(|e| {
+        let message = format!("Could not parse values from {}: {}", input_label(files), e);
+        CliError::new(exit_code(&e), message)
+    })
+}
+
+/// Compute statistics of the values in each of the inputs the same way as
+/// `watched_bundle`, measuring the time spent in each phase. `None` is
+/// returned if there aren't any values.
+fn timed_bundle(
+    files: &[PathBuf],
+    config: &StaccatoConfig,
+    reading: ReadOptions,
+    timings: &mut Timings,
+) -> Result<Option<StatisticsBundle>, CliError> {
+    let mut reader = chained_input(files, reading)?;
+    let res = match reading.extractor {
+        Some(e) => run_with_timings(config, &mut reader, e, timings),
+        None => run_with_timings(config, &mut reader, PlainExtractor, timings),
+    };
+
+    match res {
+        Ok(v) => Ok(Some(v)),
+        Err(StaccatoError::EmptyInput) => Ok(None),
+        Err(e) => {
+            let message = format!("Could not parse values from {}: {}", input_label(files), e);
+            Err(CliError::new(exit_code(&e), message))
+        }
+    }
+}
+
+/// Read each of the inputs in order as a single input, or standard input
+/// if there aren't any files.
+fn chained_input(files: &[PathBuf], reading: ReadOptions) -> Result<Box<dyn Read>, CliError> {
+    if files.is_empty() {
+        return open_input(None, reading).map(|r| Box::new(r) as Box<dyn Read>);
+    }
+
+    // Files are separated so that the last line of a file without a line
+    // ending isn't joined to the first line of the next one.
+    let separator: &'static [u8] = if reading.null_data { b"\0" } else { b"\n" };
+    let mut reader: Box<dyn Read> = Box::new(io::empty());
+    for f in files {
+        let handle = File::open(f)
+            .map_err(|e| CliError::new(EXIT_NO_INPUT, format!("Cannot open file {}: {}", f.display(), e)))?;
+        reader = Box::new(reader.chain(handle).chain(separator));
+    }
+
+    if reading.null_data {
+        reader = Box::new(NullDelimited::new(reader));
+    }
+
+    Ok(reader)
+}
+
+/// Display a histogram of the values.
+fn run_hist(opts: &HistOptions, output: &OutputOptions) {
+    let extractor = InputExtractor::from(&opts.input).unwrap_or_else(|e| e.exit());
+    let reading = ReadOptions::from(&opts.input, extractor.as_ref());
+    let outliers = if opts.clamp {
+        OutlierPolicy::Clamp
+    } else {
+        OutlierPolicy::Separate
+    };
+
+    let mut hist = match &opts.edges {
+        // Edges are checked when they're parsed.
+        Some(edges) => Histogram::with_edges(&edges.value).expect("invalid bucket edges"),
+        None if opts.log => Histogram::log_scaled(opts.buckets.unwrap_or(DEFAULT_BUCKETS)),
+        None => Histogram::with_buckets(opts.buckets.unwrap_or(DEFAULT_BUCKETS)),
+    }
+    .outliers(outliers);
+
+    let mut lines = read_values(&opts.input.files, sorting_policy(true), reading);
+    if lines.is_empty() {
+        warn!("{}", StaccatoError::EmptyInput);
+        return;
+    }
+
+    hist.fill(SortedValues::sort(&mut lines));
+    let separator = output.separator.clone().unwrap_or_default();
+    print_output(HistogramFormatter::with_sep(&hist, separator).precision(output.precision));
+}
+
+/// Display statistics (or boxplots) of the values compared to the values
+/// of another file.
+fn run_compare(opts: &CompareOptions, output: &OutputOptions) {
+    let extractor = InputExtractor::from(&opts.input).unwrap_or_else(|e| e.exit());
+    let reading = ReadOptions::from(&opts.input, extractor.as_ref());
+    let method = opts.quantile_method.unwrap_or_default();
+
+    if opts.boxplot {
+        let whiskers = opts.whiskers.unwrap_or_default();
+        print_boxplots(
+            &opts.input.files,
+            Some(&opts.to),
+            whiskers,
+            method,
+            reading,
+            output.precision,
+        );
+        return;
+    }
+
+    let config = StaccatoConfig::new()
+        .with_percentiles(Percentiles::merged(&opts.percentiles))
+        .with_quantiles(&opts.quantiles.clone().unwrap_or_default().value)
+        .with_quantile_method(method);
+    let sorted = config.sorting_policy().is_sorted();
+    print_comparison(
+        &opts.input.files,
+        &opts.to,
+        config,
+        sorted,
+        reading,
+        opts.threshold,
+        output,
+    );
+}
+
+/// Display a boxplot of the values, and of the values of another file if
+/// given, using the same scale.
+fn print_boxplots(
+    files: &[PathBuf],
+    other: Option<&PathBuf>,
+    whiskers: WhiskerPolicy,
+    method: QuantileMethod,
+    reading: ReadOptions,
+    precision: usize,
+) {
+    let mut inputs = vec![(input_label(files), read_values(files, sorting_policy(true), reading))];
+    if let Some(other) = other {
+        inputs.push((
+            input_label(slice::from_ref(other)),
+            read_values(slice::from_ref(other), sorting_policy(true), reading),
+        ));
+    }
+
+    let plots: Vec<(String, BoxPlot)> = inputs
+        .into_iter()
+        .flat_map(|(label, lines)| BoxPlot::from(&lines, whiskers, method).map(|p| (label, p)))
+        .collect();
+
+    if plots.is_empty() {
+        warn!("{}", StaccatoError::EmptyInput);
+        return;
+    }
+
+    let formatter = plots
+        .iter()
+        .fold(BoxPlotFormatter::new(terminal_width()), |f, (label, plot)| {
+            f.plot(label, plot)
+        });
+
+    print_output(formatter.precision(precision));
+}
+
+/// Display statistics of the values and the values of another file side by
+/// side, along with their correlation if they can be paired.
+fn print_comparison(
+    files: &[PathBuf],
+    other: &PathBuf,
+    config: StaccatoConfig,
+    sorted: bool,
+    reading: ReadOptions,
+    threshold: Option<f64>,
+    output: &OutputOptions,
+) {
+    // Values are paired by position so they need to be read in their
+    // original order and then sorted if required.
+    let mut lines_a = read_values(files, sorting_policy(false), reading);
+    let mut lines_b = read_values(slice::from_ref(other), sorting_policy(false), reading);
+    let paired = PairedStatistics::from(&lines_a, &lines_b);
+    if sorted {
+        sort_values(&mut lines_a);
+        sort_values(&mut lines_b);
+    }
+
+    let config = config.with_statistics(StatisticsConfig::default());
+    let stats_a = config.bundle(&lines_a);
+    let stats_b = config.bundle(&lines_b);
+
+    if let (Some(a), Some(b)) = (stats_a, stats_b) {
+        let report = compare_bundles(&a, &b);
+        print_output(
+            ComparisonFormatter::new(&report)
+                .precision(output.precision)
+                .threshold(threshold)
+                .color(stdout().is_terminal()),
+        );
+
+        if let Some(p) = paired {
+            let separator = output.separator.clone().unwrap_or_default();
+            print_output(PairedStatisticsFormatter::with_sep(&p, separator).precision(output.precision));
+        }
+    } else {
+        warn!("No values to compare stats for");
+    }
+}
+
+/// Display statistics combined from files of statistics serialized as JSON.
+#[cfg(feature = "serde")]
+fn run_merge(opts: &MergeOptions, output: &OutputOptions) {
+    let policy = if opts.strict {
+        MergePolicy::Strict
+    } else {
+        MergePolicy::DropUnmatched
+    };
+
+    let (merged, dropped) = match merge_bundles(&opts.files, policy) {
+        Ok(v) => v,
+        Err(e) => e.exit(),
+    };
+
+    if !dropped.is_empty() {
+        let dropped: Vec<String> = dropped.iter().map(|p| p.to_string()).collect();
+        warn!(
+            "omitted percentiles {} (not in every file, use --strict to fail instead)",
+            dropped.join(",")
+        );
+    }
+
+    if !output.quiet {
+        for line in accuracy_lines(&merged) {
+            eprintln!("notice: {}", line);
+        }
+    }
+
+    print_bundle(
+        &*bundle_formatter(output, StatisticsConfig::default(), None, None),
+        &merged,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        default_args_index, env_option_args, env_var_name, exit_code, key_list, log_line, omitted_warning, option_args,
+        safe_file_name, unique_file_names, with_default_args, Edges, LogLevel, MemorySize, OutputFormat, PrecisionMode,
+        ProgressMode, Quantiles, FIVE_NUMBERS, QUARTILES,
+    };
+    use staccato::{KeyCase, Omission, Percentiles, StaccatoConfig, StaccatoError, StatisticsConfig};
+    use std::collections::HashSet;
+    use std::ffi::OsString;
+    use std::io;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_key_list() {
+        let config = StaccatoConfig::new()
+            .with_statistics(StatisticsConfig::none().with_count(true).with_median(true))
+            .with_percentiles(Percentiles::new(&[90]).unwrap())
+            .with_quantiles(&[99.9, 25.0])
+            .with_quantile_name(25.0, "q1");
+        let list = key_list(&config, KeyCase::Kebab);
+        let rows: Vec<Vec<&str>> = list
+            .lines()
+            .map(|l| l.split("  ").map(str::trim).filter(|c| !c.is_empty()).collect())
+            .collect();
+
+        assert_eq!(vec!["key", "condition", "description"], rows[0]);
+        assert_eq!(vec!["count", "count", "number of values"], rows[1]);
+        assert!(rows[2][2].ends_with("(extra pass over the values)"));
+        assert_eq!(
+            vec!["p99.9", "p99.9", "value at quantile 99.9 (sorts the values)"],
+            rows[4]
+        );
+        assert_eq!(vec!["q1", "p25", "value at quantile 25 (sorts the values)"], rows[5]);
+        assert_eq!("count-90", rows[6][0]);
+        assert_eq!("p90.median", rows[7][1]);
+        assert_eq!(8, rows.len());
+    }
+
+    #[test]
+    fn test_exit_codes_distinct() {
+        let io = exit_code(&StaccatoError::Io(io::Error::other("broken")));
+        let data = exit_code(&StaccatoError::ParseFailure {
+            line: 1,
+            content: "x".to_string(),
+        });
+        let usage = exit_code(&StaccatoError::InvalidSeparator);
+
+        let codes: HashSet<i32> = [io, data, usage].iter().cloned().collect();
+        assert_eq!(3, codes.len());
+        assert!(!codes.contains(&0));
+        assert_eq!(data, exit_code(&StaccatoError::Utf8 { line: 1 }));
+    }
+
+    #[test]
+    fn test_option_args() {
+        let args = |toml: &str, takes_value| {
+            let table: toml::Table = toml.parse().unwrap();
+            let (key, value) = table.iter().next().unwrap();
+            option_args(key, value, takes_value)
+        };
+
+        assert_eq!(Ok(vec!["--precision=2".to_string()]), args("precision = 2", true));
+        assert_eq!(
+            Ok(vec!["--percentiles=50,90".to_string()]),
+            args("percentiles = [50, 90]", true)
+        );
+        assert_eq!(Ok(vec!["--separator=tab".to_string()]), args("separator = 'tab'", true));
+        assert_eq!(Ok(vec!["--integers".to_string()]), args("integers = true", false));
+        assert_eq!(Ok(Vec::new()), args("integers = false", false));
+        assert!(args("integers = 'yes'", false).is_err());
+        assert!(args("keys = { count = true }", true).is_err());
+    }
+
+    #[test]
+    fn test_env_option_args() {
+        assert_eq!(
+            Ok(vec!["--precision=2".to_string()]),
+            env_option_args("precision", "2", true)
+        );
+        assert_eq!(
+            Ok(vec!["--integers".to_string()]),
+            env_option_args("integers", "1", false)
+        );
+        assert_eq!(Ok(Vec::new()), env_option_args("integers", "false", false));
+        assert_eq!(Ok(Vec::new()), env_option_args("integers", "", false));
+        assert!(env_option_args("integers", "yes", false).is_err());
+    }
+
+    #[test]
+    fn test_env_var_name() {
+        assert_eq!("ST_PRECISION", env_var_name("precision"));
+        assert_eq!("ST_KEY_CASE", env_var_name("key-case"));
+    }
+
+    #[test]
+    fn test_with_default_args() {
+        let args: Vec<OsString> = ["st", "-p", "50"].iter().map(OsString::from).collect();
+        let out = with_default_args(&args, &["--precision=2".to_string()], 1);
+
+        assert_eq!(vec!["st", "--precision=2", "-p", "50"], out);
+    }
+
+    #[test]
+    fn test_with_default_args_subcommand() {
+        let args: Vec<OsString> = ["st", "--precision=1", "hist", "--log"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        let index = default_args_index(&args, Some("hist"));
+        let out = with_default_args(&args, &["--buckets=5".to_string()], index);
+
+        assert_eq!(3, index);
+        assert_eq!(vec!["st", "--precision=1", "hist", "--buckets=5", "--log"], out);
+        assert_eq!(1, default_args_index(&args, None));
+    }
+
+    #[test]
+    fn test_parse_quantiles_err_not_in_range() {
+        let quantiles = "50,100.1";
+        let res = Quantiles::from_str(quantiles);
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_parse_quantiles_err_not_a_number() {
+        let quantiles = "50,banana";
+        let res = Quantiles::from_str(quantiles);
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_parse_quantiles_ok() {
+        let quantiles = "0,25,99.9,100";
+        let res = Quantiles::from_str(quantiles);
+
+        assert_eq!(vec![0f64, 25f64, 99.9, 100f64], res.unwrap().value);
+    }
+
+    #[test]
+    fn test_quantiles_all() {
+        let quantiles = Quantiles::all();
+
+        assert_eq!(99, quantiles.value.len());
+        assert_eq!(Some(&1f64), quantiles.value.first());
+        assert_eq!(Some(&99f64), quantiles.value.last());
+    }
+
+    #[test]
+    fn test_omitted_warning() {
+        assert_eq!(None, omitted_warning("percentile slices", &[]));
+
+        let omitted = [
+            ("5".to_string(), Omission::InsufficientData),
+            ("10".to_string(), Omission::InsufficientData),
+            ("40".to_string(), Omission::BelowMinSamples { min: 3 }),
+        ];
+        assert_eq!(
+            Some("omitted percentile slices 5,10 (not enough values), 40 (fewer than 3 values)".to_string()),
+            omitted_warning("percentile slices", &omitted)
+        );
+    }
+
+    #[test]
+    fn test_quantiles_with_named() {
+        let quantiles = Quantiles::default().with_named(QUARTILES);
+        assert_eq!(vec![25f64, 50f64, 75f64], quantiles.value);
+
+        let quantiles = Quantiles::from_str("99,50").unwrap().with_named(QUARTILES);
+        assert_eq!(vec![99f64, 50f64, 25f64, 75f64], quantiles.value);
+
+        let quantiles = Quantiles::from_str("50").unwrap().with_named(FIVE_NUMBERS);
+        assert_eq!(vec![50f64, 0f64, 25f64, 75f64, 100f64], quantiles.value);
+    }
+
+    #[test]
+    fn test_log_line() {
+        assert_eq!(None, log_line(0, LogLevel::Info, format_args!("reading {}", 1)));
+        assert_eq!(
+            Some("info: reading 1\n".to_string()),
+            log_line(1, LogLevel::Info, format_args!("reading {}", 1))
+        );
+        assert_eq!(None, log_line(1, LogLevel::Debug, format_args!("reading {}", 1)));
+        assert_eq!(
+            Some("debug: reading 1\n".to_string()),
+            log_line(2, LogLevel::Debug, format_args!("reading {}", 1))
+        );
+        assert_eq!(
+            Some("info: reading 1\n".to_string()),
+            log_line(3, LogLevel::Info, format_args!("reading {}", 1))
+        );
+    }
+
+    #[test]
+    fn test_parse_edges() {
+        assert_eq!(vec![0.0, 10.0, 100.0], Edges::from_str("0,10,100").unwrap().value);
+        assert!(Edges::from_str("1").is_err());
+        assert!(Edges::from_str("1,1").is_err());
+        assert!(Edges::from_str("1,foo").is_err());
+    }
+
+    #[test]
+    fn test_parse_precision_mode() {
+        assert_eq!(PrecisionMode::F64, PrecisionMode::from_str("f64").unwrap());
+        assert_eq!(PrecisionMode::F32, PrecisionMode::from_str("f32").unwrap());
+        assert!(PrecisionMode::from_str("f16").is_err());
+    }
+
+    #[test]
+    fn test_parse_output_format() {
+        assert_eq!(OutputFormat::Text, OutputFormat::from_str("text").unwrap());
+        assert!(OutputFormat::from_str("yaml").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_parse_output_format_json() {
+        assert_eq!(OutputFormat::Json, OutputFormat::from_str("json").unwrap());
+    }
+
+    #[test]
+    fn test_parse_memory_size() {
+        assert_eq!(Ok(MemorySize { bytes: 100 }), MemorySize::from_str("100"));
+        assert_eq!(Ok(MemorySize { bytes: 2048 }), MemorySize::from_str("2K"));
+        assert_eq!(Ok(MemorySize { bytes: 512 << 20 }), MemorySize::from_str("512M"));
+        assert_eq!(Ok(MemorySize { bytes: 1 << 30 }), MemorySize::from_str("1g"));
+        assert!(MemorySize::from_str("").is_err());
+        assert!(MemorySize::from_str("M").is_err());
+        assert!(MemorySize::from_str("1.5G").is_err());
+        assert!(MemorySize::from_str("12X").is_err());
+    }
+
+    #[test]
+    fn test_parse_progress_mode() {
+        assert_eq!(ProgressMode::Auto, ProgressMode::from_str("auto").unwrap());
+        assert_eq!(ProgressMode::Always, ProgressMode::from_str("always").unwrap());
+        assert_eq!(ProgressMode::Never, ProgressMode::from_str("never").unwrap());
+        assert!(ProgressMode::from_str("sometimes").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_merge_bundles() {
+        use super::{accuracy_lines, merge_bundles};
+        use staccato::{MergePolicy, StatisticsBundle};
+        use std::{env, fs, process};
+
+        let dir = env::temp_dir();
+        let paths: Vec<_> = (0..2)
+            .map(|i| dir.join(format!("staccato-test-merge-{}-{}.json", process::id(), i)))
+            .collect();
+
+        let shards: [&[f64]; 2] = [&[1f64, 2f64, 3f64], &[4f64, 5f64]];
+        for (path, vals) in paths.iter().zip(shards.iter()) {
+            let bundle = StatisticsBundle::with_percentiles(vals, &[50]).unwrap();
+            fs::write(path, serde_json::to_string(&bundle).unwrap()).unwrap();
+        }
+
+        let merged = merge_bundles(&paths, MergePolicy::Strict);
+        for path in &paths {
+            fs::remove_file(path).unwrap();
+        }
+
+        let (merged, dropped) = merged.unwrap();
+        assert_eq!(5, merged.global_stats().count());
+        assert_eq!(15f64, merged.global_stats().sum());
+        assert_eq!(1, merged.percentile_stats().len());
+        assert!(dropped.is_empty());
+        assert_eq!(
+            vec![
+                "exact: count, sum, mean, upper, lower",
+                "formula: stddev",
+                "estimate: median, percentile slices 50",
+            ],
+            accuracy_lines(&merged)
+        );
+        assert_eq!(
+            vec!["exact: count, sum, mean, upper, lower, median, stddev, percentile slices 50"],
+            accuracy_lines(&StatisticsBundle::with_percentiles(&[1f64, 2f64], &[50]).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_safe_file_name() {
+        assert_eq!("api.log", safe_file_name("api.log"));
+        assert_eq!("_var_log_api.log", safe_file_name("/var/log/api.log"));
+        assert_eq!("a_b_c_d", safe_file_name("a\\b\0c\nd"));
+        assert_eq!("__hidden.log", safe_file_name("..hidden.log"));
+        assert_eq!("__", safe_file_name(".."));
+        assert_eq!("_.x", safe_file_name("_.x"));
+        assert_eq!("_", safe_file_name(""));
+        assert_eq!(200, safe_file_name(&"é".repeat(150)).len());
+    }
+
+    #[test]
+    fn test_unique_file_names() {
+        assert_eq!(
+            vec!["a.txt", "A-2.txt", "a-3.txt", "index-2.txt", "b_c.txt", "b_c-2.txt"],
+            unique_file_names(&["a", "A", "a", "index", "b/c", "b\\c"], "txt")
+        );
     }
 }
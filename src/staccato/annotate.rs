@@ -0,0 +1,255 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::quantile::{quantile, QuantileMethod};
+use crate::sorted::percentile_rank;
+use crate::{write_ordered, KeyValueSep, DISPLAY_PRECISION};
+use std::fmt;
+use std::str::FromStr;
+
+/// Something computed for each value based on the entire sequence of values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Annotation {
+    /// Which of the given number of equally sized quantile bins (numbered
+    /// starting from 1) a value falls into.
+    Bin(usize),
+    /// Percentile rank (from 0 to 100) of a value, the percent of values
+    /// less than it plus half of the percent of values equal to it.
+    Rank,
+}
+
+impl Annotation {
+    fn is_integral(&self) -> bool {
+        match *self {
+            Annotation::Bin(_) => true,
+            Annotation::Rank => false,
+        }
+    }
+}
+
+impl FromStr for Annotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Annotation, Self::Err> {
+        if "rank" == s {
+            return Ok(Annotation::Rank);
+        }
+
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("bin"), Some(n)) => match n.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(Annotation::Bin(n)),
+                _ => Err(format!("Invalid number of bins {}", n)),
+            },
+            _ => Err(format!("Invalid annotation {}", s)),
+        }
+    }
+}
+
+/// Computes an annotation for individual values based on a **sorted**
+/// sequence of all values.
+#[derive(Debug, Clone)]
+pub struct Annotator {
+    annotation: Annotation,
+    points: Vec<f64>,
+}
+
+impl Annotator {
+    /// Create an annotator from a **sorted** sequence of values.
+    ///
+    /// For bins, the edges between bins are the quantiles of the values
+    /// computed with the given method. For ranks, a copy of all the values
+    /// is kept and the method is unused.
+    pub fn new(annotation: Annotation, sorted: &[f64], method: QuantileMethod) -> Annotator {
+        let points = match annotation {
+            Annotation::Bin(n) => (1..n)
+                .flat_map(|i| quantile(sorted, i as f64 / n as f64, method))
+                .collect(),
+            Annotation::Rank => sorted.to_vec(),
+        };
+
+        Annotator { annotation, points }
+    }
+
+    pub fn annotation(&self) -> Annotation {
+        self.annotation
+    }
+
+    /// Compute the annotation for a single value.
+    ///
+    /// Values that fall exactly on the edge between two bins are put in the
+    /// lower of the two bins.
+    pub fn annotate(&self, val: f64) -> f64 {
+        match self.annotation {
+            Annotation::Bin(_) => (self.points.partition_point(|&e| e < val) + 1) as f64,
+            Annotation::Rank => percentile_rank(&self.points, val).unwrap_or(0f64),
+        }
+    }
+}
+
+/// Render each of a sequence of values in its original order followed by
+/// its annotation, separated by a separator.
+#[derive(Debug)]
+pub struct AnnotationFormatter<'a> {
+    vals: &'a [f64],
+    annotator: &'a Annotator,
+    sep: KeyValueSep,
+    precision: usize,
+}
+
+impl<'a> AnnotationFormatter<'a> {
+    pub fn new(vals: &'a [f64], annotator: &'a Annotator) -> AnnotationFormatter<'a> {
+        Self::with_sep(vals, annotator, KeyValueSep::Colon)
+    }
+
+    pub fn with_sep(vals: &'a [f64], annotator: &'a Annotator, sep: KeyValueSep) -> AnnotationFormatter<'a> {
+        AnnotationFormatter {
+            vals,
+            annotator,
+            sep,
+            precision: DISPLAY_PRECISION,
+        }
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+}
+
+impl<'a> fmt::Display for AnnotationFormatter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let integral = self.annotator.annotation().is_integral();
+        let precision = self.precision;
+
+        write_ordered(f, self.vals, &self.sep, |v| {
+            let a = self.annotator.annotate(v);
+            if integral {
+                vec![format!("{:.*}", precision, v), format!("{:.0}", a)]
+            } else {
+                vec![format!("{:.*}", precision, v), format!("{:.*}", precision, a)]
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Annotation, AnnotationFormatter, Annotator};
+    use crate::quantile::QuantileMethod;
+    use crate::{sort_values, KeyValueSep};
+
+    const UNSORTED: &[f64] = &[7f64, 3f64, 10f64, 1f64, 5f64, 9f64, 2f64, 8f64, 4f64, 6f64];
+
+    fn sorted(vals: &[f64]) -> Vec<f64> {
+        let mut out = vals.to_vec();
+        sort_values(&mut out);
+        out
+    }
+
+    #[test]
+    fn test_annotation_from_str() {
+        assert_eq!(Annotation::Bin(10), "bin:10".parse::<Annotation>().unwrap());
+        assert!("bin:0".parse::<Annotation>().is_err());
+        assert!("bin:".parse::<Annotation>().is_err());
+        assert!("bin".parse::<Annotation>().is_err());
+        assert!("banana:3".parse::<Annotation>().is_err());
+        assert_eq!(Annotation::Rank, "rank".parse::<Annotation>().unwrap());
+        assert!("rank:3".parse::<Annotation>().is_err());
+    }
+
+    #[test]
+    fn test_annotator_deciles() {
+        // Edges of 1.9, 2.8, ... so each value lands in its own decile
+        let annotator = Annotator::new(Annotation::Bin(10), &sorted(UNSORTED), QuantileMethod::Linear);
+        let bins: Vec<f64> = UNSORTED.iter().map(|&v| annotator.annotate(v)).collect();
+
+        assert_eq!(vec![7f64, 3f64, 10f64, 1f64, 5f64, 9f64, 2f64, 8f64, 4f64, 6f64], bins);
+    }
+
+    #[test]
+    fn test_annotator_edge_goes_to_lower_bin() {
+        // Median edge is exactly 3 with the nearest rank method
+        let vals = &[1f64, 2f64, 3f64, 4f64, 5f64, 6f64];
+        let annotator = Annotator::new(Annotation::Bin(2), vals, QuantileMethod::Nearest);
+
+        assert_eq!(1f64, annotator.annotate(3f64));
+        assert_eq!(2f64, annotator.annotate(3.5));
+    }
+
+    #[test]
+    fn test_annotator_out_of_range() {
+        let annotator = Annotator::new(Annotation::Bin(4), &sorted(UNSORTED), QuantileMethod::Linear);
+
+        assert_eq!(1f64, annotator.annotate(-100f64));
+        assert_eq!(4f64, annotator.annotate(100f64));
+    }
+
+    #[test]
+    fn test_annotator_single_bin() {
+        let annotator = Annotator::new(Annotation::Bin(1), &sorted(UNSORTED), QuantileMethod::Linear);
+        assert!(UNSORTED.iter().all(|&v| annotator.annotate(v) == 1f64));
+    }
+
+    #[test]
+    fn test_annotation_formatter_original_order() {
+        let vals = &[3f64, 1f64, 4f64, 2f64];
+        let annotator = Annotator::new(Annotation::Bin(2), &sorted(vals), QuantileMethod::Linear);
+        let out = format!(
+            "{}",
+            AnnotationFormatter::with_sep(vals, &annotator, KeyValueSep::Tab).precision(1)
+        );
+
+        assert_eq!("3.0\t2\n1.0\t1\n4.0\t2\n2.0\t1\n", out);
+    }
+
+    #[test]
+    fn test_annotator_rank_with_duplicates() {
+        // 10 values: 1 below 1, 2 ones, 3 twos, 1 three, 3 fives. A value of 2
+        // has 3 values below it and 3 equal to it so (3 + 1.5) / 10 = 45%.
+        let vals = &[2f64, 5f64, 1f64, 2f64, 0f64, 5f64, 3f64, 1f64, 2f64, 5f64];
+        let annotator = Annotator::new(Annotation::Rank, &sorted(vals), QuantileMethod::Linear);
+        let ranks: Vec<f64> = vals.iter().map(|&v| annotator.annotate(v)).collect();
+
+        assert_eq!(
+            vec![45f64, 85f64, 20f64, 45f64, 5f64, 85f64, 65f64, 20f64, 45f64, 85f64],
+            ranks
+        );
+    }
+
+    #[test]
+    fn test_annotator_rank_out_of_range() {
+        let annotator = Annotator::new(Annotation::Rank, &sorted(UNSORTED), QuantileMethod::Linear);
+
+        assert_eq!(0f64, annotator.annotate(-100f64));
+        assert_eq!(100f64, annotator.annotate(100f64));
+        assert_eq!(30f64, annotator.annotate(3.5));
+    }
+
+    #[test]
+    fn test_annotation_formatter_rank() {
+        let vals = &[3f64, 1f64, 3f64, 2f64];
+        let annotator = Annotator::new(Annotation::Rank, &sorted(vals), QuantileMethod::Linear);
+        let out = format!(
+            "{}",
+            AnnotationFormatter::with_sep(vals, &annotator, KeyValueSep::Tab).precision(1)
+        );
+
+        assert_eq!("3.0\t75.0\n1.0\t12.5\n3.0\t75.0\n2.0\t37.5\n", out);
+    }
+}
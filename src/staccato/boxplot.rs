@@ -0,0 +1,368 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::quantile::QuantileMethod;
+use crate::summary::FiveNumberSummary;
+use crate::DISPLAY_PRECISION;
+use std::fmt;
+use std::str::FromStr;
+
+const WHISKER_END: char = '|';
+const WHISKER: char = '-';
+const BOX_LOWER: char = '[';
+const BOX_UPPER: char = ']';
+const BOX: char = '=';
+const MEDIAN: char = '#';
+const OUTLIER: char = 'o';
+
+/// Where the whiskers of a boxplot end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WhiskerPolicy {
+    /// Whiskers extend to the lowest and highest values and there are
+    /// never any outliers.
+    Range,
+    /// Whiskers extend to the most extreme values within 1.5 times the
+    /// interquartile range of the quartiles. Values beyond the whiskers
+    /// are outliers.
+    #[default]
+    Iqr,
+}
+
+impl FromStr for WhiskerPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<WhiskerPolicy, Self::Err> {
+        if "range" == s {
+            Ok(WhiskerPolicy::Range)
+        } else if "iqr" == s {
+            Ok(WhiskerPolicy::Iqr)
+        } else {
+            Err(format!("Invalid whisker policy {}", s))
+        }
+    }
+}
+
+/// Positions of each part of a boxplot of a sequence of values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoxPlot {
+    lower: f64,
+    upper: f64,
+    lower_whisker: f64,
+    q1: f64,
+    median: f64,
+    q3: f64,
+    upper_whisker: f64,
+    outliers: Vec<f64>,
+}
+
+impl BoxPlot {
+    /// Create a boxplot from a **sorted** sequence of values.
+    ///
+    /// This method returns `None` if the sequence of values is empty.
+    pub fn from(vals: &[f64], whiskers: WhiskerPolicy, method: QuantileMethod) -> Option<BoxPlot> {
+        let summary = FiveNumberSummary::from(vals, method)?;
+        let (lower_whisker, upper_whisker) = match whiskers {
+            WhiskerPolicy::Range => (summary.lower(), summary.upper()),
+            WhiskerPolicy::Iqr => {
+                let iqr = summary.q3() - summary.q1();
+                let low = summary.q1() - 1.5 * iqr;
+                let high = summary.q3() + 1.5 * iqr;
+
+                // The whiskers end at actual values, not the fences themselves,
+                // and there is always at least one value between the fences
+                // since the quartiles are between them.
+                (
+                    vals.iter().cloned().find(|&v| v >= low).unwrap_or(summary.lower()),
+                    vals.iter()
+                        .rev()
+                        .cloned()
+                        .find(|&v| v <= high)
+                        .unwrap_or(summary.upper()),
+                )
+            }
+        };
+
+        let outliers = vals
+            .iter()
+            .cloned()
+            .filter(|&v| v < lower_whisker || v > upper_whisker)
+            .collect();
+
+        Some(BoxPlot {
+            lower: summary.lower(),
+            upper: summary.upper(),
+            lower_whisker,
+            q1: summary.q1(),
+            median: summary.median(),
+            q3: summary.q3(),
+            upper_whisker,
+            outliers,
+        })
+    }
+
+    pub fn lower(&self) -> f64 {
+        self.lower
+    }
+
+    pub fn upper(&self) -> f64 {
+        self.upper
+    }
+
+    pub fn lower_whisker(&self) -> f64 {
+        self.lower_whisker
+    }
+
+    pub fn q1(&self) -> f64 {
+        self.q1
+    }
+
+    pub fn median(&self) -> f64 {
+        self.median
+    }
+
+    pub fn q3(&self) -> f64 {
+        self.q3
+    }
+
+    pub fn upper_whisker(&self) -> f64 {
+        self.upper_whisker
+    }
+
+    pub fn outliers(&self) -> &[f64] {
+        &self.outliers
+    }
+}
+
+/// Map a value to one of `width` columns on a scale from `lower` to `upper`.
+///
+/// Values outside of the scale are clamped to the first or last column. If
+/// the scale is degenerate (`lower` is not less than `upper`) every value is
+/// mapped to the middle column.
+pub fn scale_to_column(value: f64, lower: f64, upper: f64, width: usize) -> usize {
+    if width == 0 {
+        return 0;
+    }
+
+    let last = width - 1;
+    if lower >= upper || !value.is_finite() {
+        return last / 2;
+    }
+
+    let pos = (value - lower) / (upper - lower) * last as f64;
+    if pos <= 0f64 {
+        0
+    } else {
+        (pos.round() as usize).min(last)
+    }
+}
+
+/// Render a single boxplot into `width` columns on a scale from `lower` to
+/// `upper`.
+pub fn render_line(plot: &BoxPlot, lower: f64, upper: f64, width: usize) -> String {
+    let mut line = vec![' '; width];
+    if width == 0 {
+        return String::new();
+    }
+
+    let col = |v: f64| scale_to_column(v, lower, upper, width);
+
+    for &v in plot.outliers() {
+        line[col(v)] = OUTLIER;
+    }
+
+    let (lw, q1, med, q3, uw) = (
+        col(plot.lower_whisker()),
+        col(plot.q1()),
+        col(plot.median()),
+        col(plot.q3()),
+        col(plot.upper_whisker()),
+    );
+
+    for c in line.iter_mut().take(uw + 1).skip(lw) {
+        *c = WHISKER;
+    }
+
+    line[lw] = WHISKER_END;
+    line[uw] = WHISKER_END;
+
+    for c in line.iter_mut().take(q3 + 1).skip(q1) {
+        *c = BOX;
+    }
+
+    line[q1] = BOX_LOWER;
+    line[q3] = BOX_UPPER;
+    line[med] = MEDIAN;
+
+    line.into_iter().collect::<String>().trim_end().to_string()
+}
+
+/// Render one or more labeled boxplots on a shared scale so that they can
+/// be visually compared, followed by an axis showing the ends of the scale.
+#[derive(Debug)]
+pub struct BoxPlotFormatter<'a> {
+    plots: Vec<(&'a str, &'a BoxPlot)>,
+    width: usize,
+    precision: usize,
+}
+
+impl<'a> BoxPlotFormatter<'a> {
+    /// Create a new formatter that renders boxplots in `width` columns,
+    /// including the labels.
+    pub fn new(width: usize) -> BoxPlotFormatter<'a> {
+        BoxPlotFormatter {
+            plots: Vec::new(),
+            width,
+            precision: DISPLAY_PRECISION,
+        }
+    }
+
+    pub fn plot(mut self, label: &'a str, plot: &'a BoxPlot) -> Self {
+        self.plots.push((label, plot));
+        self
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+}
+
+impl<'a> fmt::Display for BoxPlotFormatter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.plots.is_empty() {
+            return Ok(());
+        }
+
+        let lower = self.plots.iter().map(|(_, p)| p.lower()).fold(f64::MAX, f64::min);
+        let upper = self.plots.iter().map(|(_, p)| p.upper()).fold(f64::MIN, f64::max);
+        let label_width = self.plots.iter().map(|(l, _)| l.len()).max().unwrap_or(0);
+        let plot_width = self.width.saturating_sub(label_width + 1);
+
+        for (label, plot) in self.plots.iter() {
+            let line = render_line(plot, lower, upper, plot_width);
+            writeln!(f, "{:<width$} {}", label, line, width = label_width)?;
+        }
+
+        let low = format!("{:.*}", self.precision, lower);
+        let high = format!("{:.*}", self.precision, upper);
+        let gap = plot_width.saturating_sub(low.len() + high.len()).max(1);
+        writeln!(
+            f,
+            "{:<width$} {}{}{}",
+            "",
+            low,
+            " ".repeat(gap),
+            high,
+            width = label_width
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_line, scale_to_column, BoxPlot, BoxPlotFormatter, WhiskerPolicy};
+    use crate::quantile::QuantileMethod;
+
+    const VALUES: &[f64] = &[1f64, 2f64, 3f64, 4f64, 5f64, 6f64, 7f64, 8f64, 9f64, 50f64];
+
+    const EQUAL: &[f64] = &[4f64, 4f64, 4f64, 4f64];
+
+    #[test]
+    fn test_scale_to_column_bounds() {
+        assert_eq!(0, scale_to_column(0f64, 0f64, 10f64, 11));
+        assert_eq!(10, scale_to_column(10f64, 0f64, 10f64, 11));
+        assert_eq!(5, scale_to_column(5f64, 0f64, 10f64, 11));
+    }
+
+    #[test]
+    fn test_scale_to_column_clamped() {
+        assert_eq!(0, scale_to_column(-5f64, 0f64, 10f64, 11));
+        assert_eq!(10, scale_to_column(15f64, 0f64, 10f64, 11));
+    }
+
+    #[test]
+    fn test_scale_to_column_degenerate() {
+        assert_eq!(5, scale_to_column(4f64, 4f64, 4f64, 11));
+        assert_eq!(0, scale_to_column(4f64, 4f64, 4f64, 0));
+        assert_eq!(0, scale_to_column(4f64, 4f64, 4f64, 1));
+    }
+
+    #[test]
+    fn test_boxplot_iqr_outliers() {
+        let plot = BoxPlot::from(VALUES, WhiskerPolicy::Iqr, QuantileMethod::Linear).unwrap();
+
+        assert_eq!(1f64, plot.lower_whisker());
+        assert_eq!(9f64, plot.upper_whisker());
+        assert_eq!(&[50f64], plot.outliers());
+    }
+
+    #[test]
+    fn test_boxplot_range_no_outliers() {
+        let plot = BoxPlot::from(VALUES, WhiskerPolicy::Range, QuantileMethod::Linear).unwrap();
+
+        assert_eq!(1f64, plot.lower_whisker());
+        assert_eq!(50f64, plot.upper_whisker());
+        assert!(plot.outliers().is_empty());
+    }
+
+    #[test]
+    fn test_boxplot_empty() {
+        assert!(BoxPlot::from(&[], WhiskerPolicy::Iqr, QuantileMethod::Linear).is_none());
+    }
+
+    #[test]
+    fn test_render_line_range() {
+        let vals = &[0f64, 2f64, 5f64, 8f64, 10f64];
+        let plot = BoxPlot::from(vals, WhiskerPolicy::Range, QuantileMethod::Linear).unwrap();
+
+        assert_eq!("|-[==#==]-|", render_line(&plot, 0f64, 10f64, 11));
+    }
+
+    #[test]
+    fn test_render_line_outliers() {
+        let plot = BoxPlot::from(VALUES, WhiskerPolicy::Iqr, QuantileMethod::Linear).unwrap();
+        let line = render_line(&plot, 1f64, 50f64, 50);
+
+        assert!(line.starts_with('|'));
+        assert!(line.ends_with('o'));
+        assert_eq!(50, line.len());
+    }
+
+    #[test]
+    fn test_render_line_all_equal() {
+        let plot = BoxPlot::from(EQUAL, WhiskerPolicy::Iqr, QuantileMethod::Linear).unwrap();
+
+        assert_eq!("     #", render_line(&plot, 4f64, 4f64, 11));
+        assert_eq!("", render_line(&plot, 4f64, 4f64, 0));
+    }
+
+    #[test]
+    fn test_boxplot_formatter_shared_scale() {
+        let a = BoxPlot::from(&[0f64, 5f64, 10f64], WhiskerPolicy::Range, QuantileMethod::Linear).unwrap();
+        let b = BoxPlot::from(&[10f64, 15f64, 20f64], WhiskerPolicy::Range, QuantileMethod::Linear).unwrap();
+        let out = format!(
+            "{}",
+            BoxPlotFormatter::new(23).precision(0).plot("a", &a).plot("bb", &b)
+        );
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!("a  |-[==#=]--|", lines[0]);
+        assert_eq!("bb           |-[=#==]-|", lines[1]);
+        assert_eq!("   0                 20", lines[2]);
+    }
+}
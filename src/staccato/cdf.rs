@@ -0,0 +1,272 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{KeyValueSep, DISPLAY_PRECISION};
+use std::fmt;
+use std::str::FromStr;
+
+/// Iterator over each distinct value of a **sorted** sequence of values and
+/// the fraction of values that are less than or equal to it.
+///
+/// Repeated values produce a single item with the fraction after the last of
+/// them. The final item always has a fraction of exactly `1.0`.
+#[derive(Debug, Clone)]
+pub struct Cdf<'a> {
+    vals: &'a [f64],
+    index: usize,
+}
+
+impl<'a> Cdf<'a> {
+    pub fn new(vals: &'a [f64]) -> Cdf<'a> {
+        Cdf { vals, index: 0 }
+    }
+}
+
+impl<'a> Iterator for Cdf<'a> {
+    type Item = (f64, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let val = *self.vals.get(self.index)?;
+        let run = self.vals[self.index..].iter().take_while(|&&v| v == val).count();
+
+        // A NaN doesn't equal itself, make sure we always make progress.
+        self.index += run.max(1);
+        Some((val, self.index as f64 / self.vals.len() as f64))
+    }
+}
+
+/// Iterator over `points` evenly spaced values from the lowest to highest value
+/// of a **sorted** sequence of values and the fraction of values that are less
+/// than or equal to each of them.
+///
+/// The final item is always the highest value with a fraction of exactly `1.0`.
+/// If all values are the same, only a single item is produced.
+#[derive(Debug, Clone)]
+pub struct SampledCdf<'a> {
+    vals: &'a [f64],
+    points: usize,
+    index: usize,
+}
+
+impl<'a> SampledCdf<'a> {
+    pub fn new(vals: &'a [f64], points: usize) -> SampledCdf<'a> {
+        let points = match (vals.first(), vals.last()) {
+            (Some(first), Some(last)) if first < last => points,
+            (Some(_), Some(_)) => points.min(1),
+            _ => 0,
+        };
+
+        SampledCdf { vals, points, index: 0 }
+    }
+}
+
+impl<'a> Iterator for SampledCdf<'a> {
+    type Item = (f64, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.points {
+            return None;
+        }
+
+        let lower = self.vals[0];
+        let upper = self.vals[self.vals.len() - 1];
+        self.index += 1;
+
+        // Use the highest value directly for the final point instead of computing
+        // it so that floating point error doesn't leave out any values.
+        let val = if self.index == self.points {
+            upper
+        } else {
+            lower + (upper - lower) * (self.index - 1) as f64 / (self.points - 1) as f64
+        };
+
+        let below = self.vals.partition_point(|&v| v <= val);
+        Some((val, below as f64 / self.vals.len() as f64))
+    }
+}
+
+/// Which values of a sequence an empirical CDF should be computed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CdfPoints {
+    /// Every distinct value.
+    All,
+    /// The given number of evenly spaced values.
+    Count(usize),
+}
+
+impl Default for CdfPoints {
+    fn default() -> Self {
+        CdfPoints::Count(100)
+    }
+}
+
+impl FromStr for CdfPoints {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CdfPoints, Self::Err> {
+        if "all" == s {
+            return Ok(CdfPoints::All);
+        }
+
+        match s.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(CdfPoints::Count(n)),
+            _ => Err(format!("Invalid number of CDF points {}", s)),
+        }
+    }
+}
+
+/// Render the empirical CDF of a **sorted** sequence of values as rows of
+/// each value and cumulative fraction, separated by a separator.
+#[derive(Debug)]
+pub struct CdfFormatter<'a> {
+    vals: &'a [f64],
+    points: CdfPoints,
+    sep: KeyValueSep,
+    precision: usize,
+}
+
+impl<'a> CdfFormatter<'a> {
+    pub fn new(vals: &'a [f64], points: CdfPoints) -> CdfFormatter<'a> {
+        Self::with_sep(vals, points, KeyValueSep::Colon)
+    }
+
+    pub fn with_sep(vals: &'a [f64], points: CdfPoints, sep: KeyValueSep) -> CdfFormatter<'a> {
+        CdfFormatter {
+            vals,
+            points,
+            sep,
+            precision: DISPLAY_PRECISION,
+        }
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+}
+
+impl<'a> fmt::Display for CdfFormatter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows: Box<dyn Iterator<Item = (f64, f64)>> = match self.points {
+            CdfPoints::All => Box::new(Cdf::new(self.vals)),
+            CdfPoints::Count(n) => Box::new(SampledCdf::new(self.vals, n)),
+        };
+
+        for (val, frac) in rows {
+            writeln!(f, "{:.*}{}{:.*}", self.precision, val, self.sep, self.precision, frac)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cdf, CdfFormatter, CdfPoints, SampledCdf};
+    use crate::KeyValueSep;
+
+    const VALUES: &[f64] = &[1f64, 2f64, 3f64, 4f64];
+
+    const DUPLICATES: &[f64] = &[1f64, 1f64, 1f64, 2f64, 2f64, 5f64, 5f64, 5f64, 5f64, 5f64];
+
+    const SAME: &[f64] = &[7f64, 7f64, 7f64, 7f64, 7f64, 7f64, 7f64];
+
+    #[test]
+    fn test_cdf_empty() {
+        assert_eq!(0, Cdf::new(&[]).count());
+    }
+
+    #[test]
+    fn test_cdf_distinct_values() {
+        let rows: Vec<(f64, f64)> = Cdf::new(VALUES).collect();
+        assert_eq!(vec![(1f64, 0.25), (2f64, 0.5), (3f64, 0.75), (4f64, 1f64)], rows);
+    }
+
+    #[test]
+    fn test_cdf_duplicate_values() {
+        let rows: Vec<(f64, f64)> = Cdf::new(DUPLICATES).collect();
+        assert_eq!(vec![(1f64, 0.3), (2f64, 0.5), (5f64, 1f64)], rows);
+    }
+
+    #[test]
+    fn test_cdf_all_same_value() {
+        let rows: Vec<(f64, f64)> = Cdf::new(SAME).collect();
+        assert_eq!(vec![(7f64, 1f64)], rows);
+    }
+
+    #[test]
+    fn test_cdf_last_row_exactly_one() {
+        let vals: Vec<f64> = (0..997).map(|v| (v / 7) as f64).collect();
+        let last = Cdf::new(&vals).last().unwrap();
+        assert_eq!(1f64, last.1);
+    }
+
+    #[test]
+    fn test_sampled_cdf_evenly_spaced() {
+        let rows: Vec<(f64, f64)> = SampledCdf::new(VALUES, 7).collect();
+        let vals: Vec<f64> = rows.iter().map(|r| r.0).collect();
+        let fracs: Vec<f64> = rows.iter().map(|r| r.1).collect();
+
+        assert_eq!(vec![1f64, 1.5, 2f64, 2.5, 3f64, 3.5, 4f64], vals);
+        assert_eq!(vec![0.25, 0.25, 0.5, 0.5, 0.75, 0.75, 1f64], fracs);
+    }
+
+    #[test]
+    fn test_sampled_cdf_duplicate_values() {
+        let rows: Vec<(f64, f64)> = SampledCdf::new(DUPLICATES, 5).collect();
+        assert_eq!(
+            vec![(1f64, 0.3), (2f64, 0.5), (3f64, 0.5), (4f64, 0.5), (5f64, 1f64)],
+            rows
+        );
+    }
+
+    #[test]
+    fn test_sampled_cdf_all_same_value() {
+        let rows: Vec<(f64, f64)> = SampledCdf::new(SAME, 10).collect();
+        assert_eq!(vec![(7f64, 1f64)], rows);
+    }
+
+    #[test]
+    fn test_sampled_cdf_single_point() {
+        let rows: Vec<(f64, f64)> = SampledCdf::new(VALUES, 1).collect();
+        assert_eq!(vec![(4f64, 1f64)], rows);
+    }
+
+    #[test]
+    fn test_sampled_cdf_empty() {
+        assert_eq!(0, SampledCdf::new(&[], 10).count());
+    }
+
+    #[test]
+    fn test_cdf_points_from_str() {
+        assert_eq!(CdfPoints::All, "all".parse::<CdfPoints>().unwrap());
+        assert_eq!(CdfPoints::Count(20), "20".parse::<CdfPoints>().unwrap());
+        assert!("0".parse::<CdfPoints>().is_err());
+        assert!("banana".parse::<CdfPoints>().is_err());
+    }
+
+    #[test]
+    fn test_cdf_formatter() {
+        let out = format!(
+            "{}",
+            CdfFormatter::with_sep(DUPLICATES, CdfPoints::All, KeyValueSep::Tab).precision(1)
+        );
+
+        assert_eq!("1.0\t0.3\n2.0\t0.5\n5.0\t1.0\n", out);
+    }
+}
@@ -0,0 +1,227 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{merge_sorted_values, sort_values, strip_line_ending, SortingPolicy, StaccatoError};
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+/// Smallest number of bytes parsed by each thread, so that small files
+/// aren't split into more chunks than is worth starting threads for.
+const MIN_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Parse values from a regular file using up to `threads` threads, each of
+/// which parses a separate range of bytes of the file. The result is
+/// identical to `get_values` reading the whole file.
+///
+/// The file is split into ranges of about the same size and each line is
+/// parsed as part of the range that its first byte is in, so a line that
+/// spans the boundary between two ranges is parsed exactly once.
+///
+/// This method returns `Ok(None)` if the file isn't a regular file (such as
+/// a pipe) since it can't be read from more than one place at once.
+pub fn get_values_from_file_parallel(
+    path: &Path,
+    threads: usize,
+    sort: SortingPolicy,
+) -> Result<Option<Vec<f64>>, StaccatoError> {
+    let meta = File::open(path)?.metadata()?;
+    if !meta.is_file() {
+        return Ok(None);
+    }
+
+    let len = meta.len();
+    let chunks = (threads as u64).min(len.div_ceil(MIN_CHUNK_SIZE)).max(1);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.max(1))
+        .build()
+        .map_err(io::Error::other)?;
+
+    let per_chunk = pool.install(|| {
+        (0..chunks)
+            .into_par_iter()
+            .map(|i| {
+                let mut reader = BufReader::new(File::open(path)?);
+                let mut values = get_values_in_range(&mut reader, len * i / chunks, len * (i + 1) / chunks)?;
+                if sort.needs_sort() {
+                    sort_values(&mut values);
+                }
+
+                Ok(values)
+            })
+            .collect::<Result<Vec<Vec<f64>>, io::Error>>()
+    })?;
+
+    // Chunks of values that are already sorted are in order, so they only
+    // need to be merged if each chunk was sorted separately.
+    if sort.needs_sort() {
+        Ok(Some(merge_sorted_values(&per_chunk)))
+    } else {
+        // Values that should already be sorted are checked all at once so
+        // that values on either side of each chunk boundary are checked too.
+        let mut values = per_chunk.concat();
+        sort.sort_with(&mut values, sort_values)?;
+        Ok(Some(values))
+    }
+}
+
+/// Parse the values of each line whose first byte is at an offset from
+/// `start` up to (not including) `end` in a reader, the same way
+/// `get_values` parses them. The last line may extend past `end`.
+fn get_values_in_range<R: BufRead + Seek>(reader: &mut R, start: u64, end: u64) -> Result<Vec<f64>, io::Error> {
+    let mut pos = start;
+    if start > 0 {
+        // If the byte before the start is a newline, a line starts exactly at
+        // `start`. Otherwise the line that `start` is in began in a previous
+        // range so skip the rest of it.
+        reader.seek(SeekFrom::Start(start - 1))?;
+        pos = start - 1 + reader.skip_until(b'\n')? as u64;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+
+    let mut values = Vec::new();
+    let mut line = String::new();
+    while pos < end {
+        let read = reader.read_line(&mut line)?;
+        if read == 0 {
+            break;
+        }
+
+        pos += read as u64;
+        if let Ok(v) = strip_line_ending(&line).trim().parse::<f64>() {
+            values.push(v);
+        }
+
+        line.clear();
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_values_from_file_parallel, get_values_in_range};
+    use crate::{get_values, SortingPolicy};
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+    use std::process;
+
+    fn parse_in_ranges(input: &str, splits: &[u64]) -> Vec<f64> {
+        let mut cursor = Cursor::new(input.as_bytes());
+        let mut bounds = vec![0];
+        bounds.extend_from_slice(splits);
+        bounds.push(input.len() as u64);
+
+        bounds
+            .windows(2)
+            .flat_map(|w| get_values_in_range(&mut cursor, w[0], w[1]).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_get_values_in_range_split_within_value() {
+        // "12.5" is split after "12" and must only be parsed by the first range
+        let input = "1\n12.5\n3\n";
+        let mut cursor = Cursor::new(input.as_bytes());
+
+        assert_eq!(vec![1f64, 12.5f64], get_values_in_range(&mut cursor, 0, 4).unwrap());
+        assert_eq!(vec![3f64], get_values_in_range(&mut cursor, 4, 9).unwrap());
+    }
+
+    #[test]
+    fn test_get_values_in_range_split_after_newline() {
+        let input = "1\n2\n3\n";
+        let mut cursor = Cursor::new(input.as_bytes());
+
+        assert_eq!(vec![1f64], get_values_in_range(&mut cursor, 0, 2).unwrap());
+        assert_eq!(vec![2f64, 3f64], get_values_in_range(&mut cursor, 2, 6).unwrap());
+    }
+
+    #[test]
+    fn test_get_values_in_range_split_at_newline() {
+        let input = "1\n2\n3\n";
+        let mut cursor = Cursor::new(input.as_bytes());
+
+        assert_eq!(vec![1f64], get_values_in_range(&mut cursor, 0, 1).unwrap());
+        assert_eq!(vec![2f64, 3f64], get_values_in_range(&mut cursor, 1, 6).unwrap());
+    }
+
+    #[test]
+    fn test_get_values_in_range_split_crlf() {
+        let input = "1\r\n2\r\n3";
+        assert_eq!(vec![1f64, 2f64, 3f64], parse_in_ranges(input, &[2]));
+        assert_eq!(vec![1f64, 2f64, 3f64], parse_in_ranges(input, &[5, 6]));
+    }
+
+    #[test]
+    fn test_get_values_in_range_line_spans_ranges() {
+        // The second line spans the second and third ranges entirely
+        let input = "1\n2222222222\n3\n";
+        let mut cursor = Cursor::new(input.as_bytes());
+
+        assert_eq!(vec![2222222222f64], get_values_in_range(&mut cursor, 2, 4).unwrap());
+        assert!(get_values_in_range(&mut cursor, 4, 8).unwrap().is_empty());
+        assert_eq!(vec![1f64, 2222222222f64, 3f64], parse_in_ranges(input, &[2, 4, 8]));
+    }
+
+    #[test]
+    fn test_get_values_in_range_every_split() {
+        let input = "10\n-2.5\n\nfoo\n 7 \r\n300\n4";
+        let expected = get_values(&mut input.as_bytes(), SortingPolicy::Unsorted).unwrap();
+        let len = input.len() as u64;
+
+        for a in 0..=len {
+            for b in a..=len {
+                assert_eq!(expected, parse_in_ranges(input, &[a, b]), "splits at {} and {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_values_from_file_parallel() {
+        let path = env::temp_dir().join(format!("staccato-test-chunked-{}", process::id()));
+        let contents: String = (0..100_000).map(|i| format!("{}\n", (i * 7919) % 100_003)).collect();
+        fs::write(&path, &contents).unwrap();
+
+        let unsorted = get_values(&mut contents.as_bytes(), SortingPolicy::Unsorted).unwrap();
+        let sorted = get_values(&mut contents.as_bytes(), SortingPolicy::Sorted).unwrap();
+        for &threads in &[1, 3, 7] {
+            let res = get_values_from_file_parallel(&path, threads, SortingPolicy::Unsorted).unwrap();
+            assert_eq!(Some(&unsorted), res.as_ref());
+
+            let res = get_values_from_file_parallel(&path, threads, SortingPolicy::Sorted).unwrap();
+            assert_eq!(Some(&sorted), res.as_ref());
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_get_values_from_file_parallel_empty() {
+        let path = env::temp_dir().join(format!("staccato-test-chunked-empty-{}", process::id()));
+        fs::write(&path, "").unwrap();
+
+        let res = get_values_from_file_parallel(&path, 4, SortingPolicy::Unsorted).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(Some(Vec::new()), res);
+    }
+}
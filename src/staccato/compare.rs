@@ -0,0 +1,387 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::quantile::Quantile;
+use crate::{Statistics, StatisticsBundle, DISPLAY_PRECISION};
+use std::cmp::Ordering;
+use std::fmt;
+
+const MISSING: &str = "-";
+const COLOR_REGRESSION: &str = "\x1b[31m";
+const COLOR_RESET: &str = "\x1b[0m";
+
+/// Difference between a single statistic computed for two sets of values.
+///
+/// Either side may be missing when one of the sets didn't have enough
+/// values to compute a particular percentile slice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonRow {
+    key: String,
+    integral: bool,
+    a: Option<f64>,
+    b: Option<f64>,
+}
+
+impl ComparisonRow {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn a(&self) -> Option<f64> {
+        self.a
+    }
+
+    pub fn b(&self) -> Option<f64> {
+        self.b
+    }
+
+    /// Absolute change from `a` to `b` or `None` if either is missing.
+    pub fn delta(&self) -> Option<f64> {
+        match (self.a, self.b) {
+            (Some(a), Some(b)) => Some(b - a),
+            _ => None,
+        }
+    }
+
+    /// Change from `a` to `b` as a percentage of `a` or `None` if either is
+    /// missing or the change can't be expressed relative to `a` (`a` is zero
+    /// and `b` isn't).
+    pub fn percent_change(&self) -> Option<f64> {
+        match (self.a, self.b) {
+            (Some(a), Some(b)) if a == b => Some(0f64),
+            (Some(a), Some(b)) if a != 0f64 => Some((b - a) / a.abs() * 100f64),
+            _ => None,
+        }
+    }
+
+    /// Return true if `b` is more than `threshold` percent larger than `a`.
+    pub fn is_regression(&self, threshold: f64) -> bool {
+        self.percent_change().map(|p| p > threshold).unwrap_or(false)
+    }
+}
+
+/// Statistic-by-statistic comparison of two statistics bundles.
+///
+/// Global statistics come first followed by quantile values and then
+/// statistics for each percentile in ascending order. A quantile or
+/// percentile present in only one of the bundles will have a row with
+/// the other side missing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonReport {
+    rows: Vec<ComparisonRow>,
+}
+
+impl ComparisonReport {
+    pub fn rows(&self) -> &[ComparisonRow] {
+        &self.rows
+    }
+}
+
+/// Compare each statistic in bundle `a` to the same statistic in bundle `b`.
+pub fn compare_bundles(a: &StatisticsBundle, b: &StatisticsBundle) -> ComparisonReport {
+    let mut rows = Vec::new();
+    push_rows(&mut rows, None, Some(a.global_stats()), Some(b.global_stats()));
+
+    let mut quantiles: Vec<&Quantile> = a.quantile_values().iter().chain(b.quantile_values()).collect();
+    quantiles.sort_by(|x, y| x.percent().partial_cmp(&y.percent()).unwrap_or(Ordering::Less));
+    quantiles.dedup_by(|x, y| x.percent() == y.percent());
+
+    for q in quantiles {
+        let find = |bundle: &'_ StatisticsBundle| {
+            bundle
+                .quantile_values()
+                .iter()
+                .find(|v| v.percent() == q.percent())
+                .map(Quantile::value)
+        };
+
+        rows.push(ComparisonRow {
+            key: q.key(),
+            integral: false,
+            a: find(a),
+            b: find(b),
+        });
+    }
+
+    let mut percentiles: Vec<u8> = a.percentiles().chain(b.percentiles()).collect();
+    percentiles.sort_unstable();
+    percentiles.dedup();
+
+    for p in percentiles {
+        push_rows(&mut rows, Some(p), a.percentile_for(p), b.percentile_for(p));
+    }
+
+    ComparisonReport { rows }
+}
+
+fn named_values(stats: Option<&Statistics>) -> [(&'static str, bool, Option<f64>); 7] {
+    [
+        ("count", true, stats.map(|s| s.count() as f64)),
+        ("sum", false, stats.map(|s| s.sum())),
+        ("mean", false, stats.map(|s| s.mean())),
+        ("upper", false, stats.map(|s| s.upper())),
+        ("lower", false, stats.map(|s| s.lower())),
+        ("median", false, stats.map(|s| s.median())),
+        ("stddev", false, stats.map(|s| s.stddev())),
+    ]
+}
+
+fn push_rows(rows: &mut Vec<ComparisonRow>, percentile: Option<u8>, a: Option<&Statistics>, b: Option<&Statistics>) {
+    for (&(name, integral, a), &(_, _, b)) in named_values(a).iter().zip(named_values(b).iter()) {
+        let key = match percentile {
+            Some(p) => format!("{}_{}", name, p),
+            None => name.to_string(),
+        };
+
+        rows.push(ComparisonRow { key, integral, a, b });
+    }
+}
+
+/// Render a comparison report as a table with columns for each side, the
+/// absolute delta, and the percent change.
+///
+/// When a threshold is set and color is enabled, rows that have increased
+/// by more than the threshold percent are highlighted.
+#[derive(Debug)]
+pub struct ComparisonFormatter<'a> {
+    report: &'a ComparisonReport,
+    threshold: Option<f64>,
+    color: bool,
+    precision: usize,
+}
+
+impl<'a> ComparisonFormatter<'a> {
+    pub fn new(report: &'a ComparisonReport) -> ComparisonFormatter<'a> {
+        ComparisonFormatter {
+            report,
+            threshold: None,
+            color: false,
+            precision: DISPLAY_PRECISION,
+        }
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    pub fn threshold(mut self, threshold: Option<f64>) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    fn format_value(&self, val: Option<f64>, integral: bool) -> String {
+        match val {
+            Some(v) if integral => format!("{:.0}", v),
+            Some(v) => format!("{:.*}", self.precision, v),
+            None => MISSING.to_string(),
+        }
+    }
+
+    fn format_percent(val: Option<f64>) -> String {
+        match val {
+            Some(v) => format!("{:+.2}%", v),
+            None => MISSING.to_string(),
+        }
+    }
+}
+
+impl<'a> fmt::Display for ComparisonFormatter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = [
+            "stat".to_string(),
+            "a".to_string(),
+            "b".to_string(),
+            "delta".to_string(),
+            "change".to_string(),
+        ];
+
+        let cells: Vec<[String; 5]> = self
+            .report
+            .rows()
+            .iter()
+            .map(|r| {
+                [
+                    r.key().to_string(),
+                    self.format_value(r.a(), r.integral),
+                    self.format_value(r.b(), r.integral),
+                    self.format_value(r.delta(), r.integral),
+                    Self::format_percent(r.percent_change()),
+                ]
+            })
+            .collect();
+
+        let mut widths = [0usize; 5];
+        for row in std::iter::once(&header).chain(cells.iter()) {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.len());
+            }
+        }
+
+        let write_row = |f: &mut fmt::Formatter, row: &[String; 5]| -> fmt::Result {
+            write!(f, "{:<width$}", row[0], width = widths[0])?;
+            for (cell, &width) in row.iter().zip(widths.iter()).skip(1) {
+                write!(f, "  {:>width$}", cell, width = width)?;
+            }
+            Ok(())
+        };
+
+        write_row(f, &header)?;
+        writeln!(f)?;
+
+        for (row, cell) in self.report.rows().iter().zip(cells.iter()) {
+            let highlight = self.color && self.threshold.map(|t| row.is_regression(t)).unwrap_or(false);
+            if highlight {
+                write!(f, "{}", COLOR_REGRESSION)?;
+            }
+
+            write_row(f, cell)?;
+
+            if highlight {
+                write!(f, "{}", COLOR_RESET)?;
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_bundles, ComparisonFormatter, ComparisonRow};
+    use crate::quantile::QuantileMethod;
+    use crate::StatisticsBundle;
+
+    const VALUES_A: &[f64] = &[1f64, 2f64, 3f64, 4f64, 5f64, 6f64, 7f64, 8f64, 9f64, 10f64];
+
+    const VALUES_B: &[f64] = &[2f64, 4f64, 6f64];
+
+    fn row(a: Option<f64>, b: Option<f64>) -> ComparisonRow {
+        ComparisonRow {
+            key: "mean".to_string(),
+            integral: false,
+            a,
+            b,
+        }
+    }
+
+    #[test]
+    fn test_comparison_row_delta() {
+        assert_eq!(Some(2f64), row(Some(3f64), Some(5f64)).delta());
+        assert_eq!(Some(-2f64), row(Some(5f64), Some(3f64)).delta());
+        assert_eq!(None, row(None, Some(3f64)).delta());
+        assert_eq!(None, row(Some(3f64), None).delta());
+    }
+
+    #[test]
+    fn test_comparison_row_percent_change() {
+        assert_eq!(Some(50f64), row(Some(4f64), Some(6f64)).percent_change());
+        assert_eq!(Some(-25f64), row(Some(4f64), Some(3f64)).percent_change());
+        assert_eq!(Some(0f64), row(Some(0f64), Some(0f64)).percent_change());
+        assert_eq!(None, row(Some(0f64), Some(1f64)).percent_change());
+        assert_eq!(None, row(None, Some(1f64)).percent_change());
+    }
+
+    #[test]
+    fn test_comparison_row_is_regression() {
+        assert!(row(Some(100f64), Some(111f64)).is_regression(10f64));
+        assert!(!row(Some(100f64), Some(110f64)).is_regression(10f64));
+        assert!(!row(Some(100f64), Some(80f64)).is_regression(10f64));
+        assert!(!row(None, Some(80f64)).is_regression(10f64));
+    }
+
+    #[test]
+    fn test_compare_bundles_global_only() {
+        let a = StatisticsBundle::from(VALUES_A).unwrap();
+        let b = StatisticsBundle::from(VALUES_B).unwrap();
+        let report = compare_bundles(&a, &b);
+        let keys: Vec<&str> = report.rows().iter().map(|r| r.key()).collect();
+
+        assert_eq!(vec!["count", "sum", "mean", "upper", "lower", "median", "stddev"], keys);
+        assert_eq!(Some(10f64), report.rows()[0].a());
+        assert_eq!(Some(3f64), report.rows()[0].b());
+    }
+
+    #[test]
+    fn test_compare_bundles_missing_percentile() {
+        // Only 3 values in B so there aren't enough for a 10th percentile slice
+        let a = StatisticsBundle::with_percentiles(VALUES_A, &[10, 50]).unwrap();
+        let b = StatisticsBundle::with_percentiles(VALUES_B, &[10, 50]).unwrap();
+        let report = compare_bundles(&a, &b);
+
+        let count_10 = report.rows().iter().find(|r| r.key() == "count_10").unwrap();
+        assert_eq!(Some(1f64), count_10.a());
+        assert_eq!(None, count_10.b());
+        assert_eq!(None, count_10.delta());
+
+        let count_50 = report.rows().iter().find(|r| r.key() == "count_50").unwrap();
+        assert_eq!(Some(5f64), count_50.a());
+        assert_eq!(Some(1f64), count_50.b());
+        assert_eq!(21, report.rows().len());
+    }
+
+    #[test]
+    fn test_compare_bundles_quantiles() {
+        let a = StatisticsBundle::with_quantiles(VALUES_A, &[], &[50f64, 90f64], QuantileMethod::Linear).unwrap();
+        let b = StatisticsBundle::with_quantiles(VALUES_B, &[], &[90f64], QuantileMethod::Linear).unwrap();
+        let report = compare_bundles(&a, &b);
+        let keys: Vec<&str> = report.rows().iter().skip(7).map(|r| r.key()).collect();
+
+        assert_eq!(vec!["p50", "p90"], keys);
+        assert_eq!(None, report.rows()[7].b());
+        assert_eq!(Some(5.6), report.rows()[8].b());
+    }
+
+    #[test]
+    fn test_comparison_formatter_missing_values() {
+        let a = StatisticsBundle::with_percentiles(VALUES_A, &[10]).unwrap();
+        let b = StatisticsBundle::with_percentiles(VALUES_B, &[10]).unwrap();
+        let report = compare_bundles(&a, &b);
+        let out = format!("{}", ComparisonFormatter::new(&report));
+        let line = out.lines().find(|l| l.starts_with("count_10")).unwrap();
+        let cells: Vec<&str> = line.split_whitespace().collect();
+
+        assert_eq!(vec!["count_10", "1", "-", "-", "-"], cells);
+    }
+
+    #[test]
+    fn test_comparison_formatter_highlight_regressions() {
+        let a = StatisticsBundle::from(VALUES_B).unwrap();
+        let b = StatisticsBundle::from(VALUES_A).unwrap();
+        let report = compare_bundles(&a, &b);
+
+        let plain = format!("{}", ComparisonFormatter::new(&report).threshold(Some(10f64)));
+        assert!(!plain.contains('\x1b'));
+
+        let colored = format!(
+            "{}",
+            ComparisonFormatter::new(&report).threshold(Some(10f64)).color(true)
+        );
+        let count_line = colored.lines().find(|l| l.contains("count")).unwrap();
+        let lower_line = colored.lines().find(|l| l.contains("lower")).unwrap();
+        assert!(count_line.starts_with("\x1b[31m"));
+        assert!(!lower_line.contains('\x1b'));
+    }
+}
@@ -0,0 +1,381 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{StatName, StatisticsBundle};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// How a statistic is compared to a value in a condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    /// Operators for each comparison, longest first so that e.g. `<=` is
+    /// matched before `<`.
+    const OPERATORS: &'static [(&'static str, Comparison)] = &[
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+        }
+    }
+
+    /// True if `a` compared to `b` holds, e.g. `a < b` for `Less`.
+    pub fn holds(self, a: f64, b: f64) -> bool {
+        match self {
+            Comparison::Less => a < b,
+            Comparison::LessOrEqual => a <= b,
+            Comparison::Greater => a > b,
+            Comparison::GreaterOrEqual => a >= b,
+            Comparison::Equal => a == b,
+            Comparison::NotEqual => a != b,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+/// Statistic that a condition compares to a value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConditionStat {
+    /// A statistic of all values, e.g. `mean`.
+    Global(StatName),
+    /// A statistic of a percentile slice, e.g. `p99.upper`.
+    Percentile(u8, StatName),
+    /// The value of a quantile, e.g. `p99.9`, the same as its key when
+    /// displayed.
+    Quantile(f64),
+}
+
+impl fmt::Display for ConditionStat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConditionStat::Global(name) => name.fmt(f),
+            ConditionStat::Percentile(p, name) => write!(f, "p{}.{}", p, name),
+            ConditionStat::Quantile(q) => write!(f, "p{}", q),
+        }
+    }
+}
+
+impl FromStr for ConditionStat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ConditionStat, Self::Err> {
+        if let Ok(name) = s.parse::<StatName>() {
+            return Ok(ConditionStat::Global(name));
+        }
+
+        let rest = s
+            .strip_prefix('p')
+            .ok_or_else(|| format!("unknown statistic '{}'", s))?;
+
+        if let Some((p, name)) = rest.split_once('.') {
+            if let Ok(name) = name.parse::<StatName>() {
+                return match p.parse::<u8>() {
+                    Ok(p) if (1..=99).contains(&p) => Ok(ConditionStat::Percentile(p, name)),
+                    _ => Err(format!("invalid percentile '{}' in '{}'", p, s)),
+                };
+            }
+        }
+
+        match rest.parse::<f64>() {
+            Ok(q) if (0f64..=100f64).contains(&q) => Ok(ConditionStat::Quantile(q)),
+            _ => Err(format!("unknown statistic '{}'", s)),
+        }
+    }
+}
+
+/// Reason that a condition couldn't be parsed or evaluated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConditionError {
+    /// An expression wasn't a valid condition.
+    Invalid { expression: String, reason: String },
+    /// A condition referenced a statistic that wasn't computed, e.g. a
+    /// percentile that wasn't requested or a statistic left out by the
+    /// statistics config.
+    NotComputed(ConditionStat),
+}
+
+impl fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConditionError::Invalid { expression, reason } => {
+                write!(f, "Invalid condition '{}': {}", expression, reason)
+            }
+            ConditionError::NotComputed(stat) => write!(f, "Statistic {} was not computed", stat),
+        }
+    }
+}
+
+impl Error for ConditionError {}
+
+/// Comparison of a statistic to a value, parsed from an expression of a
+/// statistic, an operator (`<`, `<=`, `>`, `>=`, `==`, or `!=`), and a
+/// number, e.g. `mean > 100` or `p99.upper >= 250`.
+///
+/// Statistics are named the same as when displayed: `mean` for all values,
+/// `p99.upper` for the 99th percentile slice, and `p99.9` for a quantile.
+///
+/// ```
+/// use staccato::{Condition, StatisticsBundle};
+///
+/// let bundle = StatisticsBundle::with_percentiles(&[1.0, 2.0, 3.0, 4.0], &[50]).unwrap();
+/// let cond: Condition = "p50.upper > 1.5".parse().unwrap();
+///
+/// assert_eq!(Ok(true), cond.evaluate(&bundle));
+/// assert_eq!(Ok(false), "mean >= 3".parse::<Condition>().unwrap().evaluate(&bundle));
+/// assert!("p90.upper > 1".parse::<Condition>().unwrap().evaluate(&bundle).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Condition {
+    stat: ConditionStat,
+    comparison: Comparison,
+    value: f64,
+}
+
+impl Condition {
+    pub fn new(stat: ConditionStat, comparison: Comparison, value: f64) -> Condition {
+        Condition {
+            stat,
+            comparison,
+            value,
+        }
+    }
+
+    pub fn stat(&self) -> ConditionStat {
+        self.stat
+    }
+
+    pub fn comparison(&self) -> Comparison {
+        self.comparison
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Value of the statistic from a bundle, or an error if it wasn't
+    /// computed.
+    pub fn actual(&self, bundle: &StatisticsBundle) -> Result<f64, ConditionError> {
+        let actual = match self.stat {
+            ConditionStat::Global(name) => Some(bundle.global_stats().value(name).as_f64()),
+            ConditionStat::Percentile(p, name) => bundle.percentile_for(p).map(|s| s.value(name).as_f64()),
+            ConditionStat::Quantile(q) => bundle
+                .quantile_values()
+                .iter()
+                .find(|v| v.percent() == q)
+                .map(|v| v.value()),
+        };
+
+        // Statistics left out by the statistics config are NaN.
+        actual
+            .filter(|v| !v.is_nan())
+            .ok_or(ConditionError::NotComputed(self.stat))
+    }
+
+    /// True if the statistic from a bundle compared to the value holds, or
+    /// an error if the statistic wasn't computed.
+    pub fn evaluate(&self, bundle: &StatisticsBundle) -> Result<bool, ConditionError> {
+        self.actual(bundle).map(|v| self.comparison.holds(v, self.value))
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.stat, self.comparison, self.value)
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ConditionError;
+
+    fn from_str(s: &str) -> Result<Condition, Self::Err> {
+        let invalid = |reason: String| ConditionError::Invalid {
+            expression: s.to_string(),
+            reason,
+        };
+
+        let (index, op, comparison) = Comparison::OPERATORS
+            .iter()
+            .filter_map(|&(op, c)| s.find(op).map(|i| (i, op, c)))
+            .min_by_key(|&(i, op, _)| (i, usize::MAX - op.len()))
+            .ok_or_else(|| invalid("expected a comparison operator".to_string()))?;
+
+        let stat = s[..index].trim();
+        let value = s[index + op.len()..].trim();
+        if stat.is_empty() {
+            return Err(invalid("expected a statistic before the operator".to_string()));
+        }
+
+        let stat = stat.parse::<ConditionStat>().map_err(invalid)?;
+        let value = match value.parse::<f64>() {
+            Ok(v) if !v.is_nan() => v,
+            _ => {
+                return Err(invalid(format!(
+                    "expected a number after the operator, got '{}'",
+                    value
+                )))
+            }
+        };
+
+        Ok(Condition::new(stat, comparison, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparison, Condition, ConditionError, ConditionStat};
+    use crate::{QuantileMethod, StatName, StatisticsBundle, StatisticsConfig};
+
+    fn bundle() -> StatisticsBundle {
+        let vals: Vec<f64> = (1..=100).map(f64::from).collect();
+        StatisticsBundle::with_quantiles(&vals, &[50, 90], &[99.9], QuantileMethod::Linear).unwrap()
+    }
+
+    fn evaluate(expression: &str) -> Result<bool, ConditionError> {
+        expression.parse::<Condition>()?.evaluate(&bundle())
+    }
+
+    #[test]
+    fn test_parse() {
+        let cond: Condition = "p99.upper > 250".parse().unwrap();
+
+        assert_eq!(ConditionStat::Percentile(99, StatName::Upper), cond.stat());
+        assert_eq!(Comparison::Greater, cond.comparison());
+        assert_eq!(250f64, cond.value());
+        assert_eq!("p99.upper > 250", cond.to_string());
+    }
+
+    #[test]
+    fn test_parse_without_whitespace() {
+        let cond: Condition = "mean<=1.5".parse().unwrap();
+
+        assert_eq!(ConditionStat::Global(StatName::Mean), cond.stat());
+        assert_eq!(Comparison::LessOrEqual, cond.comparison());
+        assert_eq!(1.5, cond.value());
+    }
+
+    #[test]
+    fn test_parse_quantile() {
+        let cond: Condition = "p99.9 >= 1e3".parse().unwrap();
+
+        assert_eq!(ConditionStat::Quantile(99.9), cond.stat());
+        assert_eq!(1000f64, cond.value());
+        assert_eq!(
+            ConditionStat::Quantile(50f64),
+            "p50 == 1".parse::<Condition>().unwrap().stat()
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        for expression in [
+            "",
+            "mean",
+            "mean 100",
+            "> 100",
+            "mean >",
+            "mean > foo",
+            "mean > NaN",
+            "mode > 1",
+            "p0.mean > 1",
+            "p100.mean > 1",
+            "p101 > 1",
+            "pfoo > 1",
+            "mean >> 1",
+        ] {
+            assert!(
+                matches!(expression.parse::<Condition>(), Err(ConditionError::Invalid { .. })),
+                "{}",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn test_each_operator() {
+        assert_eq!(Ok(true), evaluate("mean < 51"));
+        assert_eq!(Ok(false), evaluate("mean < 50.5"));
+        assert_eq!(Ok(true), evaluate("mean <= 50.5"));
+        assert_eq!(Ok(false), evaluate("mean <= 50"));
+        assert_eq!(Ok(true), evaluate("mean > 50"));
+        assert_eq!(Ok(false), evaluate("mean > 50.5"));
+        assert_eq!(Ok(true), evaluate("mean >= 50.5"));
+        assert_eq!(Ok(false), evaluate("mean >= 51"));
+        assert_eq!(Ok(true), evaluate("count == 100"));
+        assert_eq!(Ok(false), evaluate("count == 99"));
+        assert_eq!(Ok(true), evaluate("count != 99"));
+        assert_eq!(Ok(false), evaluate("count != 100"));
+    }
+
+    #[test]
+    fn test_percentiles_and_quantiles() {
+        assert_eq!(Ok(true), evaluate("p90.upper == 90"));
+        assert_eq!(Ok(true), evaluate("p50.count == 50"));
+        assert_eq!(Ok(true), evaluate("p99.9 > 99"));
+    }
+
+    #[test]
+    fn test_not_computed() {
+        assert_eq!(
+            Err(ConditionError::NotComputed(ConditionStat::Percentile(
+                99,
+                StatName::Upper
+            ))),
+            evaluate("p99.upper > 1")
+        );
+        assert_eq!(
+            Err(ConditionError::NotComputed(ConditionStat::Quantile(99f64))),
+            evaluate("p99 > 1")
+        );
+
+        let vals = [1.0, 2.0, 3.0];
+        let config = StatisticsConfig::none().with_mean(true);
+        let bundle = StatisticsBundle::with_config(&vals, &[], &[], QuantileMethod::Linear, config).unwrap();
+        let cond: Condition = "median > 1".parse().unwrap();
+        assert_eq!(Err(ConditionError::NotComputed(cond.stat())), cond.evaluate(&bundle));
+        assert_eq!(
+            "Statistic median was not computed",
+            cond.evaluate(&bundle).unwrap_err().to_string()
+        );
+    }
+}
@@ -0,0 +1,520 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{ParsedValues, StaccatoError, StatisticsBundle};
+use std::fmt;
+use std::str::FromStr;
+
+/// Names of each statistic that can be selected, in the order they're displayed.
+pub const STATISTICS_KEYS: &[&str] = &["count", "sum", "mean", "upper", "lower", "median", "stddev"];
+
+/// Each statistic computed for a sequence of values, displayed as its
+/// canonical key (one of `STATISTICS_KEYS`), e.g. "stddev".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StatName {
+    Count,
+    Sum,
+    Mean,
+    Upper,
+    Lower,
+    Median,
+    Stddev,
+}
+
+impl StatName {
+    /// Every statistic, in the order they're displayed.
+    pub const ALL: &'static [StatName] = &[
+        StatName::Count,
+        StatName::Sum,
+        StatName::Mean,
+        StatName::Upper,
+        StatName::Lower,
+        StatName::Median,
+        StatName::Stddev,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatName::Count => "count",
+            StatName::Sum => "sum",
+            StatName::Mean => "mean",
+            StatName::Upper => "upper",
+            StatName::Lower => "lower",
+            StatName::Median => "median",
+            StatName::Stddev => "stddev",
+        }
+    }
+
+    /// Short description of the statistic, e.g. "largest value".
+    pub fn description(&self) -> &'static str {
+        match self {
+            StatName::Count => "number of values",
+            StatName::Sum => "sum of the values",
+            StatName::Mean => "arithmetic mean of the values",
+            StatName::Upper => "largest value",
+            StatName::Lower => "smallest value",
+            StatName::Median => "middle value, or the mean of the two middle values",
+            StatName::Stddev => "population standard deviation of the values",
+        }
+    }
+
+    /// True if computing the statistic takes an extra pass over the values
+    /// after the one that computes the others, which is skipped when it
+    /// isn't selected.
+    pub fn needs_extra_pass(&self) -> bool {
+        matches!(self, StatName::Median | StatName::Stddev)
+    }
+}
+
+impl fmt::Display for StatName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl FromStr for StatName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<StatName, Self::Err> {
+        StatName::ALL.iter().find(|n| n.as_str() == s).copied().ok_or_else(|| {
+            format!(
+                "Invalid statistic {}, expected one of {}",
+                s,
+                STATISTICS_KEYS.join(", ")
+            )
+        })
+    }
+}
+
+/// Value of a statistic, which is an integer for counts and a float otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatValue {
+    Integer(u64),
+    Float(f64),
+}
+
+impl StatValue {
+    /// Value as a float, which may round integers larger than 2^53.
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            StatValue::Integer(v) => v as f64,
+            StatValue::Float(v) => v,
+        }
+    }
+}
+
+impl From<u64> for StatValue {
+    fn from(v: u64) -> Self {
+        StatValue::Integer(v)
+    }
+}
+
+impl From<f64> for StatValue {
+    fn from(v: f64) -> Self {
+        StatValue::Float(v)
+    }
+}
+
+/// Display the value like the integer or float it is. The precision (e.g.
+/// `{:.3}`) only applies to floats, integers are always displayed without
+/// a decimal point.
+impl fmt::Display for StatValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StatValue::Integer(v) => write!(f, "{}", v),
+            StatValue::Float(v) => fmt::Display::fmt(&v, f),
+        }
+    }
+}
+
+/// Serialize the value as the integer or float it is, e.g. so that a count
+/// is a JSON integer.
+#[cfg(feature = "serde")]
+impl serde::Serialize for StatValue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            StatValue::Integer(v) => serializer.serialize_u64(v),
+            StatValue::Float(v) => serializer.serialize_f64(v),
+        }
+    }
+}
+
+/// Which statistics should be computed and displayed.
+///
+/// By default every statistic is selected. Statistics that need an extra
+/// pass over the values (the median and standard deviation) are skipped
+/// entirely by `Statistics::from_with_config` when they aren't selected.
+///
+/// ```
+/// use staccato::StatisticsConfig;
+///
+/// let config = StatisticsConfig::new().with_stddev(false).with_median(false);
+/// assert!(config.mean());
+/// assert!(!config.stddev());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StatisticsConfig {
+    count: bool,
+    sum: bool,
+    mean: bool,
+    upper: bool,
+    lower: bool,
+    median: bool,
+    stddev: bool,
+}
+
+impl StatisticsConfig {
+    /// Create a config that selects every statistic.
+    pub fn new() -> StatisticsConfig {
+        Self::all(true)
+    }
+
+    /// Create a config that doesn't select any statistics, to build up from
+    /// only the ones that are needed.
+    pub fn none() -> StatisticsConfig {
+        Self::all(false)
+    }
+
+    fn all(selected: bool) -> StatisticsConfig {
+        StatisticsConfig {
+            count: selected,
+            sum: selected,
+            mean: selected,
+            upper: selected,
+            lower: selected,
+            median: selected,
+            stddev: selected,
+        }
+    }
+
+    pub fn with_count(mut self, selected: bool) -> Self {
+        self.count = selected;
+        self
+    }
+
+    pub fn with_sum(mut self, selected: bool) -> Self {
+        self.sum = selected;
+        self
+    }
+
+    pub fn with_mean(mut self, selected: bool) -> Self {
+        self.mean = selected;
+        self
+    }
+
+    pub fn with_upper(mut self, selected: bool) -> Self {
+        self.upper = selected;
+        self
+    }
+
+    pub fn with_lower(mut self, selected: bool) -> Self {
+        self.lower = selected;
+        self
+    }
+
+    pub fn with_median(mut self, selected: bool) -> Self {
+        self.median = selected;
+        self
+    }
+
+    pub fn with_stddev(mut self, selected: bool) -> Self {
+        self.stddev = selected;
+        self
+    }
+
+    pub fn count(&self) -> bool {
+        self.count
+    }
+
+    pub fn sum(&self) -> bool {
+        self.sum
+    }
+
+    pub fn mean(&self) -> bool {
+        self.mean
+    }
+
+    pub fn upper(&self) -> bool {
+        self.upper
+    }
+
+    pub fn lower(&self) -> bool {
+        self.lower
+    }
+
+    pub fn median(&self) -> bool {
+        self.median
+    }
+
+    pub fn stddev(&self) -> bool {
+        self.stddev
+    }
+
+    /// True if the given statistic is selected.
+    pub fn includes(&self, name: StatName) -> bool {
+        match name {
+            StatName::Count => self.count,
+            StatName::Sum => self.sum,
+            StatName::Mean => self.mean,
+            StatName::Upper => self.upper,
+            StatName::Lower => self.lower,
+            StatName::Median => self.median,
+            StatName::Stddev => self.stddev,
+        }
+    }
+
+    /// Select or deselect the given statistic.
+    pub fn with_name(self, name: StatName, selected: bool) -> Self {
+        match name {
+            StatName::Count => self.with_count(selected),
+            StatName::Sum => self.with_sum(selected),
+            StatName::Mean => self.with_mean(selected),
+            StatName::Upper => self.with_upper(selected),
+            StatName::Lower => self.with_lower(selected),
+            StatName::Median => self.with_median(selected),
+            StatName::Stddev => self.with_stddev(selected),
+        }
+    }
+
+    /// True if the statistic with the given name (one of `STATISTICS_KEYS`)
+    /// is selected. Unknown names are never selected.
+    pub fn is_selected(&self, key: &str) -> bool {
+        key.parse::<StatName>().is_ok_and(|n| self.includes(n))
+    }
+
+    /// Select or deselect the statistic with the given name, returning
+    /// `None` if the name isn't one of `STATISTICS_KEYS`.
+    pub fn with_key(self, key: &str, selected: bool) -> Option<Self> {
+        key.parse::<StatName>().ok().map(|n| self.with_name(n, selected))
+    }
+}
+
+impl Default for StatisticsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parse a comma separated list of the names of statistics to select, e.g.
+/// "count,mean". Statistics that aren't in the list aren't selected.
+impl FromStr for StatisticsConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<StatisticsConfig, Self::Err> {
+        s.split(',').try_fold(StatisticsConfig::none(), |config, key| {
+            let key = key.trim();
+            key.parse::<StatName>().map(|n| config.with_name(n, true))
+        })
+    }
+}
+
+/// What to produce for a sequence without any values (or only `NaN` values),
+/// which doesn't have any statistics.
+///
+/// ```
+/// use staccato::{EmptyPolicy, StatisticsBundle};
+///
+/// let vals: &[f64] = &[];
+/// assert!(EmptyPolicy::None.apply(StatisticsBundle::with_percentiles(vals, &[90])).is_none());
+///
+/// let bundle = EmptyPolicy::Zeroed.apply(StatisticsBundle::with_percentiles(vals, &[90])).unwrap();
+/// assert_eq!(0, bundle.global_stats().count());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EmptyPolicy {
+    /// Produce nothing, e.g. `None` or an error.
+    #[default]
+    None,
+    /// Produce `StatisticsBundle::empty`, with a count and sum of zero.
+    Zeroed,
+}
+
+impl EmptyPolicy {
+    /// Bundle to use for a sequence given its bundle, if it has any values.
+    pub fn apply(self, bundle: Option<StatisticsBundle>) -> Option<StatisticsBundle> {
+        match self {
+            EmptyPolicy::None => bundle,
+            EmptyPolicy::Zeroed => Some(bundle.unwrap_or_else(StatisticsBundle::empty)),
+        }
+    }
+}
+
+/// What to do with lines of input that aren't values, such as text.
+///
+/// ```
+/// use staccato::{get_parsed_values, ErrorPolicy, SortingPolicy};
+///
+/// let parsed = get_parsed_values(&mut "1\nfoo\n2\n".as_bytes(), SortingPolicy::Unsorted).unwrap();
+/// assert_eq!(vec![1f64, 2f64], ErrorPolicy::Skip.apply(parsed.clone()).unwrap());
+/// assert!(ErrorPolicy::Fail.apply(parsed).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ErrorPolicy {
+    /// Skip them and use the other lines.
+    #[default]
+    Skip,
+    /// Fail with `StaccatoError::Malformed`, like `st --strict`.
+    Fail,
+}
+
+impl ErrorPolicy {
+    /// Values parsed from input, or an error if any lines were malformed and
+    /// this policy is `Fail`. Blank lines are always skipped.
+    pub fn apply<V>(self, parsed: ParsedValues<V>) -> Result<Vec<V>, StaccatoError> {
+        if self == ErrorPolicy::Fail && parsed.malformed() > 0 {
+            return Err(StaccatoError::Malformed {
+                count: parsed.malformed(),
+                samples: parsed.first_errors().to_vec(),
+            });
+        }
+
+        Ok(parsed.into_values())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmptyPolicy, ErrorPolicy, StatName, StatValue, StatisticsConfig, STATISTICS_KEYS};
+    use crate::{get_parsed_values, SortingPolicy, StaccatoError, StatisticsBundle};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_default_selects_everything() {
+        let config = StatisticsConfig::default();
+        assert!(STATISTICS_KEYS.iter().all(|k| config.is_selected(k)));
+    }
+
+    #[test]
+    fn test_none_selects_nothing() {
+        let config = StatisticsConfig::none();
+        assert!(STATISTICS_KEYS.iter().all(|k| !config.is_selected(k)));
+    }
+
+    #[test]
+    fn test_builder() {
+        let config = StatisticsConfig::new().with_stddev(false).with_median(false);
+        assert!(config.count());
+        assert!(config.mean());
+        assert!(!config.median());
+        assert!(!config.stddev());
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let config = StatisticsConfig::from_str("mean, upper").unwrap();
+        assert_eq!(StatisticsConfig::none().with_mean(true).with_upper(true), config);
+    }
+
+    #[test]
+    fn test_parse_keys_invalid() {
+        assert!(StatisticsConfig::from_str("mean,p99").is_err());
+        assert!(StatisticsConfig::from_str("").is_err());
+    }
+
+    #[test]
+    fn test_is_selected_unknown() {
+        assert!(!StatisticsConfig::new().is_selected("p99"));
+    }
+
+    #[test]
+    fn test_stat_name_keys() {
+        let keys: Vec<&str> = StatName::ALL.iter().map(|n| n.as_str()).collect();
+        assert_eq!(STATISTICS_KEYS, keys.as_slice());
+    }
+
+    #[test]
+    fn test_stat_name_descriptions() {
+        for name in StatName::ALL {
+            assert!(!name.description().is_empty(), "{}", name);
+        }
+
+        assert!(StatName::Median.needs_extra_pass());
+        assert!(!StatName::Count.needs_extra_pass());
+    }
+
+    #[test]
+    fn test_stat_name_round_trip() {
+        for name in StatName::ALL {
+            assert_eq!(*name, name.to_string().parse().unwrap());
+        }
+
+        assert!(StatName::from_str("variance").is_err());
+    }
+
+    #[test]
+    fn test_includes() {
+        let config = StatisticsConfig::none().with_name(StatName::Median, true);
+        assert!(config.includes(StatName::Median));
+        assert!(!config.includes(StatName::Mean));
+    }
+
+    #[test]
+    fn test_stat_value_as_f64() {
+        assert_eq!(3f64, StatValue::Integer(3).as_f64());
+        assert_eq!(2.5, StatValue::Float(2.5).as_f64());
+    }
+
+    #[test]
+    fn test_stat_value_display() {
+        assert_eq!("3", format!("{:.5}", StatValue::Integer(3)));
+        assert_eq!("2.50000", format!("{:.5}", StatValue::Float(2.5)));
+        assert_eq!("2.5", StatValue::from(2.5).to_string());
+        assert_eq!(StatValue::Integer(3), StatValue::from(3u64));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_stat_value_serialize() {
+        let json = serde_json::to_value([StatValue::Integer(3), StatValue::Float(3.0)]).unwrap();
+        assert!(json[0].is_u64());
+        assert!(json[1].is_f64());
+        assert_eq!("[3,3.0]", json.to_string());
+    }
+
+    #[test]
+    fn test_empty_policy_none() {
+        assert!(EmptyPolicy::None.apply(None).is_none());
+        let bundle = StatisticsBundle::from(&[1.0, 2.0]);
+        assert_eq!(bundle, EmptyPolicy::None.apply(bundle.clone()));
+    }
+
+    #[test]
+    fn test_empty_policy_zeroed() {
+        let bundle = EmptyPolicy::Zeroed.apply(None).unwrap();
+        assert_eq!(0, bundle.global_stats().count());
+
+        let bundle = StatisticsBundle::from(&[1.0, 2.0]);
+        assert_eq!(bundle, EmptyPolicy::Zeroed.apply(bundle.clone()));
+    }
+
+    #[test]
+    fn test_error_policy_skip() {
+        let parsed = get_parsed_values(&mut "1\n\nfoo\n2\n".as_bytes(), SortingPolicy::Sorted).unwrap();
+        assert_eq!(vec![1f64, 2f64], ErrorPolicy::Skip.apply(parsed).unwrap());
+    }
+
+    #[test]
+    fn test_error_policy_fail() {
+        let parsed = get_parsed_values(&mut "1\n\n2\n".as_bytes(), SortingPolicy::Sorted).unwrap();
+        assert_eq!(vec![1f64, 2f64], ErrorPolicy::Fail.apply(parsed).unwrap());
+
+        let parsed = get_parsed_values(&mut "1\nfoo\n2\nbar\n".as_bytes(), SortingPolicy::Sorted).unwrap();
+        let res = ErrorPolicy::Fail.apply(parsed);
+        assert!(matches!(res, Err(StaccatoError::Malformed { count: 2, ref samples }) if samples[0].line() == 2));
+    }
+}
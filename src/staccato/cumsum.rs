@@ -0,0 +1,214 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{write_ordered, KeyValueSep, DISPLAY_PRECISION};
+use std::fmt;
+use std::str::FromStr;
+
+/// Iterator over the running total of a sequence of values.
+///
+/// The total is computed using compensated (Kahan-Babuska) summation so that
+/// rounding error doesn't accumulate over long sequences of values.
+#[derive(Debug, Clone)]
+pub struct CumulativeSum<I> {
+    iter: I,
+    sum: f64,
+    compensation: f64,
+}
+
+impl<I> CumulativeSum<I>
+where
+    I: Iterator<Item = f64>,
+{
+    pub fn new(iter: I) -> CumulativeSum<I> {
+        CumulativeSum {
+            iter,
+            sum: 0f64,
+            compensation: 0f64,
+        }
+    }
+}
+
+impl<I> Iterator for CumulativeSum<I>
+where
+    I: Iterator<Item = f64>,
+{
+    type Item = f64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let val = self.iter.next()?;
+        let total = self.sum + val;
+
+        // Keep track of the low-order bits lost by the addition above,
+        // from whichever of the two operands was smaller.
+        if self.sum.abs() >= val.abs() {
+            self.compensation += (self.sum - total) + val;
+        } else {
+            self.compensation += (val - total) + self.sum;
+        }
+
+        self.sum = total;
+        Some(self.sum + self.compensation)
+    }
+}
+
+/// What should be done with the running total of a sequence of values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CumulativeSumMode {
+    /// Display only the running total for each value.
+    #[default]
+    Totals,
+    /// Display each value followed by the running total.
+    WithValues,
+    /// Compute statistics of the running totals instead of the values.
+    Stats,
+}
+
+impl FromStr for CumulativeSumMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CumulativeSumMode, Self::Err> {
+        if "totals" == s {
+            Ok(CumulativeSumMode::Totals)
+        } else if "with-values" == s {
+            Ok(CumulativeSumMode::WithValues)
+        } else if "stats" == s {
+            Ok(CumulativeSumMode::Stats)
+        } else {
+            Err(format!("Invalid cumulative sum mode {}", s))
+        }
+    }
+}
+
+/// Render the running total of a sequence of values in its original order,
+/// optionally preceded by each value and a separator.
+#[derive(Debug)]
+pub struct CumulativeSumFormatter<'a> {
+    vals: &'a [f64],
+    with_values: bool,
+    sep: KeyValueSep,
+    precision: usize,
+}
+
+impl<'a> CumulativeSumFormatter<'a> {
+    pub fn new(vals: &'a [f64]) -> CumulativeSumFormatter<'a> {
+        Self::with_sep(vals, KeyValueSep::Colon)
+    }
+
+    pub fn with_sep(vals: &'a [f64], sep: KeyValueSep) -> CumulativeSumFormatter<'a> {
+        CumulativeSumFormatter {
+            vals,
+            with_values: false,
+            sep,
+            precision: DISPLAY_PRECISION,
+        }
+    }
+
+    pub fn with_values(mut self, with_values: bool) -> Self {
+        self.with_values = with_values;
+        self
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+}
+
+impl<'a> fmt::Display for CumulativeSumFormatter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut totals = CumulativeSum::new(self.vals.iter().cloned());
+        let precision = self.precision;
+
+        write_ordered(f, self.vals, &self.sep, |v| {
+            let total = format!("{:.*}", precision, totals.next().unwrap_or(0f64));
+            if self.with_values {
+                vec![format!("{:.*}", precision, v), total]
+            } else {
+                vec![total]
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CumulativeSum, CumulativeSumFormatter, CumulativeSumMode};
+    use crate::KeyValueSep;
+
+    #[test]
+    fn test_cumulative_sum_empty() {
+        assert_eq!(0, CumulativeSum::new(Vec::new().into_iter()).count());
+    }
+
+    #[test]
+    fn test_cumulative_sum_values() {
+        let totals: Vec<f64> = CumulativeSum::new(vec![1f64, 2f64, 3f64, -4f64].into_iter()).collect();
+        assert_eq!(vec![1f64, 3f64, 6f64, 2f64], totals);
+    }
+
+    #[test]
+    fn test_cumulative_sum_no_drift() {
+        // Naively adding 0.1 a million times drifts from the correct total by
+        // more than 1e-6, compensated summation should be as close as possible
+        let n = 1_000_000;
+        let reference = 0.1 * n as f64;
+        let naive = (0..n).fold(0f64, |sum, _| sum + 0.1);
+        let total = CumulativeSum::new((0..n).map(|_| 0.1)).last().unwrap();
+
+        assert!((naive - reference).abs() > 1e-6);
+        assert!((total - reference).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_cumulative_sum_mixed_magnitudes() {
+        let vals = vec![1e16, 1f64, -1e16, 1f64];
+        let totals: Vec<f64> = CumulativeSum::new(vals.into_iter()).collect();
+
+        assert_eq!(2f64, totals[3]);
+    }
+
+    #[test]
+    fn test_cumulative_sum_mode_from_str() {
+        assert_eq!(CumulativeSumMode::Totals, "totals".parse().unwrap());
+        assert_eq!(CumulativeSumMode::WithValues, "with-values".parse().unwrap());
+        assert_eq!(CumulativeSumMode::Stats, "stats".parse().unwrap());
+        assert!("banana".parse::<CumulativeSumMode>().is_err());
+    }
+
+    #[test]
+    fn test_cumulative_sum_formatter_totals() {
+        let vals = &[3f64, 1f64, 4f64];
+        let out = format!("{}", CumulativeSumFormatter::new(vals).precision(0));
+
+        assert_eq!("3\n4\n8\n", out);
+    }
+
+    #[test]
+    fn test_cumulative_sum_formatter_with_values() {
+        let vals = &[3f64, 1f64, 4f64];
+        let out = format!(
+            "{}",
+            CumulativeSumFormatter::with_sep(vals, KeyValueSep::Tab)
+                .with_values(true)
+                .precision(1)
+        );
+
+        assert_eq!("3.0\t3.0\n1.0\t4.0\n4.0\t8.0\n", out);
+    }
+}
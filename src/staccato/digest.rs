@@ -0,0 +1,340 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use std::cmp::Ordering;
+use std::f64::consts::PI;
+
+const DEFAULT_COMPRESSION: f64 = 100f64;
+
+/// Number of values (as a multiple of the compression) to buffer before
+/// merging them into the existing centroids.
+const BUFFER_FACTOR: f64 = 5f64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// Sketch of a sequence of values that can estimate the value at any
+/// quantile using memory bounded by its compression, not the number of
+/// values.
+///
+/// This is a merging t-digest: values are summarized by centroids that
+/// are smaller near the tails of the distribution than in the middle so
+/// that extreme quantiles (like p99 or p99.9) are the most accurate. With
+/// the default compression of 100, estimates are within 1% of the correct
+/// rank and keep fewer than a few hundred centroids.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    buffer: Vec<Centroid>,
+    count: f64,
+    upper: f64,
+    lower: f64,
+}
+
+impl TDigest {
+    pub fn new() -> TDigest {
+        Self::with_compression(DEFAULT_COMPRESSION)
+    }
+
+    /// Create a digest with the given compression. Higher values are more
+    /// accurate but use more memory.
+    pub fn with_compression(compression: f64) -> TDigest {
+        TDigest {
+            compression,
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            count: 0f64,
+            upper: f64::MIN,
+            lower: f64::MAX,
+        }
+    }
+
+    /// Add a single value to the digest. `NaN` values are ignored.
+    pub fn push(&mut self, val: f64) {
+        if val.is_nan() {
+            return;
+        }
+
+        self.add(Centroid {
+            mean: val,
+            weight: 1f64,
+        });
+        if self.buffer.len() as f64 >= self.compression * BUFFER_FACTOR {
+            self.compress();
+        }
+    }
+
+    /// Add all the values summarized by another digest to this one.
+    pub fn merge(&mut self, other: &TDigest) {
+        for &c in other.centroids.iter().chain(other.buffer.iter()) {
+            self.add(c);
+        }
+
+        self.compress();
+    }
+
+    pub fn count(&self) -> usize {
+        self.count as usize
+    }
+
+    /// Estimate the value at quantile `q` (from 0 to 1, inclusive) of all
+    /// the values added to the digest.
+    ///
+    /// This method returns `None` if the digest is empty or if the quantile
+    /// is not between 0 and 1.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0f64 || !(0f64..=1f64).contains(&q) {
+            return None;
+        }
+
+        if self.buffer.is_empty() {
+            Some(self.interpolate(q))
+        } else {
+            let mut merged = self.clone();
+            merged.compress();
+            Some(merged.interpolate(q))
+        }
+    }
+
+    fn add(&mut self, c: Centroid) {
+        self.buffer.push(c);
+        self.count += c.weight;
+
+        if c.mean > self.upper {
+            self.upper = c.mean;
+        }
+
+        if c.mean < self.lower {
+            self.lower = c.mean;
+        }
+    }
+
+    /// Scale function mapping a quantile to an index such that each
+    /// centroid may only span a single unit of the index.
+    fn scale(&self, q: f64) -> f64 {
+        self.compression / (2f64 * PI) * (2f64 * q - 1f64).asin()
+    }
+
+    fn scale_inverse(&self, k: f64) -> f64 {
+        if k >= self.compression / 4f64 {
+            1f64
+        } else {
+            ((k * 2f64 * PI / self.compression).sin() + 1f64) / 2f64
+        }
+    }
+
+    fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let mut all = std::mem::take(&mut self.centroids);
+        all.append(&mut self.buffer);
+        all.sort_by(|a, b| a.mean.partial_cmp(&b.mean).unwrap_or(Ordering::Less));
+
+        let mut merged = Vec::new();
+        let mut current = all[0];
+        let mut before = 0f64;
+        let mut limit = self.count * self.scale_inverse(self.scale(0f64) + 1f64);
+
+        for &c in &all[1..] {
+            if before + current.weight + c.weight <= limit {
+                let weight = current.weight + c.weight;
+                current.mean += (c.mean - current.mean) * c.weight / weight;
+                current.weight = weight;
+            } else {
+                before += current.weight;
+                merged.push(current);
+                limit = self.count * self.scale_inverse(self.scale(before / self.count) + 1f64);
+                current = c;
+            }
+        }
+
+        merged.push(current);
+        self.centroids = merged;
+    }
+
+    /// Linearly interpolate between the centers of the centroids on either
+    /// side of the quantile. Must only be called once all values have been
+    /// merged into centroids.
+    fn interpolate(&self, q: f64) -> f64 {
+        let index = q * self.count;
+        let first = self.centroids[0];
+        if index <= first.weight / 2f64 {
+            let frac = if first.weight > 1f64 {
+                index / (first.weight / 2f64)
+            } else {
+                0f64
+            };
+
+            return self.lower + (first.mean - self.lower) * frac;
+        }
+
+        let mut center = first.weight / 2f64;
+        for pair in self.centroids.windows(2) {
+            let (left, right) = (pair[0], pair[1]);
+            let next = center + (left.weight + right.weight) / 2f64;
+
+            if index <= next {
+                let frac = (index - center) / (next - center);
+                return left.mean + (right.mean - left.mean) * frac;
+            }
+
+            center = next;
+        }
+
+        let last = self.centroids[self.centroids.len() - 1];
+        let remaining = self.count - center;
+        let frac = if remaining > 0f64 {
+            (index - center) / remaining
+        } else {
+            1f64
+        };
+
+        last.mean + (self.upper - last.mean) * frac.min(1f64)
+    }
+}
+
+impl Default for TDigest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TDigest;
+    use crate::sort_values;
+
+    const QUANTILES: &[f64] = &[0.001, 0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99, 0.999];
+
+    /// Deterministic, exponentially distributed (like latencies) values.
+    fn synthetic(n: usize, seed: u64) -> Vec<f64> {
+        let mut state = seed;
+        (0..n)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let uniform = (state >> 11) as f64 / (1u64 << 53) as f64;
+                -(1f64 - uniform).ln() * 100f64
+            })
+            .collect()
+    }
+
+    fn rank_error(sorted: &[f64], q: f64, estimate: f64) -> f64 {
+        let rank = sorted.partition_point(|&v| v < estimate) as f64 / sorted.len() as f64;
+        (rank - q).abs()
+    }
+
+    #[test]
+    fn test_tdigest_empty() {
+        let digest = TDigest::new();
+
+        assert_eq!(0, digest.count());
+        assert_eq!(None, digest.quantile(0.5));
+    }
+
+    #[test]
+    fn test_tdigest_out_of_range() {
+        let mut digest = TDigest::new();
+        digest.push(1f64);
+
+        assert_eq!(None, digest.quantile(-0.1));
+        assert_eq!(None, digest.quantile(1.1));
+        assert_eq!(None, digest.quantile(f64::NAN));
+    }
+
+    #[test]
+    fn test_tdigest_single_value() {
+        let mut digest = TDigest::new();
+        digest.push(42f64);
+
+        assert_eq!(Some(42f64), digest.quantile(0f64));
+        assert_eq!(Some(42f64), digest.quantile(0.5));
+        assert_eq!(Some(42f64), digest.quantile(1f64));
+    }
+
+    #[test]
+    fn test_tdigest_bounds_exact() {
+        let mut digest = TDigest::new();
+        for v in synthetic(10_000, 7) {
+            digest.push(v);
+        }
+
+        let mut sorted = synthetic(10_000, 7);
+        sort_values(&mut sorted);
+
+        assert_eq!(Some(sorted[0]), digest.quantile(0f64));
+        assert_eq!(Some(sorted[sorted.len() - 1]), digest.quantile(1f64));
+    }
+
+    #[test]
+    fn test_tdigest_ignores_nan() {
+        let mut digest = TDigest::new();
+        digest.push(f64::NAN);
+        digest.push(3f64);
+
+        assert_eq!(1, digest.count());
+        assert_eq!(Some(3f64), digest.quantile(0.5));
+    }
+
+    #[test]
+    fn test_tdigest_rank_error_one_million() {
+        let vals = synthetic(1_000_000, 42);
+        let mut digest = TDigest::new();
+        for &v in &vals {
+            digest.push(v);
+        }
+
+        let mut sorted = vals;
+        sort_values(&mut sorted);
+
+        for &q in QUANTILES {
+            let estimate = digest.quantile(q).unwrap();
+            let error = rank_error(&sorted, q, estimate);
+            assert!(error < 0.01, "rank error {} for quantile {}", error, q);
+        }
+
+        assert!(digest.centroids.len() < 500);
+    }
+
+    #[test]
+    fn test_tdigest_merge() {
+        let (a, b) = (synthetic(100_000, 1), synthetic(100_000, 2));
+        let mut digest_a = TDigest::new();
+        let mut digest_b = TDigest::new();
+        a.iter().for_each(|&v| digest_a.push(v));
+        b.iter().for_each(|&v| digest_b.push(v));
+        digest_a.merge(&digest_b);
+
+        let mut sorted: Vec<f64> = a.into_iter().chain(b).collect();
+        sort_values(&mut sorted);
+
+        assert_eq!(200_000, digest_a.count());
+        for &q in QUANTILES {
+            let estimate = digest_a.quantile(q).unwrap();
+            let error = rank_error(&sorted, q, estimate);
+            assert!(error < 0.01, "rank error {} for quantile {}", error, q);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{
+    for_each_line, QuantileMethod, Reservoir, Rng, SortedValues, StaccatoError, StatisticsBundle, StreamingStatistics,
+};
+use std::io::BufRead;
+
+/// Estimated number of bytes of memory used per value when values are kept
+/// in memory: 8 bytes for the value itself plus up to 8 more bytes of unused
+/// capacity while the vector holding the values grows.
+pub const BYTES_PER_VALUE: usize = 16;
+
+/// Method of computing a statistics bundle from values pushed one at a time.
+pub trait Engine {
+    /// Add a single value.
+    fn push(&mut self, val: f64);
+
+    /// Add values read one line at a time, parsed the same way `get_values`
+    /// parses them.
+    fn push_reader(&mut self, reader: &mut dyn BufRead) -> Result<(), StaccatoError> {
+        for_each_line(reader, |line| {
+            if let Ok(v) = line.trim().parse::<f64>() {
+                self.push(v);
+            }
+        })
+    }
+
+    /// Compute a statistics bundle from all values pushed so far, or `None`
+    /// if there are no values. Engines that can't compute percentile slices
+    /// or quantiles omit them.
+    fn bundle(&mut self, percentiles: &[u8], quantiles: &[f64], method: QuantileMethod) -> Option<StatisticsBundle>;
+}
+
+/// Engine that computes exact statistics by keeping every value in memory.
+#[derive(Debug, Clone, Default)]
+pub struct ExactEngine {
+    values: Vec<f64>,
+}
+
+impl ExactEngine {
+    pub fn new() -> ExactEngine {
+        Self::default()
+    }
+
+    /// Number of values pushed so far.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl Engine for ExactEngine {
+    fn push(&mut self, val: f64) {
+        self.values.push(val);
+    }
+
+    fn bundle(&mut self, percentiles: &[u8], quantiles: &[f64], method: QuantileMethod) -> Option<StatisticsBundle> {
+        StatisticsBundle::from_sorted(SortedValues::sort(&mut self.values), percentiles, quantiles, method)
+    }
+}
+
+/// Estimates quantiles using a `TDigest`. Percentile slices can't be computed
+/// without all the values so the bundle never has any.
+impl Engine for StreamingStatistics {
+    fn push(&mut self, val: f64) {
+        StreamingStatistics::push(self, val)
+    }
+
+    fn bundle(&mut self, _percentiles: &[u8], quantiles: &[f64], _method: QuantileMethod) -> Option<StatisticsBundle> {
+        StreamingStatistics::bundle(self, quantiles)
+    }
+}
+
+impl Engine for Reservoir {
+    fn push(&mut self, val: f64) {
+        Reservoir::push(self, val)
+    }
+
+    fn bundle(&mut self, percentiles: &[u8], quantiles: &[f64], method: QuantileMethod) -> Option<StatisticsBundle> {
+        Reservoir::bundle(self, percentiles, quantiles, method)
+    }
+}
+
+/// Engine that computes exact statistics while the values fit within a
+/// memory limit and approximate statistics once they don't.
+///
+/// Values are kept in memory until there are more than fit in the limit
+/// (using `BYTES_PER_VALUE` per value), at which point they are moved to a
+/// `Reservoir` with a sample as large as fits in the limit. Statistics are
+/// then exact other than the median, quantiles, and percentile statistics
+/// which are estimated from the sample.
+#[derive(Debug, Clone)]
+pub struct MemoryLimited {
+    max_values: usize,
+    rng: Rng,
+    exact: ExactEngine,
+    approximate: Option<Reservoir>,
+}
+
+impl MemoryLimited {
+    /// Create an engine that uses at most about `max_bytes` of memory for
+    /// values and, if it needs to sample values, chooses them using the
+    /// given seed.
+    pub fn new(max_bytes: usize, seed: u64) -> MemoryLimited {
+        Self::with_rng(max_bytes, Rng::new(seed))
+    }
+
+    /// Create an engine that uses at most about `max_bytes` of memory for
+    /// values and, if it needs to sample values, chooses them using the
+    /// given generator.
+    pub fn with_rng(max_bytes: usize, rng: Rng) -> MemoryLimited {
+        MemoryLimited {
+            max_values: (max_bytes / BYTES_PER_VALUE).max(1),
+            rng,
+            exact: ExactEngine::new(),
+            approximate: None,
+        }
+    }
+
+    /// Create an engine that computes approximate statistics from the
+    /// start, e.g. because the input is already known to be too large.
+    pub fn approximate(max_bytes: usize, seed: u64) -> MemoryLimited {
+        Self::approximate_with_rng(max_bytes, Rng::new(seed))
+    }
+
+    /// Create an engine that computes approximate statistics from the
+    /// start, choosing values to sample using the given generator.
+    pub fn approximate_with_rng(max_bytes: usize, rng: Rng) -> MemoryLimited {
+        let mut engine = Self::with_rng(max_bytes, rng);
+        engine.approximate = Some(Reservoir::with_rng(engine.max_values, engine.rng.clone()));
+        engine
+    }
+
+    /// Maximum number of values kept in memory.
+    pub fn max_values(&self) -> usize {
+        self.max_values
+    }
+
+    /// True if statistics are being estimated instead of computed exactly.
+    pub fn is_approximate(&self) -> bool {
+        self.approximate.is_some()
+    }
+}
+
+impl Engine for MemoryLimited {
+    fn push(&mut self, val: f64) {
+        if let Some(r) = self.approximate.as_mut() {
+            return r.push(val);
+        }
+
+        if self.exact.len() < self.max_values {
+            return self.exact.push(val);
+        }
+
+        let mut reservoir = Reservoir::with_rng(self.max_values, self.rng.clone());
+        for v in self.exact.values.drain(..) {
+            reservoir.push(v);
+        }
+
+        reservoir.push(val);
+        self.exact = ExactEngine::new();
+        self.approximate = Some(reservoir);
+    }
+
+    fn bundle(&mut self, percentiles: &[u8], quantiles: &[f64], method: QuantileMethod) -> Option<StatisticsBundle> {
+        match self.approximate.as_mut() {
+            Some(r) => r.bundle(percentiles, quantiles, method),
+            None => self.exact.bundle(percentiles, quantiles, method),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Engine, ExactEngine, MemoryLimited, BYTES_PER_VALUE};
+    use crate::{QuantileMethod, StatisticsFormatter, StreamingStatistics};
+    use std::io::Cursor;
+
+    fn random_values(n: usize) -> Vec<f64> {
+        let mut state = 3u64;
+        (0..n)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 40) as f64 / 100f64
+            })
+            .collect()
+    }
+
+    fn push_all<E: Engine>(engine: &mut E, vals: &[f64]) {
+        for &v in vals {
+            engine.push(v);
+        }
+    }
+
+    #[test]
+    fn test_exact_engine_push_reader() {
+        let mut engine = ExactEngine::new();
+        engine.push_reader(&mut Cursor::new("3\n1\nfoo\n2\n")).unwrap();
+
+        let bundle = engine.bundle(&[], &[], QuantileMethod::Linear).unwrap();
+        assert_eq!(3, bundle.global_stats().count());
+        assert_eq!(2f64, bundle.global_stats().median());
+    }
+
+    #[test]
+    fn test_memory_limited_under_limit_is_exact() {
+        let vals = random_values(1000);
+        let mut exact = ExactEngine::new();
+        let mut limited = MemoryLimited::new(1000 * BYTES_PER_VALUE, 1);
+        push_all(&mut exact, &vals);
+        push_all(&mut limited, &vals);
+
+        let expected = exact.bundle(&[50, 90], &[99f64], QuantileMethod::Linear).unwrap();
+        let actual = limited.bundle(&[50, 90], &[99f64], QuantileMethod::Linear).unwrap();
+
+        assert!(!limited.is_approximate());
+        assert_eq!(None, actual.sample_size());
+        assert_eq!(
+            format!("{}", StatisticsFormatter::new(&expected)),
+            format!("{}", StatisticsFormatter::new(&actual))
+        );
+    }
+
+    #[test]
+    fn test_memory_limited_over_limit_is_approximate() {
+        let vals = random_values(100_000);
+        let mut exact = ExactEngine::new();
+        let mut limited = MemoryLimited::new(5000 * BYTES_PER_VALUE, 1);
+        push_all(&mut exact, &vals);
+        push_all(&mut limited, &vals);
+
+        assert!(limited.is_approximate());
+        assert_eq!(5000, limited.max_values());
+
+        let expected = exact.bundle(&[50, 90], &[99f64], QuantileMethod::Linear).unwrap();
+        let actual = limited.bundle(&[50, 90], &[99f64], QuantileMethod::Linear).unwrap();
+        let (e, a) = (expected.global_stats(), actual.global_stats());
+
+        // Everything but the median is exact
+        assert_eq!(e.count(), a.count());
+        assert_eq!(e.upper(), a.upper());
+        assert_eq!(e.lower(), a.lower());
+        assert!(((e.sum() - a.sum()) / e.sum()).abs() < 1e-12);
+        assert!(((e.stddev() - a.stddev()) / e.stddev()).abs() < 1e-9);
+        assert_eq!(Some(5000), actual.sample_size());
+
+        // The median and quantiles of uniform values are within a few
+        // percent of the range of the correct value with a sample of 5000
+        let tolerance = (e.upper() - e.lower()) * 0.03;
+        assert!((e.median() - a.median()).abs() < tolerance);
+        assert!((expected.quantile_values()[0].value() - actual.quantile_values()[0].value()).abs() < tolerance);
+        for (e, a) in expected.percentile_stats().iter().zip(actual.percentile_stats()) {
+            assert_eq!(e.percentile(), a.percentile());
+            assert!((e.median() - a.median()).abs() < tolerance);
+            assert!((e.mean() - a.mean()).abs() < tolerance);
+        }
+    }
+
+    #[test]
+    fn test_memory_limited_approximate_from_start() {
+        let vals = random_values(100);
+        let mut limited = MemoryLimited::approximate(1000 * BYTES_PER_VALUE, 1);
+        push_all(&mut limited, &vals);
+
+        assert!(limited.is_approximate());
+        assert_eq!(
+            100,
+            limited
+                .bundle(&[], &[], QuantileMethod::Linear)
+                .unwrap()
+                .global_stats()
+                .count()
+        );
+    }
+
+    #[test]
+    fn test_streaming_engine_omits_percentiles() {
+        let mut engine = StreamingStatistics::new();
+        push_all(&mut engine, &random_values(100));
+
+        let bundle = Engine::bundle(&mut engine, &[50], &[50f64], QuantileMethod::Linear).unwrap();
+        assert!(bundle.percentile_stats().is_empty());
+        assert_eq!(1, bundle.quantile_values().len());
+    }
+}
@@ -0,0 +1,220 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::MalformedLine;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Reason that values couldn't be read or an option couldn't be parsed.
+#[derive(Debug)]
+pub enum StaccatoError {
+    /// Reading input (or writing temporary files) failed.
+    Io(io::Error),
+    /// A line of input (counting from 1) wasn't valid UTF-8.
+    Utf8 { line: usize },
+    /// A percentile wasn't a number from 1 to 99.
+    InvalidPercentile { value: String },
+    /// A range of percentiles, e.g. "90-99" or "90-99:3", wasn't valid.
+    InvalidPercentileRange { value: String, reason: &'static str },
+    /// A separator between keys and values was empty.
+    InvalidSeparator,
+    /// A separator between keys and values had a backslash that wasn't
+    /// part of a known escape sequence, e.g. a trailing backslash.
+    InvalidEscape { value: String },
+    /// There were no values to compute anything from.
+    EmptyInput,
+    /// A line of input (counting from 1) couldn't be parsed as a value when
+    /// every line must be a value, e.g. when reading integers.
+    ParseFailure { line: usize, content: String },
+    /// Some lines of input couldn't be parsed as values when they were
+    /// expected to be, with a sample of the first few of them.
+    Malformed { count: usize, samples: Vec<MalformedLine> },
+    /// Values that should have already been sorted weren't, starting with
+    /// the value at a position (counting from 1) that's less than the value
+    /// before it.
+    NotSorted { position: usize },
+}
+
+impl fmt::Display for StaccatoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StaccatoError::Io(e) => e.fmt(f),
+            StaccatoError::Utf8 { line } => write!(f, "line {} is not valid UTF-8", line),
+            StaccatoError::InvalidPercentile { value } => write!(f, "Invalid percentile value {}", value),
+            StaccatoError::InvalidPercentileRange { value, reason } => {
+                write!(f, "Invalid percentile range {}: {}", value, reason)
+            }
+            StaccatoError::InvalidSeparator => write!(f, "Separator must not be empty"),
+            StaccatoError::InvalidEscape { value } => write!(f, "Invalid escape sequence in separator {}", value),
+            StaccatoError::EmptyInput => write!(f, "No values to compute stats for"),
+            StaccatoError::ParseFailure { line, content } => {
+                write!(f, "line {} is not a valid value: '{}'", line, content)
+            }
+            StaccatoError::Malformed { count, samples } => {
+                if *count == 1 {
+                    write!(f, "1 line is not a valid value")?;
+                } else {
+                    write!(f, "{} lines are not valid values", count)?;
+                }
+
+                for m in samples {
+                    write!(f, "\n  {}", m)?;
+                }
+
+                Ok(())
+            }
+            StaccatoError::NotSorted { position } => {
+                write!(
+                    f,
+                    "value {} is less than the value before it, values are not sorted",
+                    position
+                )
+            }
+        }
+    }
+}
+
+impl Error for StaccatoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StaccatoError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StaccatoError {
+    fn from(e: io::Error) -> Self {
+        StaccatoError::Io(e)
+    }
+}
+
+/// Reason a line couldn't be parsed as a value by `parse_values`, with the
+/// number of the line (counting from 1).
+#[derive(Debug)]
+pub enum ParseLineError {
+    /// Reading the line failed.
+    Io { line: usize, error: io::Error },
+    /// The line wasn't valid UTF-8.
+    Utf8 { line: usize },
+    /// The line wasn't a number.
+    Invalid { line: usize, content: String },
+}
+
+impl ParseLineError {
+    /// Number of the line (counting from 1) that couldn't be parsed.
+    pub fn line(&self) -> usize {
+        match self {
+            ParseLineError::Io { line, .. } => *line,
+            ParseLineError::Utf8 { line } => *line,
+            ParseLineError::Invalid { line, .. } => *line,
+        }
+    }
+}
+
+impl fmt::Display for ParseLineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseLineError::Io { line, error } => write!(f, "line {} could not be read: {}", line, error),
+            ParseLineError::Utf8 { line } => write!(f, "line {} is not valid UTF-8", line),
+            ParseLineError::Invalid { line, content } => write!(f, "line {} is not a valid value: '{}'", line, content),
+        }
+    }
+}
+
+impl Error for ParseLineError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseLineError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseLineError> for StaccatoError {
+    fn from(e: ParseLineError) -> Self {
+        match e {
+            ParseLineError::Io { error, .. } => StaccatoError::Io(error),
+            ParseLineError::Utf8 { line } => StaccatoError::Utf8 { line },
+            ParseLineError::Invalid { line, content } => StaccatoError::ParseFailure { line, content },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParseLineError, StaccatoError};
+    use crate::MalformedLine;
+    use std::error::Error;
+    use std::io;
+
+    #[test]
+    fn test_from_io_error() {
+        let err = StaccatoError::from(io::Error::new(io::ErrorKind::NotFound, "missing"));
+        assert!(matches!(err, StaccatoError::Io(ref e) if e.kind() == io::ErrorKind::NotFound));
+        assert!(err.source().is_some());
+        assert_eq!("missing", err.to_string());
+    }
+
+    #[test]
+    fn test_display() {
+        let err = StaccatoError::ParseFailure {
+            line: 3,
+            content: "2.5".to_string(),
+        };
+        assert_eq!("line 3 is not a valid value: '2.5'", err.to_string());
+        assert_eq!("line 7 is not valid UTF-8", StaccatoError::Utf8 { line: 7 }.to_string());
+        assert!(StaccatoError::EmptyInput.source().is_none());
+    }
+
+    #[test]
+    fn test_display_malformed() {
+        let err = StaccatoError::Malformed {
+            count: 3,
+            samples: vec![MalformedLine::new(2, 4, "foo"), MalformedLine::new(5, 20, "bar")],
+        };
+        assert_eq!(
+            "3 lines are not valid values\n  line 2 (byte 4): foo\n  line 5 (byte 20): bar",
+            err.to_string()
+        );
+
+        let err = StaccatoError::Malformed {
+            count: 1,
+            samples: Vec::new(),
+        };
+        assert_eq!("1 line is not a valid value", err.to_string());
+    }
+
+    #[test]
+    fn test_parse_line_error_into_staccato_error() {
+        let err = ParseLineError::Invalid {
+            line: 2,
+            content: "foo".to_string(),
+        };
+        assert_eq!(2, err.line());
+        assert!(matches!(
+            StaccatoError::from(err),
+            StaccatoError::ParseFailure { line: 2, ref content } if content == "foo"
+        ));
+        assert!(matches!(
+            StaccatoError::from(ParseLineError::Utf8 { line: 4 }),
+            StaccatoError::Utf8 { line: 4 }
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::StaccatoError;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Number of bytes at the start of a file used to estimate the number of
+/// values in the whole file.
+const PROBE_SIZE: usize = 64 * 1024;
+
+/// Largest number of values that space is reserved for before reading them,
+/// 512MiB of `f64` values, so that a bad estimate can't cause an absurdly
+/// large allocation. Beyond this the vector of values grows as it's read.
+pub const MAX_RESERVED_VALUES: usize = 64 * 1024 * 1024;
+
+/// Estimate the number of values (lines) in a file based on the average
+/// length of the lines at the start of it.
+///
+/// This method returns `Ok(None)` if the file isn't a regular file (such as
+/// a pipe) since the size of the input can't be known in advance.
+pub fn estimate_values_in_file(path: &Path) -> Result<Option<usize>, StaccatoError> {
+    Ok(probe_file(path)?.map(|(bytes, len)| estimate_values(&bytes, len)))
+}
+
+/// Number of values to reserve space for before reading values from a file,
+/// or zero if the file isn't a regular file or can't be read.
+pub fn capacity_for_file(path: &Path) -> usize {
+    match probe_file(path) {
+        Ok(Some((bytes, len))) => estimate_capacity(&bytes, len),
+        _ => 0,
+    }
+}
+
+/// Estimate the number of values to reserve space for when reading
+/// `total_len` bytes given a sample of the first bytes.
+///
+/// The estimate is the number of lines in the sample scaled to the total
+/// length, limited to the most values that could fit in `total_len` bytes
+/// (a digit and a newline each) and to `MAX_RESERVED_VALUES`.
+pub fn estimate_capacity(probe: &[u8], total_len: u64) -> usize {
+    let most = total_len.div_ceil(2).min(usize::MAX as u64) as usize;
+    estimate_values(probe, total_len).min(most).min(MAX_RESERVED_VALUES)
+}
+
+/// Estimate the number of lines in `total_len` bytes given a sample of them.
+fn estimate_values(probe: &[u8], total_len: u64) -> usize {
+    if probe.is_empty() {
+        return 0;
+    }
+
+    let lines = probe.iter().filter(|&&b| b == b'\n').count().max(1);
+    let estimate = total_len as f64 * lines as f64 / probe.len() as f64;
+    estimate.ceil() as usize
+}
+
+/// Read the first bytes of a regular file along with its total length.
+fn probe_file(path: &Path) -> Result<Option<(Vec<u8>, u64)>, io::Error> {
+    let file = File::open(path)?;
+    let meta = file.metadata()?;
+    if !meta.is_file() {
+        return Ok(None);
+    }
+
+    let mut probe = Vec::with_capacity(PROBE_SIZE);
+    file.take(PROBE_SIZE as u64).read_to_end(&mut probe)?;
+    Ok(Some((probe, meta.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{capacity_for_file, estimate_capacity, estimate_values, estimate_values_in_file, MAX_RESERVED_VALUES};
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::process;
+
+    #[test]
+    fn test_estimate_values() {
+        assert_eq!(0, estimate_values(b"", 0));
+        assert_eq!(1, estimate_values(b"123", 3));
+        assert_eq!(100, estimate_values(b"1.5\n2.5\n", 400));
+    }
+
+    #[test]
+    fn test_estimate_capacity() {
+        assert_eq!(0, estimate_capacity(b"", 0));
+        assert_eq!(100, estimate_capacity(b"1.5\n2.5\n", 400));
+    }
+
+    #[test]
+    fn test_estimate_capacity_at_most_half_of_length() {
+        // Only empty lines in the sample suggests one value per byte
+        assert_eq!(50, estimate_capacity(b"\n\n\n\n", 100));
+        assert_eq!(51, estimate_capacity(b"\n\n\n\n", 101));
+    }
+
+    #[test]
+    fn test_estimate_capacity_limited() {
+        assert_eq!(MAX_RESERVED_VALUES, estimate_capacity(b"1\n", 1 << 40));
+    }
+
+    #[test]
+    fn test_estimate_values_in_file() {
+        let path = env::temp_dir().join(format!("staccato-test-estimate-{}", process::id()));
+        let contents: String = (0..10_000).map(|i| format!("{}\n", i % 100)).collect();
+        fs::write(&path, &contents).unwrap();
+
+        let estimate = estimate_values_in_file(&path).unwrap().unwrap();
+        let capacity = capacity_for_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!((9_000..11_000).contains(&estimate), "estimate {}", estimate);
+        assert_eq!(estimate, capacity);
+    }
+
+    #[test]
+    fn test_capacity_for_file_missing() {
+        assert_eq!(0, capacity_for_file(Path::new("/does/not/exist")));
+    }
+}
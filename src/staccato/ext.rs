@@ -0,0 +1,98 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{SortedValues, Statistics, StatisticsBundle};
+use std::borrow::Borrow;
+
+/// Compute statistics of the values produced by any iterator of `f64` or
+/// `&f64` values, available by importing `staccato::prelude::*`.
+///
+/// The values are collected and sorted, and the results are the same as
+/// the `Statistics::from_sorted` and `StatisticsBundle::from_vec` methods.
+///
+/// ```
+/// use staccato::prelude::*;
+///
+/// let lines = ["12ms", "15ms", "oops", "9ms", "30ms"];
+/// let stats = lines
+///     .iter()
+///     .filter_map(|l| l.trim_end_matches("ms").parse::<f64>().ok())
+///     .map(|ms| ms / 1000.0)
+///     .staccato_stats()
+///     .unwrap();
+/// assert_eq!(4, stats.count());
+/// assert_eq!(0.03, stats.upper());
+///
+/// let vals = vec![4.0, 1.0, 3.0, 2.0];
+/// let bundle = vals.iter().filter(|&&v| v > 1.0).staccato_bundle(&[50]).unwrap();
+/// assert_eq!(3, bundle.global_stats().count());
+/// assert_eq!(Some(2.0), bundle.percentile_for(50).map(|s| s.sum()));
+/// ```
+pub trait StatisticsExt: Iterator {
+    /// Statistics of every value, or `None` if there aren't any.
+    fn staccato_stats(self) -> Option<Statistics>;
+
+    /// Statistics of every value and the lowest percent of them for each
+    /// percentile, or `None` if there aren't any values.
+    fn staccato_bundle(self, percentiles: &[u8]) -> Option<StatisticsBundle>;
+}
+
+impl<I> StatisticsExt for I
+where
+    I: Iterator,
+    I::Item: Borrow<f64>,
+{
+    fn staccato_stats(self) -> Option<Statistics> {
+        let mut vals: Vec<f64> = self.map(|v| *v.borrow()).collect();
+        Statistics::from_sorted(SortedValues::sort(&mut vals), None)
+    }
+
+    fn staccato_bundle(self, percentiles: &[u8]) -> Option<StatisticsBundle> {
+        let vals: Vec<f64> = self.map(|v| *v.borrow()).collect();
+        StatisticsBundle::from_vec(vals, percentiles).map(|(bundle, _)| bundle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatisticsExt;
+    use crate::{SortedValues, Statistics, StatisticsBundle};
+
+    const VALUES: &[f64] = &[9f64, 1f64, 12f64, 5f64, 2f64, 7f64];
+
+    #[test]
+    fn test_staccato_stats_owned_and_borrowed() {
+        let mut sorted = VALUES.to_vec();
+        let expected = Statistics::from_sorted(SortedValues::sort(&mut sorted), None);
+
+        assert_eq!(expected, VALUES.iter().staccato_stats());
+        assert_eq!(expected, VALUES.iter().copied().staccato_stats());
+    }
+
+    #[test]
+    fn test_staccato_bundle() {
+        let expected = StatisticsBundle::from_vec(VALUES.to_vec(), &[50, 90]).map(|(b, _)| b);
+        assert_eq!(expected, VALUES.iter().staccato_bundle(&[50, 90]));
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(VALUES.iter().filter(|&&v| v > 100f64).staccato_stats().is_none());
+        assert!(std::iter::empty::<f64>().staccato_bundle(&[50]).is_none());
+    }
+}
@@ -0,0 +1,439 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use std::fmt;
+use std::str::{FromStr, Split, SplitWhitespace};
+
+/// Produces values from each line of input, e.g. by parsing the whole line
+/// as a number or only one field of it.
+///
+/// Implementations are used by `parse_values_with` and the functions built
+/// on it, which skip blank lines before calling the extractor. Library users
+/// can implement this for input in other formats.
+///
+/// ```
+/// use staccato::{collect_values_with, LineExtractor};
+///
+/// /// Values in milliseconds from lines like "took 250ms".
+/// struct Millis;
+///
+/// impl LineExtractor for Millis {
+///     fn extract(&self, line: &str, out: &mut Vec<f64>) -> bool {
+///         match line.strip_prefix("took ").and_then(|l| l.strip_suffix("ms")) {
+///             Some(v) => v.parse().map(|v| out.push(v)).is_ok(),
+///             None => false,
+///         }
+///     }
+/// }
+///
+/// let vals = collect_values_with("took 250ms\noops\ntook 10ms\n".as_bytes(), Millis).unwrap();
+/// assert_eq!(vec![250.0, 10.0], vals);
+/// ```
+pub trait LineExtractor {
+    /// Add each value in a line (without its line ending) to `out`, in the
+    /// order they appear, returning `false` if the line is malformed. Any
+    /// values added for a malformed line are discarded.
+    fn extract(&self, line: &str, out: &mut Vec<f64>) -> bool;
+}
+
+impl<E: LineExtractor + ?Sized> LineExtractor for &E {
+    fn extract(&self, line: &str, out: &mut Vec<f64>) -> bool {
+        (**self).extract(line, out)
+    }
+}
+
+impl<E: LineExtractor + ?Sized> LineExtractor for Box<E> {
+    fn extract(&self, line: &str, out: &mut Vec<f64>) -> bool {
+        (**self).extract(line, out)
+    }
+}
+
+/// Parse each line as a single number, ignoring leading and trailing
+/// whitespace. This is how values are read by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PlainExtractor;
+
+impl LineExtractor for PlainExtractor {
+    fn extract(&self, line: &str, out: &mut Vec<f64>) -> bool {
+        line.trim().parse::<f64>().map(|v| out.push(v)).is_ok()
+    }
+}
+
+/// How a line is split into fields by `FieldExtractor` and `SplitExtractor`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Delimiter {
+    /// Fields are separated by any amount of whitespace.
+    #[default]
+    Whitespace,
+    /// Fields are separated by a single character, e.g. a comma.
+    Char(char),
+}
+
+impl Delimiter {
+    fn fields<'a>(&self, line: &'a str) -> Fields<'a> {
+        match *self {
+            Delimiter::Whitespace => Fields::Whitespace(line.split_whitespace()),
+            Delimiter::Char(c) => Fields::Char(line.split(c)),
+        }
+    }
+}
+
+/// Fields of a line split by a `Delimiter`, with surrounding whitespace removed.
+enum Fields<'a> {
+    Whitespace(SplitWhitespace<'a>),
+    Char(Split<'a, char>),
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Fields::Whitespace(it) => it.next(),
+            Fields::Char(it) => it.next().map(str::trim),
+        }
+    }
+}
+
+impl fmt::Display for Delimiter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Delimiter::Whitespace => write!(f, "whitespace"),
+            Delimiter::Char('\t') => write!(f, "tab"),
+            Delimiter::Char(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+/// Parse a delimiter from the literal string 'whitespace', the literal
+/// string 'tab', or any single character.
+impl FromStr for Delimiter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Delimiter, Self::Err> {
+        let mut chars = s.chars();
+        if "whitespace" == s {
+            Ok(Delimiter::Whitespace)
+        } else if "tab" == s {
+            Ok(Delimiter::Char('\t'))
+        } else if let (Some(c), None) = (chars.next(), chars.next()) {
+            Ok(Delimiter::Char(c))
+        } else {
+            Err(format!("Invalid delimiter {}", s))
+        }
+    }
+}
+
+/// Parse a single field (counting from 1) of each line as a number, e.g. a
+/// column of a CSV file or the latency from a log line. Lines without the
+/// field are malformed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FieldExtractor {
+    index: usize,
+    delimiter: Delimiter,
+}
+
+impl FieldExtractor {
+    /// Create an extractor for a field (counting from 1) separated from
+    /// others by whitespace. This method returns `None` if the field is 0.
+    pub fn new(field: usize) -> Option<FieldExtractor> {
+        field.checked_sub(1).map(|index| FieldExtractor {
+            index,
+            delimiter: Delimiter::default(),
+        })
+    }
+
+    pub fn delimiter(mut self, delimiter: Delimiter) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Field being parsed, counting from 1.
+    pub fn field(&self) -> usize {
+        self.index + 1
+    }
+}
+
+impl LineExtractor for FieldExtractor {
+    fn extract(&self, line: &str, out: &mut Vec<f64>) -> bool {
+        match self.delimiter.fields(line).nth(self.index).map(str::parse::<f64>) {
+            Some(Ok(v)) => {
+                out.push(v);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parse every field of each line as a number, for input with several
+/// values on each line. Empty fields are skipped, and lines with any field
+/// that isn't a number are malformed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SplitExtractor {
+    delimiter: Delimiter,
+}
+
+impl SplitExtractor {
+    /// Create an extractor for fields separated by whitespace.
+    pub fn new() -> SplitExtractor {
+        Self::default()
+    }
+
+    pub fn delimiter(mut self, delimiter: Delimiter) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+}
+
+impl LineExtractor for SplitExtractor {
+    fn extract(&self, line: &str, out: &mut Vec<f64>) -> bool {
+        self.delimiter
+            .fields(line)
+            .filter(|f| !f.is_empty())
+            .all(|f| f.parse::<f64>().map(|v| out.push(v)).is_ok())
+    }
+}
+
+/// Parse the number immediately following each occurrence of a literal
+/// marker in a line, e.g. "time=" to get values from lines of a log like
+/// "GET /index.html status=200 time=12.5ms". Lines without the marker or
+/// without a number after it are malformed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MarkerExtractor {
+    marker: String,
+}
+
+impl MarkerExtractor {
+    /// Create an extractor for numbers following a marker. This method
+    /// returns `None` if the marker is empty.
+    pub fn new(marker: &str) -> Option<MarkerExtractor> {
+        if marker.is_empty() {
+            None
+        } else {
+            Some(MarkerExtractor {
+                marker: marker.to_string(),
+            })
+        }
+    }
+
+    pub fn marker(&self) -> &str {
+        &self.marker
+    }
+}
+
+impl LineExtractor for MarkerExtractor {
+    fn extract(&self, line: &str, out: &mut Vec<f64>) -> bool {
+        let mut found = false;
+        for (i, _) in line.match_indices(&self.marker) {
+            match number_prefix(&line[i + self.marker.len()..]) {
+                Some(v) => out.push(v),
+                None => return false,
+            }
+
+            found = true;
+        }
+
+        found
+    }
+}
+
+/// Parse each line as a duration and add it in milliseconds, e.g. 250 from
+/// "250ms" or 1500 from "1.5s", for timings logged with mixed units. Units
+/// are 'ns', 'us' (or 'µs'), 'ms', 's', 'm', and 'h' and may be combined
+/// like "1m30s". Numbers without a unit are already milliseconds.
+///
+/// ```
+/// use staccato::{collect_values_with, DurationExtractor};
+///
+/// let vals = collect_values_with("250ms\n1.5s\n1m30s\n12\n".as_bytes(), DurationExtractor).unwrap();
+/// assert_eq!(vec![250.0, 1500.0, 90000.0, 12.0], vals);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct DurationExtractor;
+
+impl LineExtractor for DurationExtractor {
+    fn extract(&self, line: &str, out: &mut Vec<f64>) -> bool {
+        parse_duration_millis(line).map(|v| out.push(v)).is_some()
+    }
+}
+
+/// Parse a duration like "250ms", "1.5s", or "1h2m3s" as a number of
+/// milliseconds, ignoring leading and trailing whitespace. A number without
+/// a unit is taken to be milliseconds already.
+pub fn parse_duration_millis(s: &str) -> Option<f64> {
+    let s = s.trim();
+    if let Ok(v) = s.parse::<f64>() {
+        return Some(v);
+    }
+
+    if s.is_empty() {
+        return None;
+    }
+
+    let mut rest = s;
+    let mut total = 0.0;
+    while !rest.is_empty() {
+        let end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let amount = rest[..end].parse::<f64>().ok()?;
+        rest = rest[end..].trim_start();
+
+        let end = rest.find(|c: char| !c.is_alphabetic()).unwrap_or(rest.len());
+        total += amount * unit_millis(&rest[..end])?;
+        rest = &rest[end..];
+    }
+
+    Some(total)
+}
+
+/// Number of milliseconds in one of a unit of time.
+fn unit_millis(unit: &str) -> Option<f64> {
+    match unit {
+        "ns" => Some(1e-6),
+        "us" | "µs" | "μs" => Some(1e-3),
+        "ms" => Some(1.0),
+        "s" => Some(1e3),
+        "m" => Some(60e3),
+        "h" => Some(3600e3),
+        _ => None,
+    }
+}
+
+/// Parse the longest prefix of a string that's a number, e.g. 12.5 from
+/// "12.5ms" or 3 from "3e" (since "3e" isn't a number).
+fn number_prefix(s: &str) -> Option<f64> {
+    let end = s
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E')))
+        .unwrap_or(s.len());
+
+    (1..=end).rev().find_map(|i| s[..i].parse::<f64>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_duration_millis, Delimiter, DurationExtractor, FieldExtractor, LineExtractor, MarkerExtractor,
+        PlainExtractor, SplitExtractor,
+    };
+
+    fn extract<E: LineExtractor>(extractor: E, line: &str) -> Option<Vec<f64>> {
+        let mut out = Vec::new();
+        if extractor.extract(line, &mut out) {
+            Some(out)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn test_plain_extractor() {
+        assert_eq!(Some(vec![1.5]), extract(PlainExtractor, "  1.5 \t"));
+        assert_eq!(Some(vec![-1000.0]), extract(PlainExtractor, "-1e3"));
+        assert_eq!(None, extract(PlainExtractor, "1 2"));
+        assert_eq!(None, extract(PlainExtractor, "foo"));
+    }
+
+    #[test]
+    fn test_delimiter_from_str() {
+        assert_eq!(Delimiter::Whitespace, "whitespace".parse().unwrap());
+        assert_eq!(Delimiter::Char('\t'), "tab".parse().unwrap());
+        assert_eq!(Delimiter::Char(','), ",".parse().unwrap());
+        assert_eq!(Delimiter::Char('é'), "é".parse().unwrap());
+        assert!("".parse::<Delimiter>().is_err());
+        assert!(",;".parse::<Delimiter>().is_err());
+    }
+
+    #[test]
+    fn test_field_extractor_whitespace() {
+        let extractor = FieldExtractor::new(2).unwrap();
+        assert_eq!(2, extractor.field());
+        assert_eq!(Some(vec![12.5]), extract(extractor, "GET   12.5  200"));
+        assert_eq!(None, extract(extractor, "GET"));
+        assert_eq!(None, extract(extractor, "GET fast 200"));
+    }
+
+    #[test]
+    fn test_field_extractor_delimiter() {
+        let extractor = FieldExtractor::new(3).unwrap().delimiter(Delimiter::Char(','));
+        assert_eq!(Some(vec![7.0]), extract(extractor, "a,b, 7 ,d"));
+        // Empty fields count when splitting on a character.
+        assert_eq!(Some(vec![3.0]), extract(extractor, ",,3"));
+        assert_eq!(None, extract(extractor, "a,b,,d"));
+    }
+
+    #[test]
+    fn test_field_extractor_zero() {
+        assert!(FieldExtractor::new(0).is_none());
+    }
+
+    #[test]
+    fn test_split_extractor() {
+        assert_eq!(Some(vec![1.0, 2.0, 3.5]), extract(SplitExtractor::new(), " 1  2\t3.5 "));
+        assert_eq!(None, extract(SplitExtractor::new(), "1 two 3"));
+
+        let commas = SplitExtractor::new().delimiter(Delimiter::Char(','));
+        assert_eq!(Some(vec![1.0, 2.0]), extract(commas, "1,,2,"));
+        assert_eq!(Some(vec![]), extract(commas, ",,"));
+    }
+
+    #[test]
+    fn test_marker_extractor() {
+        let extractor = MarkerExtractor::new("time=").unwrap();
+        assert_eq!(
+            Some(vec![12.5]),
+            extract(&extractor, "GET /index.html status=200 time=12.5ms")
+        );
+        assert_eq!(Some(vec![1.0, 2e3]), extract(&extractor, "time=1 time=2e3"));
+        assert_eq!(Some(vec![3.0]), extract(&extractor, "time=3e"));
+        assert_eq!(None, extract(&extractor, "status=200"));
+        assert_eq!(None, extract(&extractor, "time=fast"));
+        assert!(MarkerExtractor::new("").is_none());
+    }
+
+    #[test]
+    fn test_duration_extractor() {
+        assert_eq!(Some(vec![250.0]), extract(DurationExtractor, " 250ms "));
+        assert_eq!(Some(vec![1500.0]), extract(DurationExtractor, "1.5s"));
+        assert_eq!(Some(vec![12.0]), extract(DurationExtractor, "12"));
+        assert_eq!(None, extract(DurationExtractor, "fast"));
+    }
+
+    #[test]
+    fn test_parse_duration_millis() {
+        assert_eq!(Some(0.5), parse_duration_millis("500us"));
+        assert_eq!(Some(0.5), parse_duration_millis("500µs"));
+        assert_eq!(Some(0.002), parse_duration_millis("2000ns"));
+        assert_eq!(Some(3.0), parse_duration_millis("3 ms"));
+        assert_eq!(Some(90_000.0), parse_duration_millis("1m30s"));
+        assert_eq!(Some(3_723_000.0), parse_duration_millis("1h2m3s"));
+        assert_eq!(Some(-4.0), parse_duration_millis("-4"));
+        assert_eq!(None, parse_duration_millis(""));
+        assert_eq!(None, parse_duration_millis("ms"));
+        assert_eq!(None, parse_duration_millis("5 days"));
+        assert_eq!(None, parse_duration_millis("1.2.3s"));
+        assert_eq!(None, parse_duration_millis("-4s"));
+    }
+
+    #[test]
+    fn test_boxed_extractor() {
+        let boxed: Box<dyn LineExtractor> = Box::new(PlainExtractor);
+        assert_eq!(Some(vec![4.0]), extract(boxed, "4"));
+    }
+}
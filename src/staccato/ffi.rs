@@ -0,0 +1,242 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::Statistics;
+use std::os::raw::c_int;
+use std::ptr;
+use std::slice;
+
+/// Statistics were computed.
+pub const STACCATO_OK: c_int = 0;
+/// A pointer that must not be null was null.
+pub const STACCATO_ERR_NULL: c_int = -1;
+/// There were no values (other than `NaN` values) to compute statistics for.
+pub const STACCATO_ERR_EMPTY: c_int = -2;
+
+/// Statistics of a sequence of values, with the same fields as `Statistics`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StaccatoStatistics {
+    pub count: u64,
+    pub sum: f64,
+    pub mean: f64,
+    pub upper: f64,
+    pub lower: f64,
+    pub median: f64,
+    pub stddev: f64,
+}
+
+impl From<&Statistics> for StaccatoStatistics {
+    fn from(stats: &Statistics) -> Self {
+        StaccatoStatistics {
+            count: stats.count() as u64,
+            sum: stats.sum(),
+            mean: stats.mean(),
+            upper: stats.upper(),
+            lower: stats.lower(),
+            median: stats.median(),
+            stddev: stats.stddev(),
+        }
+    }
+}
+
+/// Values pushed one at a time to compute statistics of, opaque to C.
+#[derive(Debug, Default)]
+pub struct StaccatoStats {
+    values: Vec<f64>,
+}
+
+/// Write statistics of values to `out`, returning one of the status codes.
+///
+/// # Safety
+///
+/// `out` must be null or valid for writes.
+unsafe fn compute(values: &[f64], out: *mut StaccatoStatistics) -> c_int {
+    if out.is_null() {
+        return STACCATO_ERR_NULL;
+    }
+
+    match Statistics::from(values, None) {
+        Some(stats) => {
+            ptr::write(out, StaccatoStatistics::from(&stats));
+            STACCATO_OK
+        }
+        None => STACCATO_ERR_EMPTY,
+    }
+}
+
+/// Create an empty set of values to push to, which must be freed with
+/// `staccato_stats_free`.
+#[no_mangle]
+pub extern "C" fn staccato_stats_new() -> *mut StaccatoStats {
+    Box::into_raw(Box::default())
+}
+
+/// Add a value to a set of values, returning `STACCATO_ERR_NULL` if `stats`
+/// is null.
+///
+/// # Safety
+///
+/// `stats` must be null or a pointer returned by `staccato_stats_new` that
+/// hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn staccato_stats_push(stats: *mut StaccatoStats, value: f64) -> c_int {
+    match stats.as_mut() {
+        Some(stats) => {
+            stats.values.push(value);
+            STACCATO_OK
+        }
+        None => STACCATO_ERR_NULL,
+    }
+}
+
+/// Write statistics of every value pushed so far to `out`, which is left
+/// unchanged if there aren't any values or either pointer is null.
+///
+/// # Safety
+///
+/// `stats` must be null or a pointer returned by `staccato_stats_new` that
+/// hasn't been freed, and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn staccato_stats_compute(stats: *const StaccatoStats, out: *mut StaccatoStatistics) -> c_int {
+    match stats.as_ref() {
+        Some(stats) => compute(&stats.values, out),
+        None => STACCATO_ERR_NULL,
+    }
+}
+
+/// Free a set of values. Freeing a null pointer does nothing.
+///
+/// # Safety
+///
+/// `stats` must be null or a pointer returned by `staccato_stats_new` that
+/// hasn't already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn staccato_stats_free(stats: *mut StaccatoStats) {
+    if !stats.is_null() {
+        drop(Box::from_raw(stats));
+    }
+}
+
+/// Write statistics of `len` values to `out`. `values` may be null only if
+/// `len` is zero, in which case `STACCATO_ERR_EMPTY` is returned.
+///
+/// # Safety
+///
+/// `values` must be null or valid for reads of `len` values, and `out` must
+/// be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn staccato_compute(values: *const f64, len: usize, out: *mut StaccatoStatistics) -> c_int {
+    if values.is_null() {
+        return if len == 0 { compute(&[], out) } else { STACCATO_ERR_NULL };
+    }
+
+    compute(slice::from_raw_parts(values, len), out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StaccatoStatistics, StaccatoStats, STACCATO_ERR_EMPTY, STACCATO_ERR_NULL, STACCATO_OK};
+    use crate::Statistics;
+    use std::os::raw::c_int;
+    use std::ptr;
+
+    // Declared the same way a C caller would see them, so the tests go
+    // through the exported symbols rather than the Rust functions. The
+    // handle is only ever used through a pointer so its layout doesn't matter.
+    #[allow(improper_ctypes)]
+    extern "C" {
+        fn staccato_stats_new() -> *mut StaccatoStats;
+        fn staccato_stats_push(stats: *mut StaccatoStats, value: f64) -> c_int;
+        fn staccato_stats_compute(stats: *const StaccatoStats, out: *mut StaccatoStatistics) -> c_int;
+        fn staccato_stats_free(stats: *mut StaccatoStats);
+        fn staccato_compute(values: *const f64, len: usize, out: *mut StaccatoStatistics) -> c_int;
+    }
+
+    const VALUES: &[f64] = &[4.0, 1.0, 3.0, 2.0, 5.0];
+
+    #[test]
+    fn test_push_and_compute() {
+        let expected = StaccatoStatistics::from(&Statistics::from(VALUES, None).unwrap());
+        let mut out = StaccatoStatistics::default();
+
+        unsafe {
+            let stats = staccato_stats_new();
+            for &v in VALUES {
+                assert_eq!(STACCATO_OK, staccato_stats_push(stats, v));
+            }
+
+            assert_eq!(STACCATO_OK, staccato_stats_compute(stats, &mut out));
+            staccato_stats_free(stats);
+        }
+
+        assert_eq!(expected, out);
+        assert_eq!(5, out.count);
+        assert_eq!(3f64, out.median);
+    }
+
+    #[test]
+    fn test_compute_empty() {
+        let mut out = StaccatoStatistics::default();
+
+        unsafe {
+            let stats = staccato_stats_new();
+            assert_eq!(STACCATO_ERR_EMPTY, staccato_stats_compute(stats, &mut out));
+            assert_eq!(STACCATO_OK, staccato_stats_push(stats, f64::NAN));
+            assert_eq!(STACCATO_ERR_EMPTY, staccato_stats_compute(stats, &mut out));
+            staccato_stats_free(stats);
+        }
+
+        assert_eq!(StaccatoStatistics::default(), out);
+    }
+
+    #[test]
+    fn test_null_pointers() {
+        let mut out = StaccatoStatistics::default();
+
+        unsafe {
+            assert_eq!(STACCATO_ERR_NULL, staccato_stats_push(ptr::null_mut(), 1.0));
+            assert_eq!(STACCATO_ERR_NULL, staccato_stats_compute(ptr::null(), &mut out));
+            staccato_stats_free(ptr::null_mut());
+
+            let stats = staccato_stats_new();
+            assert_eq!(STACCATO_ERR_NULL, staccato_stats_compute(stats, ptr::null_mut()));
+            staccato_stats_free(stats);
+
+            assert_eq!(STACCATO_ERR_NULL, staccato_compute(ptr::null(), 3, &mut out));
+            assert_eq!(
+                STACCATO_ERR_NULL,
+                staccato_compute(VALUES.as_ptr(), VALUES.len(), ptr::null_mut())
+            );
+        }
+    }
+
+    #[test]
+    fn test_compute_one_shot() {
+        let expected = StaccatoStatistics::from(&Statistics::from(VALUES, None).unwrap());
+        let mut out = StaccatoStatistics::default();
+
+        unsafe {
+            assert_eq!(STACCATO_OK, staccato_compute(VALUES.as_ptr(), VALUES.len(), &mut out));
+            assert_eq!(expected, out);
+
+            assert_eq!(STACCATO_ERR_EMPTY, staccato_compute(ptr::null(), 0, &mut out));
+            assert_eq!(STACCATO_ERR_EMPTY, staccato_compute(VALUES.as_ptr(), 0, &mut out));
+        }
+    }
+}
@@ -0,0 +1,1241 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+use crate::{
+    KeyValueSep, Quantile, StatValue, Statistics, StatisticsBundle, StatisticsConfig, UnitConversion, DISPLAY_PRECISION,
+};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// Naming style of the keys that statistics are displayed with.
+///
+/// Keys are snake case (e.g. "sample_count" or "mean_90") by default and
+/// are converted to other styles word by word, where the percentile of a
+/// slice counts as a word.
+///
+/// ```
+/// use staccato::KeyCase;
+///
+/// assert_eq!("sampleCount", KeyCase::Camel.apply("sample_count"));
+/// assert_eq!("mean90", KeyCase::Camel.apply("mean_90"));
+/// assert_eq!("mean-90", KeyCase::Kebab.apply("mean_90"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum KeyCase {
+    /// Words separated by underscores, e.g. "sample_count".
+    #[default]
+    Snake,
+    /// Words after the first capitalized without a separator, e.g. "sampleCount".
+    Camel,
+    /// Words separated by hyphens, e.g. "sample-count".
+    Kebab,
+}
+
+impl KeyCase {
+    /// Convert a snake case key to this style.
+    pub fn apply(self, key: &str) -> Cow<'_, str> {
+        match self {
+            KeyCase::Snake => Cow::Borrowed(key),
+            KeyCase::Kebab => Cow::Owned(key.replace('_', "-")),
+            KeyCase::Camel => {
+                let mut words = key.split('_');
+                let mut out = words.next().unwrap_or_default().to_string();
+                for word in words {
+                    let mut chars = word.chars();
+                    if let Some(c) = chars.next() {
+                        out.extend(c.to_uppercase());
+                        out.push_str(chars.as_str());
+                    }
+                }
+
+                Cow::Owned(out)
+            }
+        }
+    }
+}
+
+impl FromStr for KeyCase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<KeyCase, Self::Err> {
+        match s {
+            "snake" => Ok(KeyCase::Snake),
+            "camel" => Ok(KeyCase::Camel),
+            "kebab" => Ok(KeyCase::Kebab),
+            _ => Err(format!("Invalid key case {}, expected one of snake, camel, kebab", s)),
+        }
+    }
+}
+
+/// How statistics are rounded to integers when they're formatted, e.g. for
+/// systems that only accept integer values. Counts are always integers.
+///
+/// ```
+/// use staccato::Rounding;
+///
+/// assert_eq!(2.0, Rounding::Nearest.apply(2.5));
+/// assert_eq!(4.0, Rounding::Nearest.apply(3.5));
+/// assert_eq!(3.0, Rounding::Ceil.apply(2.1));
+/// assert_eq!(2.0, Rounding::Floor.apply(2.9));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Rounding {
+    /// Round to the nearest integer, and to the even integer for values
+    /// half-way between two, e.g. 2.5 to 2 and 3.5 to 4.
+    #[default]
+    Nearest,
+    /// Round up to the next integer.
+    Ceil,
+    /// Round down to the previous integer.
+    Floor,
+}
+
+impl Rounding {
+    /// Round a value to an integer, never negative zero.
+    pub fn apply(self, val: f64) -> f64 {
+        let rounded = match self {
+            Rounding::Nearest => val.round_ties_even(),
+            Rounding::Ceil => val.ceil(),
+            Rounding::Floor => val.floor(),
+        };
+
+        rounded + 0.0
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Rounding, Self::Err> {
+        match s {
+            "nearest" => Ok(Rounding::Nearest),
+            "ceil" => Ok(Rounding::Ceil),
+            "floor" => Ok(Rounding::Floor),
+            _ => Err(format!("Invalid rounding {}, expected one of nearest, ceil, floor", s)),
+        }
+    }
+}
+
+/// Format a float with the precision, or as an integer without a decimal
+/// point if it's rounded.
+pub(crate) fn format_float(val: f64, precision: usize, rounding: Option<Rounding>) -> String {
+    match rounding {
+        Some(r) => format!("{:.0}", r.apply(val)),
+        None => format!("{:.*}", precision, val),
+    }
+}
+
+/// Way of writing the statistics in a bundle as text.
+///
+/// Implementations exist for key/value pairs (`KeyValueFormatter`) and, with
+/// the `serde` feature, JSON (`JsonFormatter`). Other crates can implement
+/// this trait for their own formats and display bundles with them using
+/// `FormattedBundle`.
+///
+/// ```
+/// use staccato::{BundleFormatter, FormattedBundle, StatisticsBundle};
+/// use std::fmt;
+///
+/// struct MeanOnly;
+///
+/// impl BundleFormatter for MeanOnly {
+///     fn format(&self, bundle: &StatisticsBundle, out: &mut dyn fmt::Write) -> fmt::Result {
+///         writeln!(out, "{}", bundle.global_stats().mean())
+///     }
+/// }
+///
+/// let bundle = StatisticsBundle::with_percentiles(&[1.0, 2.0, 3.0], &[]).unwrap();
+/// assert_eq!("2\n", FormattedBundle::new(&MeanOnly, &bundle).to_string());
+/// ```
+pub trait BundleFormatter {
+    /// Write the statistics in the bundle to `out`.
+    fn format(&self, bundle: &StatisticsBundle, out: &mut dyn fmt::Write) -> fmt::Result;
+
+    /// Write the statistics in each of several bundles to `out`, each along
+    /// with a label identifying it, e.g. the file its values were read from.
+    /// By default each bundle is written after a line with its label.
+    fn format_labeled(&self, bundles: &[(&str, &StatisticsBundle)], out: &mut dyn fmt::Write) -> fmt::Result {
+        for (label, bundle) in bundles {
+            writeln!(out, "{}", label)?;
+            self.format(bundle, out)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Bundle that can be displayed (e.g. printed) using any `BundleFormatter`.
+pub struct FormattedBundle<'a, F: BundleFormatter + ?Sized> {
+    formatter: &'a F,
+    bundle: &'a StatisticsBundle,
+}
+
+impl<'a, F: BundleFormatter + ?Sized> FormattedBundle<'a, F> {
+    pub fn new(formatter: &'a F, bundle: &'a StatisticsBundle) -> FormattedBundle<'a, F> {
+        FormattedBundle { formatter, bundle }
+    }
+}
+
+impl<'a, F: BundleFormatter + ?Sized> fmt::Display for FormattedBundle<'a, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.formatter.format(self.bundle, f)
+    }
+}
+
+/// Labeled bundles that can be displayed (e.g. printed) using any
+/// `BundleFormatter`.
+pub struct FormattedBundles<'a, F: BundleFormatter + ?Sized> {
+    formatter: &'a F,
+    bundles: &'a [(&'a str, &'a StatisticsBundle)],
+}
+
+impl<'a, F: BundleFormatter + ?Sized> FormattedBundles<'a, F> {
+    pub fn new(formatter: &'a F, bundles: &'a [(&'a str, &'a StatisticsBundle)]) -> FormattedBundles<'a, F> {
+        FormattedBundles { formatter, bundles }
+    }
+}
+
+impl<'a, F: BundleFormatter + ?Sized> fmt::Display for FormattedBundles<'a, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.formatter.format_labeled(self.bundles, f)
+    }
+}
+
+/// Format each statistic as a key and value on its own line, e.g. "mean: 2.5".
+///
+/// Statistics for each percentile slice have the percentile appended to the
+/// key, e.g. "mean_90: 2.5", and quantiles use keys like "p99".
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyValueFormatter {
+    sep: KeyValueSep,
+    precision: usize,
+    trim_trailing_zeros: bool,
+    config: StatisticsConfig,
+    key_case: KeyCase,
+    label: Option<String>,
+    unit: Option<UnitConversion>,
+    rounding: Option<Rounding>,
+    sort_keys: bool,
+}
+
+impl KeyValueFormatter {
+    pub fn new() -> KeyValueFormatter {
+        Self::with_sep(KeyValueSep::Colon)
+    }
+
+    pub fn with_sep(sep: KeyValueSep) -> KeyValueFormatter {
+        KeyValueFormatter {
+            sep,
+            precision: DISPLAY_PRECISION,
+            trim_trailing_zeros: false,
+            config: StatisticsConfig::default(),
+            key_case: KeyCase::default(),
+            label: None,
+            unit: None,
+            rounding: None,
+            sort_keys: false,
+        }
+    }
+
+    /// Number of digits to display after the decimal point, `DISPLAY_PRECISION`
+    /// by default.
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Remove zeros at the end of each value after formatting it with the
+    /// precision, e.g. display "2.5" instead of "2.50000". Off by default.
+    pub fn trim_trailing_zeros(mut self, trim: bool) -> Self {
+        self.trim_trailing_zeros = trim;
+        self
+    }
+
+    /// Round every value other than counts to an integer, which is then
+    /// displayed without a decimal point regardless of the precision. Not
+    /// rounded by default.
+    pub fn rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = Some(rounding);
+        self
+    }
+
+    /// Only display the statistics selected by the config for all values
+    /// and each percentile slice. Quantiles are always displayed.
+    pub fn config(mut self, config: StatisticsConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Naming style of each key, snake case by default.
+    pub fn key_case(mut self, key_case: KeyCase) -> Self {
+        self.key_case = key_case;
+        self
+    }
+
+    /// Prefix each key with a label and a period, e.g. "api.mean" for the
+    /// label "api", so that statistics of different values can be displayed
+    /// together. The label is sanitized with `sanitize_label`. No label by
+    /// default.
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = Some(sanitize_label(label));
+        self
+    }
+
+    /// Convert every statistic other than counts to another unit (e.g. from
+    /// seconds to milliseconds) when formatting it, and write the unit before
+    /// the statistics of each bundle with the key "unit". No conversion by
+    /// default.
+    pub fn unit(mut self, conversion: UnitConversion) -> Self {
+        self.unit = Some(conversion);
+        self
+    }
+
+    /// Write the keys of each section (statistics of all values, quantiles,
+    /// and each percentile slice) in alphabetical order instead of the order
+    /// of the statistics, e.g. to compare output that was sorted. Sections
+    /// are always written in the same order, with percentile slices in
+    /// ascending order of percentile. Off by default.
+    pub fn sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// Format a value with the precision and rounding, which integers (e.g.
+    /// the count) ignore.
+    fn format_value(&self, val: StatValue) -> String {
+        let formatted = match val {
+            StatValue::Integer(v) => v.to_string(),
+            StatValue::Float(v) => format_float(v, self.precision, self.rounding),
+        };
+        if self.trim_trailing_zeros {
+            trim_trailing_zeros(formatted)
+        } else {
+            formatted
+        }
+    }
+
+    fn quantile_line(&self, quantile: &Quantile) -> (String, String) {
+        (
+            labeled_key(self.key_case, self.label.as_deref(), &quantile.key()).into_owned(),
+            self.format_value(quantile.value().into()),
+        )
+    }
+
+    /// Write a key and value on each line, sorted by key if required.
+    fn write_lines(&self, mut lines: Vec<(String, String)>, out: &mut dyn fmt::Write) -> fmt::Result {
+        if self.sort_keys {
+            lines.sort();
+        }
+
+        for (key, val) in lines {
+            writeln!(out, "{}{}{}", key, self.sep, val)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write each statistic selected by the config as a key and value on
+    /// its own line, which is how every statistic of a bundle is written.
+    pub fn format_stats(&self, stats: &Statistics, out: &mut dyn fmt::Write) -> fmt::Result {
+        match self.unit {
+            Some(u) => self.write_stats(&stats.scaled(u.factor()), out),
+            None => self.write_stats(stats, out),
+        }
+    }
+
+    /// Write statistics that have already been converted to the unit.
+    fn write_stats(&self, stats: &Statistics, out: &mut dyn fmt::Write) -> fmt::Result {
+        self.write_lines(self.stats_lines(stats), out)
+    }
+
+    fn stats_lines(&self, stats: &Statistics) -> Vec<(String, String)> {
+        stats
+            .iter()
+            .filter(|(name, _)| self.config.includes(*name))
+            .map(|(name, val)| {
+                let key = match stats.percentile() {
+                    Some(p) => format!("{}_{}", name, p),
+                    None => name.to_string(),
+                };
+
+                (
+                    labeled_key(self.key_case, self.label.as_deref(), &key).into_owned(),
+                    self.format_value(val),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Default for KeyValueFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BundleFormatter for KeyValueFormatter {
+    fn format(&self, bundle: &StatisticsBundle, out: &mut dyn fmt::Write) -> fmt::Result {
+        let scaled;
+        let bundle = match self.unit {
+            Some(u) => {
+                let key = labeled_key(self.key_case, self.label.as_deref(), "unit");
+                writeln!(out, "{}{}{}", key, self.sep, u.to())?;
+                scaled = bundle.scaled(u.factor());
+                &scaled
+            }
+            None => bundle,
+        };
+
+        let quantiles: Vec<(String, String)> = bundle.quantile_values().iter().map(|q| self.quantile_line(q)).collect();
+        let mut global = self.stats_lines(bundle.global_stats());
+        if let Some(n) = bundle.sample_size() {
+            let key = labeled_key(self.key_case, self.label.as_deref(), "sample_count");
+            global.push((key.into_owned(), n.to_string()));
+        }
+
+        // A quantile named the same as a statistic of all values (e.g. the
+        // median quartile) is written instead of it so keys are unique.
+        global.retain(|(key, _)| !quantiles.iter().any(|(k, _)| k == key));
+
+        self.write_lines(global, out)?;
+        self.write_lines(quantiles, out)?;
+
+        for stats in bundle.percentile_stats() {
+            self.write_stats(stats, out)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write each bundle after a line with the key "label" and its label,
+    /// e.g. "label: a.log".
+    fn format_labeled(&self, bundles: &[(&str, &StatisticsBundle)], out: &mut dyn fmt::Write) -> fmt::Result {
+        for (label, bundle) in bundles {
+            writeln!(out, "label{}{}", self.sep, label)?;
+            self.format(bundle, out)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Statistics that can be displayed (e.g. printed) using a `KeyValueFormatter`,
+/// created by `Statistics::format_with`.
+#[derive(Debug, Clone)]
+pub struct FormattedStatistics<'a> {
+    stats: &'a Statistics,
+    format: KeyValueFormatter,
+}
+
+impl<'a> FormattedStatistics<'a> {
+    pub fn new(stats: &'a Statistics, format: KeyValueFormatter) -> FormattedStatistics<'a> {
+        FormattedStatistics { stats, format }
+    }
+}
+
+impl<'a> fmt::Display for FormattedStatistics<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.format.format_stats(self.stats, f)
+    }
+}
+
+/// Display statistics the same way as they're displayed as part of a bundle
+/// by `StatisticsFormatter`, e.g. "mean: 2.50000" or "mean_90: 2.50000".
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        KeyValueFormatter::new().format_stats(self, f)
+    }
+}
+
+/// Format a bundle as a single line of JSON, the same way it's serialized
+/// with serde so that it can be read back (e.g. to merge bundles).
+///
+/// Every value is written with full precision and every statistic is
+/// included. Only JSON with snake case keys (the default) can be read back.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct JsonFormatter {
+    key_case: KeyCase,
+    label: Option<String>,
+    unit: Option<UnitConversion>,
+    rounding: Option<Rounding>,
+    omitted_as_null: bool,
+    sort_keys: bool,
+}
+
+#[cfg(feature = "serde")]
+impl JsonFormatter {
+    pub fn new() -> JsonFormatter {
+        JsonFormatter::default()
+    }
+
+    /// Naming style of each key, snake case by default.
+    pub fn key_case(mut self, key_case: KeyCase) -> Self {
+        self.key_case = key_case;
+        self
+    }
+
+    /// Write each bundle as an object with the label as its only key, e.g.
+    /// `{"api":{"global":...}}`, so that statistics of different values can
+    /// be displayed together. No label by default.
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    /// Convert every statistic other than counts to another unit (e.g. from
+    /// seconds to milliseconds) and add the unit to each bundle object with
+    /// the key "unit". No conversion by default.
+    pub fn unit(mut self, conversion: UnitConversion) -> Self {
+        self.unit = Some(conversion);
+        self
+    }
+
+    /// Round every value other than counts to an integer, after converting
+    /// it to the unit. Not rounded by default.
+    pub fn rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = Some(rounding);
+        self
+    }
+
+    /// Include a slice for each percentile that was omitted from a bundle
+    /// (see `StatisticsBundle::omitted_percentiles`) with `null` for every
+    /// statistic, so that the same keys are present no matter how many
+    /// values there were. JSON with `null` statistics can't be read back.
+    /// Omitted slices are left out by default.
+    pub fn omitted_as_null(mut self, omitted_as_null: bool) -> Self {
+        self.omitted_as_null = omitted_as_null;
+        self
+    }
+
+    /// Write the keys of every object in alphabetical order instead of the
+    /// order of the statistics. Percentile slices are always in ascending
+    /// order of percentile. Off by default.
+    pub fn sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// JSON value of a bundle converted to the unit and rounded, with keys
+    /// in the style.
+    fn to_value(&self, bundle: &StatisticsBundle) -> Result<serde_json::Value, fmt::Error> {
+        let mut bundle = Cow::Borrowed(bundle);
+        if let Some(u) = self.unit {
+            bundle = Cow::Owned(bundle.scaled(u.factor()));
+        }
+        if let Some(r) = self.rounding {
+            bundle = Cow::Owned(bundle.map_values(|v| r.apply(v)));
+        }
+
+        let mut json = serde_json::to_value(&*bundle).map_err(|_| fmt::Error)?;
+        if let (Some(u), serde_json::Value::Object(map)) = (self.unit, &mut json) {
+            map.insert("unit".to_string(), u.to().to_string().into());
+        }
+
+        if self.omitted_as_null {
+            insert_omitted(&mut json, bundle.omitted_percentiles())?;
+        }
+
+        if self.key_case != KeyCase::Snake {
+            json = rename_keys(json, self.key_case);
+        }
+
+        if self.sort_keys {
+            json = sorted_keys(json);
+        }
+
+        Ok(json)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl BundleFormatter for JsonFormatter {
+    fn format(&self, bundle: &StatisticsBundle, out: &mut dyn fmt::Write) -> fmt::Result {
+        if let Some(label) = &self.label {
+            return self.format_labeled(&[(label, bundle)], out);
+        }
+
+        if self.key_case == KeyCase::Snake
+            && self.unit.is_none()
+            && self.rounding.is_none()
+            && !self.omitted_as_null
+            && !self.sort_keys
+        {
+            let json = serde_json::to_string(bundle).map_err(|_| fmt::Error)?;
+            return writeln!(out, "{}", json);
+        }
+
+        writeln!(out, "{}", self.to_value(bundle)?)
+    }
+
+    /// Write a single line JSON object with each label as a key of the
+    /// bundle with that label, in order.
+    fn format_labeled(&self, bundles: &[(&str, &StatisticsBundle)], out: &mut dyn fmt::Write) -> fmt::Result {
+        let mut map = serde_json::Map::new();
+        for (label, bundle) in bundles {
+            map.insert(label.to_string(), self.to_value(bundle)?);
+        }
+
+        writeln!(out, "{}", serde_json::Value::Object(map))
+    }
+}
+
+/// Add a slice with `null` statistics to the percentile slices of a JSON
+/// bundle for each omitted percentile, in ascending order of percentile.
+#[cfg(feature = "serde")]
+fn insert_omitted(json: &mut serde_json::Value, omitted: &[crate::OmittedPercentile]) -> fmt::Result {
+    use serde_json::Value;
+
+    let keys = match serde_json::to_value(Statistics::empty()).map_err(|_| fmt::Error)? {
+        Value::Object(map) => map.into_iter().map(|(k, _)| k).collect::<Vec<String>>(),
+        _ => return Err(fmt::Error),
+    };
+
+    let slices = match json.get_mut("percentiles") {
+        Some(Value::Array(slices)) => slices,
+        _ => return Err(fmt::Error),
+    };
+
+    for o in omitted {
+        let mut map = serde_json::Map::new();
+        map.insert("percentile".to_string(), o.percentile().into());
+        for k in &keys {
+            map.insert(k.clone(), Value::Null);
+        }
+
+        let pos = slices
+            .iter()
+            .position(|s| s.get("percentile").and_then(Value::as_u64) > Some(u64::from(o.percentile())))
+            .unwrap_or(slices.len());
+        slices.insert(pos, Value::Object(map));
+    }
+
+    Ok(())
+}
+
+/// Convert the keys of every object in a JSON value to the given style.
+#[cfg(feature = "serde")]
+fn rename_keys(value: serde_json::Value, key_case: KeyCase) -> serde_json::Value {
+    use serde_json::Value;
+
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| (key_case.apply(&k).into_owned(), rename_keys(v, key_case)))
+                .collect(),
+        ),
+        Value::Array(vals) => Value::Array(vals.into_iter().map(|v| rename_keys(v, key_case)).collect()),
+        v => v,
+    }
+}
+
+/// JSON value with the keys of every object in it in alphabetical order.
+#[cfg(feature = "serde")]
+fn sorted_keys(value: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().map(|(k, v)| (k, sorted_keys(v))).collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(entries.into_iter().collect())
+        }
+        Value::Array(vals) => Value::Array(vals.into_iter().map(sorted_keys).collect()),
+        v => v,
+    }
+}
+
+/// Replace each character of a label that can't be part of a key in text
+/// output with an underscore, so that the label can't be mistaken for a
+/// separator or the end of a key. Letters, digits, '-', '_', and '.' are
+/// kept as is.
+///
+/// ```
+/// use staccato::sanitize_label;
+///
+/// assert_eq!("latency-api", sanitize_label("latency-api"));
+/// assert_eq!("my_service_v1.2", sanitize_label("my service:v1.2"));
+/// ```
+pub fn sanitize_label(label: &str) -> String {
+    label
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Key of a statistic in a style, prefixed with a label (which must already
+/// be sanitized) and a period if there is one.
+pub(crate) fn labeled_key<'a>(key_case: KeyCase, label: Option<&str>, key: &'a str) -> Cow<'a, str> {
+    match label {
+        Some(l) => Cow::Owned(format!("{}.{}", l, key_case.apply(key))),
+        None => key_case.apply(key),
+    }
+}
+
+/// Remove zeros after the decimal point (and the point itself if nothing is
+/// left after it) from a formatted number, e.g. "2.50000" becomes "2.5".
+fn trim_trailing_zeros(formatted: String) -> String {
+    if !formatted.contains('.') {
+        return formatted;
+    }
+
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "serde")]
+    use super::JsonFormatter;
+    use super::{
+        format_float, sanitize_label, trim_trailing_zeros, BundleFormatter, FormattedBundle, FormattedBundles, KeyCase,
+        KeyValueFormatter, Rounding,
+    };
+    use crate::{
+        KeyValueSep, Statistics, StatisticsBundle, StatisticsConfig, StatisticsFormatter, Unit, UnitConversion,
+    };
+    use std::fmt;
+
+    const VALUES: &[f64] = &[1f64, 2f64, 5f64, 7f64, 9f64, 12f64];
+
+    /// Formatter for a made up format to check that the trait can be
+    /// implemented outside of this module.
+    struct CountAndMean;
+
+    impl BundleFormatter for CountAndMean {
+        fn format(&self, bundle: &StatisticsBundle, out: &mut dyn fmt::Write) -> fmt::Result {
+            let stats = bundle.global_stats();
+            write!(out, "{} values averaging {}", stats.count(), stats.mean())?;
+            for stats in bundle.percentile_stats() {
+                write!(out, ", {} under p{}", stats.count(), stats.percentile().unwrap())?;
+            }
+
+            writeln!(out)
+        }
+    }
+
+    #[test]
+    fn test_custom_formatter() {
+        let bundle = StatisticsBundle::with_percentiles(VALUES, &[50]).unwrap();
+        let out = FormattedBundle::new(&CountAndMean, &bundle).to_string();
+
+        assert_eq!("6 values averaging 6, 3 under p50\n", out);
+    }
+
+    #[test]
+    fn test_custom_formatter_as_trait_object() {
+        let bundle = StatisticsBundle::with_percentiles(VALUES, &[]).unwrap();
+        let formatters: Vec<Box<dyn BundleFormatter>> = vec![
+            Box::new(CountAndMean),
+            Box::new(KeyValueFormatter::new().config(StatisticsConfig::none().with_count(true))),
+        ];
+        let out: Vec<String> = formatters
+            .iter()
+            .map(|f| FormattedBundle::new(f.as_ref(), &bundle).to_string())
+            .collect();
+
+        assert_eq!(vec!["6 values averaging 6\n", "count: 6\n"], out);
+    }
+
+    #[test]
+    fn test_custom_formatter_labeled() {
+        let a = StatisticsBundle::with_percentiles(VALUES, &[]).unwrap();
+        let b = StatisticsBundle::with_percentiles(&[1f64, 2f64], &[]).unwrap();
+        let out = FormattedBundles::new(&CountAndMean, &[("a", &a), ("b", &b)]).to_string();
+
+        assert_eq!("a\n6 values averaging 6\nb\n2 values averaging 1.5\n", out);
+    }
+
+    #[test]
+    fn test_key_value_formatter_labeled() {
+        let a = StatisticsBundle::with_percentiles(VALUES, &[]).unwrap();
+        let b = StatisticsBundle::with_percentiles(&[1f64, 2f64], &[]).unwrap();
+        let formatter =
+            KeyValueFormatter::with_sep(KeyValueSep::Equals).config(StatisticsConfig::none().with_count(true));
+        let out = FormattedBundles::new(&formatter, &[("a.log", &a), ("total", &b)]).to_string();
+
+        assert_eq!("label=a.log\ncount=6\nlabel=total\ncount=2\n", out);
+    }
+
+    #[test]
+    fn test_key_value_formatter_same_as_statistics_formatter() {
+        let bundle = StatisticsBundle::with_percentiles(VALUES, &[50, 90]).unwrap();
+        let formatter = KeyValueFormatter::with_sep(KeyValueSep::Tab).precision(2);
+
+        assert_eq!(
+            StatisticsFormatter::with_sep(&bundle, KeyValueSep::Tab)
+                .precision(2)
+                .to_string(),
+            FormattedBundle::new(&formatter, &bundle).to_string()
+        );
+    }
+
+    #[test]
+    fn test_display_statistics() {
+        let stats = Statistics::from(VALUES, None).unwrap();
+        assert_eq!(
+            "count: 6\n\
+             sum: 36.00000\n\
+             mean: 6.00000\n\
+             upper: 12.00000\n\
+             lower: 1.00000\n\
+             median: 6.00000\n\
+             stddev: 3.82971\n",
+            stats.to_string()
+        );
+    }
+
+    #[test]
+    fn test_display_statistics_percentile() {
+        let stats = Statistics::from(VALUES, Some(50)).unwrap();
+        assert_eq!(
+            "count_50: 3\n\
+             sum_50: 8.00000\n\
+             mean_50: 2.66667\n\
+             upper_50: 5.00000\n\
+             lower_50: 1.00000\n\
+             median_50: 2.00000\n\
+             stddev_50: 1.69967\n",
+            stats.to_string()
+        );
+    }
+
+    #[test]
+    fn test_statistics_format_with() {
+        let stats = Statistics::from(VALUES, Some(90)).unwrap();
+        assert_eq!(
+            "count_90\t5\n\
+             sum_90\t24.0\n\
+             mean_90\t4.8\n\
+             upper_90\t9.0\n\
+             lower_90\t1.0\n\
+             median_90\t5.0\n\
+             stddev_90\t3.0\n",
+            stats.format_with(KeyValueSep::Tab, 1).to_string()
+        );
+    }
+
+    #[test]
+    fn test_bundle_formatter_same_as_each_statistics() {
+        let bundle = StatisticsBundle::with_percentiles(VALUES, &[50, 90]).unwrap();
+        let expected: String = std::iter::once(bundle.global_stats())
+            .chain(bundle.percentile_stats())
+            .map(|s| s.to_string())
+            .collect();
+
+        assert_eq!(expected, StatisticsFormatter::new(&bundle).to_string());
+    }
+
+    #[test]
+    fn test_count_formatted_as_integer() {
+        let bundle = StatisticsBundle::with_percentiles(VALUES, &[50]).unwrap();
+        let formatter = KeyValueFormatter::new()
+            .precision(3)
+            .config(StatisticsConfig::none().with_count(true));
+        let out = FormattedBundle::new(&formatter, &bundle).to_string();
+
+        assert_eq!("count: 6\ncount_50: 3\n", out);
+    }
+
+    #[test]
+    fn test_key_case_apply() {
+        assert_eq!("sample_count", KeyCase::Snake.apply("sample_count"));
+        assert_eq!("sampleCount", KeyCase::Camel.apply("sample_count"));
+        assert_eq!("sample-count", KeyCase::Kebab.apply("sample_count"));
+        assert_eq!("stddev", KeyCase::Camel.apply("stddev"));
+        assert_eq!("p99", KeyCase::Kebab.apply("p99"));
+    }
+
+    #[test]
+    fn test_key_case_parse() {
+        assert_eq!(Ok(KeyCase::Snake), "snake".parse());
+        assert_eq!(Ok(KeyCase::Camel), "camel".parse());
+        assert_eq!(Ok(KeyCase::Kebab), "kebab".parse());
+        assert!("pascal".parse::<KeyCase>().is_err());
+    }
+
+    #[test]
+    fn test_key_value_formatter_key_case() {
+        let mut bundle = StatisticsBundle::with_percentiles(VALUES, &[50]).unwrap();
+        bundle.sample_size = Some(6);
+        let config = StatisticsConfig::none().with_count(true).with_mean(true);
+        let format = |key_case| {
+            let formatter = KeyValueFormatter::new().precision(1).config(config).key_case(key_case);
+            FormattedBundle::new(&formatter, &bundle).to_string()
+        };
+
+        assert_eq!(
+            "count: 6\nmean: 6.0\nsample_count: 6\ncount_50: 3\nmean_50: 2.7\n",
+            format(KeyCase::Snake)
+        );
+        assert_eq!(
+            "count: 6\nmean: 6.0\nsampleCount: 6\ncount50: 3\nmean50: 2.7\n",
+            format(KeyCase::Camel)
+        );
+        assert_eq!(
+            "count: 6\nmean: 6.0\nsample-count: 6\ncount-50: 3\nmean-50: 2.7\n",
+            format(KeyCase::Kebab)
+        );
+    }
+
+    #[test]
+    fn test_key_value_formatter_label() {
+        let mut bundle = StatisticsBundle::from_sorted(
+            crate::SortedValues::new_unchecked(VALUES),
+            &[50],
+            &[90f64],
+            crate::QuantileMethod::default(),
+        )
+        .unwrap();
+        bundle.sample_size = Some(6);
+        let config = StatisticsConfig::none().with_count(true);
+        let formatter = KeyValueFormatter::new()
+            .config(config)
+            .key_case(KeyCase::Camel)
+            .label("latency api");
+        let out = FormattedBundle::new(&formatter, &bundle).to_string();
+
+        assert_eq!(
+            "latency_api.count: 6\nlatency_api.sampleCount: 6\nlatency_api.p90: 10.50000\nlatency_api.count50: 3\n",
+            out
+        );
+    }
+
+    #[test]
+    fn test_key_value_formatter_unit() {
+        let bundle =
+            StatisticsBundle::with_quantiles(&[1f64, 3f64], &[50], &[50f64], crate::QuantileMethod::Linear).unwrap();
+        let conversion = UnitConversion::new(Unit::Seconds, Unit::Milliseconds).unwrap();
+        let config = StatisticsConfig::none()
+            .with_count(true)
+            .with_mean(true)
+            .with_stddev(true);
+        let formatter = KeyValueFormatter::new().config(config).unit(conversion).label("api");
+        let out = FormattedBundle::new(&formatter, &bundle).to_string();
+
+        assert_eq!(
+            concat!(
+                "api.unit: ms\napi.count: 2\napi.mean: 2000.00000\napi.stddev: 1000.00000\n",
+                "api.p50: 2000.00000\napi.count_50: 1\napi.mean_50: 1000.00000\napi.stddev_50: 0.00000\n",
+            ),
+            out
+        );
+    }
+
+    #[test]
+    fn test_key_value_formatter_named_quantiles() {
+        let bundle = StatisticsBundle::with_quantiles(VALUES, &[], &[25f64, 50f64], crate::QuantileMethod::Linear)
+            .unwrap()
+            .with_quantile_name(25f64, "q1")
+            .with_quantile_name(50f64, "median");
+        let config = StatisticsConfig::none().with_count(true).with_median(true);
+        let formatter = KeyValueFormatter::new().precision(1).config(config);
+
+        assert_eq!(
+            "count: 6\nq1: 2.8\nmedian: 6.0\n",
+            FormattedBundle::new(&formatter, &bundle).to_string()
+        );
+    }
+
+    #[test]
+    fn test_key_value_formatter_sort_keys() {
+        let mut bundle = StatisticsBundle::with_quantiles(
+            &[1f64, 2f64, 3f64, 4f64],
+            &[90, 50],
+            &[90f64, 10f64],
+            crate::QuantileMethod::Linear,
+        )
+        .unwrap();
+        bundle.sample_size = Some(4);
+        let config = StatisticsConfig::none()
+            .with_count(true)
+            .with_mean(true)
+            .with_upper(true);
+        let formatter = KeyValueFormatter::new().precision(1).config(config).sort_keys(true);
+        let out = FormattedBundle::new(&formatter, &bundle).to_string();
+
+        assert_eq!(
+            concat!(
+                "count: 4\nmean: 2.5\nsample_count: 4\nupper: 4.0\n",
+                "p10: 1.3\np90: 3.7\n",
+                "count_50: 2\nmean_50: 1.5\nupper_50: 2.0\n",
+                "count_90: 3\nmean_90: 2.0\nupper_90: 3.0\n",
+            ),
+            out
+        );
+
+        let unsorted = KeyValueFormatter::new().precision(1).config(config);
+        assert_eq!(
+            concat!(
+                "count: 4\nmean: 2.5\nupper: 4.0\nsample_count: 4\n",
+                "p90: 3.7\np10: 1.3\n",
+                "count_50: 2\nmean_50: 1.5\nupper_50: 2.0\n",
+                "count_90: 3\nmean_90: 2.0\nupper_90: 3.0\n",
+            ),
+            FormattedBundle::new(&unsorted, &bundle).to_string()
+        );
+    }
+
+    #[test]
+    fn test_rounding_half_to_even() {
+        let round = |v| Rounding::Nearest.apply(v);
+        assert_eq!(0.0, round(0.5));
+        assert_eq!(2.0, round(1.5));
+        assert_eq!(2.0, round(2.5));
+        assert_eq!(-2.0, round(-2.5));
+        assert_eq!(-4.0, round(-3.5));
+        assert_eq!(3.0, round(2.5000001));
+        assert_eq!(1.0, round(1.4999999));
+    }
+
+    #[test]
+    fn test_rounding_ceil_floor() {
+        assert_eq!(3.0, Rounding::Ceil.apply(2.0001));
+        assert_eq!(-2.0, Rounding::Ceil.apply(-2.9));
+        assert_eq!(2.0, Rounding::Floor.apply(2.9999));
+        assert_eq!(-3.0, Rounding::Floor.apply(-2.1));
+        assert!(Rounding::Nearest.apply(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn test_rounding_never_negative_zero() {
+        for rounding in [Rounding::Nearest, Rounding::Ceil] {
+            let v = rounding.apply(-0.4);
+            assert!(v == 0.0 && v.is_sign_positive());
+        }
+
+        assert_eq!("0", format_float(-0.4, 5, Some(Rounding::Nearest)));
+    }
+
+    #[test]
+    fn test_rounding_parse() {
+        assert_eq!(Ok(Rounding::Nearest), "nearest".parse());
+        assert_eq!(Ok(Rounding::Ceil), "ceil".parse());
+        assert_eq!(Ok(Rounding::Floor), "floor".parse());
+        assert!("up".parse::<Rounding>().is_err());
+    }
+
+    #[test]
+    fn test_key_value_formatter_rounding() {
+        let bundle =
+            StatisticsBundle::with_quantiles(&[1f64, 2f64, 4f64], &[], &[50f64, 75f64], crate::QuantileMethod::Linear)
+                .unwrap();
+        let config = StatisticsConfig::none()
+            .with_count(true)
+            .with_sum(true)
+            .with_mean(true)
+            .with_stddev(true);
+        let formatter = KeyValueFormatter::new()
+            .config(config)
+            .precision(3)
+            .rounding(Rounding::Nearest);
+        let out = FormattedBundle::new(&formatter, &bundle).to_string();
+
+        // The mean is 2.333, the standard deviation 1.247, and the 75th
+        // quantile 3.0 (half way between 2 and 4) so it's rounded to 3.
+        assert_eq!("count: 3\nsum: 7\nmean: 2\nstddev: 1\np50: 2\np75: 3\n", out);
+
+        let ceil = KeyValueFormatter::new().config(config).rounding(Rounding::Ceil);
+        assert_eq!(
+            "count: 3\nsum: 7\nmean: 3\nstddev: 2\np50: 2\np75: 3\n",
+            FormattedBundle::new(&ceil, &bundle).to_string()
+        );
+    }
+
+    #[test]
+    fn test_sanitize_label() {
+        assert_eq!("latency-api.v2", sanitize_label("latency-api.v2"));
+        assert_eq!("a_b_c_d", sanitize_label("a b:c=d"));
+        assert_eq!("_", sanitize_label("\n"));
+        assert_eq!("größe", sanitize_label("größe"));
+    }
+
+    #[test]
+    fn test_trim_trailing_zeros() {
+        assert_eq!("2.5", trim_trailing_zeros("2.50000".to_string()));
+        assert_eq!("100", trim_trailing_zeros("100.000".to_string()));
+        assert_eq!("100", trim_trailing_zeros("100".to_string()));
+        assert_eq!("NaN", trim_trailing_zeros("NaN".to_string()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_formatter_round_trip() {
+        let bundle = StatisticsBundle::with_percentiles(VALUES, &[50]).unwrap();
+        let out = FormattedBundle::new(&JsonFormatter::new(), &bundle).to_string();
+
+        assert!(out.ends_with('\n'));
+        assert_eq!(bundle, serde_json::from_str::<StatisticsBundle>(&out).unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_formatter_count_is_integer() {
+        let bundle = StatisticsBundle::with_percentiles(VALUES, &[50]).unwrap();
+        let out = FormattedBundle::new(&JsonFormatter::new(), &bundle).to_string();
+        let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+
+        assert!(json["global"]["count"].is_u64());
+        assert!(json["global"]["sum"].is_f64());
+        assert!(json["percentiles"][0]["count"].is_u64());
+        assert!(json["percentiles"][0]["percentile"].is_u64());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_formatter_key_case() {
+        let mut bundle = StatisticsBundle::with_percentiles(&[1f64, 2f64], &[50]).unwrap();
+        bundle.sample_size = Some(2);
+        let format = |key_case| FormattedBundle::new(&JsonFormatter::new().key_case(key_case), &bundle).to_string();
+
+        let expected = concat!(
+            r#"{"global":{"count":2,"sum":3.0,"mean":1.5,"upper":2.0,"lower":1.0,"median":1.5,"stddev":0.5},"#,
+            r#""quantiles":[],"percentiles":[{"percentile":50,"count":1,"sum":1.0,"mean":1.0,"upper":1.0,"#,
+            r#""lower":1.0,"median":1.0,"stddev":0.0}],"sample_count":2}"#,
+            "\n"
+        );
+
+        assert_eq!(expected, format(KeyCase::Snake));
+        assert_eq!(expected.replace("sample_count", "sampleCount"), format(KeyCase::Camel));
+        assert_eq!(expected.replace("sample_count", "sample-count"), format(KeyCase::Kebab));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_formatter_unit() {
+        let bundle = StatisticsBundle::with_percentiles(&[1024f64, 3072f64], &[]).unwrap();
+        let conversion = UnitConversion::new(Unit::Bytes, Unit::Kibibytes).unwrap();
+        let out = FormattedBundle::new(&JsonFormatter::new().unit(conversion), &bundle).to_string();
+        let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+
+        assert_eq!("KiB", json["unit"]);
+        assert_eq!(2, json["global"]["count"]);
+        assert_eq!(2.0, json["global"]["mean"]);
+        assert_eq!(1.0, json["global"]["stddev"]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_formatter_rounding() {
+        let bundle = StatisticsBundle::with_percentiles(&[1f64, 2f64], &[50]).unwrap();
+        let formatter = JsonFormatter::new().rounding(Rounding::Nearest);
+        let out = FormattedBundle::new(&formatter, &bundle).to_string();
+        let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+
+        assert_eq!(2, json["global"]["count"]);
+        assert_eq!(2.0, json["global"]["mean"]);
+        assert_eq!(0.0, json["global"]["stddev"]);
+        assert_eq!(50, json["percentiles"][0]["percentile"]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_formatter_omitted_as_null() {
+        let bundle = StatisticsBundle::with_percentiles(&[1f64, 2f64, 3f64, 4f64, 5f64], &[5, 50, 10, 99]).unwrap();
+        let without = FormattedBundle::new(&JsonFormatter::new(), &bundle).to_string();
+        let json: serde_json::Value = serde_json::from_str(&without).unwrap();
+        assert_eq!(2, json["percentiles"].as_array().unwrap().len());
+
+        let formatter = JsonFormatter::new().omitted_as_null(true);
+        let out = FormattedBundle::new(&formatter, &bundle).to_string();
+        let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+        let slices = json["percentiles"].as_array().unwrap();
+        let percentiles: Vec<u64> = slices.iter().flat_map(|s| s["percentile"].as_u64()).collect();
+
+        assert_eq!(vec![5, 10, 50, 99], percentiles);
+        assert!(slices[0]["count"].is_null());
+        assert!(slices[1]["stddev"].is_null());
+        assert_eq!(2, slices[2]["count"]);
+
+        let keys = |s: &serde_json::Value| s.as_object().unwrap().keys().cloned().collect::<Vec<String>>();
+        assert_eq!(keys(&slices[2]), keys(&slices[0]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_formatter_sort_keys() {
+        let bundle = StatisticsBundle::with_percentiles(&[1f64, 2f64, 3f64], &[90, 50]).unwrap();
+        let formatter = JsonFormatter::new().sort_keys(true);
+        let out = FormattedBundle::new(&formatter, &bundle).to_string();
+        let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+
+        let keys = |v: &serde_json::Value| v.as_object().unwrap().keys().cloned().collect::<Vec<String>>();
+        assert_eq!(vec!["global", "percentiles", "quantiles"], keys(&json));
+        assert_eq!(
+            vec!["count", "lower", "mean", "median", "stddev", "sum", "upper"],
+            keys(&json["global"])
+        );
+
+        let slices = json["percentiles"].as_array().unwrap();
+        assert_eq!(50, slices[0]["percentile"]);
+        assert_eq!(90, slices[1]["percentile"]);
+        assert_eq!(
+            vec![
+                "count",
+                "lower",
+                "mean",
+                "median",
+                "percentile",
+                "stddev",
+                "sum",
+                "upper"
+            ],
+            keys(&slices[0])
+        );
+
+        let plain: serde_json::Value =
+            serde_json::from_str(&FormattedBundle::new(&JsonFormatter::new(), &bundle).to_string()).unwrap();
+        assert_eq!(plain, json);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_formatter_label() {
+        let bundle = StatisticsBundle::with_percentiles(&[1f64, 2f64], &[]).unwrap();
+        let formatter = JsonFormatter::new().label("latency api");
+        let labeled = FormattedBundle::new(&formatter, &bundle).to_string();
+        let plain = FormattedBundle::new(&JsonFormatter::new(), &bundle).to_string();
+
+        assert_eq!(format!("{{\"latency api\":{}}}\n", plain.trim_end()), labeled);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_formatter_labeled() {
+        let mut a = StatisticsBundle::with_percentiles(&[1f64, 2f64], &[]).unwrap();
+        a.sample_size = Some(2);
+        let b = StatisticsBundle::with_percentiles(&[4f64], &[]).unwrap();
+        let formatter = JsonFormatter::new().key_case(KeyCase::Camel);
+        let out = FormattedBundles::new(&formatter, &[("a.log", &a), ("total", &b)]).to_string();
+        let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+
+        assert_eq!(1, out.lines().count());
+        assert_eq!(2, json["a.log"]["global"]["count"]);
+        assert_eq!(2, json["a.log"]["sampleCount"]);
+        assert_eq!(4.0, json["total"]["global"]["sum"]);
+        assert_eq!(
+            vec!["a.log", "total"],
+            json.as_object().unwrap().keys().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,280 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{EmptyPolicy, Percentiles, QuantileMethod, SortedValues, StatisticsBundle};
+use std::collections::BTreeMap;
+
+/// Values accumulated into groups by key, to compute a statistics bundle for
+/// each group (and for all of them combined) with the same percentiles.
+///
+/// Every value is kept in memory until the statistics are computed.
+///
+/// ```
+/// use staccato::{GroupedStatistics, Percentiles};
+///
+/// let mut grouped = GroupedStatistics::new(Percentiles::new(&[50]).unwrap());
+/// grouped.push("GET", 12.0);
+/// grouped.push("POST", 40.0);
+/// grouped.push("GET", 8.0);
+/// assert_eq!(2, grouped.count("GET"));
+///
+/// let overall = grouped.overall().unwrap();
+/// assert_eq!(3, overall.global_stats().count());
+///
+/// let bundles = grouped.finalize();
+/// let keys: Vec<&str> = bundles.keys().map(|k| k.as_str()).collect();
+/// assert_eq!(vec!["GET", "POST"], keys);
+/// assert_eq!(20f64, bundles["GET"].global_stats().sum());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GroupedStatistics {
+    percentiles: Percentiles,
+    groups: BTreeMap<String, Vec<f64>>,
+    total: usize,
+    empty: EmptyPolicy,
+}
+
+impl GroupedStatistics {
+    /// Create an empty set of groups whose bundles include slices for the
+    /// given percentiles.
+    pub fn new(percentiles: Percentiles) -> GroupedStatistics {
+        GroupedStatistics {
+            percentiles,
+            groups: BTreeMap::new(),
+            total: 0,
+            empty: EmptyPolicy::default(),
+        }
+    }
+
+    /// Set what's computed for groups without any values (or only `NaN`
+    /// values). Default is `None`, which omits them from `finalize`.
+    pub fn empty_policy(mut self, empty: EmptyPolicy) -> Self {
+        self.empty = empty;
+        self
+    }
+
+    /// Create a group with the given key without adding any values to it,
+    /// e.g. so every expected group is included by `finalize` with the
+    /// `Zeroed` policy even if no values are ever added to it.
+    pub fn add_group(&mut self, key: &str) {
+        if !self.groups.contains_key(key) {
+            self.groups.insert(key.to_string(), Vec::new());
+        }
+    }
+
+    /// Add a value to the group with the given key, creating the group if
+    /// this is the first value for it.
+    pub fn push(&mut self, key: &str, value: f64) {
+        match self.groups.get_mut(key) {
+            Some(vals) => vals.push(value),
+            None => {
+                self.groups.insert(key.to_string(), vec![value]);
+            }
+        }
+
+        self.total += 1;
+    }
+
+    /// Number of values added to the group with the given key so far, zero
+    /// if there is no such group.
+    pub fn count(&self, key: &str) -> usize {
+        self.groups.get(key).map_or(0, |vals| vals.len())
+    }
+
+    /// Key and number of values added so far for each group, ordered by key.
+    pub fn counts(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.groups.iter().map(|(k, vals)| (k.as_str(), vals.len()))
+    }
+
+    /// Number of values added to every group so far.
+    pub fn total_count(&self) -> usize {
+        self.total
+    }
+
+    /// Number of groups.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn percentiles(&self) -> &Percentiles {
+        &self.percentiles
+    }
+
+    /// Compute a statistics bundle for the values of every group combined.
+    ///
+    /// This method returns `None` if no values have been added, unless the
+    /// empty policy is `Zeroed`.
+    pub fn overall(&self) -> Option<StatisticsBundle> {
+        let mut vals = Vec::with_capacity(self.total);
+        for group in self.groups.values() {
+            vals.extend_from_slice(group);
+        }
+
+        self.empty.apply(bundle(&mut vals, &self.percentiles))
+    }
+
+    /// Compute a statistics bundle for each group, ordered by key. Keys are
+    /// always in lexicographic order of their bytes (so uppercase letters
+    /// come before lowercase ones), no matter what order values were added
+    /// in. Groups without any values are omitted unless the empty policy is
+    /// `Zeroed`.
+    pub fn finalize(self) -> BTreeMap<String, StatisticsBundle> {
+        let (percentiles, empty) = (self.percentiles, self.empty);
+        self.groups
+            .into_iter()
+            .flat_map(|(k, mut vals)| empty.apply(bundle(&mut vals, &percentiles)).map(|b| (k, b)))
+            .collect()
+    }
+}
+
+fn bundle(vals: &mut [f64], percentiles: &Percentiles) -> Option<StatisticsBundle> {
+    StatisticsBundle::from_sorted(
+        SortedValues::sort(vals),
+        percentiles.as_slice(),
+        &[],
+        QuantileMethod::default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GroupedStatistics;
+    use crate::{EmptyPolicy, Percentiles, StatisticsBundle};
+
+    #[test]
+    fn test_empty() {
+        let grouped = GroupedStatistics::default();
+
+        assert!(grouped.is_empty());
+        assert_eq!(0, grouped.count("missing"));
+        assert!(grouped.overall().is_none());
+        assert!(grouped.finalize().is_empty());
+    }
+
+    #[test]
+    fn test_counts_during_accumulation() {
+        let mut grouped = GroupedStatistics::default();
+        grouped.push("b", 1.0);
+        grouped.push("a", 2.0);
+        grouped.push("b", 3.0);
+
+        let counts: Vec<(&str, usize)> = grouped.counts().collect();
+        assert_eq!(vec![("a", 1), ("b", 2)], counts);
+        assert_eq!(3, grouped.total_count());
+        assert_eq!(2, grouped.len());
+    }
+
+    #[test]
+    fn test_finalize_ordered_by_key() {
+        let mut grouped = GroupedStatistics::default();
+        for key in &["put", "GET", "b", "a10", "a2", "POST", "a1", "é"] {
+            grouped.push(key, 1.0);
+        }
+
+        let keys: Vec<String> = grouped.finalize().into_keys().collect();
+        assert_eq!(vec!["GET", "POST", "a1", "a10", "a2", "b", "put", "é"], keys);
+    }
+
+    #[test]
+    fn test_many_small_groups() {
+        let percentiles = Percentiles::new(&[50, 90]).unwrap();
+        let mut grouped = GroupedStatistics::new(percentiles.clone());
+        for i in 0..1000 {
+            let key = format!("key-{:04}", i % 500);
+            grouped.push(&key, i as f64);
+        }
+
+        assert_eq!(500, grouped.len());
+        assert!(grouped.counts().all(|(_, c)| c == 2));
+
+        let overall = grouped.overall().unwrap();
+        let expected = StatisticsBundle::with_percentiles(&(0..1000).map(|i| i as f64).collect::<Vec<_>>(), &[50, 90]);
+        assert_eq!(expected.unwrap(), overall);
+
+        let bundles = grouped.finalize();
+        assert_eq!(500, bundles.len());
+        assert_eq!("key-0000", bundles.keys().next().unwrap());
+        assert_eq!("key-0499", bundles.keys().last().unwrap());
+
+        let first = &bundles["key-0007"];
+        assert_eq!(2, first.global_stats().count());
+        assert_eq!(7f64, first.global_stats().lower());
+        assert_eq!(507f64, first.global_stats().upper());
+        assert_eq!(2, first.percentile_stats().len());
+        assert_eq!(1, first.percentile_stats()[0].count());
+    }
+
+    #[test]
+    fn test_single_huge_group() {
+        let mut grouped = GroupedStatistics::new(Percentiles::new(&[99]).unwrap());
+        for i in (0..100_000).rev() {
+            grouped.push("only", i as f64);
+        }
+
+        assert_eq!(100_000, grouped.count("only"));
+
+        let overall = grouped.overall().unwrap();
+        let bundles = grouped.finalize();
+        assert_eq!(1, bundles.len());
+
+        let bundle = &bundles["only"];
+        assert_eq!(&overall, bundle);
+        assert_eq!(100_000, bundle.global_stats().count());
+        assert_eq!(0f64, bundle.global_stats().lower());
+        assert_eq!(99_999f64, bundle.global_stats().upper());
+
+        let p99 = &bundle.percentile_stats()[0];
+        assert_eq!(Some(99), p99.percentile());
+        assert_eq!(99_000, p99.count());
+        assert_eq!(98_999f64, p99.upper());
+    }
+
+    #[test]
+    fn test_empty_groups_omitted_by_default() {
+        let mut grouped = GroupedStatistics::new(Percentiles::default());
+        grouped.add_group("GET");
+        grouped.push("POST", 1.0);
+        grouped.push("PUT", f64::NAN);
+
+        let keys: Vec<String> = grouped.finalize().into_keys().collect();
+        assert_eq!(vec!["POST"], keys);
+    }
+
+    #[test]
+    fn test_empty_groups_zeroed() {
+        let mut grouped = GroupedStatistics::new(Percentiles::new(&[90]).unwrap()).empty_policy(EmptyPolicy::Zeroed);
+        assert_eq!(0, grouped.overall().unwrap().global_stats().count());
+
+        grouped.add_group("GET");
+        grouped.push("POST", 1.0);
+        grouped.add_group("POST");
+        grouped.push("PUT", f64::NAN);
+        assert_eq!(1, grouped.count("POST"));
+
+        let bundles = grouped.finalize();
+        assert_eq!(3, bundles.len());
+        assert_eq!(0, bundles["GET"].global_stats().count());
+        assert!(bundles["GET"].percentile_stats().is_empty());
+        assert_eq!(0, bundles["PUT"].global_stats().count());
+        assert_eq!(1, bundles["POST"].global_stats().count());
+    }
+}
@@ -0,0 +1,502 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{trim_nan, KeyValueSep, SortedValues, Value, DISPLAY_PRECISION};
+use std::fmt;
+
+/// What to do with values outside the edges of a histogram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutlierPolicy {
+    /// Count values below the lowest edge and above the highest edge in
+    /// separate underflow and overflow buckets.
+    #[default]
+    Separate,
+    /// Count values below the lowest edge in the first bucket and values
+    /// above the highest edge in the last bucket.
+    Clamp,
+}
+
+/// How the edges of a histogram's buckets are chosen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Layout {
+    /// Evenly spaced edges from the lowest to the highest value.
+    Linear(usize),
+    /// Evenly spaced edges on a log scale from the lowest positive value to
+    /// the highest value.
+    Log(usize),
+    /// Edges given when the histogram was created.
+    Fixed,
+}
+
+/// Count of values in each of a number of buckets between edges.
+///
+/// Each bucket includes values equal to its lower edge but not its upper edge
+/// (which belong to the next bucket), except for the last bucket which also
+/// includes values equal to the highest edge. `NaN` values aren't counted.
+///
+/// ```
+/// use staccato::{Histogram, SortedValues};
+///
+/// let mut vals = vec![1.0, 2.0, 2.5, 3.0, 4.0, 10.0];
+/// let mut hist = Histogram::with_edges(&[0.0, 2.0, 4.0]).unwrap();
+/// hist.fill(SortedValues::sort(&mut vals));
+///
+/// assert_eq!(&[1, 4], hist.counts());
+/// assert_eq!(1, hist.overflow());
+/// assert_eq!(vec![1, 5], hist.cumulative_counts());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Histogram {
+    layout: Layout,
+    outliers: OutlierPolicy,
+    edges: Vec<f64>,
+    counts: Vec<u64>,
+    underflow: u64,
+    overflow: u64,
+}
+
+impl Histogram {
+    /// Create a histogram with `buckets` evenly spaced buckets (at least one)
+    /// from the lowest to the highest value it's filled with. If every value
+    /// is the same, the buckets span from that value to one more than it.
+    pub fn with_buckets(buckets: usize) -> Histogram {
+        Self::new(Layout::Linear(buckets.max(1)), Vec::new())
+    }
+
+    /// Create a histogram with buckets between the given edges, which must be
+    /// finite and strictly increasing. This method returns `None` if there are
+    /// fewer than two edges or they aren't valid.
+    pub fn with_edges(edges: &[f64]) -> Option<Histogram> {
+        let valid = edges.len() >= 2 && edges.iter().all(|e| e.is_finite()) && edges.windows(2).all(|w| w[0] < w[1]);
+        if valid {
+            Some(Self::new(Layout::Fixed, edges.to_vec()))
+        } else {
+            None
+        }
+    }
+
+    /// Create a histogram with `buckets` buckets (at least one) whose edges
+    /// are evenly spaced on a log scale from the lowest positive value to the
+    /// highest value it's filled with, for values spanning several orders of
+    /// magnitude such as latencies. Values that aren't positive are below the
+    /// lowest edge.
+    pub fn log_scaled(buckets: usize) -> Histogram {
+        Self::new(Layout::Log(buckets.max(1)), Vec::new())
+    }
+
+    fn new(layout: Layout, edges: Vec<f64>) -> Histogram {
+        let counts = vec![0; edges.len().saturating_sub(1)];
+        Histogram {
+            layout,
+            outliers: OutlierPolicy::default(),
+            edges,
+            counts,
+            underflow: 0,
+            overflow: 0,
+        }
+    }
+
+    /// Set what to do with values outside the edges, which is only possible
+    /// for histograms with edges given when they're created or values that
+    /// aren't positive for log scaled histograms. Default is to count them
+    /// separately.
+    pub fn outliers(mut self, outliers: OutlierPolicy) -> Self {
+        self.outliers = outliers;
+        self
+    }
+
+    /// Count values into buckets, replacing any previous counts. Edges that
+    /// depend on the values are chosen again, and if there aren't any values
+    /// to choose them from there are no edges or buckets.
+    pub fn fill<V: Value>(&mut self, vals: SortedValues<V>) {
+        let vals = trim_nan(vals.as_slice());
+        match self.layout {
+            Layout::Linear(buckets) => self.edges = linear_edges(vals, buckets),
+            Layout::Log(buckets) => self.edges = log_edges(vals, buckets),
+            Layout::Fixed => {}
+        }
+
+        self.counts = vec![0; self.edges.len().saturating_sub(1)];
+        self.underflow = 0;
+        self.overflow = 0;
+
+        let (first, last) = match (self.edges.first(), self.edges.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return,
+        };
+
+        let below = vals.partition_point(|&v| v.into() < first);
+        let within = vals.partition_point(|&v| v.into() <= last);
+        let mut start = below;
+        let inner = self.edges.len() - 2;
+        for (i, &edge) in self.edges[1..].iter().enumerate() {
+            let end = if i == inner {
+                within
+            } else {
+                vals.partition_point(|&v| v.into() < edge)
+            };
+
+            self.counts[i] = (end - start) as u64;
+            start = end;
+        }
+
+        let (below, above) = (below as u64, (vals.len() - within) as u64);
+        match self.outliers {
+            OutlierPolicy::Separate => {
+                self.underflow = below;
+                self.overflow = above;
+            }
+            OutlierPolicy::Clamp => {
+                self.counts[0] += below;
+                self.counts[inner] += above;
+            }
+        }
+    }
+
+    /// Edges of each bucket in ascending order, one more than the number of
+    /// buckets.
+    pub fn edges(&self) -> &[f64] {
+        &self.edges
+    }
+
+    /// Number of values in each bucket.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Number of values less than or equal to the upper edge of each bucket
+    /// (or less than it for all but the last bucket), including values below
+    /// the lowest edge.
+    pub fn cumulative_counts(&self) -> Vec<u64> {
+        self.counts
+            .iter()
+            .scan(self.underflow, |total, &c| {
+                *total += c;
+                Some(*total)
+            })
+            .collect()
+    }
+
+    /// Lower edge, upper edge, and number of values of each bucket.
+    pub fn buckets(&self) -> impl Iterator<Item = (f64, f64, u64)> + '_ {
+        self.edges.windows(2).zip(&self.counts).map(|(w, &c)| (w[0], w[1], c))
+    }
+
+    /// Number of values below the lowest edge, when counted separately.
+    pub fn underflow(&self) -> u64 {
+        self.underflow
+    }
+
+    /// Number of values above the highest edge, when counted separately.
+    pub fn overflow(&self) -> u64 {
+        self.overflow
+    }
+
+    /// Number of values counted, including any below or above the edges.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum::<u64>() + self.underflow + self.overflow
+    }
+}
+
+fn linear_edges<V: Value>(vals: &[V], buckets: usize) -> Vec<f64> {
+    let (lower, upper) = match (vals.first(), vals.last()) {
+        (Some(&first), Some(&last)) => (first.into(), last.into()),
+        _ => return Vec::new(),
+    };
+
+    let upper = if upper > lower { upper } else { lower + 1f64 };
+    spaced_edges(lower, upper, buckets, |lower, upper, frac| {
+        lower + (upper - lower) * frac
+    })
+}
+
+fn log_edges<V: Value>(vals: &[V], buckets: usize) -> Vec<f64> {
+    let positive = vals.partition_point(|&v| v.into() <= 0f64);
+    let (lower, upper) = match (vals.get(positive), vals.last()) {
+        (Some(&first), Some(&last)) => (first.into(), last.into()),
+        _ => return Vec::new(),
+    };
+
+    let upper = if upper > lower { upper } else { lower * 10f64 };
+    spaced_edges(lower, upper, buckets, |lower, upper, frac| {
+        lower * (upper / lower).powf(frac)
+    })
+}
+
+/// Edges for `buckets` buckets using a function to compute each edge from the
+/// lowest and highest edge and its fraction of the distance between them.
+/// The first and last edges are exactly the lowest and highest edge so that
+/// floating point error doesn't leave out any values.
+fn spaced_edges<F: Fn(f64, f64, f64) -> f64>(lower: f64, upper: f64, buckets: usize, edge: F) -> Vec<f64> {
+    let mut edges: Vec<f64> = (0..buckets)
+        .map(|i| edge(lower, upper, i as f64 / buckets as f64))
+        .collect();
+    edges[0] = lower;
+    edges.push(upper);
+    edges
+}
+
+/// Render a histogram as a row for each bucket of its lower and upper edge
+/// and the number of values in it, separated by a separator, e.g.
+/// `1.0..2.0: 4`. Values below or above the edges, when counted separately,
+/// are displayed as `underflow` and `overflow` rows if there are any.
+#[derive(Debug)]
+pub struct HistogramFormatter<'a> {
+    hist: &'a Histogram,
+    sep: KeyValueSep,
+    precision: usize,
+}
+
+impl<'a> HistogramFormatter<'a> {
+    pub fn new(hist: &'a Histogram) -> HistogramFormatter<'a> {
+        Self::with_sep(hist, KeyValueSep::Colon)
+    }
+
+    pub fn with_sep(hist: &'a Histogram, sep: KeyValueSep) -> HistogramFormatter<'a> {
+        HistogramFormatter {
+            hist,
+            sep,
+            precision: DISPLAY_PRECISION,
+        }
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+}
+
+impl<'a> fmt::Display for HistogramFormatter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.hist.underflow() > 0 {
+            writeln!(f, "underflow{}{}", self.sep, self.hist.underflow())?;
+        }
+
+        for (lower, upper, count) in self.hist.buckets() {
+            writeln!(
+                f,
+                "{:.*}..{:.*}{}{}",
+                self.precision, lower, self.precision, upper, self.sep, count
+            )?;
+        }
+
+        if self.hist.overflow() > 0 {
+            writeln!(f, "overflow{}{}", self.sep, self.hist.overflow())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Histogram, HistogramFormatter, OutlierPolicy};
+    use crate::{KeyValueSep, SortedValues};
+
+    fn filled(mut hist: Histogram, vals: &[f64]) -> Histogram {
+        let mut vals = vals.to_vec();
+        hist.fill(SortedValues::sort(&mut vals));
+        hist
+    }
+
+    #[test]
+    fn test_with_edges_invalid() {
+        assert!(Histogram::with_edges(&[]).is_none());
+        assert!(Histogram::with_edges(&[1.0]).is_none());
+        assert!(Histogram::with_edges(&[1.0, 1.0]).is_none());
+        assert!(Histogram::with_edges(&[2.0, 1.0]).is_none());
+        assert!(Histogram::with_edges(&[1.0, f64::INFINITY]).is_none());
+        assert!(Histogram::with_edges(&[f64::NAN, 1.0]).is_none());
+    }
+
+    #[test]
+    fn test_fill_edge_inclusive_boundaries() {
+        let hist = Histogram::with_edges(&[0.0, 1.0, 2.0, 3.0]).unwrap();
+        let hist = filled(hist, &[0.0, 0.5, 1.0, 2.0, 2.5, 3.0]);
+
+        // Lower edges are inclusive, upper edges are exclusive except for the last.
+        assert_eq!(&[2, 1, 3], hist.counts());
+        assert_eq!(0, hist.underflow());
+        assert_eq!(0, hist.overflow());
+    }
+
+    #[test]
+    fn test_fill_just_outside_edges() {
+        let hist = Histogram::with_edges(&[0.0, 1.0]).unwrap();
+        let hist = filled(hist, &[-1e-12, 0.0, 1.0, 1.0 + 1e-12]);
+
+        assert_eq!(&[2], hist.counts());
+        assert_eq!(1, hist.underflow());
+        assert_eq!(1, hist.overflow());
+        assert_eq!(4, hist.total());
+    }
+
+    #[test]
+    fn test_fill_clamp_outliers() {
+        let hist = Histogram::with_edges(&[0.0, 1.0, 2.0])
+            .unwrap()
+            .outliers(OutlierPolicy::Clamp);
+        let hist = filled(hist, &[-5.0, -1.0, 0.5, 1.5, 7.0]);
+
+        assert_eq!(&[3, 2], hist.counts());
+        assert_eq!(0, hist.underflow());
+        assert_eq!(0, hist.overflow());
+    }
+
+    #[test]
+    fn test_fill_replaces_counts() {
+        let hist = Histogram::with_edges(&[0.0, 1.0]).unwrap();
+        let hist = filled(hist, &[0.5, 0.5, 5.0]);
+        let hist = filled(hist, &[0.5]);
+
+        assert_eq!(&[1], hist.counts());
+        assert_eq!(0, hist.overflow());
+    }
+
+    #[test]
+    fn test_fill_skips_nan() {
+        let hist = Histogram::with_edges(&[0.0, 1.0]).unwrap();
+        let hist = filled(hist, &[f64::NAN, 0.5, -f64::NAN]);
+
+        assert_eq!(&[1], hist.counts());
+        assert_eq!(1, hist.total());
+    }
+
+    #[test]
+    fn test_cumulative_counts() {
+        let hist = Histogram::with_edges(&[0.0, 1.0, 2.0]).unwrap();
+        let hist = filled(hist, &[-1.0, 0.5, 1.5, 1.5, 3.0]);
+
+        assert_eq!(vec![2, 4], hist.cumulative_counts());
+    }
+
+    #[test]
+    fn test_with_buckets() {
+        let hist = filled(Histogram::with_buckets(4), &[0.0, 1.0, 2.0, 3.0, 4.0, 4.0]);
+
+        assert_eq!(&[0.0, 1.0, 2.0, 3.0, 4.0], hist.edges());
+        assert_eq!(&[1, 1, 1, 3], hist.counts());
+        assert_eq!(0, hist.underflow());
+        assert_eq!(0, hist.overflow());
+    }
+
+    #[test]
+    fn test_with_buckets_single_value() {
+        let hist = filled(Histogram::with_buckets(2), &[3.0, 3.0]);
+
+        assert_eq!(&[3.0, 3.5, 4.0], hist.edges());
+        assert_eq!(&[2, 0], hist.counts());
+    }
+
+    #[test]
+    fn test_with_buckets_empty() {
+        let hist = filled(Histogram::with_buckets(3), &[]);
+
+        assert!(hist.edges().is_empty());
+        assert!(hist.counts().is_empty());
+        assert_eq!(0, hist.total());
+    }
+
+    #[test]
+    fn test_with_buckets_includes_every_value() {
+        let vals: Vec<f64> = (0..1000).map(|i| (i as f64) * 0.1 + 0.3).collect();
+        for buckets in 1..20 {
+            let hist = filled(Histogram::with_buckets(buckets), &vals);
+
+            assert_eq!(buckets, hist.counts().len());
+            assert_eq!(1000, hist.counts().iter().sum::<u64>());
+        }
+    }
+
+    #[test]
+    fn test_log_scaled() {
+        let hist = filled(
+            Histogram::log_scaled(3),
+            &[-1.0, 0.0, 1.0, 5.0, 10.0, 50.0, 100.0, 1000.0],
+        );
+
+        let edges = hist.edges();
+        assert_eq!(4, edges.len());
+        assert_eq!(1.0, edges[0]);
+        assert!((edges[1] - 10.0).abs() < 1e-9);
+        assert!((edges[2] - 100.0).abs() < 1e-9);
+        assert_eq!(1000.0, edges[3]);
+        assert_eq!(2, hist.underflow());
+        assert_eq!(6, hist.counts().iter().sum::<u64>());
+    }
+
+    #[test]
+    fn test_log_scaled_no_positive_values() {
+        let hist = filled(Histogram::log_scaled(3), &[-2.0, 0.0]);
+
+        assert!(hist.edges().is_empty());
+        assert_eq!(0, hist.total());
+    }
+
+    #[test]
+    fn test_buckets() {
+        let hist = Histogram::with_edges(&[0.0, 1.0, 2.0]).unwrap();
+        let hist = filled(hist, &[0.5, 1.5, 1.5]);
+        let buckets: Vec<(f64, f64, u64)> = hist.buckets().collect();
+
+        assert_eq!(vec![(0.0, 1.0, 1), (1.0, 2.0, 2)], buckets);
+    }
+
+    #[test]
+    fn test_formatter() {
+        let hist = Histogram::with_edges(&[0.0, 1.0, 2.0]).unwrap();
+        let hist = filled(hist, &[-1.0, 0.5, 1.5, 1.5, 3.0, 4.0]);
+        let out = HistogramFormatter::new(&hist).precision(1).to_string();
+
+        assert_eq!("underflow: 1\n0.0..1.0: 1\n1.0..2.0: 2\noverflow: 2\n", out);
+    }
+
+    #[test]
+    fn test_formatter_separator_no_outliers() {
+        let hist = filled(Histogram::with_buckets(2), &[1.0, 2.0, 3.0]);
+        let out = HistogramFormatter::with_sep(&hist, KeyValueSep::Tab)
+            .precision(0)
+            .to_string();
+
+        assert_eq!("1..2\t1\n2..3\t2\n", out);
+    }
+
+    #[test]
+    fn test_f32_values() {
+        let mut vals: Vec<f32> = vec![1.0, 2.0, 3.0, 10.0];
+        let mut hist = Histogram::with_edges(&[0.0, 2.0, 4.0]).unwrap();
+        hist.fill(SortedValues::sort(&mut vals));
+
+        assert_eq!(&[1, 2], hist.counts());
+        assert_eq!(1, hist.overflow());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let hist = Histogram::log_scaled(4).outliers(OutlierPolicy::Clamp);
+        let hist = filled(hist, &[0.5, 2.0, 30.0, 400.0]);
+
+        let json = serde_json::to_string(&hist).unwrap();
+        let parsed: Histogram = serde_json::from_str(&json).unwrap();
+        assert_eq!(hist, parsed);
+    }
+}
@@ -0,0 +1,502 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::format::{format_float, labeled_key, sanitize_label, Rounding};
+use crate::{
+    percentile_slice, try_for_each_line, KeyCase, KeyValueSep, SortingPolicy, StaccatoError, StatisticsConfig,
+    DISPLAY_PRECISION,
+};
+use std::fmt;
+use std::io::{BufReader, Read};
+
+/// Read integer values, one per line, from a reader.
+///
+/// Leading and trailing whitespace is removed and empty lines are skipped
+/// the same way as `get_values`, but unlike `get_values` any other line that
+/// isn't an integer in the range of an `i64` or a `u64` (from `i64::MIN` to
+/// `u64::MAX`) is a `ParseFailure` instead of being skipped, so that values
+/// are never silently left out. Values are returned as `i128` so that both
+/// ranges fit.
+pub fn get_integers<T: Read>(reader: &mut T, sort: SortingPolicy) -> Result<Vec<i128>, StaccatoError> {
+    let mut values = Vec::new();
+    let mut line_num = 0;
+
+    try_for_each_line(&mut BufReader::new(reader), |line| {
+        line_num += 1;
+        let trimmed = line.trim();
+        if !trimmed.is_empty() {
+            let v = parse_integer(trimmed).ok_or_else(|| StaccatoError::ParseFailure {
+                line: line_num,
+                content: trimmed.to_string(),
+            })?;
+            values.push(v);
+        }
+
+        Ok(())
+    })?;
+
+    sort.sort_with(&mut values, |v| v.sort_unstable())?;
+    Ok(values)
+}
+
+/// Parse an integer in the range of an `i64`, or of a `u64` if it's too
+/// large for an `i64`.
+fn parse_integer(s: &str) -> Option<i128> {
+    match s.parse::<i64>() {
+        Ok(v) => Some(v.into()),
+        Err(_) => s.parse::<u64>().ok().map(i128::from),
+    }
+}
+
+/// Statistics of a sequence of integers, or of the lowest given percent of
+/// them.
+///
+/// The count, sum, upper, and lower values are exact integers: the sum is
+/// accumulated as an `i128` so it can't overflow or lose precision for any
+/// realistic number of `i64` or `u64` values, unlike the sum of `Statistics`
+/// which is only exact up to 2^53. The mean, median, and standard deviation
+/// are floating point values computed from the exact sum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegerStatistics {
+    percentile: Option<u8>,
+    count: usize,
+    sum: i128,
+    mean: f64,
+    upper: i128,
+    lower: i128,
+    median: f64,
+    stddev: f64,
+}
+
+impl IntegerStatistics {
+    /// Compute statistics of a sequence of integers, or of the lowest given
+    /// percent of them. The values must be sorted for the percentile slice
+    /// to be correct, but don't need to be sorted otherwise.
+    ///
+    /// This method returns `None` if there are no values (in the slice).
+    pub fn from<V>(vals: &[V], percentile: Option<u8>) -> Option<IntegerStatistics>
+    where
+        V: Copy + Ord + Into<i128>,
+    {
+        let filtered = match percentile {
+            Some(p) => percentile_slice(vals, p),
+            None => vals,
+        };
+
+        let count = filtered.len();
+        if count == 0 {
+            return None;
+        }
+
+        let mut sum = 0i128;
+        let mut lower = i128::MAX;
+        let mut upper = i128::MIN;
+        for &v in filtered {
+            let v: i128 = v.into();
+            sum += v;
+            lower = lower.min(v);
+            upper = upper.max(v);
+        }
+
+        // The mean is split into an exact integer part and the remainder of
+        // dividing the sum by the count, so that deviations from the integer
+        // part are exact before being converted to floating point.
+        let n = count as i128;
+        let (whole, rem) = (sum.div_euclid(n), sum.rem_euclid(n));
+        let mean = whole as f64 + rem as f64 / count as f64;
+
+        // With deviations d = v - whole, whose sum is rem, the sum of squared
+        // deviations from the mean is sum(d^2) - rem^2 / n.
+        let squares: f64 = filtered
+            .iter()
+            .map(|&v| {
+                let d = (v.into() - whole) as f64;
+                d * d
+            })
+            .sum();
+        let m2 = (squares - (rem as f64).powi(2) / count as f64).max(0f64);
+
+        Some(IntegerStatistics {
+            percentile,
+            count,
+            sum,
+            mean,
+            upper,
+            lower,
+            median: median(filtered),
+            stddev: (m2 / count as f64).sqrt(),
+        })
+    }
+
+    pub fn percentile(&self) -> Option<u8> {
+        self.percentile
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn sum(&self) -> i128 {
+        self.sum
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    pub fn upper(&self) -> i128 {
+        self.upper
+    }
+
+    pub fn lower(&self) -> i128 {
+        self.lower
+    }
+
+    pub fn median(&self) -> f64 {
+        self.median
+    }
+
+    pub fn stddev(&self) -> f64 {
+        self.stddev
+    }
+}
+
+/// Median of a non-empty sequence of integers, selected from a copy of them
+/// if they aren't sorted.
+fn median<V: Copy + Ord + Into<i128>>(vals: &[V]) -> f64 {
+    let mut copy;
+    let sorted = if vals.windows(2).all(|w| w[0] <= w[1]) {
+        vals
+    } else {
+        copy = vals.to_vec();
+        copy.sort_unstable();
+        &copy
+    };
+
+    let len = sorted.len();
+    let upper: i128 = sorted[len / 2].into();
+    if len % 2 == 1 {
+        return upper as f64;
+    }
+
+    let lower: i128 = sorted[len / 2 - 1].into();
+    (lower + upper) as f64 / 2f64
+}
+
+/// Statistics of all of a sequence of integers and any percentile slices of
+/// them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegerStatisticsBundle {
+    global: IntegerStatistics,
+    percentiles: Vec<IntegerStatistics>,
+}
+
+impl IntegerStatisticsBundle {
+    /// Create a statistics bundle from a sequence of integers and a sequence
+    /// of percentiles. The values must be sorted if there are percentiles or
+    /// the percentile statistics will be incorrect.
+    ///
+    /// This method returns `None` if the sequence of values is empty. Slices
+    /// without any values are omitted.
+    pub fn with_percentiles<V>(vals: &[V], percentiles: &[u8]) -> Option<IntegerStatisticsBundle>
+    where
+        V: Copy + Ord + Into<i128>,
+    {
+        IntegerStatistics::from(vals, None).map(|global| IntegerStatisticsBundle {
+            global,
+            percentiles: percentiles
+                .iter()
+                .flat_map(|&p| IntegerStatistics::from(vals, Some(p)))
+                .collect(),
+        })
+    }
+
+    pub fn global_stats(&self) -> &IntegerStatistics {
+        &self.global
+    }
+
+    pub fn percentile_stats(&self) -> &[IntegerStatistics] {
+        &self.percentiles
+    }
+}
+
+#[derive(Debug)]
+pub struct IntegerStatisticsFormatter<'a> {
+    bundle: &'a IntegerStatisticsBundle,
+    sep: KeyValueSep,
+    precision: usize,
+    config: StatisticsConfig,
+    key_case: KeyCase,
+    label: Option<String>,
+    rounding: Option<Rounding>,
+}
+
+impl<'a> IntegerStatisticsFormatter<'a> {
+    pub fn new(bundle: &'a IntegerStatisticsBundle) -> IntegerStatisticsFormatter<'a> {
+        Self::with_sep(bundle, KeyValueSep::Colon)
+    }
+
+    pub fn with_sep(bundle: &'a IntegerStatisticsBundle, sep: KeyValueSep) -> IntegerStatisticsFormatter<'a> {
+        IntegerStatisticsFormatter {
+            bundle,
+            sep,
+            precision: DISPLAY_PRECISION,
+            config: StatisticsConfig::default(),
+            key_case: KeyCase::default(),
+            label: None,
+            rounding: None,
+        }
+    }
+
+    /// Number of digits after the decimal point for the mean, median, and
+    /// standard deviation. The count, sum, upper, and lower values are
+    /// always displayed as integers.
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Round the mean, median, and standard deviation to integers, the same
+    /// as `KeyValueFormatter::rounding`.
+    pub fn rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = Some(rounding);
+        self
+    }
+
+    /// Only display the statistics selected by the config.
+    pub fn config(mut self, config: StatisticsConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Naming style of each key, snake case by default.
+    pub fn key_case(mut self, key_case: KeyCase) -> Self {
+        self.key_case = key_case;
+        self
+    }
+
+    /// Prefix each key with a label and a period, the same as
+    /// `KeyValueFormatter::label`.
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = Some(sanitize_label(label));
+        self
+    }
+
+    fn write_stats(&self, f: &mut fmt::Formatter, stats: &IntegerStatistics) -> fmt::Result {
+        let float = |v| format_float(v, self.precision, self.rounding);
+        let values = [
+            ("count", stats.count().to_string()),
+            ("sum", stats.sum().to_string()),
+            ("mean", float(stats.mean())),
+            ("upper", stats.upper().to_string()),
+            ("lower", stats.lower().to_string()),
+            ("median", float(stats.median())),
+            ("stddev", float(stats.stddev())),
+        ];
+
+        for (key, val) in values.iter().filter(|(key, _)| self.config.is_selected(key)) {
+            let key = match stats.percentile() {
+                Some(pct) => format!("{}_{}", key, pct),
+                None => key.to_string(),
+            };
+
+            let key = labeled_key(self.key_case, self.label.as_deref(), &key);
+            writeln!(f, "{}{}{}", key, self.sep, val)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for IntegerStatisticsFormatter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_stats(f, self.bundle.global_stats())?;
+        for stats in self.bundle.percentile_stats() {
+            self.write_stats(f, stats)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_integers, IntegerStatistics, IntegerStatisticsBundle, IntegerStatisticsFormatter};
+    use crate::{KeyCase, Rounding, SortingPolicy, StaccatoError, Statistics, StatisticsConfig};
+    use std::io::Cursor;
+
+    const VALUES: &[i64] = &[1, 2, 5, 7, 9, 12];
+
+    #[test]
+    fn test_get_integers() {
+        let mut input = Cursor::new(" 3\n\n-1\r\n2 \n");
+        assert_eq!(
+            vec![3, -1, 2],
+            get_integers(&mut input, SortingPolicy::Unsorted).unwrap()
+        );
+
+        let mut input = Cursor::new("3\n-1\n2\n");
+        assert_eq!(vec![-1, 2, 3], get_integers(&mut input, SortingPolicy::Sorted).unwrap());
+    }
+
+    #[test]
+    fn test_get_integers_rejects_non_integers() {
+        let err = get_integers(&mut Cursor::new("1\n\n2.5\n3\n"), SortingPolicy::Unsorted).unwrap_err();
+        assert!(
+            matches!(err, StaccatoError::ParseFailure { line: 3, ref content } if content == "2.5"),
+            "{:?}",
+            err
+        );
+
+        assert!(matches!(
+            get_integers(&mut Cursor::new("foo\n"), SortingPolicy::Unsorted),
+            Err(StaccatoError::ParseFailure { line: 1, .. })
+        ));
+        assert!(matches!(
+            get_integers(&mut Cursor::new("18446744073709551616\n"), SortingPolicy::Unsorted),
+            Err(StaccatoError::ParseFailure { line: 1, .. })
+        ));
+        assert!(matches!(
+            get_integers(&mut Cursor::new("-9223372036854775809\n"), SortingPolicy::Unsorted),
+            Err(StaccatoError::ParseFailure { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_get_integers_beyond_i64_range() {
+        let mut input = Cursor::new("18446744073709551615\n9223372036854775808\n-9223372036854775808\n");
+        assert_eq!(
+            vec![i64::MIN.into(), i64::MAX as i128 + 1, u64::MAX.into()],
+            get_integers(&mut input, SortingPolicy::Sorted).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_integer_statistics_empty() {
+        assert!(IntegerStatistics::from::<i64>(&[], None).is_none());
+        assert!(IntegerStatistics::from(&[1i64], Some(50)).is_none());
+    }
+
+    #[test]
+    fn test_integer_statistics_same_as_statistics() {
+        let floats: Vec<f64> = VALUES.iter().map(|&v| v as f64).collect();
+        for &percentile in &[None, Some(50), Some(90)] {
+            let expected = Statistics::from(&floats, percentile).unwrap();
+            let stats = IntegerStatistics::from(VALUES, percentile).unwrap();
+
+            assert_eq!(expected.count(), stats.count());
+            assert_eq!(expected.sum(), stats.sum() as f64);
+            assert_eq!(expected.mean(), stats.mean());
+            assert_eq!(expected.upper(), stats.upper() as f64);
+            assert_eq!(expected.lower(), stats.lower() as f64);
+            assert_eq!(expected.median(), stats.median());
+            assert!((expected.stddev() - stats.stddev()).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_integer_statistics_unsorted_median() {
+        let stats = IntegerStatistics::from(&[9u64, 1, 7, 2], None).unwrap();
+        assert_eq!(4.5, stats.median());
+    }
+
+    #[test]
+    fn test_integer_statistics_sum_beyond_f64_exact_range() {
+        // 2^53 + 1 can't be represented exactly as an f64
+        let big = (1i64 << 53) + 1;
+        let stats = IntegerStatistics::from(&[big, big, 1], None).unwrap();
+        assert_eq!(2 * big as i128 + 1, stats.sum());
+        assert_eq!(big as i128, stats.upper());
+
+        let floats = [big as f64, big as f64, 1f64];
+        let lossy = Statistics::from(&floats, None).unwrap();
+        assert_ne!(stats.sum(), lossy.sum() as i128);
+    }
+
+    #[test]
+    fn test_integer_statistics_sum_beyond_i64_range() {
+        let stats = IntegerStatistics::from(&[i64::MAX, i64::MAX, i64::MIN], None).unwrap();
+        assert_eq!(i64::MAX as i128 - 1, stats.sum());
+        assert_eq!(i64::MIN as i128, stats.lower());
+
+        let stats = IntegerStatistics::from(&[u64::MAX, u64::MAX], None).unwrap();
+        assert_eq!(2 * u64::MAX as i128, stats.sum());
+        assert_eq!(u64::MAX as f64, stats.mean());
+        assert_eq!(0f64, stats.stddev());
+    }
+
+    #[test]
+    fn test_integer_statistics_stddev_large_offset() {
+        // Deviations from a huge mean are exact, unlike with f64 values
+        let base = 1i64 << 60;
+        let stats = IntegerStatistics::from(&[base + 1, base + 2, base + 3, base + 4], None).unwrap();
+        assert_eq!(1.25f64.sqrt(), stats.stddev());
+    }
+
+    #[test]
+    fn test_integer_statistics_formatter() {
+        let bundle = IntegerStatisticsBundle::with_percentiles(VALUES, &[50]).unwrap();
+        let out = format!("{}", IntegerStatisticsFormatter::new(&bundle).precision(1));
+
+        assert_eq!(
+            "count: 6\nsum: 36\nmean: 6.0\nupper: 12\nlower: 1\nmedian: 6.0\nstddev: 3.8\n\
+             count_50: 3\nsum_50: 8\nmean_50: 2.7\nupper_50: 5\nlower_50: 1\nmedian_50: 2.0\nstddev_50: 1.7\n",
+            out
+        );
+    }
+
+    #[test]
+    fn test_integer_statistics_formatter_rounding() {
+        let bundle = IntegerStatisticsBundle::with_percentiles(VALUES, &[50]).unwrap();
+        let out = format!("{}", IntegerStatisticsFormatter::new(&bundle).rounding(Rounding::Floor));
+
+        assert_eq!(
+            "count: 6\nsum: 36\nmean: 6\nupper: 12\nlower: 1\nmedian: 6\nstddev: 3\n\
+             count_50: 3\nsum_50: 8\nmean_50: 2\nupper_50: 5\nlower_50: 1\nmedian_50: 2\nstddev_50: 1\n",
+            out
+        );
+    }
+
+    #[test]
+    fn test_integer_statistics_formatter_key_case() {
+        let bundle = IntegerStatisticsBundle::with_percentiles(VALUES, &[50]).unwrap();
+        let config = StatisticsConfig::none().with_count(true);
+        let format = |key_case| {
+            IntegerStatisticsFormatter::new(&bundle)
+                .config(config)
+                .key_case(key_case)
+                .to_string()
+        };
+
+        assert_eq!("count: 6\ncount_50: 3\n", format(KeyCase::Snake));
+        assert_eq!("count: 6\ncount50: 3\n", format(KeyCase::Camel));
+        assert_eq!("count: 6\ncount-50: 3\n", format(KeyCase::Kebab));
+    }
+
+    #[test]
+    fn test_integer_statistics_formatter_label() {
+        let bundle = IntegerStatisticsBundle::with_percentiles(VALUES, &[50]).unwrap();
+        let out = IntegerStatisticsFormatter::new(&bundle)
+            .config(StatisticsConfig::none().with_count(true))
+            .key_case(KeyCase::Kebab)
+            .label("api/v1")
+            .to_string();
+
+        assert_eq!("api_v1.count: 6\napi_v1.count-50: 3\n", out);
+    }
+}
@@ -16,42 +16,632 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 use std::fmt;
-use std::fmt::Write;
 use std::io;
-use std::io::Read;
+use std::io::{BufRead, BufReader, Read};
 use std::str::FromStr;
 
+mod annotate;
+mod boxplot;
+mod cdf;
+#[cfg(feature = "rayon")]
+mod chunked;
+mod compare;
+mod condition;
+mod config;
+mod cumsum;
+mod digest;
+mod engine;
+mod error;
+mod estimate;
+mod ext;
+mod extract;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod format;
+mod grouped;
+mod histogram;
+mod integer;
+mod measure;
+mod merge;
+mod normalize;
+mod omitted;
+mod paired;
+mod parse;
+mod percentiles;
+#[cfg(not(target_arch = "wasm32"))]
+mod progress;
+#[cfg(feature = "python")]
+mod python;
+mod quantile;
+mod records;
+mod reservoir;
+mod rng;
+mod run;
+mod sorted;
+mod spill;
+mod streaming;
+mod summary;
+mod timing;
+mod unit;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod window;
+
+pub use crate::annotate::{Annotation, AnnotationFormatter, Annotator};
+pub use crate::boxplot::{render_line, scale_to_column, BoxPlot, BoxPlotFormatter, WhiskerPolicy};
+pub use crate::cdf::{Cdf, CdfFormatter, CdfPoints, SampledCdf};
+#[cfg(feature = "rayon")]
+pub use crate::chunked::get_values_from_file_parallel;
+pub use crate::compare::{compare_bundles, ComparisonFormatter, ComparisonReport, ComparisonRow};
+pub use crate::condition::{Comparison, Condition, ConditionError, ConditionStat};
+pub use crate::config::{EmptyPolicy, ErrorPolicy, StatName, StatValue, StatisticsConfig, STATISTICS_KEYS};
+pub use crate::cumsum::{CumulativeSum, CumulativeSumFormatter, CumulativeSumMode};
+pub use crate::digest::TDigest;
+pub use crate::engine::{Engine, ExactEngine, MemoryLimited, BYTES_PER_VALUE};
+pub use crate::error::{ParseLineError, StaccatoError};
+pub use crate::estimate::{capacity_for_file, estimate_capacity, estimate_values_in_file, MAX_RESERVED_VALUES};
+pub use crate::extract::{
+    parse_duration_millis, Delimiter, DurationExtractor, FieldExtractor, LineExtractor, MarkerExtractor,
+    PlainExtractor, SplitExtractor,
+};
+#[cfg(feature = "ffi")]
+pub use crate::ffi::{
+    staccato_compute, staccato_stats_compute, staccato_stats_free, staccato_stats_new, staccato_stats_push,
+    StaccatoStatistics, StaccatoStats, STACCATO_ERR_EMPTY, STACCATO_ERR_NULL, STACCATO_OK,
+};
+#[cfg(feature = "serde")]
+pub use crate::format::JsonFormatter;
+pub use crate::format::{
+    sanitize_label, BundleFormatter, FormattedBundle, FormattedBundles, FormattedStatistics, KeyCase,
+    KeyValueFormatter, Rounding,
+};
+pub use crate::grouped::GroupedStatistics;
+pub use crate::histogram::{Histogram, HistogramFormatter, OutlierPolicy};
+pub use crate::integer::{get_integers, IntegerStatistics, IntegerStatisticsBundle, IntegerStatisticsFormatter};
+pub use crate::measure::{get_measurements, get_measurements_from_slice, Measure};
+pub use crate::merge::{Accuracy, MergeError, MergePolicy};
+pub use crate::normalize::{Normalization, NormalizationFormatter, Normalizer};
+use crate::omitted::omitted_from;
+pub use crate::omitted::{Omission, OmittedPercentile, OmittedQuantile};
+pub use crate::paired::{ranks, PairedStatistics, PairedStatisticsFormatter};
+use crate::parse::collect_values_into;
+pub use crate::parse::{
+    collect_parsed_values, collect_parsed_values_sampled, collect_parsed_values_with, collect_values,
+    collect_values_with, parse_values, parse_values_with, MalformedLine, ParseValues, ParsedValues,
+    ValidationFormatter, DEFAULT_MALFORMED_SAMPLES,
+};
+pub use crate::percentiles::Percentiles;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::progress::{ProgressReader, DEFAULT_PROGRESS_INTERVAL};
+pub use crate::quantile::{quantile, Quantile, QuantileMethod};
+pub use crate::records::NullDelimited;
+pub use crate::reservoir::Reservoir;
+pub use crate::rng::Rng;
+pub use crate::run::{run, run_from, run_with, run_with_timings, StaccatoConfig};
+pub use crate::sorted::{SortedValues, SortedVec};
+pub use crate::spill::{ExternalSorter, DEFAULT_RUN_SIZE};
+pub use crate::streaming::{get_streaming_statistics, StreamingStatistics};
+pub use crate::summary::FiveNumberSummary;
+pub use crate::timing::{Phase, TimedReader, Timings, TimingsFormatter};
+pub use crate::unit::{Unit, UnitConversion, UnitKind};
+#[cfg(feature = "wasm")]
+pub use crate::wasm::compute_stats;
+pub use crate::window::WindowedStatistics;
+
+/// Extension traits for computing statistics, e.g. `use staccato::prelude::*;`
+/// to call `staccato_stats()` on any iterator of values.
+pub mod prelude {
+    pub use crate::ext::StatisticsExt;
+}
+
+/// Version of this crate along with the git commit (if it was built from a
+/// git checkout) and date it was built, e.g. "0.1.9 (1a2b3c4 2024-05-01)",
+/// for applications to log which version they're using.
+pub const VERSION_INFO: &str = env!("STACCATO_VERSION_INFO");
+
 const DISPLAY_PRECISION: usize = 5;
 
-#[derive(PartialEq, Eq)]
+// Reductions over values (e.g. the sum) use this many independent accumulators
+// so that each addition doesn't depend on the previous one, which lets the
+// CPU (or the compiler, by vectorizing them) perform several at once. Since
+// the values are added in a different order, results may differ from adding
+// them one at a time by rounding error. Slices shorter than the minimum length
+// use a single accumulator.
+const LANES: usize = 8;
+const MIN_LANES_LEN: usize = 64;
+
+/// Type that values can be stored as while computing statistics.
+///
+/// Statistics are always computed (and sums accumulated) as `f64`. Storing
+/// values as `f32` uses half as much memory but each value only has about 7
+/// significant digits, so statistics computed from them are within a
+/// relative error of about `1e-6` of those computed from `f64` values.
+pub trait Value: Copy + PartialOrd + Into<f64> {
+    /// Convert a parsed `f64` value into this type, rounding if required.
+    fn from_f64(val: f64) -> Self;
+}
+
+impl Value for f64 {
+    fn from_f64(val: f64) -> Self {
+        val
+    }
+}
+
+impl Value for f32 {
+    fn from_f64(val: f64) -> Self {
+        val as f32
+    }
+}
+
+/// Whether values should be sorted after they're read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum SortingPolicy {
+    /// Sort values after reading them.
     Sorted,
+    /// Keep values in the order they were read.
     Unsorted,
+    /// Sort values only if the statistics being computed need them to be
+    /// sorted, which is the case when the median is selected. Functions that
+    /// read values without a `StatisticsConfig` resolve this as if every
+    /// statistic is selected. This is the default.
+    #[default]
+    Auto,
+    /// Values are already in ascending order, e.g. from a file written by
+    /// another program that sorted them, so they don't need to be sorted.
+    /// This is checked after reading them, which takes a single pass over
+    /// the values, and values that aren't in order are an error.
+    AlreadySorted,
 }
 
-pub fn get_values<T: Read>(reader: &mut T, sort: SortingPolicy) -> Result<Vec<f64>, io::Error> {
-    let mut buf = String::new();
-    reader.read_to_string(&mut buf)?;
+impl SortingPolicy {
+    /// Resolve `Auto` to `Sorted` or `Unsorted` based on the statistics
+    /// selected by a config, leaving any other policy unchanged.
+    pub fn resolve(self, config: StatisticsConfig) -> SortingPolicy {
+        match self {
+            SortingPolicy::Auto if config.median() => SortingPolicy::Sorted,
+            SortingPolicy::Auto => SortingPolicy::Unsorted,
+            p => p,
+        }
+    }
+
+    /// True if values read using this policy are in ascending order.
+    pub fn is_sorted(self) -> bool {
+        matches!(
+            self.resolve(StatisticsConfig::default()),
+            SortingPolicy::Sorted | SortingPolicy::AlreadySorted
+        )
+    }
 
-    let mut values: Vec<f64> = buf
-        .lines()
-        .map(|v| v.trim())
-        .filter_map(|v| v.parse::<f64>().ok())
-        .collect();
+    /// True if values read using this policy need to be sorted by the reader.
+    pub(crate) fn needs_sort(self) -> bool {
+        self.resolve(StatisticsConfig::default()) == SortingPolicy::Sorted
+    }
+
+    /// Sort values read using this policy if required, using a function that
+    /// sorts them, or check that they're already sorted.
+    pub(crate) fn sort_with<T: PartialOrd, F: FnOnce(&mut [T])>(
+        self,
+        vals: &mut [T],
+        sort: F,
+    ) -> Result<(), StaccatoError> {
+        if self.needs_sort() {
+            sort(vals);
+        } else if self == SortingPolicy::AlreadySorted {
+            check_sorted(vals)?;
+        }
 
-    if sort == SortingPolicy::Sorted {
-        values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Less));
+        Ok(())
     }
+}
+
+pub fn get_values<T: Read>(reader: &mut T, sort: SortingPolicy) -> Result<Vec<f64>, StaccatoError> {
+    get_values_as(reader, sort)
+}
+
+/// Parse values the same way as `get_values` along with counts of the lines
+/// that weren't values (blank or malformed) and a sample of the malformed
+/// lines, to report the quality of the input.
+pub fn get_parsed_values<T: Read>(reader: &mut T, sort: SortingPolicy) -> Result<ParsedValues, StaccatoError> {
+    get_parsed_values_into(reader, sort, Vec::new(), PlainExtractor, DEFAULT_MALFORMED_SAMPLES)
+}
+
+/// Parse values the same way as `get_values` but store them as a particular
+/// type, e.g. `f32` to use half as much memory for large inputs.
+pub fn get_values_as<V: Value, T: Read>(reader: &mut T, sort: SortingPolicy) -> Result<Vec<V>, StaccatoError> {
+    get_values_into(reader, sort, Vec::new())
+}
+
+/// Parse values the same way as `get_values` after reserving space for
+/// `capacity` values, e.g. estimated by `capacity_for_file`, so that the
+/// values don't need to be copied as the vector of them grows.
+pub fn get_values_with_capacity<T: Read>(
+    reader: &mut T,
+    sort: SortingPolicy,
+    capacity: usize,
+) -> Result<Vec<f64>, StaccatoError> {
+    get_values_into(reader, sort, Vec::with_capacity(capacity))
+}
+
+/// Read values from each line using an extractor, e.g. to parse a single
+/// field of each line, the same way as `collect_values_with`.
+pub fn get_values_with<T: Read, E: LineExtractor>(
+    reader: &mut T,
+    sort: SortingPolicy,
+    extractor: E,
+) -> Result<Vec<f64>, StaccatoError> {
+    get_parsed_values_with(reader, sort, extractor).map(ParsedValues::into_values)
+}
+
+/// Parse values the same way as `get_values_with_capacity` along with counts
+/// of the lines that weren't values, the same as `get_parsed_values`.
+pub fn get_parsed_values_with_capacity<T: Read>(
+    reader: &mut T,
+    sort: SortingPolicy,
+    capacity: usize,
+) -> Result<ParsedValues, StaccatoError> {
+    get_parsed_values_into(
+        reader,
+        sort,
+        Vec::with_capacity(capacity),
+        PlainExtractor,
+        DEFAULT_MALFORMED_SAMPLES,
+    )
+}
 
+/// Read values from each line using an extractor the same way as
+/// `get_values_with` along with counts of the lines that weren't values,
+/// the same as `get_parsed_values`.
+pub fn get_parsed_values_with<T: Read, E: LineExtractor>(
+    reader: &mut T,
+    sort: SortingPolicy,
+    extractor: E,
+) -> Result<ParsedValues, StaccatoError> {
+    get_parsed_values_into(reader, sort, Vec::new(), extractor, DEFAULT_MALFORMED_SAMPLES)
+}
+
+/// Read values from each line using an extractor after reserving space for
+/// `capacity` values, keeping up to `samples` of the lines that weren't
+/// values the same way as `collect_parsed_values_sampled`.
+pub fn get_parsed_values_sampled<T: Read, E: LineExtractor>(
+    reader: &mut T,
+    sort: SortingPolicy,
+    extractor: E,
+    capacity: usize,
+    samples: usize,
+) -> Result<ParsedValues, StaccatoError> {
+    get_parsed_values_into(reader, sort, Vec::with_capacity(capacity), extractor, samples)
+}
+
+fn get_values_into<V: Value, T: Read>(
+    reader: &mut T,
+    sort: SortingPolicy,
+    values: Vec<V>,
+) -> Result<Vec<V>, StaccatoError> {
+    get_parsed_values_into(reader, sort, values, PlainExtractor, DEFAULT_MALFORMED_SAMPLES)
+        .map(ParsedValues::into_values)
+}
+
+fn get_parsed_values_into<V: Value, T: Read, E: LineExtractor>(
+    reader: &mut T,
+    sort: SortingPolicy,
+    values: Vec<V>,
+    extractor: E,
+    samples: usize,
+) -> Result<ParsedValues<V>, StaccatoError> {
+    let mut parsed = collect_values_into(BufReader::new(reader), values, extractor, samples)?;
+
+    sort.sort_with(parsed.values_mut(), sort_values)?;
+
+    Ok(parsed)
+}
+
+/// Parse values from a slice of bytes (such as a memory mapped file) the
+/// same way `get_values` parses them from a reader, without copying lines.
+pub fn get_values_from_slice(bytes: &[u8], sort: SortingPolicy) -> Result<Vec<f64>, StaccatoError> {
+    let mut values = Vec::new();
+    for_each_line_in_slice(bytes, |line| {
+        if let Ok(v) = line.trim().parse::<f64>() {
+            values.push(v);
+        }
+    })?;
+
+    sort.sort_with(&mut values, sort_values)?;
     Ok(values)
 }
 
-#[derive(Debug, Clone)]
+/// Call a function with each line from a reader, without its line ending,
+/// splitting lines the same way as `str::lines`.
+///
+/// A single buffer is reused for each line so that only one line is held in
+/// memory at any time, regardless of the size of the input.
+pub(crate) fn for_each_line<T, F>(reader: &mut T, mut f: F) -> Result<(), StaccatoError>
+where
+    T: BufRead + ?Sized,
+    F: FnMut(&str),
+{
+    try_for_each_line(reader, |line| {
+        f(line);
+        Ok(())
+    })
+}
+
+/// Call a fallible function with each line from a reader the same way as
+/// `for_each_line`, stopping at the first error it returns.
+pub(crate) fn try_for_each_line<T, F>(reader: &mut T, mut f: F) -> Result<(), StaccatoError>
+where
+    T: BufRead + ?Sized,
+    F: FnMut(&str) -> Result<(), StaccatoError>,
+{
+    let mut line = String::new();
+    let mut line_num = 0;
+    loop {
+        line_num += 1;
+        match reader.read_line(&mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => f(strip_line_ending(&line))?,
+            // Reading a line only fails with invalid data if it isn't UTF-8
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => return Err(StaccatoError::Utf8 { line: line_num }),
+            Err(e) => return Err(e.into()),
+        }
+
+        line.clear();
+    }
+}
+
+/// Remove a trailing `\n` or `\r\n` from a line read by `BufRead::read_line`.
+pub(crate) fn strip_line_ending(line: &str) -> &str {
+    match line.strip_suffix('\n') {
+        Some(l) => l.strip_suffix('\r').unwrap_or(l),
+        None => line,
+    }
+}
+
+/// Call a function with each line in a slice of bytes, without its line
+/// ending, splitting lines the same way as `for_each_line`.
+///
+/// Each line is validated as UTF-8 in place, returning an error for the
+/// first line that isn't valid.
+pub(crate) fn for_each_line_in_slice<F>(bytes: &[u8], mut f: F) -> Result<(), StaccatoError>
+where
+    F: FnMut(&str),
+{
+    let mut rest = bytes;
+    let mut line_num = 0;
+    while !rest.is_empty() {
+        line_num += 1;
+        let (line, next) = match rest.iter().position(|&b| b == b'\n') {
+            Some(i) => (rest[..i].strip_suffix(b"\r").unwrap_or(&rest[..i]), &rest[i + 1..]),
+            None => (rest, &rest[rest.len()..]),
+        };
+
+        rest = next;
+        match std::str::from_utf8(line) {
+            Ok(l) => f(l),
+            Err(_) => return Err(StaccatoError::Utf8 { line: line_num }),
+        }
+    }
+
+    Ok(())
+}
+
+/// Lowest `percentile` percent of a sequence of sorted values, which is the
+/// slice that statistics of a percentile are computed from.
+///
+/// The slice has the first `percentile * n / 100` values (rounded down), so
+/// it's empty if `percentile` percent of the values is less than one value.
+/// Percentiles over 100 are treated as 100. The values must be sorted (e.g.
+/// with `sort_values`) for the slice to be the lowest values.
+///
+/// ```
+/// use staccato::percentile_slice;
+///
+/// let vals = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
+/// assert_eq!(&[1.0, 2.0, 3.0, 4.0, 5.0], percentile_slice(&vals, 50));
+/// assert_eq!(&vals[..9], percentile_slice(&vals, 95));
+/// assert!(percentile_slice(&vals[..5], 10).is_empty());
+/// ```
+pub fn percentile_slice<T>(vals: &[T], percentile: u8) -> &[T] {
+    &vals[0..percentile_len(vals.len(), percentile)]
+}
+
+/// Number of values in the percentile slice of `count` values.
+pub(crate) fn percentile_len(count: usize, percentile: u8) -> usize {
+    percentile.min(100) as usize * count / 100
+}
+
+/// True if two values are within `epsilon` of each other, relative to the
+/// larger of their magnitudes when it's more than one. Equal infinities and
+/// NaNs are equal.
+fn approx_eq(a: f64, b: f64, epsilon: f64) -> bool {
+    if a == b || (a.is_nan() && b.is_nan()) {
+        return true;
+    }
+
+    if a.is_infinite() || b.is_infinite() {
+        return false;
+    }
+
+    (a - b).abs() <= epsilon * a.abs().max(b.abs()).max(1f64)
+}
+
+/// Sort a sequence of values in ascending order, the same way `get_values`
+/// does, so that it can be used for methods that require sorted values.
+///
+/// Values are sorted using the IEEE 754 total ordering so the result is the
+/// same regardless of the original order of the values, even though the sort
+/// is unstable (`-0.0` is sorted before `0.0` and `NaN` values are sorted to
+/// the beginning or end depending on their sign).
+pub fn sort_values<V: Value>(vals: &mut [V]) {
+    vals.sort_unstable_by(compare_values);
+}
+
+/// Check that values are in ascending order, e.g. values read from a file
+/// that should already be sorted, without sorting them. An error with the
+/// position of the first value that's less than the value before it is
+/// returned if they aren't.
+///
+/// ```
+/// use staccato::check_sorted;
+///
+/// assert!(check_sorted(&[1.0, 2.0, 2.0, 5.0]).is_ok());
+/// assert!(check_sorted(&[1.0, 3.0, 2.0]).is_err());
+/// ```
+pub fn check_sorted<T: PartialOrd>(vals: &[T]) -> Result<(), StaccatoError> {
+    match vals
+        .windows(2)
+        .position(|w| w[0].partial_cmp(&w[1]) == Some(std::cmp::Ordering::Greater))
+    {
+        Some(i) => Err(StaccatoError::NotSorted { position: i + 2 }),
+        None => Ok(()),
+    }
+}
+
+/// Values without any `NaN` values, which are ignored when computing
+/// statistics. The values are only copied if there are any `NaN` values.
+pub(crate) fn without_nan<V: Value>(vals: &[V]) -> Cow<'_, [V]> {
+    if vals.iter().any(|&v| v.into().is_nan()) {
+        Cow::Owned(vals.iter().copied().filter(|&v| !v.into().is_nan()).collect())
+    } else {
+        Cow::Borrowed(vals)
+    }
+}
+
+/// Sorted values without any `NaN` values, which `sort_values` sorts to the
+/// beginning or end depending on their sign.
+pub(crate) fn trim_nan<V: Value>(sorted: &[V]) -> &[V] {
+    let start = sorted.iter().take_while(|&&v| v.into().is_nan()).count();
+    let end = sorted.len() - sorted[start..].iter().rev().take_while(|&&v| v.into().is_nan()).count();
+    &sorted[start..end]
+}
+
+/// Sort a sequence of values in ascending order using up to the given number
+/// of threads. The result is identical to using `sort_values`, which is used
+/// when the number of threads is one or less.
+#[cfg(feature = "rayon")]
+pub fn sort_values_parallel<V: Value + Send>(vals: &mut [V], threads: usize) {
+    use rayon::slice::ParallelSliceMut;
+
+    if threads <= 1 {
+        return sort_values(vals);
+    }
+
+    match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+        Ok(pool) => pool.install(|| vals.par_sort_unstable_by(compare_values)),
+        Err(_) => sort_values(vals),
+    }
+}
+
+/// Merge several **sorted** sequences of values into a single sorted
+/// sequence. The result is identical to concatenating the sequences in order
+/// and sorting them with `sort_values`.
+pub fn merge_sorted_values(sorted: &[Vec<f64>]) -> Vec<f64> {
+    let mut out = Vec::with_capacity(sorted.iter().map(|s| s.len()).sum());
+    let mut heads: BinaryHeap<MergeHead> = sorted
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| !s.is_empty())
+        .map(|(seq, s)| MergeHead {
+            val: s[0],
+            seq,
+            index: 0,
+        })
+        .collect();
+
+    while let Some(head) = heads.pop() {
+        out.push(head.val);
+
+        let next = head.index + 1;
+        if let Some(&val) = sorted[head.seq].get(next) {
+            heads.push(MergeHead {
+                val,
+                seq: head.seq,
+                index: next,
+            });
+        }
+    }
+
+    out
+}
+
+/// Next value from one of the sequences being merged, ordered so that the
+/// smallest value (from the earliest sequence, for equal values) is the
+/// greatest and hence first out of a max-heap.
+struct MergeHead {
+    val: f64,
+    seq: usize,
+    index: usize,
+}
+
+impl Ord for MergeHead {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_values(&other.val, &self.val).then(other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for MergeHead {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MergeHead {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MergeHead {}
+
+fn compare_values<V: Value>(a: &V, b: &V) -> Ordering {
+    // Converting to f64 is lossless so this is the total ordering for f32 too.
+    let (a, b): (f64, f64) = ((*a).into(), (*b).into());
+    a.total_cmp(&b)
+}
+
+/// Write a row for each of a sequence of values in its original order, one
+/// per line, made up of the columns produced for it separated by a separator.
+pub(crate) fn write_ordered<F>(f: &mut fmt::Formatter, vals: &[f64], sep: &KeyValueSep, mut columns: F) -> fmt::Result
+where
+    F: FnMut(f64) -> Vec<String>,
+{
+    for &val in vals {
+        for (i, c) in columns(val).iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", sep)?;
+            }
+
+            write!(f, "{}", c)?;
+        }
+
+        writeln!(f)?;
+    }
+
+    Ok(())
+}
+
+/// Statistics of all values and any percentile slices or quantiles of them.
+///
+/// With the `serde` feature, bundles can be serialized and deserialized using
+/// the same names for each field as the output of the command line tool.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StatisticsBundle {
     global: Statistics,
+    quantiles: Vec<Quantile>,
     percentiles: Vec<Statistics>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "sample_count", default, skip_serializing_if = "Option::is_none")
+    )]
+    sample_size: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    omitted: Vec<OmittedPercentile>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    omitted_quantiles: Vec<OmittedQuantile>,
 }
 
 impl StatisticsBundle {
@@ -61,7 +651,7 @@ impl StatisticsBundle {
     /// requirement that these values are sorted.
     ///
     /// This method returns `None` if the sequence of values is empty.
-    pub fn from(vals: &[f64]) -> Option<StatisticsBundle> {
+    pub fn from<V: Value>(vals: &[V]) -> Option<StatisticsBundle> {
         Self::with_percentiles(vals, &[])
     }
 
@@ -72,35 +662,408 @@ impl StatisticsBundle {
     ///
     /// This method returns `None` if the sequence of values is empty.
     /// Additionally, if there are not enough values to create all the
-    /// desired percentile slices (e.g. 90th percentile for a series of
-    /// only 7 values) the slices without enough values will be omitted.
-    pub fn with_percentiles(vals: &[f64], percentiles: &[u8]) -> Option<StatisticsBundle> {
+    /// desired percentile slices (e.g. 10th percentile for a series of
+    /// only 7 values) the slices without enough values will be omitted,
+    /// and are listed by `omitted_percentiles`.
+    pub fn with_percentiles<V: Value>(vals: &[V], percentiles: &[u8]) -> Option<StatisticsBundle> {
+        Self::with_quantiles(vals, percentiles, &[], QuantileMethod::default())
+    }
+
+    /// Create a statistics bundle from the values produced by an iterator
+    /// and a sequence of percentiles. The values are collected and sorted so
+    /// they don't need to be produced in order, and the result is the same
+    /// as the `with_percentiles` method for a sorted slice of them.
+    ///
+    /// This method returns `None` if the iterator doesn't produce any values.
+    ///
+    /// ```
+    /// use staccato::StatisticsBundle;
+    ///
+    /// let lines = ["7", "1", "oops", "3", "5"];
+    /// let bundle =
+    ///     StatisticsBundle::from_iter_with_percentiles(lines.iter().flat_map(|l| l.parse::<f64>()), &[50]).unwrap();
+    /// assert_eq!(4, bundle.global_stats().count());
+    /// assert_eq!(4f64, bundle.percentile_stats()[0].sum());
+    /// ```
+    pub fn from_iter_with_percentiles<I: IntoIterator<Item = f64>>(
+        iter: I,
+        percentiles: &[u8],
+    ) -> Option<StatisticsBundle> {
+        let mut vals: Vec<f64> = iter.into_iter().collect();
+        if !percentiles.is_empty() {
+            sort_values(&mut vals);
+        }
+
+        Self::with_percentiles(&vals, percentiles)
+    }
+
+    /// Create a statistics bundle from a **sorted** sequence of values, a
+    /// sequence of percentiles, and a sequence of quantiles (expressed as
+    /// percents from 0 to 100) whose values should be computed with the
+    /// given method.
+    ///
+    /// The values must be sorted or the statistics will be incorrect.
+    ///
+    /// This method returns `None` if the sequence of values is empty. Slices
+    /// for percentiles without enough values are omitted as described for
+    /// the `with_percentiles` method. Quantiles outside of the range 0 to 100
+    /// are omitted.
+    pub fn with_quantiles<V: Value>(
+        vals: &[V],
+        percentiles: &[u8],
+        quantiles: &[f64],
+        method: QuantileMethod,
+    ) -> Option<StatisticsBundle> {
+        Self::with_config(vals, percentiles, quantiles, method, StatisticsConfig::default())
+    }
+
+    /// Create a statistics bundle the same way as the `with_quantiles`
+    /// method, computing only the statistics selected by the config for all
+    /// values and each percentile slice. Statistics that aren't selected are
+    /// `NaN`, as described for `Statistics::from_with_config`.
+    pub fn with_config<V: Value>(
+        vals: &[V],
+        percentiles: &[u8],
+        quantiles: &[f64],
+        method: QuantileMethod,
+        config: StatisticsConfig,
+    ) -> Option<StatisticsBundle> {
+        let vals = without_nan(vals);
         if vals.is_empty() {
             return None;
         }
 
+        let quantile_vals = quantiles
+            .iter()
+            .flat_map(|&q| Quantile::from(&vals, q, method))
+            .collect();
+
         let percentile_stats = percentiles
             .iter()
-            .flat_map(|&p| Statistics::from(vals, Some(p)))
+            .flat_map(|&p| Statistics::from_with_config(&vals, Some(p), config))
             .collect();
 
-        Statistics::from(vals, None).map(|global| StatisticsBundle {
-            global,
-            percentiles: percentile_stats,
-        })
+        Statistics::from_with_config(&vals, None, config)
+            .map(|global| StatisticsBundle {
+                global,
+                quantiles: quantile_vals,
+                percentiles: percentile_stats,
+                sample_size: None,
+                omitted: Vec::new(),
+                omitted_quantiles: Vec::new(),
+            })
+            .map(|b| b.with_omitted(percentiles))
+    }
+
+    /// Create a statistics bundle from a sequence of values and a sequence
+    /// of percentiles, returning the sorted values along with it for later
+    /// queries (e.g. quantiles or ranks that weren't known in advance).
+    ///
+    /// The values are sorted in place and kept, without copying them.
+    /// This method returns `None` if the sequence of values is empty.
+    ///
+    /// ```
+    /// use staccato::StatisticsBundle;
+    ///
+    /// let (bundle, sorted) = StatisticsBundle::from_vec(vec![4.0, 1.0, 3.0, 2.0], &[50]).unwrap();
+    /// assert_eq!(4, bundle.global_stats().count());
+    /// assert_eq!(&[1.0, 2.0, 3.0, 4.0], sorted.as_slice());
+    /// assert_eq!(Some(87.5), sorted.rank_of(4.0));
+    /// ```
+    pub fn from_vec(vals: Vec<f64>, percentiles: &[u8]) -> Option<(StatisticsBundle, SortedVec<f64>)> {
+        let sorted = SortedVec::sort(vals);
+        Self::from_sorted(sorted.as_sorted(), percentiles, &[], QuantileMethod::default()).map(|b| (b, sorted))
+    }
+
+    /// Create a statistics bundle from a sorted sequence of values, a
+    /// sequence of percentiles, and a sequence of quantiles (expressed as
+    /// percents from 0 to 100) whose values should be computed with the
+    /// given method.
+    ///
+    /// This is the same as the `with_quantiles` method except the values
+    /// are guaranteed to be sorted, which allows statistics for the whole
+    /// sequence and all percentile slices to be computed in a single pass.
+    pub fn from_sorted<V: Value>(
+        sorted: SortedValues<V>,
+        percentiles: &[u8],
+        quantiles: &[f64],
+        method: QuantileMethod,
+    ) -> Option<StatisticsBundle> {
+        Self::from_sorted_with_config(sorted, percentiles, quantiles, method, StatisticsConfig::default())
+    }
+
+    /// Create a statistics bundle the same way as the `from_sorted` method,
+    /// computing only the statistics selected by the config for all values
+    /// and each percentile slice. Statistics that aren't selected are `NaN`,
+    /// and the standard deviation is only accumulated if it's selected.
+    ///
+    /// ```
+    /// use staccato::{QuantileMethod, SortedValues, StatisticsBundle, StatisticsConfig};
+    ///
+    /// let mut vals = vec![4.0, 1.0, 3.0, 2.0];
+    /// let sorted = SortedValues::sort(&mut vals);
+    /// let config = StatisticsConfig::none().with_mean(true);
+    /// let bundle =
+    ///     StatisticsBundle::from_sorted_with_config(sorted, &[50], &[], QuantileMethod::default(), config).unwrap();
+    /// assert_eq!(2.5, bundle.global_stats().mean());
+    /// assert!(bundle.global_stats().stddev().is_nan());
+    /// assert_eq!(1.5, bundle.percentile_stats()[0].mean());
+    /// ```
+    pub fn from_sorted_with_config<V: Value>(
+        sorted: SortedValues<V>,
+        percentiles: &[u8],
+        quantiles: &[f64],
+        method: QuantileMethod,
+        config: StatisticsConfig,
+    ) -> Option<StatisticsBundle> {
+        let sorted = SortedValues::new_unchecked(trim_nan(sorted.as_slice()));
+        if sorted.is_empty() {
+            return None;
+        }
+
+        let quantile_vals = quantiles
+            .iter()
+            .flat_map(|&q| Quantile::from(sorted.as_slice(), q, method))
+            .collect();
+
+        Statistics::from_sorted_percentiles(sorted, percentiles, config)
+            .map(|(global, percentile_stats)| StatisticsBundle {
+                global,
+                quantiles: quantile_vals,
+                percentiles: percentile_stats,
+                sample_size: None,
+                omitted: Vec::new(),
+                omitted_quantiles: Vec::new(),
+            })
+            .map(|b| b.with_omitted(percentiles))
+    }
+
+    /// Create a statistics bundle for a sequence without any values, whose
+    /// global statistics are `Statistics::empty` and which doesn't have any
+    /// percentile slices or quantiles, since none of them have enough values.
+    pub fn empty() -> StatisticsBundle {
+        StatisticsBundle {
+            global: Statistics::empty(),
+            quantiles: Vec::new(),
+            percentiles: Vec::new(),
+            sample_size: None,
+            omitted: Vec::new(),
+            omitted_quantiles: Vec::new(),
+        }
+    }
+
+    /// Record each of the requested percentiles that this bundle doesn't
+    /// have a slice of as omitted for not having enough values, and put the
+    /// slices in ascending order of percentile no matter what order they
+    /// were requested in.
+    pub(crate) fn with_omitted(mut self, requested: &[u8]) -> StatisticsBundle {
+        self.percentiles.sort_by_key(|s| s.percentile());
+        self.omitted = omitted_from(requested, self.percentiles());
+        self
+    }
+
+    /// Bundle without the percentile slices that have fewer than `min`
+    /// values, which are listed by `omitted_percentiles` instead, and
+    /// without any quantiles if there are fewer than `min` values in total,
+    /// which are listed by `omitted_quantiles`. Statistics of all values
+    /// are kept.
+    ///
+    /// ```
+    /// use staccato::{Omission, StatisticsBundle};
+    ///
+    /// let vals: Vec<f64> = (1..=20).map(f64::from).collect();
+    /// let bundle = StatisticsBundle::with_percentiles(&vals, &[10, 50]).unwrap().with_min_samples(5);
+    /// assert_eq!(Some(50), bundle.percentile_stats()[0].percentile());
+    /// assert_eq!(Omission::BelowMinSamples { min: 5 }, bundle.omitted_percentiles()[0].reason());
+    /// ```
+    pub fn with_min_samples(mut self, min: usize) -> StatisticsBundle {
+        let (kept, below): (Vec<Statistics>, Vec<Statistics>) =
+            self.percentiles.into_iter().partition(|s| s.count >= min);
+
+        self.percentiles = kept;
+        self.omitted.extend(
+            below
+                .iter()
+                .flat_map(|s| s.percentile)
+                .map(|p| OmittedPercentile::new(p, Omission::BelowMinSamples { min })),
+        );
+        self.omitted.sort_unstable_by_key(|o| o.percentile());
+
+        if self.global.count < min {
+            self.omitted_quantiles.extend(
+                self.quantiles
+                    .drain(..)
+                    .map(|q| OmittedQuantile::new(q.percent(), Omission::BelowMinSamples { min })),
+            );
+        }
+
+        self
+    }
+
+    /// Give the quantile of the given percent (if this bundle has it) a name
+    /// that it's displayed with instead of its percent, see `Quantile::key`.
+    ///
+    /// ```
+    /// use staccato::{QuantileMethod, StatisticsBundle};
+    ///
+    /// let bundle = StatisticsBundle::with_quantiles(&[1.0, 2.0, 3.0], &[], &[25.0, 50.0], QuantileMethod::Linear)
+    ///     .unwrap()
+    ///     .with_quantile_name(25.0, "q1");
+    /// let keys: Vec<String> = bundle.quantile_values().iter().map(|q| q.key()).collect();
+    /// assert_eq!(vec!["q1", "p50"], keys);
+    /// ```
+    pub fn with_quantile_name(mut self, percent: f64, name: &str) -> StatisticsBundle {
+        for q in self.quantiles.iter_mut().filter(|q| q.percent() == percent) {
+            *q = q.clone().with_name(name);
+        }
+
+        self
     }
 
     pub fn global_stats(&self) -> &Statistics {
         &self.global
     }
 
+    pub fn quantile_values(&self) -> &[Quantile] {
+        &self.quantiles
+    }
+
     pub fn percentile_stats(&self) -> &[Statistics] {
         &self.percentiles
     }
+
+    /// Statistics of the slice for the given percentile, or `None` if it
+    /// wasn't requested or was omitted because there weren't enough values.
+    ///
+    /// ```
+    /// use staccato::StatisticsBundle;
+    ///
+    /// let bundle = StatisticsBundle::with_percentiles(&[1.0, 2.0, 3.0, 4.0], &[50, 90]).unwrap();
+    /// assert_eq!(3f64, bundle.percentile_for(50).unwrap().sum());
+    /// assert!(bundle.percentile_for(99).is_none());
+    /// ```
+    pub fn percentile_for(&self, percentile: u8) -> Option<&Statistics> {
+        self.percentiles.iter().find(|s| s.percentile == Some(percentile))
+    }
+
+    /// Percentiles that slices were requested for but that this bundle
+    /// doesn't have, along with why each was omitted, in ascending order.
+    /// Computing a bundle never fails because of omitted slices, so this
+    /// should be checked to tell a missing slice from one that wasn't
+    /// requested.
+    pub fn omitted_percentiles(&self) -> &[OmittedPercentile] {
+        &self.omitted
+    }
+
+    /// Quantiles whose values were requested but that this bundle doesn't
+    /// have, along with why each was omitted, in the order they were
+    /// requested. Quantiles outside of the range 0 to 100 aren't included.
+    pub fn omitted_quantiles(&self) -> &[OmittedQuantile] {
+        &self.omitted_quantiles
+    }
+
+    /// Percentiles that this bundle has slices of, in the order of
+    /// `percentile_stats`.
+    pub fn percentiles(&self) -> impl Iterator<Item = u8> + '_ {
+        self.percentiles.iter().flat_map(|s| s.percentile)
+    }
+
+    /// True if the sum overflowed for the global statistics or for any
+    /// of the percentile slices, see `Statistics::overflowed`.
+    pub fn overflowed(&self) -> bool {
+        self.global.overflowed || self.percentiles.iter().any(|s| s.overflowed)
+    }
+
+    /// Bundle with a function applied to every statistic and quantile value
+    /// other than counts, e.g. to convert them to another unit.
+    pub(crate) fn map_values<F: Fn(f64) -> f64>(&self, f: F) -> StatisticsBundle {
+        StatisticsBundle {
+            global: self.global.map_values(&f),
+            quantiles: self.quantiles.iter().map(|q| q.map_value(&f)).collect(),
+            percentiles: self.percentiles.iter().map(|s| s.map_values(&f)).collect(),
+            sample_size: self.sample_size,
+            omitted: self.omitted.clone(),
+            omitted_quantiles: self.omitted_quantiles.clone(),
+        }
+    }
+
+    /// Number of values the median, quantiles, and percentile statistics
+    /// were estimated from if they were computed from a sample of the values
+    /// instead of all of them (e.g. by a `Reservoir`).
+    pub fn sample_size(&self) -> Option<usize> {
+        self.sample_size
+    }
+
+    /// True if both bundles have the same sample size, quantiles, and
+    /// percentile slices and every statistic and quantile value is within
+    /// `epsilon` of the other, the same way as `Statistics::approx_eq`.
+    pub fn approx_eq(&self, other: &StatisticsBundle, epsilon: f64) -> bool {
+        self.differences(other, epsilon).is_empty()
+    }
+
+    /// Keys of the statistics and quantiles that aren't within `epsilon` of
+    /// the other bundle's, named the same way as `KeyValueFormatter` displays
+    /// them (e.g. "mean_90" or "p99"), for assertion messages. Percentile
+    /// slices are matched by percentile, and a slice in only one bundle is
+    /// named like "percentile_90". Quantiles in only one bundle are included,
+    /// and "sample_count" is included if the sample sizes differ.
+    pub fn differences(&self, other: &StatisticsBundle, epsilon: f64) -> Vec<String> {
+        let mut out: Vec<String> = self
+            .global
+            .differences(&other.global, epsilon)
+            .into_iter()
+            .map(|n| n.to_string())
+            .collect();
+
+        if self.sample_size != other.sample_size {
+            out.push("sample_count".to_string());
+        }
+
+        for q in &self.quantiles {
+            match other.quantiles.iter().find(|o| o.key() == q.key()) {
+                Some(o) if approx_eq(q.value(), o.value(), epsilon) => {}
+                _ => out.push(q.key()),
+            }
+        }
+
+        for q in &other.quantiles {
+            if !self.quantiles.iter().any(|s| s.key() == q.key()) {
+                out.push(q.key());
+            }
+        }
+
+        for stats in &self.percentiles {
+            let p = stats.percentile().unwrap_or_default();
+            match stats.percentile().and_then(|p| other.percentile_for(p)) {
+                Some(o) => out.extend(
+                    stats
+                        .differences(o, epsilon)
+                        .into_iter()
+                        .map(|n| format!("{}_{}", n, p)),
+                ),
+                None => out.push(format!("percentile_{}", p)),
+            }
+        }
+
+        for stats in &other.percentiles {
+            if stats.percentile().and_then(|p| self.percentile_for(p)).is_none() {
+                out.push(format!("percentile_{}", stats.percentile().unwrap_or_default()));
+            }
+        }
+
+        out
+    }
 }
 
-#[derive(Debug, Clone)]
+/// Statistics of a sequence of values, or of the lowest percent of them.
+///
+/// `NaN` values (e.g. from lines of input like "nan") are ignored, as if
+/// they weren't part of the sequence, since they would make every statistic
+/// other than the count `NaN`. Infinite values are kept.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Statistics {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
     percentile: Option<u8>,
     count: usize,
     sum: f64,
@@ -109,14 +1072,59 @@ pub struct Statistics {
     lower: f64,
     median: f64,
     stddev: f64,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "std::ops::Not::not"))]
+    merged: bool,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "std::ops::Not::not"))]
+    overflowed: bool,
 }
 
 impl Statistics {
-    pub fn from(vals: &[f64], percentile: Option<u8>) -> Option<Statistics> {
+    /// Compute statistics of a sequence of values, or of the lowest given
+    /// percent of them. The values must be sorted for the percentile slice
+    /// to be correct.
+    ///
+    /// The values don't need to be sorted for the median to be correct. If
+    /// they aren't, the median is found by selection from a copy of the
+    /// values, which is O(n) instead of the O(n log n) of sorting them.
+    pub fn from<V: Value>(vals: &[V], percentile: Option<u8>) -> Option<Statistics> {
+        Self::from_with_config(vals, percentile, StatisticsConfig::default())
+    }
+
+    /// Compute statistics of the values produced by an iterator, e.g. one
+    /// that parses or converts values from some other type. The values are
+    /// collected and the result is the same as the `from` method for a
+    /// slice of them.
+    ///
+    /// This method returns `None` if the iterator doesn't produce any values.
+    ///
+    /// ```
+    /// use staccato::Statistics;
+    ///
+    /// let latencies_ms = [12u32, 15, 9, 30];
+    /// let stats = Statistics::from_iter(latencies_ms.iter().map(|&ms| f64::from(ms) / 1000f64)).unwrap();
+    /// assert_eq!(0.03, stats.upper());
+    /// ```
+    pub fn from_iter<I: IntoIterator<Item = f64>>(iter: I) -> Option<Statistics> {
+        let vals: Vec<f64> = iter.into_iter().collect();
+        Self::from(&vals, None)
+    }
+
+    /// Compute only the selected statistics of a sequence of values, or of
+    /// the lowest given percent of them, the same way as the `from` method.
+    ///
+    /// The median and standard deviation each need another pass over the
+    /// values, which is skipped when they aren't selected. Statistics that
+    /// aren't selected are `NaN`. The count is always computed.
+    pub fn from_with_config<V: Value>(
+        vals: &[V],
+        percentile: Option<u8>,
+        config: StatisticsConfig,
+    ) -> Option<Statistics> {
+        let vals = without_nan(vals);
         let filtered = if let Some(v) = percentile {
-            Self::slice_values(vals, v)
+            percentile_slice(&vals, v)
         } else {
-            vals
+            &vals
         };
 
         // Bail early when there are no values so that we don't have
@@ -128,44 +1136,179 @@ impl Statistics {
         }
 
         let (lower, upper, sum) = Self::compute_min_max_sum(filtered);
-        let mean = sum / count as f64;
-        let median = Self::compute_median(filtered);
-        let stddev = Self::compute_stddev(filtered, mean);
+        let overflowed = sum_overflowed(sum, lower, upper);
+        let mean = if overflowed {
+            Self::compute_mean_scaled(filtered)
+        } else {
+            sum / count as f64
+        };
+        let median = if config.median() {
+            Self::compute_median_unsorted(filtered)
+        } else {
+            f64::NAN
+        };
+        let stddev = if config.stddev() {
+            Self::compute_stddev(filtered, mean)
+        } else {
+            f64::NAN
+        };
 
+        let selected = |selected: bool, val: f64| if selected { val } else { f64::NAN };
         Some(Statistics {
             percentile,
             count,
-            sum,
-            mean,
-            upper,
-            lower,
+            sum: selected(config.sum(), sum),
+            mean: selected(config.mean(), mean),
+            upper: selected(config.upper(), upper),
+            lower: selected(config.lower(), lower),
             median,
             stddev,
+            merged: false,
+            overflowed,
         })
     }
 
-    pub fn percentile(&self) -> Option<u8> {
-        self.percentile
-    }
-
-    pub fn count(&self) -> usize {
-        self.count
+    /// These statistics with each statistic that isn't selected by the
+    /// config replaced by `NaN`. The count is always kept.
+    fn selected(self, config: StatisticsConfig) -> Statistics {
+        let selected = |selected: bool, val: f64| if selected { val } else { f64::NAN };
+        Statistics {
+            sum: selected(config.sum(), self.sum),
+            mean: selected(config.mean(), self.mean),
+            upper: selected(config.upper(), self.upper),
+            lower: selected(config.lower(), self.lower),
+            median: selected(config.median(), self.median),
+            stddev: selected(config.stddev(), self.stddev),
+            ..self
+        }
     }
 
-    pub fn sum(&self) -> f64 {
-        self.sum
-    }
+    /// Compute statistics of a sorted sequence of values, or of the lowest
+    /// given percent of them.
+    ///
+    /// Since the values are sorted, the lower and upper values are the first
+    /// and last values and the sum and standard deviation are computed in a
+    /// single pass instead of the separate passes required by the `from`
+    /// method.
+    pub fn from_sorted<V: Value>(sorted: SortedValues<V>, percentile: Option<u8>) -> Option<Statistics> {
+        let vals = trim_nan(sorted.as_slice());
+        let filtered = if let Some(v) = percentile {
+            percentile_slice(vals, v)
+        } else {
+            vals
+        };
 
-    pub fn mean(&self) -> f64 {
-        self.mean
-    }
+        if filtered.is_empty() {
+            return None;
+        }
 
-    pub fn upper(&self) -> f64 {
-        self.upper
+        let mut moments = RunningMoments::new(Self::compute_median(vals));
+        moments.push(filtered);
+        Some(moments.statistics(filtered, percentile))
     }
 
-    pub fn lower(&self) -> f64 {
-        self.lower
+    /// Compute statistics of a sorted sequence of values and of the lowest
+    /// percent of them for each of the given percentiles in a single pass.
+    ///
+    /// Each percentile slice is a prefix of the sorted values, so the sums
+    /// for all of them are accumulated while walking the values once instead
+    /// of scanning each slice separately. Results are the same as calling
+    /// `from_sorted` for the whole sequence and each percentile, other than
+    /// rounding error in the standard deviation.
+    ///
+    /// Only the statistics selected by the config are computed, as for
+    /// `from_with_config`. This method returns `None` if the sequence of
+    /// values is empty. Percentile statistics are in the order given,
+    /// omitting slices without any values.
+    fn from_sorted_percentiles<V: Value>(
+        sorted: SortedValues<V>,
+        percentiles: &[u8],
+        config: StatisticsConfig,
+    ) -> Option<(Statistics, Vec<Statistics>)> {
+        let vals = trim_nan(sorted.as_slice());
+        if vals.is_empty() {
+            return None;
+        }
+
+        let mut ends: Vec<(usize, usize)> = percentiles
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| (percentile_slice(vals, p).len(), i))
+            .collect();
+        ends.sort_unstable();
+
+        let mut moments = RunningMoments::with_variance(Self::compute_median(vals), config.stddev());
+        let mut slices: Vec<Option<Statistics>> = vec![None; percentiles.len()];
+        let mut start = 0;
+
+        for (end, i) in ends {
+            moments.push(&vals[start..end]);
+            start = end;
+
+            if end > 0 {
+                let stats = moments.statistics(&vals[0..end], Some(percentiles[i]));
+                slices[i] = Some(stats.selected(config));
+            }
+        }
+
+        moments.push(&vals[start..]);
+        let global = moments.statistics(vals, None).selected(config);
+        Some((global, slices.into_iter().flatten().collect()))
+    }
+
+    /// Create statistics for a sequence without any values, for callers that
+    /// need statistics of every sequence even when some of them are empty.
+    ///
+    /// The count and sum of no values are zero. Every other statistic is
+    /// undefined without any values so it's `NaN`, which `bounds` reports as
+    /// `None`.
+    ///
+    /// ```
+    /// use staccato::Statistics;
+    ///
+    /// let stats = Statistics::empty();
+    /// assert_eq!(0, stats.count());
+    /// assert_eq!(0f64, stats.sum());
+    /// assert!(stats.mean().is_nan());
+    /// assert_eq!(None, stats.bounds());
+    /// ```
+    pub fn empty() -> Statistics {
+        Statistics {
+            percentile: None,
+            count: 0,
+            sum: 0f64,
+            mean: f64::NAN,
+            upper: f64::NAN,
+            lower: f64::NAN,
+            median: f64::NAN,
+            stddev: f64::NAN,
+            merged: false,
+            overflowed: false,
+        }
+    }
+
+    pub fn percentile(&self) -> Option<u8> {
+        self.percentile
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    pub fn upper(&self) -> f64 {
+        self.upper
+    }
+
+    pub fn lower(&self) -> f64 {
+        self.lower
     }
 
     pub fn median(&self) -> f64 {
@@ -176,226 +1319,1656 @@ impl Statistics {
         self.stddev
     }
 
-    fn slice_values(vals: &[f64], percentile: u8) -> &[f64] {
-        let num_vals = vals.len();
-        let index = (percentile as usize * num_vals) / 100;
-        &vals[0..index]
+    /// Lowest and highest values, or `None` if there weren't any values.
+    pub fn bounds(&self) -> Option<(f64, f64)> {
+        if self.count == 0 {
+            None
+        } else {
+            Some((self.lower, self.upper))
+        }
+    }
+
+    /// Display these statistics with a separator between each key and value
+    /// and the given number of digits after the decimal point, instead of
+    /// the defaults used by `Display`.
+    ///
+    /// ```
+    /// use staccato::{KeyValueSep, Statistics};
+    ///
+    /// let stats = Statistics::from(&[1.0, 2.0], Some(50)).unwrap();
+    /// assert!(stats.format_with(KeyValueSep::Tab, 2).to_string().starts_with("count_50\t1\nsum_50\t1.00\n"));
+    /// ```
+    pub fn format_with(&self, sep: KeyValueSep, precision: usize) -> FormattedStatistics<'_> {
+        FormattedStatistics::new(self, KeyValueFormatter::with_sep(sep).precision(precision))
+    }
+
+    /// Value of a single statistic.
+    pub fn value(&self, name: StatName) -> StatValue {
+        match name {
+            StatName::Count => StatValue::Integer(self.count as u64),
+            StatName::Sum => StatValue::Float(self.sum),
+            StatName::Mean => StatValue::Float(self.mean),
+            StatName::Upper => StatValue::Float(self.upper),
+            StatName::Lower => StatValue::Float(self.lower),
+            StatName::Median => StatValue::Float(self.median),
+            StatName::Stddev => StatValue::Float(self.stddev),
+        }
+    }
+
+    /// Name and value of each statistic, in the order they're displayed.
+    ///
+    /// ```
+    /// use staccato::{StatName, StatValue, Statistics};
+    ///
+    /// let stats = Statistics::from(&[1.0, 2.0, 3.0], None).unwrap();
+    /// let mut iter = stats.iter();
+    /// assert_eq!(Some((StatName::Count, StatValue::Integer(3))), iter.next());
+    /// assert_eq!(Some((StatName::Sum, StatValue::Float(6.0))), iter.next());
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (StatName, StatValue)> + '_ {
+        StatName::ALL.iter().map(move |&n| (n, self.value(n)))
+    }
+
+    /// True if these statistics were created by merging statistics of
+    /// separate sequences of values, in which case the median is an estimate
+    /// and a percentile slice isn't exactly the lowest percent of all values.
+    pub fn is_merged(&self) -> bool {
+        self.merged
+    }
+
+    /// True if the sum of the values overflowed to infinity even though
+    /// every value is finite, e.g. for values close to `f64::MAX`.
+    ///
+    /// The sum is infinite in this case but the mean is still computed
+    /// from the values scaled down by the count, so it's accurate. The
+    /// standard deviation may be infinite too if the values are spread far
+    /// enough apart that their squared deviations overflow.
+    ///
+    /// ```
+    /// use staccato::Statistics;
+    ///
+    /// let stats = Statistics::from(&[f64::MAX, f64::MAX], None).unwrap();
+    /// assert!(stats.overflowed());
+    /// assert!(stats.sum().is_infinite());
+    /// assert_eq!(f64::MAX, stats.mean());
+    /// ```
+    pub fn overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    /// Statistics with a function applied to every value other than the
+    /// count, e.g. to convert them to another unit.
+    pub(crate) fn map_values<F: Fn(f64) -> f64>(&self, f: F) -> Statistics {
+        Statistics {
+            sum: f(self.sum),
+            mean: f(self.mean),
+            upper: f(self.upper),
+            lower: f(self.lower),
+            median: f(self.median),
+            stddev: f(self.stddev),
+            ..*self
+        }
+    }
+
+    /// True if both are statistics of the same percentile and every value is
+    /// within `epsilon` of the other.
+    ///
+    /// Values are compared relative to the larger of their magnitudes when it's
+    /// more than one, and absolutely otherwise, so that an epsilon like `1e-9`
+    /// is useful for large and small values alike. NaN values are equal to each
+    /// other, e.g. for statistics that weren't computed.
+    ///
+    /// ```
+    /// use staccato::Statistics;
+    ///
+    /// let a = Statistics::from(&[0.1, 0.2, 0.3], None).unwrap();
+    /// let b = Statistics::from(&[0.3, 0.2, 0.1], None).unwrap();
+    /// assert!(a.approx_eq(&b, 1e-12));
+    /// ```
+    pub fn approx_eq(&self, other: &Statistics, epsilon: f64) -> bool {
+        self.percentile == other.percentile && self.differences(other, epsilon).is_empty()
+    }
+
+    /// Statistics whose values aren't within `epsilon` of the other's, compared
+    /// the same way as `approx_eq`, e.g. for assertion messages. Counts must be
+    /// equal.
+    pub fn differences(&self, other: &Statistics, epsilon: f64) -> Vec<StatName> {
+        self.iter()
+            .zip(other.iter())
+            .filter(|((_, a), (_, b))| match (a, b) {
+                (StatValue::Integer(a), StatValue::Integer(b)) => a != b,
+                _ => !approx_eq(a.as_f64(), b.as_f64(), epsilon),
+            })
+            .map(|((name, _), _)| name)
+            .collect()
+    }
+
+    /// Median of values that must already be sorted, see `compute_median_unsorted`
+    /// for values in any order.
+    fn compute_median<V: Value>(vals: &[V]) -> f64 {
+        debug_assert!(
+            vals.windows(2)
+                .all(|w| compare_values(&w[0], &w[1]) != Ordering::Greater),
+            "values are not sorted"
+        );
+
+        let len = vals.len();
+        let is_odd = len % 2 == 1;
+
+        if is_odd {
+            let mid = len / 2;
+            return vals[mid].into();
+        }
+
+        let upper_med = len / 2;
+        let lower_med = upper_med - 1;
+        // It should never be the case that these indexes
+        // don't exist in the slice. If there's no entries
+        // we should have already just returned the default
+        // stats instance. If there's only one entry we
+        // would have handled that with the 'is_odd' case
+        // above. Otherwise this will do the right thing.
+        (vals[upper_med].into() + vals[lower_med].into()) / 2f64
+    }
+
+    /// Median of values in any order, selecting the middle values from a
+    /// copy instead of sorting when the values aren't already sorted.
+    fn compute_median_unsorted<V: Value>(vals: &[V]) -> f64 {
+        if vals
+            .windows(2)
+            .all(|w| compare_values(&w[0], &w[1]) != Ordering::Greater)
+        {
+            return Self::compute_median(vals);
+        }
+
+        let mut copy = vals.to_vec();
+        let len = copy.len();
+        let upper_med = len / 2;
+        let (below, &mut upper, _) = copy.select_nth_unstable_by(upper_med, compare_values);
+
+        if len % 2 == 1 {
+            return upper.into();
+        }
+
+        // The lower median is the largest of the values before the upper
+        // median, all of which are less than or equal to it after selecting.
+        let lower = below.iter().copied().max_by(compare_values).unwrap_or(upper);
+        (upper.into() + lower.into()) / 2f64
+    }
+
+    fn compute_min_max_sum<V: Value>(vals: &[V]) -> (f64, f64, f64) {
+        if vals.len() < MIN_LANES_LEN {
+            let (lower, upper, sum) = Self::compute_min_max_sum_scalar(vals);
+            return (lower, upper, sum.total());
+        }
+
+        let mut upper = [f64::MIN; LANES];
+        let mut lower = [f64::MAX; LANES];
+        let mut sum = [CompensatedSum::default(); LANES];
+
+        let chunks = vals.chunks_exact(LANES);
+        let rest = chunks.remainder();
+
+        for chunk in chunks {
+            for i in 0..LANES {
+                let val: f64 = chunk[i].into();
+                upper[i] = if val > upper[i] { val } else { upper[i] };
+                lower[i] = if val < lower[i] { val } else { lower[i] };
+                sum[i].add(val);
+            }
+        }
+
+        let (rest_lower, rest_upper, rest_sum) = Self::compute_min_max_sum_scalar(rest);
+        let upper = upper.iter().fold(rest_upper, |a, &b| if b > a { b } else { a });
+        let lower = lower.iter().fold(rest_lower, |a, &b| if b < a { b } else { a });
+        let sum = sum.iter().fold(rest_sum, |mut a, b| {
+            a.merge(b);
+            a
+        });
+
+        (lower, upper, sum.total())
+    }
+
+    fn compute_min_max_sum_scalar<V: Value>(vals: &[V]) -> (f64, f64, CompensatedSum) {
+        let mut upper = f64::MIN;
+        let mut lower = f64::MAX;
+        let mut sum = CompensatedSum::default();
+
+        // Compute min, max, and sum in the same method to avoid
+        // extra loops through all the values. Thus we only do two
+        // loops, this one and the standard deviation loop.
+        for &v in vals {
+            let val: f64 = v.into();
+            if val > upper {
+                upper = val;
+            }
+
+            if val < lower {
+                lower = val;
+            }
+
+            sum.add(val);
+        }
+
+        (lower, upper, sum)
+    }
+
+    /// Mean of values whose sum overflows, computed by summing each value
+    /// divided by the count instead of dividing the sum by the count.
+    fn compute_mean_scaled<V: Value>(vals: &[V]) -> f64 {
+        let num = vals.len() as f64;
+        let mut sum = CompensatedSum::default();
+        for &v in vals {
+            sum.add(v.into() / num);
+        }
+
+        sum.total()
+    }
+
+    fn compute_stddev<V: Value>(vals: &[V], mean: f64) -> f64 {
+        let num = vals.len() as f64;
+        let mut sums = [CompensatedSum::default(); LANES];
+
+        if vals.len() < MIN_LANES_LEN {
+            for &v in vals {
+                sums[0].add((v.into() - mean).powi(2));
+            }
+        } else {
+            let chunks = vals.chunks_exact(LANES);
+            let rest = chunks.remainder();
+
+            for chunk in chunks {
+                for i in 0..LANES {
+                    sums[i].add((chunk[i].into() - mean).powi(2));
+                }
+            }
+
+            for &v in rest {
+                sums[0].add((v.into() - mean).powi(2));
+            }
+        }
+
+        let sum_deviance = sums.iter().fold(CompensatedSum::default(), |mut a, b| {
+            a.merge(b);
+            a
+        });
+
+        let deviance = sum_deviance.total() / num;
+        deviance.sqrt()
+    }
+}
+
+/// Sum of a sequence of values using Neumaier's variant of Kahan summation,
+/// which keeps track of the low order bits lost when adding each value to
+/// the running total. The error of the result doesn't grow with the number
+/// of values, unlike simply adding them (e.g. adding many `1.0` values to
+/// `1e16` is exact instead of each `1.0` being lost to rounding).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) struct CompensatedSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl CompensatedSum {
+    pub(crate) fn add(&mut self, val: f64) {
+        let total = self.sum + val;
+        // Selecting the larger and smaller values instead of branching
+        // keeps this free of branches so that it can be vectorized.
+        let (larger, smaller) = if self.sum.abs() >= val.abs() {
+            (self.sum, val)
+        } else {
+            (val, self.sum)
+        };
+
+        self.compensation += (larger - total) + smaller;
+        self.sum = total;
+    }
+
+    pub(crate) fn merge(&mut self, other: &CompensatedSum) {
+        self.add(other.sum);
+        self.compensation += other.compensation;
+    }
+
+    pub(crate) fn total(&self) -> f64 {
+        // Once the sum is infinite the compensation is too (or `NaN`), with
+        // the opposite sign, so adding them would turn the sum into `NaN`.
+        if self.sum.is_finite() {
+            self.sum + self.compensation
+        } else {
+            self.sum
+        }
+    }
+}
+
+/// True if a sum is infinite (or `NaN`) even though the lowest and highest
+/// values summed are finite, meaning that adding them overflowed.
+pub(crate) fn sum_overflowed(sum: f64, lower: f64, upper: f64) -> bool {
+    !sum.is_finite() && lower.is_finite() && upper.is_finite()
+}
+
+/// Running sum and moments of a prefix of a sorted sequence of values, used
+/// to compute the sum and standard deviation of the prefix in a single pass.
+///
+/// The sum of squared deviations from the mean is updated with Welford's
+/// method for each value instead of being computed from the sum of squares
+/// of the values (`E[x^2] - E[x]^2`), which loses all precision when the mean
+/// is large compared to the deviation (e.g. timestamps). Values are shifted
+/// by something close to the mean (e.g. the median) before being pushed so
+/// that rounding error in the running mean stays small relative to the
+/// deviation as well.
+#[derive(Debug, Clone, Copy)]
+struct RunningMoments {
+    shift: f64,
+    count: usize,
+    sum: CompensatedSum,
+    mean: f64,
+    m2: f64,
+    variance: bool,
+}
+
+impl RunningMoments {
+    fn new(shift: f64) -> RunningMoments {
+        Self::with_variance(shift, true)
+    }
+
+    /// Moments that only accumulate the count and sum unless `variance` is
+    /// true, for when the standard deviation isn't needed.
+    fn with_variance(shift: f64, variance: bool) -> RunningMoments {
+        RunningMoments {
+            shift,
+            count: 0,
+            sum: CompensatedSum::default(),
+            mean: 0f64,
+            m2: 0f64,
+            variance,
+        }
+    }
+
+    fn push<V: Value>(&mut self, vals: &[V]) {
+        if vals.len() < MIN_LANES_LEN {
+            return self.push_scalar(vals);
+        }
+
+        let mut lanes = [RunningMoments::with_variance(self.shift, self.variance); LANES];
+        let chunks = vals.chunks_exact(LANES);
+        let rest = chunks.remainder();
+
+        for chunk in chunks {
+            for i in 0..LANES {
+                lanes[i].push_value(chunk[i].into());
+            }
+        }
+
+        for lane in &lanes {
+            self.merge(lane);
+        }
+
+        self.push_scalar(rest);
+    }
+
+    fn push_scalar<V: Value>(&mut self, vals: &[V]) {
+        for &v in vals {
+            self.push_value(v.into());
+        }
+    }
+
+    fn push_value(&mut self, val: f64) {
+        self.count += 1;
+        self.sum.add(val);
+
+        if self.variance {
+            let shifted = val - self.shift;
+            let delta = shifted - self.mean;
+            self.mean += delta / self.count as f64;
+            self.m2 += delta * (shifted - self.mean);
+        }
+    }
+
+    /// Combine the moments of another sequence of values, shifted by the
+    /// same amount, using the method of Chan et al.
+    fn merge(&mut self, other: &RunningMoments) {
+        debug_assert_eq!(self.shift.to_bits(), other.shift.to_bits());
+        if other.count == 0 {
+            return;
+        }
+
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        let weight = other.count as f64 / count as f64;
+
+        self.m2 += other.m2 + delta * delta * self.count as f64 * weight;
+        self.mean += delta * weight;
+        self.count = count;
+        self.sum.merge(&other.sum);
+    }
+
+    /// Statistics of the non-empty prefix of values pushed so far.
+    fn statistics<V: Value>(&self, vals: &[V], percentile: Option<u8>) -> Statistics {
+        let count = vals.len();
+        let stats = self.statistics_with(
+            percentile,
+            vals[0].into(),
+            vals[count - 1].into(),
+            Statistics::compute_median(vals),
+        );
+
+        // Without the running mean, the mean of values whose sum overflowed
+        // takes another pass over them.
+        if stats.overflowed && !self.variance {
+            Statistics {
+                mean: Statistics::compute_mean_scaled(vals),
+                ..stats
+            }
+        } else {
+            stats
+        }
+    }
+
+    /// Statistics of the non-empty prefix of values pushed so far given
+    /// the lowest, highest, and median values of the prefix.
+    fn statistics_with(&self, percentile: Option<u8>, lower: f64, upper: f64, median: f64) -> Statistics {
+        let sum = self.sum.total();
+        let overflowed = sum_overflowed(sum, lower, upper);
+        // The running mean is of the shifted values, which doesn't overflow
+        // even when the sum does.
+        let mean = if overflowed {
+            self.shift + self.mean
+        } else {
+            sum / self.count as f64
+        };
+
+        Statistics {
+            percentile,
+            count: self.count,
+            sum,
+            mean,
+            upper,
+            lower,
+            median,
+            stddev: if self.variance {
+                (self.m2 / self.count as f64).sqrt()
+            } else {
+                f64::NAN
+            },
+            merged: false,
+            overflowed,
+        }
+    }
+}
+
+/// Separator written between each key and value, e.g. "mean: 2.5".
+///
+/// Separators are parsed from keywords (e.g. "tab" or "equals") or from
+/// any other string, which may contain escape sequences. Strings that are
+/// the same as a keyword's separator parse to that separator, so "=" is
+/// the same as "equals" and ":" is the same as "colon".
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Default)]
+pub enum KeyValueSep {
+    /// A tab character.
+    Tab,
+    /// A colon followed by a space, the default.
+    #[default]
+    Colon,
+    /// A space.
+    Space,
+    /// An equals sign, without spaces around it.
+    Equals,
+    /// A comma, without a space after it.
+    Comma,
+    /// No separator at all.
+    None,
+    Other(String),
+}
+
+impl KeyValueSep {
+    /// Separators that have a keyword, in the order of `keyword`.
+    const NAMED: &'static [KeyValueSep] = &[
+        KeyValueSep::Tab,
+        KeyValueSep::Colon,
+        KeyValueSep::Space,
+        KeyValueSep::Equals,
+        KeyValueSep::Comma,
+        KeyValueSep::None,
+    ];
+
+    fn get_sep(&self) -> &str {
+        match *self {
+            KeyValueSep::Tab => "\t",
+            KeyValueSep::Colon => ": ",
+            KeyValueSep::Space => " ",
+            KeyValueSep::Equals => "=",
+            KeyValueSep::Comma => ",",
+            KeyValueSep::None => "",
+            KeyValueSep::Other(ref s) => s,
+        }
+    }
+
+    /// Keyword that parses to this separator, e.g. "tab", or `None` for
+    /// other separators.
+    pub fn keyword(&self) -> Option<&'static str> {
+        match *self {
+            KeyValueSep::Tab => Some("tab"),
+            KeyValueSep::Colon => Some("colon"),
+            KeyValueSep::Space => Some("space"),
+            KeyValueSep::Equals => Some("equals"),
+            KeyValueSep::Comma => Some("comma"),
+            KeyValueSep::None => Some("none"),
+            KeyValueSep::Other(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for KeyValueSep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.get_sep().fmt(f)
+    }
+}
+
+impl FromStr for KeyValueSep {
+    type Err = StaccatoError;
+
+    fn from_str(s: &str) -> Result<KeyValueSep, Self::Err> {
+        if let Some(sep) = Self::NAMED.iter().find(|n| n.keyword() == Some(s)) {
+            return Ok(sep.clone());
+        }
+
+        if s.is_empty() {
+            return Err(StaccatoError::InvalidSeparator);
+        }
+
+        let sep = unescape_separator(s)?;
+        // A colon without the space is still the usual colon separator
+        let named = if sep == ":" {
+            Some(&KeyValueSep::Colon)
+        } else {
+            Self::NAMED.iter().find(|n| n.get_sep() == sep)
+        };
+
+        Ok(named.cloned().unwrap_or(KeyValueSep::Other(sep)))
+    }
+}
+
+/// Replace the escape sequences `\t`, `\n`, `\0`, and `\\` in a separator with
+/// the characters they stand for. Any other backslash is an error.
+fn unescape_separator(s: &str) -> Result<String, StaccatoError> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('0') => out.push('\0'),
+            Some('\\') => out.push('\\'),
+            _ => {
+                return Err(StaccatoError::InvalidEscape { value: s.to_string() });
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Format a bundle as key/value pairs using a `KeyValueFormatter`, e.g. to
+/// print it with `{}`.
+#[derive(Debug)]
+pub struct StatisticsFormatter<'a> {
+    bundle: &'a StatisticsBundle,
+    format: KeyValueFormatter,
+}
+
+impl<'a> StatisticsFormatter<'a> {
+    pub fn new(bundle: &'a StatisticsBundle) -> StatisticsFormatter<'a> {
+        Self::with_sep(bundle, KeyValueSep::Colon)
+    }
+
+    pub fn with_sep(bundle: &'a StatisticsBundle, sep: KeyValueSep) -> StatisticsFormatter<'a> {
+        StatisticsFormatter {
+            bundle,
+            format: KeyValueFormatter::with_sep(sep),
+        }
+    }
+
+    /// Number of digits to display after the decimal point, `DISPLAY_PRECISION`
+    /// by default.
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.format = self.format.precision(precision);
+        self
+    }
+
+    /// Remove zeros at the end of each value after formatting it with the
+    /// precision, e.g. display "2.5" instead of "2.50000". Off by default.
+    pub fn trim_trailing_zeros(mut self, trim: bool) -> Self {
+        self.format = self.format.trim_trailing_zeros(trim);
+        self
+    }
+
+    /// Round every value other than counts to an integer, which is then
+    /// displayed without a decimal point regardless of the precision. Not
+    /// rounded by default.
+    pub fn rounding(mut self, rounding: Rounding) -> Self {
+        self.format = self.format.rounding(rounding);
+        self
+    }
+
+    /// Only display the statistics selected by the config for all values
+    /// and each percentile slice. Quantiles are always displayed.
+    pub fn config(mut self, config: StatisticsConfig) -> Self {
+        self.format = self.format.config(config);
+        self
+    }
+
+    /// Naming style of each key, snake case by default.
+    pub fn key_case(mut self, key_case: KeyCase) -> Self {
+        self.format = self.format.key_case(key_case);
+        self
+    }
+
+    /// Write the formatted statistics to an `io::Write` implementation such as
+    /// standard output, returning any error from the writer (e.g. a closed pipe)
+    /// instead of panicking.
+    pub fn write_to<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "{}", self)
+    }
+}
+
+impl<'a> fmt::Display for StatisticsFormatter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.format.format(self.bundle, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "rayon")]
+    use super::sort_values_parallel;
+    use super::{
+        approx_eq, check_sorted, for_each_line, for_each_line_in_slice, get_parsed_values, get_parsed_values_with,
+        get_parsed_values_with_capacity, get_values, get_values_as, get_values_from_slice, get_values_with_capacity,
+        merge_sorted_values, percentile_slice, sort_values, CompensatedSum, FieldExtractor, KeyValueSep, Omission,
+        OmittedPercentile, OmittedQuantile, QuantileMethod, SortedValues, SortingPolicy, StaccatoError, StatName,
+        StatValue, Statistics, StatisticsBundle, StatisticsConfig, StatisticsFormatter, StreamingStatistics,
+        VERSION_INFO,
+    };
+    use std::io::{self, Cursor};
+
+    const VALUES: &[f64] = &[1f64, 2f64, 5f64, 7f64, 9f64, 12f64];
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_statistics_bundle_serde_round_trip() {
+        let bundle = StatisticsBundle::from(VALUES).unwrap();
+        let json = serde_json::to_string(&bundle).unwrap();
+
+        assert_eq!(bundle, serde_json::from_str::<StatisticsBundle>(&json).unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_statistics_bundle_serde_round_trip_percentiles() {
+        let bundle = StatisticsBundle::with_quantiles(VALUES, &[50, 90], &[99f64], QuantileMethod::Linear).unwrap();
+        let json = serde_json::to_string(&bundle).unwrap();
+
+        assert_eq!(bundle, serde_json::from_str::<StatisticsBundle>(&json).unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_statistics_bundle_serde_field_names() {
+        let bundle = StatisticsBundle::with_quantiles(VALUES, &[50], &[99f64], QuantileMethod::Linear).unwrap();
+        let json = serde_json::to_value(&bundle).unwrap();
+
+        let global = &json["global"];
+        for key in &["count", "sum", "mean", "upper", "lower", "median", "stddev"] {
+            assert!(global.get(key).is_some(), "missing {}", key);
+        }
+
+        assert!(global.get("percentile").is_none());
+        assert!(global.get("merged").is_none());
+        assert!(json.get("sample_count").is_none());
+        assert_eq!(50, json["percentiles"][0]["percentile"]);
+        assert_eq!(99f64, json["quantiles"][0]["percent"]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_statistics_bundle_serde_sample_count() {
+        let mut bundle = StatisticsBundle::from(VALUES).unwrap();
+        bundle.sample_size = Some(3);
+        let json = serde_json::to_value(&bundle).unwrap();
+
+        assert_eq!(3, json["sample_count"]);
+        assert_eq!(bundle, serde_json::from_value::<StatisticsBundle>(json).unwrap());
+    }
+
+    const SINGLE: &[f64] = &[13f64];
+
+    const EMPTY: &[f64] = &[];
+
+    #[test]
+    fn test_get_values_filter_invalids() {
+        let bytes: Vec<u8> = ["asdf\n", "4.5\n", "xyz\n"]
+            .iter()
+            .flat_map(|v| v.as_bytes())
+            .copied()
+            .collect();
+
+        let mut reader = Cursor::new(bytes);
+        assert_eq!(vec![4.5], get_values(&mut reader, SortingPolicy::Sorted).unwrap());
+    }
+
+    #[test]
+    fn test_get_values_ordered() {
+        let bytes: Vec<u8> = ["9.8\n", "4.5\n", "5.6\n"]
+            .iter()
+            .flat_map(|v| v.as_bytes())
+            .copied()
+            .collect();
+
+        let mut reader = Cursor::new(bytes);
+        assert_eq!(
+            vec![4.5, 5.6, 9.8],
+            get_values(&mut reader, SortingPolicy::Sorted).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_values_auto_sorted() {
+        let mut reader = Cursor::new("9.8\n4.5\n5.6\n");
+        assert_eq!(
+            vec![4.5, 5.6, 9.8],
+            get_values(&mut reader, SortingPolicy::Auto).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_values_already_sorted() {
+        let mut reader = Cursor::new("1\n2\n2\n7\n");
+        assert_eq!(
+            vec![1f64, 2f64, 2f64, 7f64],
+            get_values(&mut reader, SortingPolicy::AlreadySorted).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_values_already_sorted_out_of_order() {
+        let mut reader = Cursor::new("1\n2\n4\n3\n5\n");
+        let res = get_values(&mut reader, SortingPolicy::AlreadySorted);
+
+        assert!(
+            matches!(res, Err(StaccatoError::NotSorted { position: 4 })),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_check_sorted() {
+        assert!(check_sorted::<f64>(&[]).is_ok());
+        assert!(check_sorted(&[1.0]).is_ok());
+        assert!(check_sorted(&[-1.0, 0.0, 0.0, 7.5]).is_ok());
+        assert!(matches!(
+            check_sorted(&[2.0, 1.0]),
+            Err(StaccatoError::NotSorted { position: 2 })
+        ));
+        assert!(matches!(
+            check_sorted(&[1, 2, 3, 3, 2]),
+            Err(StaccatoError::NotSorted { position: 5 })
+        ));
+    }
+
+    #[test]
+    fn test_sorting_policy_resolve() {
+        let config = StatisticsConfig::new();
+        assert_eq!(SortingPolicy::Sorted, SortingPolicy::Auto.resolve(config));
+        assert_eq!(
+            SortingPolicy::Unsorted,
+            SortingPolicy::Auto.resolve(config.with_median(false))
+        );
+        assert_eq!(SortingPolicy::Unsorted, SortingPolicy::Unsorted.resolve(config));
+        assert_eq!(
+            SortingPolicy::AlreadySorted,
+            SortingPolicy::AlreadySorted.resolve(config.with_median(false))
+        );
+
+        assert!(SortingPolicy::Auto.is_sorted());
+        assert!(SortingPolicy::AlreadySorted.is_sorted());
+        assert!(!SortingPolicy::Unsorted.is_sorted());
+    }
+
+    #[test]
+    fn test_get_values_trim_whitespace() {
+        let bytes: Vec<u8> = ["9.8   \n", "4.5 \n", "5.6\t\n"]
+            .iter()
+            .flat_map(|v| v.as_bytes())
+            .copied()
+            .collect();
+
+        let mut reader = Cursor::new(bytes);
+        assert_eq!(
+            vec![4.5, 5.6, 9.8],
+            get_values(&mut reader, SortingPolicy::Sorted).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_values_with_capacity() {
+        let mut reader = Cursor::new("9.8\nfoo\n4.5\n5.6\n");
+        let vals = get_values_with_capacity(&mut reader, SortingPolicy::Unsorted, 100).unwrap();
+
+        assert_eq!(vec![9.8, 4.5, 5.6], vals);
+        assert!(vals.capacity() >= 100);
+    }
+
+    #[test]
+    fn test_get_parsed_values_sorted() {
+        let mut reader = Cursor::new("9.8\nfoo\n\n4.5\n5.6\n");
+        let parsed = get_parsed_values(&mut reader, SortingPolicy::Sorted).unwrap();
+
+        assert_eq!(&[4.5, 5.6, 9.8], parsed.values());
+        assert_eq!(5, parsed.total_lines());
+        assert_eq!(1, parsed.malformed());
+        assert_eq!(1, parsed.blank());
+        assert_eq!(2, parsed.first_errors()[0].line());
+        assert_eq!(4, parsed.first_errors()[0].offset());
+        assert_eq!("foo", parsed.first_errors()[0].content());
+    }
+
+    #[test]
+    fn test_get_parsed_values_with_capacity() {
+        let mut reader = Cursor::new("9.8\nfoo\n\n4.5\n");
+        let parsed = get_parsed_values_with_capacity(&mut reader, SortingPolicy::Unsorted, 100).unwrap();
+
+        assert_eq!(&[9.8, 4.5], parsed.values());
+        assert_eq!(1, parsed.malformed());
+        assert_eq!(1, parsed.blank());
+        assert!(parsed.into_values().capacity() >= 100);
+    }
+
+    #[test]
+    fn test_get_parsed_values_with_extractor() {
+        let mut reader = Cursor::new("a 9.8\nb\nc 4.5\n");
+        let extractor = FieldExtractor::new(2).unwrap();
+        let parsed = get_parsed_values_with(&mut reader, SortingPolicy::Sorted, extractor).unwrap();
+
+        assert_eq!(&[4.5, 9.8], parsed.values());
+        assert_eq!(3, parsed.total_lines());
+        assert_eq!(1, parsed.malformed());
+    }
+
+    #[test]
+    fn test_get_values_unordered() {
+        let bytes: Vec<u8> = ["9.8\n", "4.5\n", "5.6\n"]
+            .iter()
+            .flat_map(|v| v.as_bytes())
+            .copied()
+            .collect();
+
+        let mut reader = Cursor::new(bytes);
+        assert_eq!(
+            vec![9.8, 4.5, 5.6],
+            get_values(&mut reader, SortingPolicy::Unsorted).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_values_line_endings() {
+        let mut reader = Cursor::new("9.8\r\n4.5\n\n5.6".as_bytes());
+        assert_eq!(
+            vec![9.8, 4.5, 5.6],
+            get_values(&mut reader, SortingPolicy::Unsorted).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_values_invalid_utf8() {
+        let mut reader = Cursor::new(vec![b'1', b'\n', 0xff, b'\n']);
+        assert!(matches!(
+            get_values(&mut reader, SortingPolicy::Unsorted),
+            Err(StaccatoError::Utf8 { line: 2 })
+        ));
+    }
+
+    const LINE_INPUTS: &[&str] = &["", "a", "a\n", "a\r\nb", "a\n\nb\n", "a\r", "\r\n\r\n", "a\rb\n", "\n"];
+
+    #[test]
+    fn test_for_each_line_same_as_lines() {
+        for input in LINE_INPUTS {
+            let mut lines = Vec::new();
+            for_each_line(&mut input.as_bytes(), |l| lines.push(l.to_string())).unwrap();
+
+            assert_eq!(input.lines().collect::<Vec<&str>>(), lines, "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_for_each_line_in_slice_same_as_lines() {
+        for input in LINE_INPUTS {
+            let mut lines = Vec::new();
+            for_each_line_in_slice(input.as_bytes(), |l| lines.push(l.to_string())).unwrap();
+
+            assert_eq!(input.lines().collect::<Vec<&str>>(), lines, "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_get_values_from_slice_same_as_reader() {
+        let input = "9.8\r\nasdf\n 4.5 \n\n5.6";
+        assert_eq!(
+            get_values(&mut input.as_bytes(), SortingPolicy::Sorted).unwrap(),
+            get_values_from_slice(input.as_bytes(), SortingPolicy::Sorted).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_values_from_slice_invalid_utf8() {
+        assert!(matches!(
+            get_values_from_slice(&[b'1', b'\n', 0xff, b'\n'], SortingPolicy::Unsorted),
+            Err(StaccatoError::Utf8 { line: 2 })
+        ));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_sort_values_parallel_same_as_serial() {
+        let mut state = 17u64;
+        let vals: Vec<f64> = (0..100_000)
+            .map(|i| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let v = (state >> 40) as f64 / 1000f64;
+                if i % 3 == 0 {
+                    -v
+                } else if i % 101 == 0 {
+                    -0f64
+                } else {
+                    v
+                }
+            })
+            .collect();
+
+        let mut serial = vals.clone();
+        sort_values(&mut serial);
+
+        for &threads in &[1, 2, 4] {
+            let mut parallel = vals.clone();
+            sort_values_parallel(&mut parallel, threads);
+
+            let bits = |v: &[f64]| v.iter().map(|f| f.to_bits()).collect::<Vec<u64>>();
+            assert_eq!(bits(&serial), bits(&parallel));
+        }
+    }
+
+    #[test]
+    fn test_sort_values_total_ordering() {
+        let mut vals = vec![0f64, 3f64, -0f64, f64::NAN, -1f64, 0f64, -f64::NAN, -0f64];
+        sort_values(&mut vals);
+
+        let bits = |v: &[f64]| v.iter().map(|f| f.to_bits()).collect::<Vec<u64>>();
+        assert_eq!(
+            bits(&[-f64::NAN, -1f64, -0f64, -0f64, 0f64, 0f64, 3f64, f64::NAN]),
+            bits(&vals)
+        );
+    }
+
+    #[test]
+    fn test_merge_sorted_values_same_as_sorting() {
+        let seqs = vec![
+            vec![1f64, 4f64, 4f64, 9f64],
+            vec![],
+            vec![-0f64, 2f64, 4f64, 10f64],
+            vec![0f64, 3f64],
+        ];
+
+        let mut expected: Vec<f64> = seqs.concat();
+        sort_values(&mut expected);
+
+        let bits = |v: &[f64]| v.iter().map(|f| f.to_bits()).collect::<Vec<u64>>();
+        assert_eq!(bits(&expected), bits(&merge_sorted_values(&seqs)));
+    }
+
+    #[test]
+    fn test_merge_sorted_values_empty() {
+        assert!(merge_sorted_values(&[]).is_empty());
+        assert!(merge_sorted_values(&[vec![], vec![]]).is_empty());
+    }
+
+    #[test]
+    fn test_statistics_f32_within_tolerance_of_f64() {
+        let fixtures = [
+            include_str!("../../benches/values-small.log"),
+            include_str!("../../benches/values-med.log"),
+            include_str!("../../benches/values-large.log"),
+        ];
+
+        for fixture in &fixtures {
+            let wide: Vec<f64> = get_values_as(&mut fixture.as_bytes(), SortingPolicy::Sorted).unwrap();
+            let narrow: Vec<f32> = get_values_as(&mut fixture.as_bytes(), SortingPolicy::Sorted).unwrap();
+
+            let a = StatisticsBundle::with_quantiles(&wide, &[50, 90], &[99f64], QuantileMethod::Linear).unwrap();
+            let b = StatisticsBundle::with_quantiles(&narrow, &[50, 90], &[99f64], QuantileMethod::Linear).unwrap();
+
+            let diff = a.differences(&b, 1e-6);
+            assert!(diff.is_empty(), "{:?}", diff);
+        }
+    }
+
+    #[test]
+    fn test_statistics_from_sorted_same_as_from() {
+        let sorted = SortedValues::new(VALUES).unwrap();
+        for &p in &[None, Some(50), Some(90), Some(1)] {
+            let a = Statistics::from(VALUES, p);
+            let b = Statistics::from_sorted(sorted, p);
+
+            assert_eq!(a.is_some(), b.is_some());
+            if let (Some(a), Some(b)) = (a, b) {
+                assert_eq!(a.percentile(), b.percentile());
+                assert_eq!(a.count(), b.count());
+                assert_eq!(a.sum(), b.sum());
+                assert_eq!(a.mean(), b.mean());
+                assert_eq!(a.upper(), b.upper());
+                assert_eq!(a.lower(), b.lower());
+                assert_eq!(a.median(), b.median());
+                assert!((a.stddev() - b.stddev()).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_statistics_from_sorted_large_offset() {
+        let vals: Vec<f64> = VALUES.iter().map(|v| v + 1e9).collect();
+        let a = Statistics::from(VALUES, None).unwrap();
+        let b = Statistics::from_sorted(SortedValues::new(&vals).unwrap(), None).unwrap();
+
+        assert!((a.stddev() - b.stddev()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_statistics_bundle_from_sorted_same_as_each_percentile() {
+        let vals: Vec<f64> = (0..1000).map(|i| ((i * 7919) % 1000) as f64 / 7f64 + 1e6).collect();
+        let mut sorted_vals = vals.clone();
+        let sorted = SortedValues::sort(&mut sorted_vals);
+        let percentiles = &[90, 50, 0, 99, 50, 1, 100];
+
+        let bundle = StatisticsBundle::from_sorted(sorted, percentiles, &[], QuantileMethod::Linear).unwrap();
+        let mut expected: Vec<Statistics> = percentiles
+            .iter()
+            .flat_map(|&p| Statistics::from_sorted(sorted, Some(p)))
+            .collect();
+        expected.sort_by_key(|s| s.percentile());
+
+        let fields = |s: &Statistics| {
+            (
+                s.percentile(),
+                s.count(),
+                s.sum().to_bits(),
+                s.mean().to_bits(),
+                s.upper().to_bits(),
+                s.lower().to_bits(),
+                s.median().to_bits(),
+            )
+        };
+        let assert_stddev = |expected: &Statistics, actual: &Statistics| {
+            let (a, b) = (expected.stddev(), actual.stddev());
+            assert!(((a - b) / a).abs() < 1e-12, "{} != {}", a, b);
+        };
+
+        let global = Statistics::from_sorted(sorted, None).unwrap();
+        assert_eq!(fields(&global), fields(bundle.global_stats()));
+        assert_stddev(&global, bundle.global_stats());
+        assert_eq!(
+            expected.iter().map(fields).collect::<Vec<_>>(),
+            bundle.percentile_stats().iter().map(fields).collect::<Vec<_>>()
+        );
+        for (a, b) in expected.iter().zip(bundle.percentile_stats()) {
+            assert_stddev(a, b);
+        }
+    }
+
+    #[test]
+    fn test_statistics_median_unsorted_same_as_sorted() {
+        let mut state = 29u64;
+        let mut random = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 44) as f64 / 100f64
+        };
+
+        let mut inputs: Vec<Vec<f64>> = vec![
+            (0..1001).map(|_| random()).collect(),
+            (0..1000).map(|_| random()).collect(),
+            (0..1000).map(|i| (i % 3) as f64).collect(),
+            (0..999).map(|i| (999 - i) as f64).collect(),
+            (0..1000).map(|i| (500 - (i as i64 - 500).abs()) as f64).collect(),
+            vec![7f64; 100],
+            vec![2f64, 1f64],
+            vec![0f64, -0f64, 0f64, -0f64],
+        ];
+        inputs.push(inputs[0].iter().rev().copied().collect());
+
+        for vals in inputs {
+            let mut sorted = vals.clone();
+            sort_values(&mut sorted);
+
+            let expected = Statistics::from(&sorted, None).unwrap().median();
+            let actual = Statistics::from(&vals, None).unwrap().median();
+            assert_eq!(expected.to_bits(), actual.to_bits(), "{} != {}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_statistics_lanes_same_as_scalar() {
+        for &len in &[63usize, 64, 65, 1000, 1003] {
+            let vals: Vec<f64> = (0..len).map(|i| ((i * 7919) % 1009) as f64 / 3f64 - 100f64).collect();
+            let stats = Statistics::from(&vals, None).unwrap();
+
+            let mut sum = 0f64;
+            for &v in &vals {
+                sum += v;
+            }
+            let mean = sum / len as f64;
+            let stddev = (vals.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / len as f64).sqrt();
+
+            assert!((sum - stats.sum()).abs() <= 1e-12 * sum.abs().max(1f64));
+            assert!((stddev - stats.stddev()).abs() <= 1e-12 * stddev);
+            assert_eq!(-100f64, stats.lower());
+            assert_eq!(vals.iter().cloned().fold(f64::MIN, f64::max), stats.upper());
+        }
+    }
+
+    #[test]
+    fn test_compensated_sum_many_small_values() {
+        let mut naive = 1e16;
+        let mut sum = CompensatedSum::default();
+        sum.add(1e16);
+
+        for _ in 0..1000 {
+            naive += 1f64;
+            sum.add(1f64);
+        }
+
+        assert_eq!(1e16, naive);
+        assert_eq!(1e16 + 1000f64, sum.total());
+    }
+
+    #[test]
+    fn test_compensated_sum_cancellation() {
+        let mut sum = CompensatedSum::default();
+        for &v in &[1f64, 1e100, 1f64, -1e100] {
+            sum.add(v);
+        }
+
+        assert_eq!(2f64, sum.total());
+    }
+
+    #[test]
+    fn test_statistics_sum_compensated() {
+        for &len in &[10usize, 1000, 1003] {
+            let mut vals = vec![1e16];
+            vals.extend(std::iter::repeat_n(1f64, len));
+
+            let expected = 1e16 + len as f64;
+            assert_eq!(expected, Statistics::from(&vals, None).unwrap().sum());
+
+            sort_values(&mut vals);
+            let sorted = SortedValues::new(&vals).unwrap();
+            assert_eq!(expected, Statistics::from_sorted(sorted, None).unwrap().sum());
+        }
+    }
+
+    #[test]
+    fn test_statistics_stddev_compensated() {
+        // Deviations of 1e8 and -1e8 squared are 1e16 which would lose
+        // each of the squared deviations of 1 if added naively.
+        let mut vals = vec![-1e8, 1e8];
+        vals.extend((0..2000).map(|i| if i % 2 == 0 { 1f64 } else { -1f64 }));
+
+        let num = vals.len() as f64;
+        let expected = ((2e16 + 2000f64) / num).sqrt();
+        assert_eq!(expected, Statistics::from(&vals, None).unwrap().stddev());
+    }
+
+    #[test]
+    fn test_statistics_stddev_timestamps() {
+        let mut state = 11u64;
+        let mut vals: Vec<f64> = (0..10_000)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                1_600_000_000f64 + (state >> 11) as f64 / (1u64 << 53) as f64
+            })
+            .collect();
+        sort_values(&mut vals);
+
+        let two_pass = |vals: &[f64]| {
+            let mean = vals.iter().sum::<f64>() / vals.len() as f64;
+            (vals.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / vals.len() as f64).sqrt()
+        };
+        let assert_close = |expected: f64, actual: f64| {
+            assert!(
+                ((expected - actual) / expected).abs() < 1e-9,
+                "{} != {}",
+                expected,
+                actual
+            );
+        };
+
+        let sorted = SortedValues::new(&vals).unwrap();
+        let bundle = StatisticsBundle::from_sorted(sorted, &[50], &[], QuantileMethod::Linear).unwrap();
+        let mut streaming = StreamingStatistics::new();
+        for &v in &vals {
+            streaming.push(v);
+        }
+
+        assert_close(two_pass(&vals), Statistics::from(&vals, None).unwrap().stddev());
+        assert_close(two_pass(&vals), bundle.global_stats().stddev());
+        assert_close(two_pass(&vals[..5000]), bundle.percentile_stats()[0].stddev());
+        assert_close(two_pass(&vals), streaming.finalize().unwrap().stddev());
+    }
+
+    #[test]
+    fn test_statistics_from_sorted_empty() {
+        assert!(Statistics::from_sorted(SortedValues::new(EMPTY).unwrap(), None).is_none());
+    }
+
+    #[test]
+    fn test_statistics_bundle_from_sorted() {
+        let sorted = SortedValues::new(VALUES).unwrap();
+        let bundle = StatisticsBundle::from_sorted(sorted, &[50, 75], &[90f64], QuantileMethod::Linear).unwrap();
+        let expected = StatisticsBundle::with_quantiles(VALUES, &[50, 75], &[90f64], QuantileMethod::Linear).unwrap();
+
+        assert_eq!(
+            format!("{}", StatisticsFormatter::new(&expected)),
+            format!("{}", StatisticsFormatter::new(&bundle))
+        );
+    }
+
+    /// Values with `NaN` at the start, in the middle, and at the end, along
+    /// with the same values without them.
+    fn with_nans() -> (Vec<f64>, Vec<f64>) {
+        let clean = vec![4f64, 1f64, 7f64, 3f64, 9f64, 2f64, 8f64];
+        let mut nans = clean.clone();
+        nans.insert(0, f64::NAN);
+        nans.insert(4, -f64::NAN);
+        nans.insert(6, f64::NAN);
+        nans.push(f64::NAN);
+        (nans, clean)
+    }
+
+    #[test]
+    fn test_statistics_from_ignores_nan() {
+        let (nans, clean) = with_nans();
+        let expected = Statistics::from(&clean, None).unwrap();
+        let stats = Statistics::from(&nans, None).unwrap();
+
+        assert_eq!(expected, stats);
+        assert_eq!(7, stats.count());
+        assert_eq!(1f64, stats.lower());
+        assert_eq!(9f64, stats.upper());
+        assert_eq!(4f64, stats.median());
+    }
+
+    #[test]
+    fn test_statistics_from_sorted_ignores_nan() {
+        let (mut nans, mut clean) = with_nans();
+        let stats = Statistics::from_sorted(SortedValues::sort(&mut nans), Some(50)).unwrap();
+        let expected = Statistics::from_sorted(SortedValues::sort(&mut clean), Some(50)).unwrap();
+
+        assert_eq!(expected, stats);
+        assert_eq!(3, stats.count());
+        assert_eq!(3f64, stats.upper());
+    }
+
+    #[test]
+    fn test_bundle_ignores_nan() {
+        let (mut nans, mut clean) = with_nans();
+        sort_values(&mut nans);
+        sort_values(&mut clean);
+
+        let expected = StatisticsBundle::with_quantiles(&clean, &[50, 90], &[50f64], QuantileMethod::Linear);
+        let unsorted_path = StatisticsBundle::with_quantiles(&nans, &[50, 90], &[50f64], QuantileMethod::Linear);
+        let sorted_path = StatisticsBundle::from_sorted(
+            SortedValues::new_unchecked(&nans),
+            &[50, 90],
+            &[50f64],
+            QuantileMethod::Linear,
+        );
+
+        let expected = expected.unwrap();
+        assert_eq!(expected, unsorted_path.unwrap());
+        assert!(expected.approx_eq(&sorted_path.unwrap(), 1e-12));
+        assert_eq!(4f64, expected.global_stats().median());
+    }
+
+    #[test]
+    fn test_bundle_only_nan() {
+        let vals = [f64::NAN, -f64::NAN];
+        assert!(StatisticsBundle::from(&vals).is_none());
+        assert!(Statistics::from(&vals, None).is_none());
+    }
+
+    #[test]
+    fn test_get_values_nan_lines() {
+        let input = "nan\n5\n1\nNaN\n3\n-nan\n";
+        let vals = get_values(&mut input.as_bytes(), SortingPolicy::Sorted).unwrap();
+        let bundle = StatisticsBundle::with_percentiles(&vals, &[50]).unwrap();
+
+        assert_eq!(3, bundle.global_stats().count());
+        assert_eq!(3f64, bundle.global_stats().median());
+        assert_eq!(1f64, bundle.percentile_stats()[0].upper());
     }
 
-    fn compute_median(vals: &[f64]) -> f64 {
-        let len = vals.len();
-        let is_odd = len % 2 == 1;
-
-        if is_odd {
-            let mid = len / 2;
-            return vals[mid];
-        }
-
-        let upper_med = len / 2;
-        let lower_med = upper_med - 1;
-        // It should never be the case that these indexes
-        // don't exist in the slice. If there's no entries
-        // we should have already just returned the default
-        // stats instance. If there's only one entry we
-        // would have handled that with the 'is_odd' case
-        // above. Otherwise this will do the right thing.
-        (vals[upper_med] + vals[lower_med]) / 2f64
+    #[test]
+    fn test_statistics_from_with_config_same_as_from() {
+        let config = StatisticsConfig::new().with_median(false).with_stddev(false);
+        let expected = Statistics::from(VALUES, Some(50)).unwrap();
+        let stats = Statistics::from_with_config(VALUES, Some(50), config).unwrap();
+
+        assert_eq!(expected.count(), stats.count());
+        assert_eq!(expected.sum(), stats.sum());
+        assert_eq!(expected.mean(), stats.mean());
+        assert_eq!(expected.upper(), stats.upper());
+        assert_eq!(expected.lower(), stats.lower());
+        assert!(stats.median().is_nan());
+        assert!(stats.stddev().is_nan());
     }
 
-    fn compute_min_max_sum(vals: &[f64]) -> (f64, f64, f64) {
-        let mut upper = std::f64::MIN;
-        let mut lower = std::f64::MAX;
-        let mut sum = 0f64;
+    #[test]
+    fn test_statistics_from_with_config_only_mean() {
+        let config = StatisticsConfig::none().with_mean(true);
+        let stats = Statistics::from_with_config(VALUES, None, config).unwrap();
 
-        // Compute min, max, and sum in the same method to avoid
-        // extra loops through all the values. Thus we only do two
-        // loops, this one and the standard deviation loop.
-        for &val in vals {
-            if val > upper {
-                upper = val;
-            }
+        assert_eq!(6, stats.count());
+        assert_eq!(6f64, stats.mean());
+        assert!(stats.sum().is_nan());
+        assert!(stats.upper().is_nan());
+        assert!(stats.lower().is_nan());
+    }
 
-            if val < lower {
-                lower = val;
+    #[test]
+    fn test_statistics_bundle_from_sorted_with_config_same_as_with_config() {
+        let mut vals: Vec<f64> = (0..1000).map(|i| ((i * 7919) % 1000) as f64 / 7f64).collect();
+        let sorted = SortedValues::sort(&mut vals);
+
+        for config in [
+            StatisticsConfig::none().with_mean(true),
+            StatisticsConfig::new().with_stddev(false),
+            StatisticsConfig::none().with_stddev(true).with_median(true),
+        ] {
+            let expected =
+                StatisticsBundle::with_config(sorted.as_slice(), &[50, 90], &[], QuantileMethod::Linear, config)
+                    .unwrap();
+            let bundle =
+                StatisticsBundle::from_sorted_with_config(sorted, &[50, 90], &[], QuantileMethod::Linear, config)
+                    .unwrap();
+
+            let stats = std::iter::once(bundle.global_stats()).chain(bundle.percentile_stats());
+            let expected_stats = std::iter::once(expected.global_stats()).chain(expected.percentile_stats());
+            for (e, a) in expected_stats.zip(stats) {
+                assert_eq!(e.count(), a.count());
+                for name in StatName::ALL.iter().filter(|&&n| n != StatName::Count) {
+                    let (e, a) = (e.value(*name).as_f64(), a.value(*name).as_f64());
+                    assert_eq!(e.is_nan(), a.is_nan(), "{}", name);
+                    assert!(e.is_nan() || (e - a).abs() < 1e-9 * e.abs().max(1f64), "{}", name);
+                }
             }
-
-            sum += val;
         }
+    }
 
-        (lower, upper, sum)
+    #[test]
+    fn test_statistics_bundle_from_sorted_with_config_overflowed_mean() {
+        let vals = &[f64::MAX / 2f64, f64::MAX / 2f64, f64::MAX];
+        let config = StatisticsConfig::none().with_mean(true);
+        let bundle = StatisticsBundle::from_sorted_with_config(
+            SortedValues::new_unchecked(vals),
+            &[],
+            &[],
+            QuantileMethod::Linear,
+            config,
+        )
+        .unwrap();
+
+        assert!(bundle.overflowed());
+        assert_eq!(
+            StatisticsBundle::from(vals).unwrap().global_stats().mean(),
+            bundle.global_stats().mean()
+        );
     }
 
-    fn compute_stddev(vals: &[f64], mean: f64) -> f64 {
-        let num = vals.len() as f64;
-        let sum_deviance = vals.iter().fold(0f64, |sum, &x| sum + (x - mean).powi(2));
+    #[test]
+    fn test_statistics_formatter_config() {
+        let config = StatisticsConfig::none().with_count(true).with_mean(true);
+        let bundle = StatisticsBundle::with_config(VALUES, &[50], &[90f64], QuantileMethod::Linear, config).unwrap();
+        let out = format!("{}", StatisticsFormatter::new(&bundle).precision(1).config(config));
 
-        let deviance = sum_deviance / num;
-        deviance.sqrt()
+        assert_eq!("count: 6\nmean: 6.0\np90: 10.5\ncount_50: 3\nmean_50: 2.7\n", out);
     }
-}
 
-#[derive(PartialEq, Eq, Debug, Hash, Clone)]
-pub enum KeyValueSep {
-    Tab,
-    Colon,
-    Other(String),
-}
+    #[test]
+    fn test_statistics_formatter_precision_zero() {
+        let config = StatisticsConfig::none()
+            .with_count(true)
+            .with_mean(true)
+            .with_stddev(true);
+        let bundle = StatisticsBundle::with_percentiles(&[2f64, 3f64], &[]).unwrap();
+        let out = format!("{}", StatisticsFormatter::new(&bundle).precision(0).config(config));
+
+        assert_eq!("count: 2\nmean: 2\nstddev: 0\n", out);
+    }
 
-impl KeyValueSep {
-    fn get_sep(&self) -> &str {
-        match *self {
-            KeyValueSep::Tab => "\t",
-            KeyValueSep::Colon => ": ",
-            KeyValueSep::Other(ref s) => s,
-        }
+    #[test]
+    fn test_statistics_formatter_precision_twelve() {
+        let config = StatisticsConfig::none().with_count(true).with_mean(true);
+        let bundle = StatisticsBundle::with_percentiles(&[1f64, 2f64, 2f64], &[]).unwrap();
+        let out = format!("{}", StatisticsFormatter::new(&bundle).precision(12).config(config));
+
+        assert_eq!("count: 3\nmean: 1.666666666667\n", out);
     }
-}
 
-impl fmt::Display for KeyValueSep {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.get_sep().fmt(f)
+    #[test]
+    fn test_statistics_formatter_trim_trailing_zeros() {
+        let config = StatisticsConfig::none()
+            .with_count(true)
+            .with_mean(true)
+            .with_upper(true);
+        let bundle = StatisticsBundle::with_percentiles(&[2f64, 3f64, 2.5], &[]).unwrap();
+        let out = format!(
+            "{}",
+            StatisticsFormatter::new(&bundle)
+                .trim_trailing_zeros(true)
+                .config(config)
+        );
+
+        assert_eq!("count: 3\nmean: 2.5\nupper: 3\n", out);
     }
-}
 
-impl Default for KeyValueSep {
-    fn default() -> Self {
-        KeyValueSep::Colon
+    /// Writer that accepts a limited number of bytes and then fails like a
+    /// closed pipe.
+    struct FailingWriter {
+        remaining: usize,
     }
-}
 
-impl FromStr for KeyValueSep {
-    type Err = String;
+    impl io::Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.remaining == 0 {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"));
+            }
 
-    fn from_str(s: &str) -> Result<KeyValueSep, Self::Err> {
-        if "tab" == s {
-            Ok(KeyValueSep::Tab)
-        } else if "colon" == s {
-            Ok(KeyValueSep::Colon)
-        } else {
-            Ok(KeyValueSep::Other(s.to_string()))
+            let n = buf.len().min(self.remaining);
+            self.remaining -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
         }
     }
-}
 
-#[derive(Debug)]
-pub struct StatisticsFormatter<'a> {
-    bundle: &'a StatisticsBundle,
-    sep: KeyValueSep,
-}
+    #[test]
+    fn test_statistics_formatter_write_to() {
+        let bundle = StatisticsBundle::with_percentiles(VALUES, &[50]).unwrap();
+        let formatter = StatisticsFormatter::new(&bundle);
+        let mut out = Vec::new();
+        formatter.write_to(&mut out).unwrap();
 
-impl<'a> StatisticsFormatter<'a> {
-    pub fn new(bundle: &'a StatisticsBundle) -> StatisticsFormatter<'a> {
-        Self::with_sep(bundle, KeyValueSep::Colon)
+        assert_eq!(formatter.to_string().into_bytes(), out);
     }
 
-    pub fn with_sep(bundle: &'a StatisticsBundle, sep: KeyValueSep) -> StatisticsFormatter<'a> {
-        StatisticsFormatter { bundle, sep }
-    }
-
-    fn write_to_buf<T: Write>(buf: &mut T, stats: &Statistics, sep: &KeyValueSep) {
-        if let Some(p) = stats.percentile() {
-            writeln!(buf, "count_{}{}{:.*}", p, sep, DISPLAY_PRECISION, stats.count()).unwrap();
-            writeln!(buf, "sum_{}{}{:.*}", p, sep, DISPLAY_PRECISION, stats.sum()).unwrap();
-            writeln!(buf, "mean_{}{}{:.*}", p, sep, DISPLAY_PRECISION, stats.mean()).unwrap();
-            writeln!(buf, "upper_{}{}{:.*}", p, sep, DISPLAY_PRECISION, stats.upper()).unwrap();
-            writeln!(buf, "lower_{}{}{:.*}", p, sep, DISPLAY_PRECISION, stats.lower()).unwrap();
-            writeln!(buf, "median_{}{}{:.*}", p, sep, DISPLAY_PRECISION, stats.median()).unwrap();
-            writeln!(buf, "stddev_{}{}{:.*}", p, sep, DISPLAY_PRECISION, stats.stddev()).unwrap();
-        } else {
-            writeln!(buf, "count{}{:.*}", sep, DISPLAY_PRECISION, stats.count()).unwrap();
-            writeln!(buf, "sum{}{:.*}", sep, DISPLAY_PRECISION, stats.sum()).unwrap();
-            writeln!(buf, "mean{}{:.*}", sep, DISPLAY_PRECISION, stats.mean()).unwrap();
-            writeln!(buf, "upper{}{:.*}", sep, DISPLAY_PRECISION, stats.upper()).unwrap();
-            writeln!(buf, "lower{}{:.*}", sep, DISPLAY_PRECISION, stats.lower()).unwrap();
-            writeln!(buf, "median{}{:.*}", sep, DISPLAY_PRECISION, stats.median()).unwrap();
-            writeln!(buf, "stddev{}{:.*}", sep, DISPLAY_PRECISION, stats.stddev()).unwrap();
-        }
+    #[test]
+    fn test_statistics_formatter_write_to_error() {
+        let bundle = StatisticsBundle::with_percentiles(VALUES, &[50]).unwrap();
+        let formatter = StatisticsFormatter::new(&bundle);
+        let res = formatter.write_to(FailingWriter { remaining: 20 });
+
+        assert_eq!(io::ErrorKind::BrokenPipe, res.unwrap_err().kind());
     }
-}
 
-impl<'a> fmt::Display for StatisticsFormatter<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut buf = String::new();
+    #[test]
+    fn test_statistics_iter() {
+        let stats = Statistics::from(VALUES, Some(50)).unwrap();
+        let pairs: Vec<(StatName, StatValue)> = stats.iter().collect();
 
-        let global_stats = self.bundle.global_stats();
-        Self::write_to_buf(&mut buf, global_stats, &self.sep);
+        assert_eq!(StatName::ALL.len(), pairs.len());
+        assert_eq!((StatName::Count, StatValue::Integer(3)), pairs[0]);
+        assert_eq!(
+            vec![
+                stats.sum(),
+                stats.mean(),
+                stats.upper(),
+                stats.lower(),
+                stats.median(),
+                stats.stddev()
+            ],
+            pairs[1..].iter().map(|(_, v)| v.as_f64()).collect::<Vec<f64>>()
+        );
+    }
 
-        for stats in self.bundle.percentile_stats() {
-            Self::write_to_buf(&mut buf, stats, &self.sep);
+    #[test]
+    fn test_percentile_slice_len_matches_convention() {
+        for n in 0..=100 {
+            let vals: Vec<f64> = (0..n).map(|i| i as f64).collect();
+            for p in 0..=100u8 {
+                let slice = percentile_slice(&vals, p);
+                assert_eq!(p as usize * n / 100, slice.len(), "n {} p {}", n, p);
+                assert_eq!(&vals[..slice.len()], slice);
+            }
         }
-
-        buf.fmt(f)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::{get_values, KeyValueSep, SortingPolicy, Statistics};
-    use std::io::Cursor;
 
-    const VALUES: &'static [f64] = &[1f64, 2f64, 5f64, 7f64, 9f64, 12f64];
-
-    const SINGLE: &'static [f64] = &[13f64];
+    #[test]
+    fn test_percentile_slice_monotonic() {
+        for n in 0..=100 {
+            let vals = vec![0u8; n];
+            let lens: Vec<usize> = (0..=100).map(|p| percentile_slice(&vals, p).len()).collect();
+            assert!(lens.windows(2).all(|w| w[0] <= w[1]), "n {}", n);
+            assert_eq!(n, lens[100]);
+        }
+    }
 
-    const EMPTY: &'static [f64] = &[];
+    #[test]
+    fn test_percentile_slice_over_100() {
+        assert_eq!(VALUES, percentile_slice(VALUES, 200));
+    }
 
     #[test]
-    fn test_get_values_filter_invalids() {
-        let bytes: Vec<u8> = vec!["asdf\n", "4.5\n", "xyz\n"]
-            .iter()
-            .flat_map(|v| v.as_bytes())
-            .map(|&v| v)
-            .collect();
+    fn test_percentile_slice_same_as_statistics() {
+        for p in 1..100 {
+            let slice = percentile_slice(VALUES, p);
+            let stats = Statistics::from(VALUES, Some(p));
+            assert_eq!(slice.len(), stats.map(|s| s.count()).unwrap_or_default());
+        }
+    }
 
-        let mut reader = Cursor::new(bytes);
-        assert_eq!(vec![4.5], get_values(&mut reader, SortingPolicy::Sorted).unwrap());
+    #[test]
+    fn test_approx_eq_values() {
+        assert!(approx_eq(1f64, 1f64 + 1e-10, 1e-9));
+        assert!(!approx_eq(1f64, 1f64 + 1e-8, 1e-9));
+        assert!(approx_eq(1e12, 1e12 + 1f64, 1e-9));
+        assert!(approx_eq(f64::NAN, f64::NAN, 1e-9));
+        assert!(approx_eq(f64::INFINITY, f64::INFINITY, 1e-9));
+        assert!(!approx_eq(f64::INFINITY, f64::NEG_INFINITY, 1e-9));
+        assert!(!approx_eq(f64::INFINITY, 1e300, 1e-9));
+        assert!(!approx_eq(1f64, f64::NAN, 1e-9));
     }
 
     #[test]
-    fn test_get_values_ordered() {
-        let bytes: Vec<u8> = vec!["9.8\n", "4.5\n", "5.6\n"]
-            .iter()
-            .flat_map(|v| v.as_bytes())
-            .map(|&v| v)
-            .collect();
+    fn test_statistics_differences() {
+        let a = Statistics::from(VALUES, None).unwrap();
+        let b = Statistics::from(&[1f64, 2f64, 5f64, 7f64, 9f64, 12.000001], None).unwrap();
 
-        let mut reader = Cursor::new(bytes);
+        assert!(a.approx_eq(&a, 0f64));
         assert_eq!(
-            vec![4.5, 5.6, 9.8],
-            get_values(&mut reader, SortingPolicy::Sorted).unwrap()
+            vec![StatName::Sum, StatName::Mean, StatName::Upper, StatName::Stddev],
+            a.differences(&b, 1e-9)
         );
+        assert!(a.approx_eq(&b, 1e-6));
+        assert!(!a.approx_eq(&Statistics::from(VALUES, Some(50)).unwrap(), 1e-6));
     }
 
     #[test]
-    fn test_get_values_trim_whitespace() {
-        let bytes: Vec<u8> = vec!["9.8   \n", "4.5 \n", "5.6\t\n"]
-            .iter()
-            .flat_map(|v| v.as_bytes())
-            .map(|&v| v)
-            .collect();
+    fn test_statistics_differences_count() {
+        let a = Statistics::from(&[1f64, 2f64], None).unwrap();
+        let b = Statistics::from(&[1f64, 1f64, 2f64, 2f64], None).unwrap();
+        assert_eq!(vec![StatName::Count, StatName::Sum], a.differences(&b, 1e-9));
+    }
 
-        let mut reader = Cursor::new(bytes);
+    #[test]
+    fn test_statistics_bundle_differences() {
+        let a = StatisticsBundle::with_quantiles(VALUES, &[50, 90], &[99f64], QuantileMethod::Linear).unwrap();
+        let b = StatisticsBundle::with_quantiles(VALUES, &[90, 50], &[99f64], QuantileMethod::Linear).unwrap();
+        assert!(a.approx_eq(&b, 0f64));
+
+        let c = StatisticsBundle::with_quantiles(
+            &[1f64, 2f64, 5f64, 7f64, 9f64, 13f64],
+            &[50, 75],
+            &[],
+            QuantileMethod::Linear,
+        )
+        .unwrap();
         assert_eq!(
-            vec![4.5, 5.6, 9.8],
-            get_values(&mut reader, SortingPolicy::Sorted).unwrap()
+            vec![
+                "sum",
+                "mean",
+                "upper",
+                "stddev",
+                "p99",
+                "percentile_90",
+                "percentile_75"
+            ],
+            a.differences(&c, 1e-9)
         );
     }
 
     #[test]
-    fn test_get_values_unordered() {
-        let bytes: Vec<u8> = vec!["9.8\n", "4.5\n", "5.6\n"]
-            .iter()
-            .flat_map(|v| v.as_bytes())
-            .map(|&v| v)
-            .collect();
+    fn test_statistics_from_iter_same_as_from() {
+        let expected = Statistics::from(VALUES, None);
+        assert_eq!(expected, Statistics::from_iter(VALUES.iter().rev().cloned()));
+    }
 
-        let mut reader = Cursor::new(bytes);
+    #[test]
+    fn test_statistics_from_iter_empty() {
+        assert_eq!(None, Statistics::from_iter(EMPTY.iter().cloned()));
         assert_eq!(
-            vec![9.8, 4.5, 5.6],
-            get_values(&mut reader, SortingPolicy::Unsorted).unwrap()
+            None,
+            StatisticsBundle::from_iter_with_percentiles(EMPTY.iter().cloned(), &[50])
         );
     }
 
+    #[test]
+    fn test_statistics_bundle_from_iter_with_percentiles_same_as_sorted() {
+        let expected = StatisticsBundle::with_percentiles(VALUES, &[50, 75]);
+        let bundle = StatisticsBundle::from_iter_with_percentiles(VALUES.iter().rev().cloned(), &[50, 75]);
+        assert_eq!(expected, bundle);
+    }
+
     #[test]
     fn test_statistics_full_values_count() {
         let stats = Statistics::from(VALUES, None).unwrap();
@@ -480,11 +3053,250 @@ mod tests {
         assert!((1.70 - stats.stddev()).abs() < 0.01);
     }
 
+    #[test]
+    fn test_version_info() {
+        let (version, details) = VERSION_INFO.split_once(' ').unwrap();
+        assert_eq!(env!("CARGO_PKG_VERSION"), version);
+        assert!(details.starts_with('(') && details.ends_with(')'), "{}", details);
+    }
+
     #[test]
     fn test_statistics_empty_values() {
         assert!(Statistics::from(EMPTY, None).is_none());
     }
 
+    #[test]
+    fn test_statistics_empty() {
+        let stats = Statistics::empty();
+        assert_eq!(None, stats.percentile());
+        assert_eq!(0, stats.count());
+        assert_eq!(0f64, stats.sum());
+        assert!(stats.mean().is_nan());
+        assert!(stats.upper().is_nan());
+        assert!(stats.lower().is_nan());
+        assert!(stats.median().is_nan());
+        assert!(stats.stddev().is_nan());
+        assert!(!stats.is_merged());
+        assert_eq!(None, stats.bounds());
+    }
+
+    #[test]
+    fn test_statistics_bounds() {
+        let stats = Statistics::from(VALUES, None).unwrap();
+        assert_eq!(Some((stats.lower(), stats.upper())), stats.bounds());
+    }
+
+    /// Finite values whose sum is larger than `f64::MAX`, and their mean.
+    const HUGE_VALUES: &[f64] = &[f64::MAX / 2f64, f64::MAX, f64::MAX];
+    const HUGE_MEAN: f64 = f64::MAX / 6f64 * 5f64;
+
+    fn assert_relative_eq(expected: f64, actual: f64) {
+        assert!(
+            ((expected - actual) / expected).abs() < 1e-15,
+            "expected {} got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_statistics_sum_overflowed() {
+        let stats = Statistics::from(HUGE_VALUES, None).unwrap();
+        assert!(stats.overflowed());
+        assert_eq!(f64::INFINITY, stats.sum());
+        assert_relative_eq(HUGE_MEAN, stats.mean());
+        assert_eq!(f64::MAX, stats.upper());
+        assert_eq!(f64::MAX / 2f64, stats.lower());
+    }
+
+    #[test]
+    fn test_statistics_sum_overflowed_sorted() {
+        let stats = Statistics::from_sorted(SortedValues::new(HUGE_VALUES).unwrap(), None).unwrap();
+        assert!(stats.overflowed());
+        assert_eq!(f64::INFINITY, stats.sum());
+        assert_relative_eq(HUGE_MEAN, stats.mean());
+    }
+
+    #[test]
+    fn test_statistics_sum_overflowed_negative() {
+        let vals: Vec<f64> = HUGE_VALUES.iter().map(|v| -v).collect();
+        let stats = Statistics::from(&vals, None).unwrap();
+        assert!(stats.overflowed());
+        assert_eq!(f64::NEG_INFINITY, stats.sum());
+        assert_relative_eq(-HUGE_MEAN, stats.mean());
+    }
+
+    #[test]
+    fn test_statistics_sum_overflowed_many_values() {
+        // Enough values to be summed in separate lanes, with deviations
+        // from the mean small enough that the stddev doesn't overflow.
+        let vals = vec![f64::MAX / 4f64; 100];
+        for stats in [
+            Statistics::from(&vals, None).unwrap(),
+            Statistics::from_sorted(SortedValues::new(&vals).unwrap(), None).unwrap(),
+        ] {
+            assert!(stats.overflowed());
+            assert_relative_eq(f64::MAX / 4f64, stats.mean());
+            assert_eq!(0f64, stats.stddev());
+        }
+    }
+
+    #[test]
+    fn test_statistics_sum_overflowed_percentiles() {
+        let mut vals = vec![1f64; 8];
+        vals.extend_from_slice(HUGE_VALUES);
+        let bundle = StatisticsBundle::with_percentiles(&vals, &[50]).unwrap();
+
+        assert!(bundle.global_stats().overflowed());
+        assert!(!bundle.percentile_for(50).unwrap().overflowed());
+        assert!(bundle.overflowed());
+        assert!(!StatisticsBundle::with_percentiles(VALUES, &[50]).unwrap().overflowed());
+    }
+
+    #[test]
+    fn test_statistics_infinite_values_not_overflowed() {
+        let stats = Statistics::from(&[1f64, f64::INFINITY], None).unwrap();
+        assert!(!stats.overflowed());
+        assert_eq!(f64::INFINITY, stats.sum());
+        assert_eq!(f64::INFINITY, stats.mean());
+        assert!(!Statistics::from(VALUES, None).unwrap().overflowed());
+    }
+
+    #[test]
+    fn test_bundle_from_vec() {
+        let vals = vec![9f64, 1f64, f64::NAN, 12f64, 5f64, 2f64, 7f64];
+        let ptr = vals.as_ptr();
+        let (bundle, sorted) = StatisticsBundle::from_vec(vals, &[50]).unwrap();
+
+        assert_eq!(StatisticsBundle::with_percentiles(VALUES, &[50]).unwrap(), bundle);
+        assert_eq!(VALUES, sorted.as_slice());
+        assert_eq!(ptr, sorted.as_slice().as_ptr());
+    }
+
+    #[test]
+    fn test_bundle_from_vec_empty() {
+        assert!(StatisticsBundle::from_vec(vec![f64::NAN], &[50]).is_none());
+    }
+
+    #[test]
+    fn test_bundle_percentile_for() {
+        let bundle = StatisticsBundle::with_percentiles(VALUES, &[90, 50]).unwrap();
+        assert_eq!(vec![50, 90], bundle.percentiles().collect::<Vec<u8>>());
+        assert_eq!(Some(&bundle.percentile_stats()[0]), bundle.percentile_for(50));
+        assert_eq!(Some(90), bundle.percentile_for(90).unwrap().percentile());
+        assert!(bundle.percentile_for(75).is_none());
+    }
+
+    #[test]
+    fn test_bundle_percentiles_ascending() {
+        let requested = &[99, 25, 90, 50];
+        let expected = vec![25, 50, 90, 99];
+        let vals: Vec<f64> = (1..=100).map(f64::from).collect();
+
+        let bundle = StatisticsBundle::with_percentiles(&vals, requested).unwrap();
+        assert_eq!(expected, bundle.percentiles().collect::<Vec<u8>>());
+
+        let (bundle, _) = StatisticsBundle::from_vec(vals, requested).unwrap();
+        assert_eq!(expected, bundle.percentiles().collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_bundle_percentile_for_omitted() {
+        let bundle = StatisticsBundle::with_percentiles(SINGLE, &[99]).unwrap();
+        assert!(bundle.percentile_for(99).is_none());
+        assert_eq!(0, bundle.percentiles().count());
+    }
+
+    #[test]
+    fn test_bundle_with_min_samples_boundary() {
+        let vals: Vec<f64> = (1..=10).map(f64::from).collect();
+        // Slices of 5 and 4 values.
+        let bundle = StatisticsBundle::with_quantiles(&vals, &[50, 40], &[50f64], QuantileMethod::Linear).unwrap();
+
+        let at = bundle.clone().with_min_samples(5);
+        assert_eq!(vec![50], at.percentiles().collect::<Vec<u8>>());
+        assert_eq!(
+            &[OmittedPercentile::new(40, Omission::BelowMinSamples { min: 5 })],
+            at.omitted_percentiles()
+        );
+        assert_eq!(1, at.quantile_values().len());
+
+        let total_at = bundle.clone().with_min_samples(10);
+        assert_eq!(1, total_at.quantile_values().len());
+        assert_eq!(0, total_at.percentile_stats().len());
+
+        let total_below = bundle.clone().with_min_samples(11);
+        assert!(total_below.quantile_values().is_empty());
+        assert_eq!(
+            &[OmittedQuantile::new(50f64, Omission::BelowMinSamples { min: 11 })],
+            total_below.omitted_quantiles()
+        );
+        assert_eq!("p50", total_below.omitted_quantiles()[0].key());
+        assert_eq!(10, total_below.global_stats().count());
+        assert_eq!(2, total_below.omitted_percentiles().len());
+
+        assert_eq!(bundle, bundle.clone().with_min_samples(1));
+    }
+
+    #[test]
+    fn test_bundle_with_min_samples_keeps_omitted_in_order() {
+        let vals = &[1f64, 2f64, 3f64, 4f64, 5f64];
+        let bundle = StatisticsBundle::with_percentiles(vals, &[10, 40, 99])
+            .unwrap()
+            .with_min_samples(3);
+        let omitted: Vec<(u8, Omission)> = bundle
+            .omitted_percentiles()
+            .iter()
+            .map(|o| (o.percentile(), o.reason()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (10, Omission::InsufficientData),
+                (40, Omission::BelowMinSamples { min: 3 }),
+            ],
+            omitted
+        );
+        assert_eq!(vec![99], bundle.percentiles().collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_bundle_omitted_percentiles() {
+        let vals = &[1f64, 2f64, 3f64, 4f64, 5f64];
+        let expected = vec![
+            OmittedPercentile::new(5, Omission::InsufficientData),
+            OmittedPercentile::new(10, Omission::InsufficientData),
+        ];
+
+        // The lowest 99 percent of 5 values has 4 of them, but the lowest
+        // 10 percent doesn't have any.
+        let sorted = StatisticsBundle::with_percentiles(vals, &[99, 10, 5]).unwrap();
+        assert_eq!(expected, sorted.omitted_percentiles());
+        assert_eq!(Some(99), sorted.percentile_stats()[0].percentile());
+
+        let unsorted = StatisticsBundle::with_config(
+            vals,
+            &[99, 10, 5],
+            &[],
+            QuantileMethod::Linear,
+            StatisticsConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, unsorted.omitted_percentiles());
+
+        let all = StatisticsBundle::with_percentiles(vals, &[20, 50]).unwrap();
+        assert!(all.omitted_percentiles().is_empty());
+    }
+
+    #[test]
+    fn test_bundle_empty() {
+        let bundle = StatisticsBundle::empty();
+        assert!(bundle.global_stats().approx_eq(&Statistics::empty(), 0f64));
+        assert!(bundle.percentile_stats().is_empty());
+        assert!(bundle.quantile_values().is_empty());
+        assert_eq!(None, bundle.sample_size());
+    }
+
     #[test]
     fn test_statistics_single_value_count() {
         let stats = Statistics::from(SINGLE, None).unwrap();
@@ -527,6 +3339,19 @@ mod tests {
         assert_eq!(0f64, stats.stddev());
     }
 
+    #[test]
+    fn test_statistics_bundle_all_quantiles_median() {
+        let all: Vec<f64> = (1..100).map(f64::from).collect();
+
+        for vals in &[VALUES, &VALUES[1..], SINGLE] {
+            let bundle = StatisticsBundle::with_quantiles(vals, &[], &all, QuantileMethod::Linear).unwrap();
+            let p50 = bundle.quantile_values().iter().find(|q| q.key() == "p50").unwrap();
+
+            assert_eq!(99, bundle.quantile_values().len());
+            assert_eq!(bundle.global_stats().median(), p50.value());
+        }
+    }
+
     #[test]
     fn test_key_value_sep_get_sep() {
         assert_eq!("\t", KeyValueSep::Tab.get_sep());
@@ -552,5 +3377,94 @@ mod tests {
             KeyValueSep::Other(" => ".to_string()),
             " => ".parse::<KeyValueSep>().unwrap()
         );
+        assert!(matches!(
+            "".parse::<KeyValueSep>(),
+            Err(StaccatoError::InvalidSeparator)
+        ));
+    }
+
+    #[test]
+    fn test_key_value_sep_from_str_keywords() {
+        assert_eq!(KeyValueSep::Space, "space".parse().unwrap());
+        assert_eq!(KeyValueSep::Equals, "equals".parse().unwrap());
+        assert_eq!(KeyValueSep::Comma, "comma".parse().unwrap());
+        assert_eq!(KeyValueSep::None, "none".parse().unwrap());
+    }
+
+    #[test]
+    fn test_key_value_sep_from_str_same_as_keyword() {
+        assert_eq!(KeyValueSep::Colon, ":".parse().unwrap());
+        assert_eq!(KeyValueSep::Colon, ": ".parse().unwrap());
+        assert_eq!(KeyValueSep::Equals, "=".parse().unwrap());
+        assert_eq!(KeyValueSep::Comma, ",".parse().unwrap());
+        assert_eq!(KeyValueSep::Space, " ".parse().unwrap());
+        assert_eq!(KeyValueSep::Other(" = ".to_string()), " = ".parse().unwrap());
+    }
+
+    #[test]
+    fn test_key_value_sep_default() {
+        assert_eq!(KeyValueSep::Colon, KeyValueSep::default());
+    }
+
+    #[test]
+    fn test_key_value_sep_from_str_escapes() {
+        assert_eq!(KeyValueSep::Tab, "\\t".parse().unwrap());
+        assert_eq!(KeyValueSep::Other("\n".to_string()), "\\n".parse().unwrap());
+        assert_eq!(KeyValueSep::Other("\0".to_string()), "\\0".parse().unwrap());
+        assert_eq!(KeyValueSep::Other(" =\t".to_string()), " =\\t".parse().unwrap());
+    }
+
+    #[test]
+    fn test_key_value_sep_from_str_literal_backslash() {
+        assert_eq!(KeyValueSep::Other("\\".to_string()), "\\\\".parse().unwrap());
+        assert_eq!(KeyValueSep::Other("\\t".to_string()), "\\\\t".parse().unwrap());
+    }
+
+    #[test]
+    fn test_key_value_sep_from_str_invalid_escape() {
+        assert!(matches!(
+            "=\\".parse::<KeyValueSep>(),
+            Err(StaccatoError::InvalidEscape { ref value }) if value == "=\\"
+        ));
+        assert!(matches!(
+            "\\x".parse::<KeyValueSep>(),
+            Err(StaccatoError::InvalidEscape { .. })
+        ));
+    }
+
+    #[test]
+    fn test_key_value_sep_display_round_trip() {
+        for s in &["\\t", "\\n", "\\0", " => ", "space", "equals"] {
+            let sep: KeyValueSep = s.parse().unwrap();
+            assert_eq!(sep, sep.to_string().parse().unwrap());
+        }
+
+        assert_eq!("\t", "\\t".parse::<KeyValueSep>().unwrap().to_string());
+        assert_eq!("\\", "\\\\".parse::<KeyValueSep>().unwrap().to_string());
+        assert_eq!("", "none".parse::<KeyValueSep>().unwrap().to_string());
+    }
+
+    #[test]
+    fn test_key_value_sep_keyword_round_trip() {
+        for sep in KeyValueSep::NAMED {
+            assert_eq!(*sep, sep.keyword().unwrap().parse().unwrap());
+            // An empty separator can only be given by its keyword
+            if *sep != KeyValueSep::None {
+                assert_eq!(*sep, sep.to_string().parse().unwrap());
+            }
+        }
+
+        assert_eq!(None, KeyValueSep::Other(" => ".to_string()).keyword());
+    }
+
+    #[test]
+    fn test_formatter_none_separator() {
+        let bundle = StatisticsBundle::with_percentiles(VALUES, &[50]).unwrap();
+        let out = StatisticsFormatter::with_sep(&bundle, KeyValueSep::None)
+            .precision(1)
+            .config(StatisticsConfig::none().with_count(true).with_mean(true))
+            .to_string();
+
+        assert_eq!("count6\nmean6.0\ncount_503\nmean_502.7\n", out);
     }
 }
@@ -0,0 +1,162 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{for_each_line, for_each_line_in_slice, sort_values, SortingPolicy, StaccatoError};
+use std::io::{BufReader, Read};
+use std::str::FromStr;
+
+/// Something about a line of arbitrary text that can be used as a value
+/// instead of parsing the line as a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Measure {
+    /// Number of characters in the line.
+    Length,
+    /// Number of whitespace separated words in the line.
+    Words,
+    /// Number of bytes in the line.
+    Bytes,
+}
+
+impl Measure {
+    /// Compute the measure of a single line, not including the line ending.
+    pub fn measure(&self, line: &str) -> f64 {
+        match *self {
+            Measure::Length => line.chars().count() as f64,
+            Measure::Words => line.split_whitespace().count() as f64,
+            Measure::Bytes => line.len() as f64,
+        }
+    }
+}
+
+impl FromStr for Measure {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Measure, Self::Err> {
+        if "length" == s {
+            Ok(Measure::Length)
+        } else if "words" == s {
+            Ok(Measure::Words)
+        } else if "bytes" == s {
+            Ok(Measure::Bytes)
+        } else {
+            Err(format!("Invalid measure {}", s))
+        }
+    }
+}
+
+/// Read lines of text and compute a measure of each of them to use as values.
+///
+/// Unlike `get_values`, every line (including empty lines, which measure as
+/// zero) results in a value.
+pub fn get_measurements<T: Read>(
+    reader: &mut T,
+    measure: Measure,
+    sort: SortingPolicy,
+) -> Result<Vec<f64>, StaccatoError> {
+    let mut values = Vec::new();
+    for_each_line(&mut BufReader::new(reader), |line| values.push(measure.measure(line)))?;
+
+    sort.sort_with(&mut values, sort_values)?;
+    Ok(values)
+}
+
+/// Compute a measure of each line of text in a slice of bytes (such as a
+/// memory mapped file) the same way `get_measurements` does for a reader.
+pub fn get_measurements_from_slice(
+    bytes: &[u8],
+    measure: Measure,
+    sort: SortingPolicy,
+) -> Result<Vec<f64>, StaccatoError> {
+    let mut values = Vec::new();
+    for_each_line_in_slice(bytes, |line| values.push(measure.measure(line)))?;
+
+    sort.sort_with(&mut values, sort_values)?;
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_measurements, get_measurements_from_slice, Measure};
+    use crate::SortingPolicy;
+
+    const TEXT: &str = "hello world\n\n  spaced   out  words \r\nnaïve café ☕\n";
+
+    fn measure(measure: Measure, sort: SortingPolicy) -> Vec<f64> {
+        get_measurements(&mut TEXT.as_bytes(), measure, sort).unwrap()
+    }
+
+    #[test]
+    fn test_measure_from_str() {
+        assert_eq!(Measure::Length, "length".parse().unwrap());
+        assert_eq!(Measure::Words, "words".parse().unwrap());
+        assert_eq!(Measure::Bytes, "bytes".parse().unwrap());
+        assert!("banana".parse::<Measure>().is_err());
+    }
+
+    #[test]
+    fn test_get_measurements_length() {
+        assert_eq!(
+            vec![11f64, 0f64, 22f64, 12f64],
+            measure(Measure::Length, SortingPolicy::Unsorted)
+        );
+    }
+
+    #[test]
+    fn test_get_measurements_words() {
+        assert_eq!(
+            vec![2f64, 0f64, 3f64, 3f64],
+            measure(Measure::Words, SortingPolicy::Unsorted)
+        );
+    }
+
+    #[test]
+    fn test_get_measurements_bytes() {
+        // Each of 'ï' and 'é' are two bytes and '☕' is three bytes
+        assert_eq!(
+            vec![11f64, 0f64, 22f64, 16f64],
+            measure(Measure::Bytes, SortingPolicy::Unsorted)
+        );
+    }
+
+    #[test]
+    fn test_get_measurements_sorted() {
+        assert_eq!(
+            vec![0f64, 11f64, 12f64, 22f64],
+            measure(Measure::Length, SortingPolicy::Sorted)
+        );
+    }
+
+    #[test]
+    fn test_get_measurements_empty() {
+        assert!(
+            get_measurements(&mut "".as_bytes(), Measure::Length, SortingPolicy::Unsorted)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_get_measurements_from_slice_same_as_reader() {
+        for &m in &[Measure::Length, Measure::Words, Measure::Bytes] {
+            assert_eq!(
+                measure(m, SortingPolicy::Unsorted),
+                get_measurements_from_slice(TEXT.as_bytes(), m, SortingPolicy::Unsorted).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,485 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{sum_overflowed, OmittedPercentile, StatName, Statistics, StatisticsBundle};
+use std::error::Error;
+use std::fmt;
+
+/// What to do with percentile slices that are only in one of the bundles
+/// being merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MergePolicy {
+    /// Percentile slices that aren't in both bundles are left out of the
+    /// merged bundle.
+    #[default]
+    DropUnmatched,
+    /// Merging bundles that don't have the same percentile slices is an
+    /// error.
+    Strict,
+}
+
+/// How close a statistic of merged statistics is to the same statistic
+/// computed from all of the values at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Accuracy {
+    /// The same as if it was computed from all the values, other than
+    /// floating point rounding error: the count, sum, mean, upper, and
+    /// lower of all values, and every statistic that wasn't merged.
+    Exact,
+    /// Combined from the statistics being merged using a formula that
+    /// gives the same result as all the values other than rounding error,
+    /// which may be larger than for exact statistics: the standard
+    /// deviation of all values.
+    Formula,
+    /// An estimate that may be different from the statistic of all the
+    /// values: the median, and every statistic of a percentile slice since
+    /// the union of each slice isn't the lowest percent of all values.
+    Estimate,
+}
+
+impl Accuracy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Accuracy::Exact => "exact",
+            Accuracy::Formula => "formula",
+            Accuracy::Estimate => "estimate",
+        }
+    }
+}
+
+impl fmt::Display for Accuracy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+/// Reason that statistics couldn't be merged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeError {
+    /// Statistics of different percentile slices (or of a percentile slice
+    /// and all values) can't be merged.
+    PercentileMismatch(Option<u8>, Option<u8>),
+    /// A percentile slice was only in one of the bundles being merged with
+    /// the `Strict` merge policy.
+    UnmatchedPercentile(u8),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeError::PercentileMismatch(a, b) => write!(
+                f,
+                "cannot merge statistics of {} with statistics of {}",
+                describe_percentile(*a),
+                describe_percentile(*b)
+            ),
+            MergeError::UnmatchedPercentile(p) => {
+                write!(f, "percentile {} is not in every bundle being merged", p)
+            }
+        }
+    }
+}
+
+impl Error for MergeError {}
+
+fn describe_percentile(percentile: Option<u8>) -> String {
+    match percentile {
+        Some(p) => format!("percentile {}", p),
+        None => "all values".to_string(),
+    }
+}
+
+impl Statistics {
+    /// How close the given statistic is to the same statistic computed from
+    /// all of the values, which is always `Exact` unless these statistics
+    /// were merged.
+    ///
+    /// ```
+    /// use staccato::{Accuracy, StatName, Statistics};
+    ///
+    /// let a = Statistics::from(&[1.0, 2.0], None).unwrap();
+    /// let b = Statistics::from(&[3.0], None).unwrap();
+    /// assert_eq!(Accuracy::Exact, a.accuracy(StatName::Median));
+    ///
+    /// let merged = a.merge(&b).unwrap();
+    /// assert_eq!(Accuracy::Exact, merged.accuracy(StatName::Mean));
+    /// assert_eq!(Accuracy::Formula, merged.accuracy(StatName::Stddev));
+    /// assert_eq!(Accuracy::Estimate, merged.accuracy(StatName::Median));
+    /// ```
+    pub fn accuracy(&self, name: StatName) -> Accuracy {
+        if !self.merged {
+            return Accuracy::Exact;
+        }
+
+        match (self.percentile, name) {
+            (Some(_), _) | (None, StatName::Median) => Accuracy::Estimate,
+            (None, StatName::Stddev) => Accuracy::Formula,
+            (None, _) => Accuracy::Exact,
+        }
+    }
+
+    /// Combine statistics of two separate sequences of values, e.g. computed
+    /// on different machines, into statistics of both sequences without
+    /// needing the values themselves.
+    ///
+    /// The count, sum, upper, and lower values are combined exactly and the
+    /// mean and standard deviation are combined using the parallel variance
+    /// formula of Chan et al, so these are the same as statistics of all the
+    /// values other than rounding error. The median can't be combined without
+    /// the values so the merged median is the mean of each median weighted by
+    /// count. Similarly, merging percentile slices results in statistics of
+    /// the union of each slice, which isn't necessarily the lowest percent of
+    /// all values. The result is marked as merged to indicate this.
+    ///
+    /// This method returns an error if the statistics are for different
+    /// percentile slices.
+    pub fn merge(&self, other: &Statistics) -> Result<Statistics, MergeError> {
+        if self.percentile != other.percentile {
+            return Err(MergeError::PercentileMismatch(self.percentile, other.percentile));
+        }
+
+        let (n_a, n_b) = (self.count as f64, other.count as f64);
+        let count = self.count + other.count;
+        let n = count as f64;
+
+        let sum = self.sum + other.sum;
+        let upper = self.upper.max(other.upper);
+        let lower = self.lower.min(other.lower);
+        let overflowed = self.overflowed || other.overflowed || sum_overflowed(sum, lower, upper);
+        let delta = other.mean - self.mean;
+        let m2 = self.stddev.powi(2) * n_a + other.stddev.powi(2) * n_b + delta.powi(2) * n_a * n_b / n;
+        // Weighting each mean by count gives the same result as dividing the
+        // sum by the count without needing a finite sum.
+        let mean = if overflowed {
+            self.mean + delta * (n_b / n)
+        } else {
+            sum / n
+        };
+
+        Ok(Statistics {
+            percentile: self.percentile,
+            count,
+            sum,
+            mean,
+            upper,
+            lower,
+            median: (self.median * n_a + other.median * n_b) / n,
+            stddev: (m2 / n).sqrt(),
+            merged: true,
+            overflowed,
+        })
+    }
+}
+
+impl StatisticsBundle {
+    /// Combine bundles of statistics of two separate sequences of values
+    /// into a bundle of statistics of both sequences, merging the global
+    /// statistics and each pair of percentile slices with the same percentile
+    /// using `Statistics::merge`.
+    ///
+    /// Percentile slices that are only in one of the bundles are left out of
+    /// the result, or are an error with the `Strict` merge policy, so merging
+    /// bundles with different percentiles results in slices for only the
+    /// percentiles that every bundle has. Use `Statistics::accuracy` to tell
+    /// which statistics of the result are exact. Quantiles
+    /// can't be combined without the values so the merged bundle doesn't have
+    /// any. If either bundle was computed from a sample, the sample count of
+    /// the result is the combined number of values statistics were estimated
+    /// from.
+    pub fn merge(&self, other: &StatisticsBundle, policy: MergePolicy) -> Result<StatisticsBundle, MergeError> {
+        if policy == MergePolicy::Strict {
+            let unmatched = self
+                .percentiles
+                .iter()
+                .chain(other.percentiles.iter())
+                .filter_map(|s| s.percentile)
+                .find(|&p| self.percentile_for(p).is_none() || other.percentile_for(p).is_none());
+
+            if let Some(p) = unmatched {
+                return Err(MergeError::UnmatchedPercentile(p));
+            }
+        }
+
+        let mut percentiles = Vec::new();
+        for stats in &self.percentiles {
+            if let Some(o) = stats.percentile.and_then(|p| other.percentile_for(p)) {
+                percentiles.push(stats.merge(o)?);
+            }
+        }
+
+        let sample_size = match (self.sample_size, other.sample_size) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(self.global.count) + b.unwrap_or(other.global.count)),
+        };
+
+        // A slice omitted from either bundle can't be merged, so it's
+        // omitted from the result for the same reason.
+        let mut omitted: Vec<OmittedPercentile> = self.omitted.iter().chain(other.omitted.iter()).copied().collect();
+        omitted.sort_unstable_by_key(|o| o.percentile());
+        omitted.dedup_by_key(|o| o.percentile());
+
+        Ok(StatisticsBundle {
+            global: self.global.merge(&other.global)?,
+            quantiles: Vec::new(),
+            percentiles,
+            sample_size,
+            omitted,
+            omitted_quantiles: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Accuracy, MergeError, MergePolicy};
+    use crate::{QuantileMethod, StatName, Statistics, StatisticsBundle};
+
+    fn random_values(n: usize, seed: u64, offset: f64) -> Vec<f64> {
+        let mut state = seed;
+        (0..n)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                offset + (state >> 40) as f64 / 1000f64
+            })
+            .collect()
+    }
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() <= 1e-12 * expected.abs().max(1f64),
+            "expected {} got {}",
+            expected,
+            actual
+        );
+    }
+
+    fn assert_mergeable_fields_eq(expected: &Statistics, actual: &Statistics) {
+        assert_eq!(expected.count(), actual.count());
+        assert_eq!(expected.upper(), actual.upper());
+        assert_eq!(expected.lower(), actual.lower());
+        assert_close(expected.sum(), actual.sum());
+        assert_close(expected.mean(), actual.mean());
+        assert_close(expected.stddev(), actual.stddev());
+    }
+
+    #[test]
+    fn test_merge_matches_all_values() {
+        let a = random_values(1000, 1, 0f64);
+        let b = random_values(300, 2, 5000f64);
+        let all: Vec<f64> = a.iter().chain(b.iter()).copied().collect();
+
+        let merged = Statistics::from(&a, None)
+            .unwrap()
+            .merge(&Statistics::from(&b, None).unwrap())
+            .unwrap();
+        let expected = Statistics::from(&all, None).unwrap();
+
+        assert_mergeable_fields_eq(&expected, &merged);
+        assert!(merged.is_merged());
+        assert!(!expected.is_merged());
+    }
+
+    #[test]
+    fn test_merge_sum_overflowed() {
+        let a = Statistics::from(&[f64::MAX / 2f64, f64::MAX / 4f64], None).unwrap();
+        let b = Statistics::from(&[f64::MAX], None).unwrap();
+        assert!(!a.overflowed());
+        assert!(!b.overflowed());
+
+        let merged = a.merge(&b).unwrap();
+        assert!(merged.overflowed());
+        assert_eq!(f64::INFINITY, merged.sum());
+        assert_close(f64::MAX / 12f64 * 7f64, merged.mean());
+    }
+
+    #[test]
+    fn test_merge_is_symmetric() {
+        let a = Statistics::from(&random_values(100, 3, 0f64), None).unwrap();
+        let b = Statistics::from(&random_values(50, 4, 10f64), None).unwrap();
+
+        assert_mergeable_fields_eq(&a.merge(&b).unwrap(), &b.merge(&a).unwrap());
+    }
+
+    #[test]
+    fn test_merge_single_values() {
+        let a = Statistics::from(&[2f64], None).unwrap();
+        let b = Statistics::from(&[4f64], None).unwrap();
+        let merged = a.merge(&b).unwrap();
+
+        assert_mergeable_fields_eq(&Statistics::from(&[2f64, 4f64], None).unwrap(), &merged);
+        assert_eq!(3f64, merged.median());
+    }
+
+    #[test]
+    fn test_merge_median_weighted_by_count() {
+        let a = Statistics::from(&[1f64, 1f64, 1f64], None).unwrap();
+        let b = Statistics::from(&[5f64], None).unwrap();
+
+        assert_eq!(2f64, a.merge(&b).unwrap().median());
+    }
+
+    #[test]
+    fn test_merge_percentiles() {
+        let a = Statistics::from(&[1f64, 2f64, 3f64, 4f64], Some(50)).unwrap();
+        let b = Statistics::from(&[5f64, 6f64, 7f64, 8f64], Some(50)).unwrap();
+        let merged = a.merge(&b).unwrap();
+
+        // Statistics of the union of each slice, not the lowest 50% of all values
+        assert_eq!(Some(50), merged.percentile());
+        assert_mergeable_fields_eq(&Statistics::from(&[1f64, 2f64, 5f64, 6f64], None).unwrap(), &merged);
+    }
+
+    #[test]
+    fn test_merge_percentile_mismatch() {
+        let vals = &[1f64, 2f64, 3f64, 4f64];
+        let all = Statistics::from(vals, None).unwrap();
+        let p50 = Statistics::from(vals, Some(50)).unwrap();
+        let p90 = Statistics::from(vals, Some(90)).unwrap();
+
+        assert_eq!(Err(MergeError::PercentileMismatch(None, Some(50))), all.merge(&p50));
+        assert_eq!(Err(MergeError::PercentileMismatch(Some(50), Some(90))), p50.merge(&p90));
+    }
+
+    fn assert_bundle_mergeable_fields_eq(expected: &StatisticsBundle, actual: &StatisticsBundle) {
+        assert_mergeable_fields_eq(expected.global_stats(), actual.global_stats());
+        assert_eq!(expected.percentile_stats().len(), actual.percentile_stats().len());
+        for (e, a) in expected.percentile_stats().iter().zip(actual.percentile_stats()) {
+            assert_eq!(e.percentile(), a.percentile());
+            assert_mergeable_fields_eq(e, a);
+        }
+    }
+
+    #[test]
+    fn test_bundle_merge_matches_all_values() {
+        let a = random_values(1000, 1, 0f64);
+        let b = random_values(300, 2, 5000f64);
+        let all: Vec<f64> = a.iter().chain(b.iter()).copied().collect();
+
+        let merged = StatisticsBundle::from(&a)
+            .unwrap()
+            .merge(&StatisticsBundle::from(&b).unwrap(), MergePolicy::Strict)
+            .unwrap();
+
+        assert_bundle_mergeable_fields_eq(&StatisticsBundle::from(&all).unwrap(), &merged);
+        assert!(merged.global_stats().is_merged());
+        assert_eq!(None, merged.sample_size());
+    }
+
+    #[test]
+    fn test_bundle_merge_associative() {
+        let bundle = |seed: u64, offset: f64| {
+            let mut vals = random_values(500, seed, offset);
+            vals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            StatisticsBundle::with_percentiles(&vals, &[50, 90]).unwrap()
+        };
+
+        let (a, b, c) = (bundle(1, 0f64), bundle(2, 100f64), bundle(3, -50f64));
+        let policy = MergePolicy::Strict;
+
+        let left = a.merge(&b, policy).unwrap().merge(&c, policy).unwrap();
+        let right = a.merge(&b.merge(&c, policy).unwrap(), policy).unwrap();
+        let swapped = c.merge(&a, policy).unwrap().merge(&b, policy).unwrap();
+
+        assert_bundle_mergeable_fields_eq(&left, &right);
+        assert_bundle_mergeable_fields_eq(&left, &swapped);
+    }
+
+    #[test]
+    fn test_bundle_merge_drops_unmatched_percentiles() {
+        let vals = &[1f64, 2f64, 3f64, 4f64];
+        let a = StatisticsBundle::with_percentiles(vals, &[50, 75]).unwrap();
+        let b = StatisticsBundle::with_percentiles(vals, &[90, 50]).unwrap();
+
+        let merged = a.merge(&b, MergePolicy::DropUnmatched).unwrap();
+        assert_eq!(1, merged.percentile_stats().len());
+        assert_eq!(Some(50), merged.percentile_stats()[0].percentile());
+    }
+
+    #[test]
+    fn test_bundle_merge_keeps_omitted_percentiles() {
+        let a = StatisticsBundle::with_percentiles(&[1f64, 2f64, 3f64, 4f64, 5f64], &[10, 50]).unwrap();
+        let b = StatisticsBundle::with_percentiles(&[1f64, 2f64], &[10, 40, 50]).unwrap();
+
+        let merged = a.merge(&b, MergePolicy::DropUnmatched).unwrap();
+        let omitted: Vec<u8> = merged.omitted_percentiles().iter().map(|o| o.percentile()).collect();
+        assert_eq!(vec![10, 40], omitted);
+    }
+
+    #[test]
+    fn test_bundle_merge_strict_unmatched_percentiles() {
+        let vals = &[1f64, 2f64, 3f64, 4f64];
+        let a = StatisticsBundle::with_percentiles(vals, &[50]).unwrap();
+        let b = StatisticsBundle::with_percentiles(vals, &[50, 90]).unwrap();
+
+        assert_eq!(
+            Err(MergeError::UnmatchedPercentile(90)),
+            a.merge(&b, MergePolicy::Strict)
+        );
+        assert_eq!(
+            Err(MergeError::UnmatchedPercentile(90)),
+            b.merge(&a, MergePolicy::Strict)
+        );
+    }
+
+    #[test]
+    fn test_bundle_merge_drops_quantiles() {
+        let vals = &[1f64, 2f64, 3f64, 4f64];
+        let a = StatisticsBundle::with_quantiles(vals, &[], &[50f64], QuantileMethod::Linear).unwrap();
+
+        assert!(a.merge(&a, MergePolicy::Strict).unwrap().quantile_values().is_empty());
+    }
+
+    #[test]
+    fn test_bundle_merge_accuracy() {
+        let vals = &[1f64, 2f64, 3f64, 4f64];
+        let a = StatisticsBundle::with_percentiles(vals, &[50]).unwrap();
+        let merged = a.merge(&a, MergePolicy::Strict).unwrap();
+
+        let accuracy = |s: &Statistics| StatName::ALL.iter().map(|&n| s.accuracy(n)).collect::<Vec<_>>();
+        assert!(accuracy(a.global_stats()).iter().all(|&x| x == Accuracy::Exact));
+        assert!(accuracy(&a.percentile_stats()[0]).iter().all(|&x| x == Accuracy::Exact));
+        assert_eq!(
+            vec![
+                Accuracy::Exact,
+                Accuracy::Exact,
+                Accuracy::Exact,
+                Accuracy::Exact,
+                Accuracy::Exact,
+                Accuracy::Estimate,
+                Accuracy::Formula
+            ],
+            accuracy(merged.global_stats())
+        );
+        assert!(accuracy(&merged.percentile_stats()[0])
+            .iter()
+            .all(|&x| x == Accuracy::Estimate));
+    }
+
+    #[test]
+    fn test_bundle_merge_sample_count() {
+        let vals = &[1f64, 2f64, 3f64, 4f64];
+        let a = StatisticsBundle::from(vals).unwrap();
+        let mut b = StatisticsBundle::from(vals).unwrap();
+        b.sample_size = Some(2);
+
+        assert_eq!(None, a.merge(&a, MergePolicy::Strict).unwrap().sample_size());
+        assert_eq!(Some(6), a.merge(&b, MergePolicy::Strict).unwrap().sample_size());
+    }
+}
@@ -0,0 +1,158 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{write_ordered, KeyValueSep, Statistics, DISPLAY_PRECISION};
+use std::fmt;
+use std::str::FromStr;
+
+/// Transformation applied to each value based on statistics of all values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Normalization {
+    /// Scale values to the range 0 to 1 using `(x - lower) / (upper - lower)`.
+    MinMax,
+    /// Standardize values using `(x - mean) / stddev`.
+    ZScore,
+}
+
+impl FromStr for Normalization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Normalization, Self::Err> {
+        if "minmax" == s {
+            Ok(Normalization::MinMax)
+        } else if "zscore" == s {
+            Ok(Normalization::ZScore)
+        } else {
+            Err(format!("Invalid normalization {}", s))
+        }
+    }
+}
+
+/// Normalizes individual values based on statistics of all values.
+///
+/// When all values are the same (the range or standard deviation is zero)
+/// every value is normalized to `0.0` instead of dividing by zero.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Normalizer {
+    offset: f64,
+    scale: f64,
+}
+
+impl Normalizer {
+    /// Create a normalizer from global statistics of a sequence of values.
+    pub fn new(normalization: Normalization, stats: &Statistics) -> Normalizer {
+        let (offset, scale) = match normalization {
+            Normalization::MinMax => (stats.lower(), stats.upper() - stats.lower()),
+            Normalization::ZScore => (stats.mean(), stats.stddev()),
+        };
+
+        Normalizer { offset, scale }
+    }
+
+    pub fn normalize(&self, val: f64) -> f64 {
+        if self.scale == 0f64 {
+            0f64
+        } else {
+            (val - self.offset) / self.scale
+        }
+    }
+}
+
+/// Render each of a sequence of values, normalized, in its original order.
+#[derive(Debug)]
+pub struct NormalizationFormatter<'a> {
+    vals: &'a [f64],
+    normalizer: &'a Normalizer,
+    precision: usize,
+}
+
+impl<'a> NormalizationFormatter<'a> {
+    pub fn new(vals: &'a [f64], normalizer: &'a Normalizer) -> NormalizationFormatter<'a> {
+        NormalizationFormatter {
+            vals,
+            normalizer,
+            precision: DISPLAY_PRECISION,
+        }
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+}
+
+impl<'a> fmt::Display for NormalizationFormatter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_ordered(f, self.vals, &KeyValueSep::default(), |v| {
+            vec![format!("{:.*}", self.precision, self.normalizer.normalize(v))]
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Normalization, NormalizationFormatter, Normalizer};
+    use crate::Statistics;
+
+    const VALUES: &[f64] = &[4f64, 2f64, 10f64, 6f64, 8f64];
+
+    const SAME: &[f64] = &[3f64, 3f64, 3f64];
+
+    fn format(vals: &[f64], normalization: Normalization, precision: usize) -> String {
+        let stats = Statistics::from(vals, None).unwrap();
+        let normalizer = Normalizer::new(normalization, &stats);
+        format!(
+            "{}",
+            NormalizationFormatter::new(vals, &normalizer).precision(precision)
+        )
+    }
+
+    #[test]
+    fn test_normalization_from_str() {
+        assert_eq!(Normalization::MinMax, "minmax".parse().unwrap());
+        assert_eq!(Normalization::ZScore, "zscore".parse().unwrap());
+        assert!("banana".parse::<Normalization>().is_err());
+    }
+
+    #[test]
+    fn test_normalize_minmax() {
+        assert_eq!(
+            "0.25\n0.00\n1.00\n0.50\n0.75\n",
+            format(VALUES, Normalization::MinMax, 2)
+        );
+    }
+
+    #[test]
+    fn test_normalize_zscore() {
+        // Mean of 6 and a standard deviation of sqrt(8)
+        assert_eq!(
+            "-0.7071\n-1.4142\n1.4142\n0.0000\n0.7071\n",
+            format(VALUES, Normalization::ZScore, 4)
+        );
+    }
+
+    #[test]
+    fn test_normalize_minmax_all_same() {
+        assert_eq!("0.0\n0.0\n0.0\n", format(SAME, Normalization::MinMax, 1));
+    }
+
+    #[test]
+    fn test_normalize_zscore_all_same() {
+        assert_eq!("0.0\n0.0\n0.0\n", format(SAME, Normalization::ZScore, 1));
+    }
+}
@@ -0,0 +1,139 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use std::fmt;
+
+/// Reason that a requested percentile slice isn't part of a bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Omission {
+    /// There weren't enough values for the slice to contain any of them,
+    /// e.g. the 10th percentile of fewer than 10 values.
+    InsufficientData,
+    /// The slice had fewer values than the minimum needed for its
+    /// statistics to be meaningful, see `StatisticsBundle::with_min_samples`.
+    BelowMinSamples { min: usize },
+}
+
+impl fmt::Display for Omission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Omission::InsufficientData => write!(f, "not enough values"),
+            Omission::BelowMinSamples { min } => write!(f, "fewer than {} values", min),
+        }
+    }
+}
+
+/// A percentile that statistics were requested for but that was omitted
+/// from a bundle, along with the reason it was.
+///
+/// ```
+/// use staccato::{Omission, StatisticsBundle};
+///
+/// let bundle = StatisticsBundle::with_percentiles(&[1.0, 2.0, 3.0, 4.0, 5.0], &[10, 99]).unwrap();
+/// let omitted = bundle.omitted_percentiles();
+/// assert_eq!(1, omitted.len());
+/// assert_eq!(10, omitted[0].percentile());
+/// assert_eq!(Omission::InsufficientData, omitted[0].reason());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OmittedPercentile {
+    percentile: u8,
+    reason: Omission,
+}
+
+impl OmittedPercentile {
+    pub fn new(percentile: u8, reason: Omission) -> OmittedPercentile {
+        OmittedPercentile { percentile, reason }
+    }
+
+    pub fn percentile(&self) -> u8 {
+        self.percentile
+    }
+
+    pub fn reason(&self) -> Omission {
+        self.reason
+    }
+}
+
+/// A quantile whose value was requested but that was omitted from a bundle,
+/// along with the reason it was.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OmittedQuantile {
+    percent: f64,
+    reason: Omission,
+}
+
+impl OmittedQuantile {
+    pub fn new(percent: f64, reason: Omission) -> OmittedQuantile {
+        OmittedQuantile { percent, reason }
+    }
+
+    /// Quantile expressed as a percent from 0 to 100.
+    pub fn percent(&self) -> f64 {
+        self.percent
+    }
+
+    pub fn reason(&self) -> Omission {
+        self.reason
+    }
+
+    /// Key the value of the quantile would have been displayed with, e.g.
+    /// "p99.9".
+    pub fn key(&self) -> String {
+        format!("p{}", self.percent)
+    }
+}
+
+/// Percentiles that were requested but don't have statistics, each omitted
+/// for not having enough values, in ascending order without duplicates.
+pub(crate) fn omitted_from<I: IntoIterator<Item = u8>>(requested: &[u8], computed: I) -> Vec<OmittedPercentile> {
+    let computed: Vec<u8> = computed.into_iter().collect();
+    let mut out: Vec<u8> = requested.iter().copied().filter(|p| !computed.contains(p)).collect();
+    out.sort_unstable();
+    out.dedup();
+    out.into_iter()
+        .map(|p| OmittedPercentile::new(p, Omission::InsufficientData))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{omitted_from, Omission, OmittedPercentile};
+
+    #[test]
+    fn test_omitted_from() {
+        let omitted = omitted_from(&[99, 50, 95, 99], vec![50]);
+        assert_eq!(
+            vec![
+                OmittedPercentile::new(95, Omission::InsufficientData),
+                OmittedPercentile::new(99, Omission::InsufficientData),
+            ],
+            omitted
+        );
+        assert!(omitted_from(&[50], vec![50]).is_empty());
+    }
+
+    #[test]
+    fn test_omission_display() {
+        assert_eq!("not enough values", Omission::InsufficientData.to_string());
+        assert_eq!(
+            "fewer than 10 values",
+            Omission::BelowMinSamples { min: 10 }.to_string()
+        );
+    }
+}
@@ -0,0 +1,265 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{KeyValueSep, DISPLAY_PRECISION};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Statistics about the relationship between two sequences of values where
+/// the values at each position form a pair.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairedStatistics {
+    count: usize,
+    pearson_r: Option<f64>,
+    spearman_rho: Option<f64>,
+}
+
+impl PairedStatistics {
+    /// Create paired statistics from two sequences of values in their
+    /// original order (**not** sorted) so that the values at each position
+    /// are a pair.
+    ///
+    /// This method returns `None` if the sequences are empty or are not the
+    /// same length.
+    pub fn from(a: &[f64], b: &[f64]) -> Option<PairedStatistics> {
+        if a.is_empty() || a.len() != b.len() {
+            return None;
+        }
+
+        Some(PairedStatistics {
+            count: a.len(),
+            pearson_r: pearson(a, b),
+            spearman_rho: pearson(&ranks(a), &ranks(b)),
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Pearson correlation coefficient of the pairs or `None` if either
+    /// sequence has no variance.
+    pub fn pearson_r(&self) -> Option<f64> {
+        self.pearson_r
+    }
+
+    /// Spearman rank correlation coefficient of the pairs or `None` if either
+    /// sequence has no variance. This is the Pearson correlation of the ranks
+    /// of each value so it measures any monotonic relationship, not just a
+    /// linear one.
+    pub fn spearman_rho(&self) -> Option<f64> {
+        self.spearman_rho
+    }
+}
+
+/// Compute the rank (starting from 1) of each value in a sequence of values,
+/// in the same order as the values.
+///
+/// Tied values are each assigned the average of the ranks they span.
+pub fn ranks(vals: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..vals.len()).collect();
+    order.sort_by(|&x, &y| vals[x].partial_cmp(&vals[y]).unwrap_or(Ordering::Less));
+
+    let mut out = vec![0f64; vals.len()];
+    let mut start = 0;
+
+    while start < order.len() {
+        let val = vals[order[start]];
+        let end = start + order[start..].iter().take_while(|&&i| vals[i] == val).count().max(1);
+
+        // Ranks from start + 1 to end, inclusive, averaged.
+        let rank = (start + 1 + end) as f64 / 2f64;
+        for &i in &order[start..end] {
+            out[i] = rank;
+        }
+
+        start = end;
+    }
+
+    out
+}
+
+fn pearson(a: &[f64], b: &[f64]) -> Option<f64> {
+    let n = a.len() as f64;
+    let mean_a = a.iter().sum::<f64>() / n;
+    let mean_b = b.iter().sum::<f64>() / n;
+
+    let (mut cov, mut var_a, mut var_b) = (0f64, 0f64, 0f64);
+    for (&x, &y) in a.iter().zip(b.iter()) {
+        let (dx, dy) = (x - mean_a, y - mean_b);
+        cov += dx * dy;
+        var_a += dx * dx;
+        var_b += dy * dy;
+    }
+
+    if var_a == 0f64 || var_b == 0f64 {
+        return None;
+    }
+
+    // Clamp to account for rounding error pushing perfect correlations
+    // slightly outside of the valid range.
+    Some((cov / (var_a.sqrt() * var_b.sqrt())).clamp(-1f64, 1f64))
+}
+
+#[derive(Debug)]
+pub struct PairedStatisticsFormatter<'a> {
+    stats: &'a PairedStatistics,
+    sep: KeyValueSep,
+    precision: usize,
+}
+
+impl<'a> PairedStatisticsFormatter<'a> {
+    pub fn new(stats: &'a PairedStatistics) -> PairedStatisticsFormatter<'a> {
+        Self::with_sep(stats, KeyValueSep::Colon)
+    }
+
+    pub fn with_sep(stats: &'a PairedStatistics, sep: KeyValueSep) -> PairedStatisticsFormatter<'a> {
+        PairedStatisticsFormatter {
+            stats,
+            sep,
+            precision: DISPLAY_PRECISION,
+        }
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+}
+
+impl<'a> fmt::Display for PairedStatisticsFormatter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (s, sep, p) = (self.stats, &self.sep, self.precision);
+
+        writeln!(f, "pairs{}{}", sep, s.count())?;
+        if let Some(r) = s.pearson_r() {
+            writeln!(f, "pearson_r{}{:.*}", sep, p, r)?;
+        }
+
+        if let Some(r) = s.spearman_rho() {
+            writeln!(f, "spearman_r{}{:.*}", sep, p, r)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ranks, PairedStatistics, PairedStatisticsFormatter};
+
+    const VALUES: &[f64] = &[3f64, -1f64, 4f64, 1f64, 5f64, 9f64, 2f64, 6f64];
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!((expected - actual).abs() < 1e-12, "{} != {}", expected, actual);
+    }
+
+    #[test]
+    fn test_ranks_distinct() {
+        assert_eq!(vec![3f64, 1f64, 4f64, 2f64], ranks(&[30f64, 10f64, 40f64, 20f64]));
+    }
+
+    #[test]
+    fn test_ranks_ties() {
+        assert_eq!(
+            vec![1.5, 1.5, 4f64, 4f64, 4f64, 6f64],
+            ranks(&[1f64, 1f64, 2f64, 2f64, 2f64, 3f64])
+        );
+    }
+
+    #[test]
+    fn test_ranks_heavy_ties_unsorted() {
+        let vals = &[5f64, 1f64, 5f64, 5f64, 1f64, 5f64, 5f64, 9f64];
+        assert_eq!(vec![5f64, 1.5, 5f64, 5f64, 1.5, 5f64, 5f64, 8f64], ranks(vals));
+    }
+
+    #[test]
+    fn test_ranks_all_tied() {
+        assert_eq!(vec![2.5; 4], ranks(&[7f64; 4]));
+    }
+
+    #[test]
+    fn test_ranks_sum_unchanged_by_ties() {
+        let vals = &[2f64, 2f64, 2f64, 1f64, 1f64, 3f64, 3f64, 3f64, 3f64];
+        let n = vals.len() as f64;
+        assert_eq!(n * (n + 1f64) / 2f64, ranks(vals).iter().sum::<f64>());
+    }
+
+    #[test]
+    fn test_paired_statistics_mismatched() {
+        assert!(PairedStatistics::from(&[1f64, 2f64], &[1f64]).is_none());
+        assert!(PairedStatistics::from(&[], &[]).is_none());
+    }
+
+    #[test]
+    fn test_paired_statistics_linear() {
+        let doubled: Vec<f64> = VALUES.iter().map(|v| v * 2f64 + 1f64).collect();
+        let stats = PairedStatistics::from(VALUES, &doubled).unwrap();
+
+        assert_close(1f64, stats.pearson_r().unwrap());
+        assert_close(1f64, stats.spearman_rho().unwrap());
+    }
+
+    #[test]
+    fn test_spearman_monotone_transform() {
+        let transforms: Vec<fn(f64) -> f64> = vec![f64::exp, |v| v.powi(3), |v| -1f64 / (v + 10f64)];
+
+        for t in transforms {
+            let transformed: Vec<f64> = VALUES.iter().map(|&v| t(v)).collect();
+            let stats = PairedStatistics::from(VALUES, &transformed).unwrap();
+
+            assert_close(1f64, stats.spearman_rho().unwrap());
+            assert!(stats.pearson_r().unwrap() < 1f64);
+        }
+    }
+
+    #[test]
+    fn test_spearman_reversed() {
+        let reversed: Vec<f64> = VALUES.iter().map(|&v| -v.powi(3)).collect();
+        let stats = PairedStatistics::from(VALUES, &reversed).unwrap();
+
+        assert_close(-1f64, stats.spearman_rho().unwrap());
+    }
+
+    #[test]
+    fn test_spearman_heavy_ties() {
+        // Ranks of a are [2, 2, 2, 5, 5, 5] and b are [1.5, 1.5, 4, 4, 4, 6] giving
+        // a covariance of 10.5 and variances of 13.5 and 15
+        let a = &[1f64, 1f64, 1f64, 2f64, 2f64, 2f64];
+        let b = &[1f64, 1f64, 2f64, 2f64, 2f64, 3f64];
+        let stats = PairedStatistics::from(a, b).unwrap();
+
+        assert_close(10.5 / 202.5f64.sqrt(), stats.spearman_rho().unwrap());
+    }
+
+    #[test]
+    fn test_paired_statistics_no_variance() {
+        let stats = PairedStatistics::from(&[1f64, 2f64, 3f64], &[4f64, 4f64, 4f64]).unwrap();
+
+        assert_eq!(None, stats.pearson_r());
+        assert_eq!(None, stats.spearman_rho());
+    }
+
+    #[test]
+    fn test_paired_statistics_formatter() {
+        let stats = PairedStatistics::from(&[1f64, 2f64, 3f64], &[1f64, 4f64, 9f64]).unwrap();
+        let out = format!("{}", PairedStatisticsFormatter::new(&stats).precision(2));
+
+        assert_eq!("pairs: 3\npearson_r: 0.99\nspearman_r: 1.00\n", out);
+    }
+}
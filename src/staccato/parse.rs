@@ -0,0 +1,687 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{strip_line_ending, KeyValueSep, LineExtractor, ParseLineError, PlainExtractor, StaccatoError, Value};
+use std::fmt;
+use std::io::BufRead;
+
+/// Iterator over values parsed from each line of a reader, created by
+/// `parse_values` or `parse_values_with`.
+#[derive(Debug)]
+pub struct ParseValues<R, E = PlainExtractor> {
+    reader: R,
+    extractor: E,
+    buf: Vec<u8>,
+    line: usize,
+    blank: usize,
+    offset: u64,
+    read: u64,
+    pending: Vec<f64>,
+    next: usize,
+    done: bool,
+}
+
+/// Lazily parse a value from each line of a reader, so that values can be
+/// filtered or processed as they're read, or reading can stop early.
+///
+/// Leading and trailing whitespace is removed before parsing each line, and
+/// blank lines are skipped. Lines that aren't numbers result in an error with
+/// the line number (counting from 1) and reading continues with the next line,
+/// as it does after a line that isn't valid UTF-8. Reading stops after any
+/// other error from the reader.
+///
+/// ```
+/// use staccato::{parse_values, ParseLineError};
+///
+/// let mut values = parse_values("1.5\n\nfoo\n3\n".as_bytes());
+/// assert_eq!(Some(1.5), values.next().unwrap().ok());
+/// assert!(matches!(values.next(), Some(Err(ParseLineError::Invalid { line: 3, .. }))));
+/// assert_eq!(Some(3.0), values.next().unwrap().ok());
+/// assert!(values.next().is_none());
+/// ```
+pub fn parse_values<R: BufRead>(reader: R) -> ParseValues<R> {
+    parse_values_with(reader, PlainExtractor)
+}
+
+/// Lazily produce values from each line of a reader using an extractor, the
+/// same way as `parse_values` except that each line may result in any number
+/// of values. Lines the extractor reports as malformed result in an error with
+/// the line number and the line without leading or trailing whitespace.
+///
+/// ```
+/// use staccato::{parse_values_with, FieldExtractor};
+///
+/// let extractor = FieldExtractor::new(2).unwrap();
+/// let vals: Vec<f64> = parse_values_with("GET 12.5\nPOST 40\n".as_bytes(), extractor)
+///     .flat_map(|r| r.ok())
+///     .collect();
+/// assert_eq!(vec![12.5, 40.0], vals);
+/// ```
+pub fn parse_values_with<R: BufRead, E: LineExtractor>(reader: R, extractor: E) -> ParseValues<R, E> {
+    ParseValues {
+        reader,
+        extractor,
+        buf: Vec::new(),
+        line: 0,
+        blank: 0,
+        offset: 0,
+        read: 0,
+        pending: Vec::new(),
+        next: 0,
+        done: false,
+    }
+}
+
+impl<R, E> ParseValues<R, E> {
+    /// Number of lines read so far, including blank lines and lines that
+    /// couldn't be parsed.
+    pub fn lines(&self) -> usize {
+        self.line
+    }
+
+    /// Number of lines read so far that were empty or only whitespace.
+    pub fn blank_lines(&self) -> usize {
+        self.blank
+    }
+
+    /// Byte offset (counting from 0) of the start of the last line read.
+    pub fn line_offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl<R: BufRead, E: LineExtractor> Iterator for ParseValues<R, E> {
+    type Item = Result<f64, ParseLineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(&v) = self.pending.get(self.next) {
+                self.next += 1;
+                return Some(Ok(v));
+            }
+
+            if self.done {
+                return None;
+            }
+
+            self.pending.clear();
+            self.next = 0;
+            self.buf.clear();
+            match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(0) => self.done = true,
+                Ok(n) => {
+                    self.line += 1;
+                    self.offset = self.read;
+                    self.read += n as u64;
+                    // Lines that aren't UTF-8 are still consumed so that reading
+                    // can continue with the next line.
+                    let line = match std::str::from_utf8(&self.buf) {
+                        Ok(l) => strip_line_ending(l),
+                        Err(_) => return Some(Err(ParseLineError::Utf8 { line: self.line })),
+                    };
+                    let trimmed = line.trim();
+                    if trimmed.is_empty() {
+                        self.blank += 1;
+                        continue;
+                    }
+
+                    if !self.extractor.extract(line, &mut self.pending) {
+                        self.pending.clear();
+                        return Some(Err(ParseLineError::Invalid {
+                            line: self.line,
+                            content: trimmed.to_string(),
+                        }));
+                    }
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(ParseLineError::Io {
+                        line: self.line + 1,
+                        error: e,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+/// Number of lines that couldn't be parsed to keep in `ParsedValues` unless
+/// another number is given, e.g. to `collect_parsed_values_sampled`.
+pub const DEFAULT_MALFORMED_SAMPLES: usize = 5;
+
+/// Maximum number of characters of each line that couldn't be parsed to keep.
+const MAX_ERROR_CONTENT: usize = 40;
+
+/// A line that couldn't be parsed, kept as a sample of the malformed lines
+/// of the input so that they can be found and fixed.
+///
+/// The content is shortened to at most 40 characters (marked with "...")
+/// and control characters in it are escaped, so that it's safe to print.
+///
+/// ```
+/// use staccato::collect_parsed_values;
+///
+/// let parsed = collect_parsed_values("1\nfoo\tbar\n".as_bytes()).unwrap();
+/// let sample = &parsed.first_errors()[0];
+/// assert_eq!(2, sample.line());
+/// assert_eq!(2, sample.offset());
+/// assert_eq!("foo\\tbar", sample.content());
+/// assert_eq!("line 2 (byte 2): foo\\tbar", sample.to_string());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MalformedLine {
+    line: usize,
+    offset: u64,
+    content: String,
+}
+
+impl MalformedLine {
+    pub(crate) fn new(line: usize, offset: u64, content: &str) -> MalformedLine {
+        MalformedLine {
+            line,
+            offset,
+            content: escape_control(&truncate_content(content)),
+        }
+    }
+
+    /// Line number, counting from 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Byte offset of the start of the line, counting from 0.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Line without leading or trailing whitespace, shortened and escaped.
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
+impl fmt::Display for MalformedLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {} (byte {}): {}", self.line, self.offset, self.content)
+    }
+}
+
+/// Values parsed from each line of a reader along with counts of the lines
+/// that weren't values, to report the quality of the input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedValues<V = f64> {
+    values: Vec<V>,
+    total_lines: usize,
+    malformed: usize,
+    blank: usize,
+    first_errors: Vec<MalformedLine>,
+}
+
+impl<V> ParsedValues<V> {
+    /// Parsed values, in the order they were read unless they were sorted.
+    pub fn values(&self) -> &[V] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<V> {
+        self.values
+    }
+
+    /// Number of lines read, including blank lines and lines that weren't values.
+    pub fn total_lines(&self) -> usize {
+        self.total_lines
+    }
+
+    /// Number of lines that were values (or that values were extracted
+    /// from), i.e. that weren't blank or malformed.
+    pub fn valid(&self) -> usize {
+        self.total_lines - self.blank - self.malformed
+    }
+
+    /// Number of lines that weren't blank but couldn't be parsed as values.
+    pub fn malformed(&self) -> usize {
+        self.malformed
+    }
+
+    /// Number of lines that were empty or only whitespace.
+    pub fn blank(&self) -> usize {
+        self.blank
+    }
+
+    /// The first few lines that couldn't be parsed, at most as many as
+    /// requested when parsing (`DEFAULT_MALFORMED_SAMPLES` by default).
+    pub fn first_errors(&self) -> &[MalformedLine] {
+        &self.first_errors
+    }
+
+    pub(crate) fn values_mut(&mut self) -> &mut Vec<V> {
+        &mut self.values
+    }
+}
+
+/// Render a report of the number of lines that were values, blank, or
+/// malformed along with the line number, byte offset, and content of the
+/// first few malformed lines, to check input without computing statistics
+/// of it.
+///
+/// ```
+/// use staccato::{collect_parsed_values, ValidationFormatter};
+///
+/// let parsed = collect_parsed_values("1\n\nfoo\n3\n".as_bytes()).unwrap();
+/// assert_eq!(
+///     "lines: 4\nvalid: 2\nblank: 1\nmalformed: 1\nline 3 (byte 3): foo\n",
+///     ValidationFormatter::new(&parsed).to_string()
+/// );
+/// ```
+#[derive(Debug)]
+pub struct ValidationFormatter<'a, V = f64> {
+    parsed: &'a ParsedValues<V>,
+    sep: KeyValueSep,
+}
+
+impl<'a, V> ValidationFormatter<'a, V> {
+    pub fn new(parsed: &'a ParsedValues<V>) -> ValidationFormatter<'a, V> {
+        Self::with_sep(parsed, KeyValueSep::Colon)
+    }
+
+    pub fn with_sep(parsed: &'a ParsedValues<V>, sep: KeyValueSep) -> ValidationFormatter<'a, V> {
+        ValidationFormatter { parsed, sep }
+    }
+}
+
+impl<'a, V> fmt::Display for ValidationFormatter<'a, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "lines{}{}", self.sep, self.parsed.total_lines())?;
+        writeln!(f, "valid{}{}", self.sep, self.parsed.valid())?;
+        writeln!(f, "blank{}{}", self.sep, self.parsed.blank())?;
+        writeln!(f, "malformed{}{}", self.sep, self.parsed.malformed())?;
+        for m in self.parsed.first_errors() {
+            writeln!(f, "line {} (byte {}){}{}", m.line(), m.offset(), self.sep, m.content())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse values from each line of a reader the same way as `get_values`,
+/// skipping lines that aren't numbers and stopping at the first line that
+/// isn't valid UTF-8 or error reading.
+pub fn collect_values<R: BufRead>(reader: R) -> Result<Vec<f64>, StaccatoError> {
+    collect_parsed_values(reader).map(ParsedValues::into_values)
+}
+
+/// Parse values the same way as `collect_values`, keeping counts of the
+/// lines that weren't values and a sample of the first few of them.
+pub fn collect_parsed_values<R: BufRead>(reader: R) -> Result<ParsedValues, StaccatoError> {
+    collect_values_into(reader, Vec::new(), PlainExtractor, DEFAULT_MALFORMED_SAMPLES)
+}
+
+/// Produce values from each line of a reader using an extractor the same way
+/// as `parse_values_with`, skipping malformed lines and stopping at the first
+/// line that isn't valid UTF-8 or error reading.
+pub fn collect_values_with<R: BufRead, E: LineExtractor>(reader: R, extractor: E) -> Result<Vec<f64>, StaccatoError> {
+    collect_parsed_values_with(reader, extractor).map(ParsedValues::into_values)
+}
+
+/// Produce values using an extractor the same way as `collect_values_with`,
+/// keeping counts of the lines that weren't values the same way as
+/// `collect_parsed_values`.
+pub fn collect_parsed_values_with<R: BufRead, E: LineExtractor>(
+    reader: R,
+    extractor: E,
+) -> Result<ParsedValues, StaccatoError> {
+    collect_values_into(reader, Vec::new(), extractor, DEFAULT_MALFORMED_SAMPLES)
+}
+
+/// Produce values the same way as `collect_parsed_values_with`, keeping up
+/// to `samples` of the lines that weren't values instead of the default
+/// number of them. Only the sample is kept, so memory use doesn't depend on
+/// how many lines are malformed.
+///
+/// ```
+/// use staccato::{collect_parsed_values_sampled, PlainExtractor};
+///
+/// let parsed = collect_parsed_values_sampled("a\nb\n1\nc\n".as_bytes(), PlainExtractor, 2).unwrap();
+/// assert_eq!(3, parsed.malformed());
+/// assert_eq!(2, parsed.first_errors().len());
+/// ```
+pub fn collect_parsed_values_sampled<R: BufRead, E: LineExtractor>(
+    reader: R,
+    extractor: E,
+    samples: usize,
+) -> Result<ParsedValues, StaccatoError> {
+    collect_values_into(reader, Vec::new(), extractor, samples)
+}
+
+pub(crate) fn collect_values_into<V: Value, R: BufRead, E: LineExtractor>(
+    reader: R,
+    values: Vec<V>,
+    extractor: E,
+    samples: usize,
+) -> Result<ParsedValues<V>, StaccatoError> {
+    let mut parsed = ParsedValues {
+        values,
+        total_lines: 0,
+        malformed: 0,
+        blank: 0,
+        first_errors: Vec::new(),
+    };
+
+    let mut lines = parse_values_with(reader, extractor);
+    while let Some(res) = lines.next() {
+        match res {
+            Ok(v) => parsed.values.push(V::from_f64(v)),
+            Err(ParseLineError::Invalid { line, content }) => {
+                parsed.malformed += 1;
+                if parsed.first_errors.len() < samples {
+                    let offset = lines.line_offset();
+                    parsed.first_errors.push(MalformedLine::new(line, offset, &content));
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    parsed.total_lines = lines.lines();
+    parsed.blank = lines.blank_lines();
+    Ok(parsed)
+}
+
+/// Shorten the content of a line to at most `MAX_ERROR_CONTENT` characters,
+/// marking it with "..." if it was shortened.
+fn truncate_content(content: &str) -> String {
+    match content.char_indices().nth(MAX_ERROR_CONTENT) {
+        Some((i, _)) => format!("{}...", &content[..i]),
+        None => content.to_string(),
+    }
+}
+
+/// Escape control characters (e.g. tabs or terminal escape sequences) in
+/// the content of a line so that it can be printed safely.
+fn escape_control(content: &str) -> String {
+    let mut escaped = String::with_capacity(content.len());
+    for c in content.chars() {
+        if c.is_control() {
+            escaped.extend(c.escape_default());
+        } else {
+            escaped.push(c);
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        collect_parsed_values, collect_parsed_values_sampled, collect_parsed_values_with, collect_values,
+        escape_control, parse_values, parse_values_with, truncate_content, MalformedLine, ValidationFormatter,
+        DEFAULT_MALFORMED_SAMPLES, MAX_ERROR_CONTENT,
+    };
+    use crate::{
+        get_values, Delimiter, FieldExtractor, KeyValueSep, ParseLineError, PlainExtractor, SortingPolicy,
+        SplitExtractor, StaccatoError,
+    };
+    use std::io::{self, BufReader, Cursor, Read};
+
+    /// Reader that returns some bytes and then fails.
+    struct FailingReader {
+        bytes: &'static [u8],
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.bytes.is_empty() {
+                return Err(io::Error::other("disk on fire"));
+            }
+
+            let n = buf.len().min(self.bytes.len());
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_parse_values() {
+        let vals: Vec<f64> = parse_values(Cursor::new("1\n 2.5 \r\n-3\n"))
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(vec![1f64, 2.5, -3f64], vals);
+    }
+
+    #[test]
+    fn test_parse_values_line_numbers() {
+        let errors: Vec<(usize, String)> = parse_values(Cursor::new("1\nfoo\n\n  \n2\nbar\n"))
+            .filter_map(|r| match r {
+                Err(ParseLineError::Invalid { line, content }) => Some((line, content)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![(2, "foo".to_string()), (6, "bar".to_string())], errors);
+    }
+
+    #[test]
+    fn test_parse_values_blank_lines_skipped() {
+        let mut values = parse_values(Cursor::new("\n\n  \n4\n"));
+        assert_eq!(4f64, values.next().unwrap().unwrap());
+        assert!(values.next().is_none());
+        assert_eq!(4, values.lines());
+    }
+
+    #[test]
+    fn test_parse_values_early_exit() {
+        let mut values = parse_values(Cursor::new("1\n2\n3\n4\n"));
+        let first: Vec<f64> = values.by_ref().take(2).map(|r| r.unwrap()).collect();
+        assert_eq!(vec![1f64, 2f64], first);
+        assert_eq!(2, values.lines());
+    }
+
+    #[test]
+    fn test_parse_values_continues_after_invalid_utf8() {
+        let bytes: &[u8] = b"1\n\xff\xfe\n3\n";
+        let res: Vec<Result<f64, ParseLineError>> = parse_values(Cursor::new(bytes)).collect();
+        assert_eq!(3, res.len());
+        assert!(matches!(res[1], Err(ParseLineError::Utf8 { line: 2 })));
+        assert_eq!(3f64, *res[2].as_ref().unwrap());
+    }
+
+    #[test]
+    fn test_parse_values_stops_after_io_error() {
+        let reader = BufReader::new(FailingReader { bytes: b"1\n2\n" });
+        let res: Vec<Result<f64, ParseLineError>> = parse_values(reader).collect();
+        assert_eq!(3, res.len());
+        assert!(matches!(res[2], Err(ParseLineError::Io { line: 3, .. })));
+    }
+
+    #[test]
+    fn test_collect_values_same_as_get_values() {
+        let input = "1\nfoo\n\n3.5\n-2\n";
+        let expected = get_values(&mut Cursor::new(input), SortingPolicy::Unsorted).unwrap();
+        assert_eq!(expected, collect_values(Cursor::new(input)).unwrap());
+        assert_eq!(vec![1f64, 3.5, -2f64], expected);
+    }
+
+    #[test]
+    fn test_collect_values_invalid_utf8() {
+        let bytes: &[u8] = b"1\n2\n\xff\n";
+        assert!(matches!(
+            collect_values(Cursor::new(bytes)),
+            Err(StaccatoError::Utf8 { line: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_collect_parsed_values_counts() {
+        let input = "1\nfoo\n\n2.5\n  \n1e3\nbar baz\n-4\n\n";
+        let parsed = collect_parsed_values(Cursor::new(input)).unwrap();
+
+        assert_eq!(&[1f64, 2.5, 1000f64, -4f64], parsed.values());
+        assert_eq!(9, parsed.total_lines());
+        assert_eq!(2, parsed.malformed());
+        assert_eq!(3, parsed.blank());
+        assert_eq!(4, parsed.valid());
+        assert_eq!(
+            &[MalformedLine::new(2, 2, "foo"), MalformedLine::new(7, 18, "bar baz")],
+            parsed.first_errors()
+        );
+    }
+
+    #[test]
+    fn test_validation_formatter() {
+        let input = "1\nfoo\n\n2.5\nbar baz\n";
+        let parsed = collect_parsed_values(Cursor::new(input)).unwrap();
+
+        assert_eq!(
+            "lines\t5\nvalid\t2\nblank\t1\nmalformed\t2\nline 2 (byte 2)\tfoo\nline 5 (byte 11)\tbar baz\n",
+            ValidationFormatter::with_sep(&parsed, KeyValueSep::Tab).to_string()
+        );
+    }
+
+    #[test]
+    fn test_validation_formatter_clean_input() {
+        let parsed = collect_parsed_values(Cursor::new("1\n2\n")).unwrap();
+
+        assert_eq!(
+            "lines: 2\nvalid: 2\nblank: 0\nmalformed: 0\n",
+            ValidationFormatter::new(&parsed).to_string()
+        );
+    }
+
+    #[test]
+    fn test_collect_parsed_values_first_errors_capped() {
+        let input = "x\n1\n".repeat(8);
+        let parsed = collect_parsed_values(Cursor::new(input)).unwrap();
+
+        assert_eq!(8, parsed.values().len());
+        assert_eq!(8, parsed.malformed());
+        assert_eq!(0, parsed.blank());
+        let lines: Vec<usize> = parsed.first_errors().iter().map(MalformedLine::line).collect();
+        assert_eq!(vec![1, 3, 5, 7, 9], lines);
+        assert_eq!(DEFAULT_MALFORMED_SAMPLES, lines.len());
+    }
+
+    #[test]
+    fn test_collect_parsed_values_sampled() {
+        let input = "x\n1\n".repeat(8);
+        let parsed = collect_parsed_values_sampled(Cursor::new(&input), PlainExtractor, 2).unwrap();
+        let offsets: Vec<u64> = parsed.first_errors().iter().map(MalformedLine::offset).collect();
+        assert_eq!(vec![0, 4], offsets);
+
+        let parsed = collect_parsed_values_sampled(Cursor::new(&input), PlainExtractor, 0).unwrap();
+        assert_eq!(8, parsed.malformed());
+        assert!(parsed.first_errors().is_empty());
+    }
+
+    #[test]
+    fn test_collect_parsed_values_sampled_bounded() {
+        // Only the sample is kept no matter how many lines are malformed or
+        // how long they are.
+        let line = format!("{}\n", "x".repeat(10_000));
+        let input = line.repeat(1_000);
+        let parsed = collect_parsed_values_sampled(Cursor::new(input), PlainExtractor, 3).unwrap();
+
+        assert_eq!(1_000, parsed.malformed());
+        assert_eq!(3, parsed.first_errors().len());
+        assert_eq!(20_002, parsed.first_errors()[2].offset());
+        for m in parsed.first_errors() {
+            assert_eq!(MAX_ERROR_CONTENT + 3, m.content().len());
+        }
+    }
+
+    #[test]
+    fn test_malformed_line_offsets() {
+        // Offsets count bytes, including line endings, blank lines, and lines
+        // that aren't UTF-8.
+        let bytes: &[u8] = b"1\r\n\n\xff\n\xc3\xa9\nfoo\n";
+        let mut values = parse_values(Cursor::new(bytes));
+        assert!(values.next().unwrap().is_ok());
+        assert_eq!(0, values.line_offset());
+        assert!(matches!(values.next(), Some(Err(ParseLineError::Utf8 { line: 3 }))));
+        assert_eq!(4, values.line_offset());
+        assert!(matches!(
+            values.next(),
+            Some(Err(ParseLineError::Invalid { line: 4, .. }))
+        ));
+        assert_eq!(6, values.line_offset());
+        assert!(matches!(
+            values.next(),
+            Some(Err(ParseLineError::Invalid { line: 5, .. }))
+        ));
+        assert_eq!(9, values.line_offset());
+    }
+
+    #[test]
+    fn test_malformed_line_escaped() {
+        let m = MalformedLine::new(1, 0, "a\tb\u{1b}[31mc");
+        assert_eq!("a\\tb\\u{1b}[31mc", m.content());
+        assert_eq!("line 1 (byte 0): a\\tb\\u{1b}[31mc", m.to_string());
+        assert_eq!("é b", escape_control("é b"));
+    }
+
+    #[test]
+    fn test_collect_parsed_values_empty() {
+        let parsed = collect_parsed_values(Cursor::new("")).unwrap();
+        assert!(parsed.values().is_empty());
+        assert_eq!(0, parsed.total_lines());
+        assert!(parsed.first_errors().is_empty());
+    }
+
+    #[test]
+    fn test_parse_values_with_multiple_values_per_line() {
+        let extractor = SplitExtractor::new().delimiter(Delimiter::Char(','));
+        let res: Vec<Result<f64, ParseLineError>> =
+            parse_values_with(Cursor::new("1,2\n\n3\n4,x\n5,6,7\n"), extractor).collect();
+
+        let vals: Vec<f64> = res.iter().flat_map(|r| r.as_ref().ok().copied()).collect();
+        assert_eq!(vec![1f64, 2f64, 3f64, 5f64, 6f64, 7f64], vals);
+        assert!(matches!(res[3], Err(ParseLineError::Invalid { line: 4, ref content }) if content == "4,x"));
+    }
+
+    #[test]
+    fn test_parse_values_with_plain_same_as_parse_values() {
+        let input = "1\n  2.5 \r\nfoo\n\n-3\n";
+        let plain: Vec<String> = parse_values(Cursor::new(input)).map(|r| format!("{:?}", r)).collect();
+        let with: Vec<String> = parse_values_with(Cursor::new(input), crate::PlainExtractor)
+            .map(|r| format!("{:?}", r))
+            .collect();
+        assert_eq!(plain, with);
+    }
+
+    #[test]
+    fn test_collect_parsed_values_with_counts() {
+        let extractor = FieldExtractor::new(2).unwrap();
+        let parsed = collect_parsed_values_with(Cursor::new("a 1\n\nb\nc 2 3\n  \n"), extractor).unwrap();
+
+        assert_eq!(&[1f64, 2f64], parsed.values());
+        assert_eq!(5, parsed.total_lines());
+        assert_eq!(1, parsed.malformed());
+        assert_eq!(2, parsed.blank());
+        assert_eq!(&[MalformedLine::new(3, 5, "b")], parsed.first_errors());
+    }
+
+    #[test]
+    fn test_truncate_content() {
+        assert_eq!("short", truncate_content("short"));
+
+        let long = "é".repeat(MAX_ERROR_CONTENT + 10);
+        let truncated = truncate_content(&long);
+        assert_eq!(MAX_ERROR_CONTENT + 3, truncated.chars().count());
+        assert!(truncated.ends_with("..."));
+    }
+}
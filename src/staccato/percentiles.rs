@@ -0,0 +1,317 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::StaccatoError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Percentiles (from 1 to 99, inclusive) to compute statistics of the lower
+/// part of the values for, in ascending order without duplicates.
+///
+/// ```
+/// use staccato::{Percentiles, StatisticsBundle};
+///
+/// let percentiles: Percentiles = "99,50,99".parse().unwrap();
+/// assert_eq!(&[50, 99], percentiles.as_slice());
+/// assert_eq!("50,99", percentiles.to_string());
+///
+/// let bundle = StatisticsBundle::with_percentiles(&[1.0, 2.0, 3.0, 4.0], percentiles.as_slice()).unwrap();
+/// assert_eq!(2, bundle.percentile_stats().len());
+/// ```
+///
+/// Ranges of percentiles can be given as "START-END", which includes every
+/// percentile from START to END, or "START-END:STEP" for every STEP from
+/// START up to END.
+///
+/// ```
+/// use staccato::Percentiles;
+///
+/// let percentiles: Percentiles = "50,90-99:3".parse().unwrap();
+/// assert_eq!(&[50, 90, 93, 96, 99], percentiles.as_slice());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Percentiles {
+    values: Vec<u8>,
+}
+
+impl Percentiles {
+    /// Create percentiles from values that are each from 1 to 99, sorting
+    /// them and removing any duplicates.
+    pub fn new(values: &[u8]) -> Result<Percentiles, StaccatoError> {
+        let mut out = Vec::with_capacity(values.len());
+        for &p in values {
+            if p == 0 || p >= 100 {
+                return Err(StaccatoError::InvalidPercentile { value: p.to_string() });
+            }
+
+            out.push(p);
+        }
+
+        Ok(Self::from_valid(out))
+    }
+
+    /// Combine percentiles, e.g. from each time an option was given, in
+    /// ascending order without duplicates.
+    pub fn merged<'a, I: IntoIterator<Item = &'a Percentiles>>(all: I) -> Percentiles {
+        Self::from_valid(all.into_iter().flat_map(|p| p.values.iter().copied()).collect())
+    }
+
+    fn from_valid(mut values: Vec<u8>) -> Percentiles {
+        values.sort_unstable();
+        values.dedup();
+        Percentiles { values }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.values
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// Parse a comma separated list of percentiles or ranges of them, e.g.
+/// "50,90,99" or "50,90-99:3".
+impl FromStr for Percentiles {
+    type Err = StaccatoError;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        let mut out = Vec::new();
+        for p in val.split(',') {
+            if p.contains('-') {
+                parse_range(p, &mut out)?;
+                continue;
+            }
+
+            match p.parse::<u8>() {
+                Ok(i) if i > 0 && i < 100 => {
+                    out.push(i);
+                }
+                _ => {
+                    return Err(StaccatoError::InvalidPercentile { value: p.to_string() });
+                }
+            };
+        }
+
+        Ok(Self::from_valid(out))
+    }
+}
+
+/// Add each percentile in a range like "90-99" or "90-99:3" to `out`.
+fn parse_range(range: &str, out: &mut Vec<u8>) -> Result<(), StaccatoError> {
+    let invalid = |reason| StaccatoError::InvalidPercentileRange {
+        value: range.to_string(),
+        reason,
+    };
+
+    let (bounds, step) = match range.split_once(':') {
+        Some((bounds, step)) => match step.parse::<u8>() {
+            Ok(s) if s > 0 => (bounds, s),
+            _ => return Err(invalid("step must be a number from 1 to 255")),
+        },
+        None => (range, 1),
+    };
+
+    let (start, end) = bounds
+        .split_once('-')
+        .ok_or_else(|| invalid("expected START-END or START-END:STEP"))?;
+    let bound = |b: &str| match b.parse::<u8>() {
+        Ok(i) if i > 0 && i < 100 => Ok(i),
+        _ => Err(invalid("start and end must be numbers from 1 to 99")),
+    };
+
+    let (start, end) = (bound(start)?, bound(end)?);
+    if start > end {
+        return Err(invalid("start must not be greater than end"));
+    }
+
+    out.extend((start..=end).step_by(step as usize));
+    Ok(())
+}
+
+/// Display percentiles as a comma separated list that can be parsed again.
+impl fmt::Display for Percentiles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, p) in self.values.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+
+            write!(f, "{}", p)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Percentiles;
+    use crate::StaccatoError;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_parse_percentiles_err_not_in_range() {
+        let percents = "75,90,100,110";
+        let res = Percentiles::from_str(percents);
+
+        assert!(matches!(res, Err(StaccatoError::InvalidPercentile { ref value }) if value == "100"));
+    }
+
+    #[test]
+    fn test_parse_percentiles_lower_bound() {
+        let percents = "0,50,75";
+        let res = Percentiles::from_str(percents);
+
+        assert!(matches!(res, Err(StaccatoError::InvalidPercentile { ref value }) if value == "0"));
+    }
+
+    #[test]
+    fn test_parse_percentiles_upper_bound() {
+        let percents = "50,75,100";
+        let res = Percentiles::from_str(percents);
+
+        assert!(matches!(res, Err(StaccatoError::InvalidPercentile { ref value }) if value == "100"));
+    }
+
+    #[test]
+    fn test_parse_percentiles_err_not_a_number() {
+        let percents = "75,banana";
+        let res = Percentiles::from_str(percents);
+
+        assert!(matches!(res, Err(StaccatoError::InvalidPercentile { ref value }) if value == "banana"));
+    }
+
+    #[test]
+    fn test_parse_percentiles_ok() {
+        let percents = "75,90,95,98";
+        let res = Percentiles::from_str(percents);
+
+        assert_eq!(&[75, 90, 95, 98], res.unwrap().as_slice());
+    }
+
+    #[test]
+    fn test_parse_percentiles_sorted() {
+        let res = Percentiles::from_str("99,50,75").unwrap();
+        assert_eq!(&[50, 75, 99], res.as_slice());
+    }
+
+    #[test]
+    fn test_parse_percentiles_dedup() {
+        let res = Percentiles::from_str("99,50,99,50,50").unwrap();
+        assert_eq!(&[50, 99], res.as_slice());
+        assert_eq!(2, res.len());
+    }
+
+    #[test]
+    fn test_parse_percentiles_range() {
+        let res = Percentiles::from_str("95-99").unwrap();
+        assert_eq!(&[95, 96, 97, 98, 99], res.as_slice());
+
+        let res = Percentiles::from_str("50,1-2,99-99").unwrap();
+        assert_eq!(&[1, 2, 50, 99], res.as_slice());
+    }
+
+    #[test]
+    fn test_parse_percentiles_range_step() {
+        let res = Percentiles::from_str("90-99:3").unwrap();
+        assert_eq!(&[90, 93, 96, 99], res.as_slice());
+
+        let res = Percentiles::from_str("10-30:15,25").unwrap();
+        assert_eq!(&[10, 25], res.as_slice());
+
+        let res = Percentiles::from_str("1-99:200").unwrap();
+        assert_eq!(&[1], res.as_slice());
+    }
+
+    #[test]
+    fn test_parse_percentiles_range_reversed() {
+        let res = Percentiles::from_str("99-90");
+        assert!(matches!(
+            res,
+            Err(StaccatoError::InvalidPercentileRange { ref value, reason }) if value == "99-90" && reason.contains("greater")
+        ));
+    }
+
+    #[test]
+    fn test_parse_percentiles_range_step_zero() {
+        for range in &["90-99:0", "90-99:", "90-99:x", "90-99:-1"] {
+            let res = Percentiles::from_str(range);
+            assert!(
+                matches!(res, Err(StaccatoError::InvalidPercentileRange { ref value, reason }) if value == range && reason.contains("step")),
+                "{}",
+                range
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_percentiles_range_out_of_bounds() {
+        for range in &["0-50", "50-100", "-50", "50-", "a-b", "1-2-3"] {
+            let res = Percentiles::from_str(range);
+            assert!(
+                matches!(res, Err(StaccatoError::InvalidPercentileRange { ref value, .. }) if value == range),
+                "{}",
+                range
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_percentiles_range_message() {
+        let err = Percentiles::from_str("50,99-90").unwrap_err();
+        assert_eq!(
+            "Invalid percentile range 99-90: start must not be greater than end",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_merged_percentiles() {
+        let all = [
+            Percentiles::from_str("99").unwrap(),
+            Percentiles::from_str("90-92,99").unwrap(),
+            Percentiles::default(),
+        ];
+        assert_eq!(&[90, 91, 92, 99], Percentiles::merged(&all).as_slice());
+        assert!(Percentiles::merged(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_new_percentiles() {
+        assert_eq!(&[10, 20], Percentiles::new(&[20, 10, 20]).unwrap().as_slice());
+        assert!(Percentiles::new(&[]).unwrap().is_empty());
+        assert!(matches!(
+            Percentiles::new(&[50, 100]),
+            Err(StaccatoError::InvalidPercentile { ref value }) if value == "100"
+        ));
+    }
+
+    #[test]
+    fn test_percentiles_display_round_trip() {
+        let res = Percentiles::from_str("90,5,99").unwrap();
+        assert_eq!("5,90,99", res.to_string());
+        assert_eq!(res, res.to_string().parse().unwrap());
+        assert_eq!("", Percentiles::default().to_string());
+    }
+}
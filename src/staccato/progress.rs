@@ -0,0 +1,317 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+/// Default minimum time between progress updates.
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Number of reads between checks of how long it's been since the last
+/// update, so that the clock isn't read for every read.
+const READS_PER_CHECK: u32 = 16;
+
+/// Reader that writes a line of progress (e.g. to standard error) while
+/// reading from another reader, updated at most once per interval.
+///
+/// When the total number of bytes is known (e.g. the length of a regular
+/// file) progress is shown as a percentage with the rate of reading and an
+/// estimate of the time remaining. Otherwise the number of lines read so far
+/// is shown with the rate of reading.
+///
+/// Each update overwrites the previous one using a carriage return. Nothing
+/// is written if the input is read before the first update is due, and the
+/// line is ended once the input has been read or the reader is dropped.
+pub struct ProgressReader<R, W: Write> {
+    inner: R,
+    out: W,
+    label: String,
+    total: Option<u64>,
+    interval: Duration,
+    bytes: u64,
+    lines: u64,
+    reads: u32,
+    start: Instant,
+    last: Instant,
+    width: usize,
+    done: bool,
+}
+
+impl<R: Read, W: Write> ProgressReader<R, W> {
+    /// Create a reader that writes progress of reading `inner` to `out`,
+    /// prefixed by a label such as the name of the file being read.
+    pub fn new(inner: R, out: W, label: &str, total: Option<u64>) -> ProgressReader<R, W> {
+        let now = Instant::now();
+        ProgressReader {
+            inner,
+            out,
+            label: label.to_string(),
+            total,
+            interval: DEFAULT_PROGRESS_INTERVAL,
+            bytes: 0,
+            lines: 0,
+            reads: READS_PER_CHECK,
+            start: now,
+            last: now,
+            width: 0,
+            done: false,
+        }
+    }
+
+    /// Set the minimum time between progress updates.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    fn update(&mut self, now: Instant) {
+        let line = format!(
+            "{}: {}",
+            self.label,
+            format_progress(self.bytes, self.total, self.lines, now - self.start)
+        );
+
+        // Errors writing progress aren't worth failing to read values for.
+        let _ = write!(self.out, "\r{:<width$}", line, width = self.width);
+        let _ = self.out.flush();
+        self.width = self.width.max(line.len());
+        self.last = now;
+    }
+
+    fn finish(&mut self) {
+        if self.done {
+            return;
+        }
+
+        self.done = true;
+        if self.width > 0 {
+            self.update(Instant::now());
+            let _ = writeln!(self.out);
+        }
+    }
+}
+
+impl<R: Read, W: Write> Read for ProgressReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read == 0 {
+            self.finish();
+            return Ok(read);
+        }
+
+        self.bytes += read as u64;
+        if self.total.is_none() {
+            self.lines += buf[..read].iter().filter(|&&b| b == b'\n').count() as u64;
+        }
+
+        self.reads -= 1;
+        if self.reads == 0 {
+            self.reads = READS_PER_CHECK;
+            let now = Instant::now();
+            if now - self.last >= self.interval {
+                self.update(now);
+            }
+        }
+
+        Ok(read)
+    }
+}
+
+impl<R, W: Write> Drop for ProgressReader<R, W> {
+    fn drop(&mut self) {
+        // End the progress line if reading stopped early, e.g. due to an error.
+        if !self.done && self.width > 0 {
+            let _ = writeln!(self.out);
+        }
+    }
+}
+
+/// Describe how much of an input has been read after some amount of time.
+fn format_progress(bytes: u64, total: Option<u64>, lines: u64, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    let rate = if secs > 0f64 { bytes as f64 / secs } else { 0f64 };
+
+    match total {
+        Some(total) => {
+            let percent = if total > 0 {
+                bytes as f64 * 100f64 / total as f64
+            } else {
+                100f64
+            };
+            let eta = if rate > 0f64 {
+                format_duration(total.saturating_sub(bytes) as f64 / rate)
+            } else {
+                "-".to_string()
+            };
+
+            format!(
+                "{:.1}% of {} at {}/s, ETA {}",
+                percent,
+                format_bytes(total as f64),
+                format_bytes(rate),
+                eta
+            )
+        }
+        None => format!(
+            "{} lines ({}) at {}/s",
+            lines,
+            format_bytes(bytes as f64),
+            format_bytes(rate)
+        ),
+    }
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut val = bytes;
+    let mut unit = 0;
+    while val >= 1024f64 && unit < UNITS.len() - 1 {
+        val /= 1024f64;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{:.0}{}", val, UNITS[unit])
+    } else {
+        format!("{:.1}{}", val, UNITS[unit])
+    }
+}
+
+fn format_duration(secs: f64) -> String {
+    let secs = secs.ceil() as u64;
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_bytes, format_duration, format_progress, ProgressReader};
+    use std::io::{Cursor, Read};
+    use std::time::Duration;
+
+    fn read_all<R: Read>(reader: &mut R) -> Vec<u8> {
+        // Read a byte at a time so that there are enough reads for updates
+        let mut out = Vec::new();
+        let mut buf = [0u8; 1];
+        while reader.read(&mut buf).unwrap() > 0 {
+            out.push(buf[0]);
+        }
+
+        out
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!("512B", format_bytes(512f64));
+        assert_eq!("1.5KiB", format_bytes(1536f64));
+        assert_eq!("2.0GiB", format_bytes(2f64 * 1024f64 * 1024f64 * 1024f64));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!("0:00", format_duration(0f64));
+        assert_eq!("1:05", format_duration(64.5));
+        assert_eq!("2:00:01", format_duration(7201f64));
+    }
+
+    #[test]
+    fn test_format_progress_known_total() {
+        let progress = format_progress(512, Some(2048), 0, Duration::from_secs(2));
+        assert_eq!("25.0% of 2.0KiB at 256B/s, ETA 0:06", progress);
+    }
+
+    #[test]
+    fn test_format_progress_unknown_total() {
+        let progress = format_progress(2048, None, 100, Duration::from_secs(1));
+        assert_eq!("100 lines (2.0KiB) at 2.0KiB/s", progress);
+    }
+
+    #[test]
+    fn test_progress_reader_passes_through() {
+        let input = "1\n2\n3\n".repeat(100);
+        let mut out = Vec::new();
+        let mut reader = ProgressReader::new(Cursor::new(input.clone()), &mut out, "stdin", None);
+
+        let mut read = String::new();
+        reader.read_to_string(&mut read).unwrap();
+        assert_eq!(input, read);
+    }
+
+    #[test]
+    fn test_progress_reader_not_shown_before_interval() {
+        let mut out = Vec::new();
+        {
+            let mut reader = ProgressReader::new(Cursor::new("1\n".repeat(100)), &mut out, "stdin", None)
+                .interval(Duration::from_secs(3600));
+            read_all(&mut reader);
+        }
+
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_progress_reader_updates() {
+        let mut out = Vec::new();
+        {
+            let mut reader = ProgressReader::new(Cursor::new("1\n".repeat(100)), &mut out, "stdin", None)
+                .interval(Duration::from_secs(0));
+            read_all(&mut reader);
+        }
+
+        let out = String::from_utf8(out).unwrap();
+        let updates: Vec<&str> = out.trim_end().split('\r').filter(|u| !u.is_empty()).collect();
+
+        // An update every 16 reads and a final one once everything is read
+        assert_eq!(200 / 16 + 1, updates.len());
+        assert!(updates[0].starts_with("stdin: 8 lines (16B)"), "{}", updates[0]);
+        assert!(updates[updates.len() - 1].starts_with("stdin: 100 lines (200B)"));
+        assert!(out.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_progress_reader_known_total() {
+        let mut out = Vec::new();
+        {
+            let mut reader = ProgressReader::new(Cursor::new("1\n".repeat(100)), &mut out, "values.log", Some(200))
+                .interval(Duration::from_secs(0));
+            read_all(&mut reader);
+        }
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\rvalues.log: 8.0% of 200B"), "{}", out);
+        assert!(out.contains("\rvalues.log: 100.0% of 200B"), "{}", out);
+    }
+
+    #[test]
+    fn test_progress_reader_ends_line_when_dropped_early() {
+        let mut out = Vec::new();
+        {
+            let mut reader = ProgressReader::new(Cursor::new("1\n".repeat(100)), &mut out, "stdin", None)
+                .interval(Duration::from_secs(0));
+            let mut buf = [0u8; 1];
+            for _ in 0..16 {
+                reader.read_exact(&mut buf).unwrap();
+            }
+        }
+
+        assert_eq!(b'\n', *out.last().unwrap());
+    }
+}
@@ -0,0 +1,220 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{Percentiles, Quantile, StaccatoError, StatValue, Statistics, StatisticsBundle, StreamingStatistics};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::convert::TryFrom;
+
+impl From<StaccatoError> for PyErr {
+    fn from(e: StaccatoError) -> Self {
+        PyValueError::new_err(e.to_string())
+    }
+}
+
+/// Percentiles from values given by Python, which may be any integer.
+fn percentiles(values: Option<Vec<i64>>) -> Result<Percentiles, StaccatoError> {
+    let values = values.unwrap_or_default();
+    let mut out = Vec::with_capacity(values.len());
+    for v in values {
+        let p = u8::try_from(v).map_err(|_| StaccatoError::InvalidPercentile { value: v.to_string() })?;
+        out.push(p);
+    }
+
+    Percentiles::new(&out)
+}
+
+/// Every value produced by a Python iterable, which must all be floats (or
+/// convertible to them, like integers).
+fn values(iterable: &Bound<'_, PyAny>) -> PyResult<Vec<f64>> {
+    let mut out = Vec::new();
+    for v in iterable.try_iter()? {
+        out.push(v?.extract::<f64>()?);
+    }
+
+    Ok(out)
+}
+
+/// Statistics as a dict with the same keys as the JSON output of `st`.
+fn stats_dict<'py>(py: Python<'py>, stats: &Statistics) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    if let Some(p) = stats.percentile() {
+        dict.set_item("percentile", p)?;
+    }
+
+    for (name, value) in stats.iter() {
+        match value {
+            StatValue::Integer(v) => dict.set_item(name.as_str(), v)?,
+            StatValue::Float(v) => dict.set_item(name.as_str(), v)?,
+        }
+    }
+
+    if stats.is_merged() {
+        dict.set_item("merged", true)?;
+    }
+
+    Ok(dict)
+}
+
+fn quantile_dict<'py>(py: Python<'py>, quantile: &Quantile) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("percent", quantile.percent())?;
+    dict.set_item("value", quantile.value())?;
+    Ok(dict)
+}
+
+/// Bundle as nested dicts and lists with the same keys as the JSON output
+/// of `st`.
+fn bundle_dict<'py>(py: Python<'py>, bundle: &StatisticsBundle) -> PyResult<Bound<'py, PyDict>> {
+    let quantiles = bundle
+        .quantile_values()
+        .iter()
+        .map(|q| quantile_dict(py, q))
+        .collect::<PyResult<Vec<_>>>()?;
+    let percentiles = bundle
+        .percentile_stats()
+        .iter()
+        .map(|s| stats_dict(py, s))
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("global", stats_dict(py, bundle.global_stats())?)?;
+    dict.set_item("quantiles", PyList::new(py, quantiles)?)?;
+    dict.set_item("percentiles", PyList::new(py, percentiles)?)?;
+    if let Some(n) = bundle.sample_size() {
+        dict.set_item("sample_count", n)?;
+    }
+
+    Ok(dict)
+}
+
+/// Compute statistics of an iterable of floats and the lowest percent of
+/// them for each percentile (from 1 to 99), returning `None` if there
+/// aren't any values.
+#[pyfunction]
+#[pyo3(signature = (values, percentiles=None))]
+fn stats<'py>(
+    py: Python<'py>,
+    values: &Bound<'py, PyAny>,
+    percentiles: Option<Vec<i64>>,
+) -> PyResult<Option<Bound<'py, PyDict>>> {
+    let percentiles = self::percentiles(percentiles)?;
+    let vals = self::values(values)?;
+
+    StatisticsBundle::from_iter_with_percentiles(vals, percentiles.as_slice())
+        .map(|b| bundle_dict(py, &b))
+        .transpose()
+}
+
+/// Statistics of values pushed one at a time without keeping them in
+/// memory, wrapping `StreamingStatistics`.
+#[pyclass(name = "StreamingStats", module = "staccato")]
+#[derive(Debug)]
+struct PyStreamingStats {
+    inner: StreamingStatistics,
+}
+
+#[pymethods]
+impl PyStreamingStats {
+    #[new]
+    fn new() -> Self {
+        PyStreamingStats {
+            inner: StreamingStatistics::new(),
+        }
+    }
+
+    fn push(&mut self, value: f64) {
+        self.inner.push(value);
+    }
+
+    /// Push every value of an iterable of floats.
+    fn extend(&mut self, values: &Bound<'_, PyAny>) -> PyResult<()> {
+        for v in self::values(values)? {
+            self.inner.push(v);
+        }
+
+        Ok(())
+    }
+
+    /// Add the values pushed to another instance to this one.
+    fn merge(&mut self, other: &PyStreamingStats) {
+        self.inner.merge(&other.inner);
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Statistics of the values pushed so far and the estimated value of each
+    /// quantile (from 0 to 100), returning `None` if there aren't any values.
+    #[pyo3(signature = (quantiles=None))]
+    fn stats<'py>(&self, py: Python<'py>, quantiles: Option<Vec<f64>>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.inner
+            .bundle(&quantiles.unwrap_or_default())
+            .map(|b| bundle_dict(py, &b))
+            .transpose()
+    }
+}
+
+/// Python module with the same name as the library, built by maturin using
+/// the `pyproject.toml` file.
+#[pymodule]
+fn staccato(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(stats, m)?)?;
+    m.add_class::<PyStreamingStats>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::staccato;
+    use pyo3::prelude::*;
+    use pyo3::types::{PyDict, PyModule};
+    use std::ffi::CString;
+
+    /// Run each `test_*` function of the pytest style tests for the module
+    /// with the module imported as `staccato`.
+    #[test]
+    fn test_python_module() {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = pyo3::wrap_pymodule!(staccato)(py);
+            py.import("sys")?.getattr("modules")?.set_item("staccato", module)?;
+
+            let source = CString::new(include_str!("../../tests/python/test_staccato.py")).unwrap();
+            let file = CString::new("test_staccato.py").unwrap();
+            let name = CString::new("test_staccato").unwrap();
+            let tests = PyModule::from_code(py, &source, &file, &name)?;
+            let funcs: &Bound<'_, PyDict> = &tests.dict();
+
+            let mut count = 0;
+            for (name, func) in funcs.iter() {
+                let name: String = name.extract()?;
+                if name.starts_with("test_") && func.is_callable() {
+                    func.call0().inspect_err(|e| e.print(py))?;
+                    count += 1;
+                }
+            }
+
+            assert!(count > 0);
+            PyResult::Ok(())
+        })
+        .unwrap();
+    }
+}
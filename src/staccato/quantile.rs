@@ -0,0 +1,259 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::Value;
+use std::str::FromStr;
+
+/// Method used to pick the value at a quantile that falls between two
+/// of the values in a sorted sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum QuantileMethod {
+    /// Linearly interpolate between the two closest values. This is the
+    /// method used by most spreadsheets and numpy by default and produces
+    /// the same result as the median for the 0.5 quantile.
+    #[default]
+    Linear,
+    /// Use the value with the nearest rank without any interpolation. The
+    /// result is always one of the values in the sequence.
+    Nearest,
+}
+
+impl FromStr for QuantileMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<QuantileMethod, Self::Err> {
+        if "linear" == s {
+            Ok(QuantileMethod::Linear)
+        } else if "nearest" == s {
+            Ok(QuantileMethod::Nearest)
+        } else {
+            Err(format!("Invalid quantile method {}", s))
+        }
+    }
+}
+
+/// Compute the value at quantile `q` (from 0 to 1, inclusive) of a **sorted**
+/// sequence of values.
+///
+/// The values must be sorted or the result will be incorrect.
+///
+/// This method returns `None` if the sequence of values is empty or if the
+/// quantile is not between 0 and 1.
+pub fn quantile<V: Value>(vals: &[V], q: f64, method: QuantileMethod) -> Option<f64> {
+    QuantileRank::from(vals.len(), q, method).map(|r| r.value(|i| vals[i].into()))
+}
+
+/// Position(s) in a sorted sequence of values of the value at a quantile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum QuantileRank {
+    /// The value at a single position.
+    At(usize),
+    /// Linear interpolation between the values at two positions by some
+    /// fraction (from 0 to 1) of the distance between them.
+    Between(usize, usize, f64),
+}
+
+impl QuantileRank {
+    /// Position(s) of the value at quantile `q` (from 0 to 1, inclusive) of
+    /// a sorted sequence of `len` values or `None` if there are no values or
+    /// the quantile is not between 0 and 1.
+    pub(crate) fn from(len: usize, q: f64, method: QuantileMethod) -> Option<QuantileRank> {
+        if len == 0 || !(0f64..=1f64).contains(&q) {
+            return None;
+        }
+
+        let last = len - 1;
+        Some(match method {
+            QuantileMethod::Linear => {
+                let rank = q * last as f64;
+                let lower = rank.floor() as usize;
+                QuantileRank::Between(lower, rank.ceil() as usize, rank - lower as f64)
+            }
+            QuantileMethod::Nearest => {
+                let rank = (q * len as f64).ceil() as usize;
+                QuantileRank::At(rank.saturating_sub(1).min(last))
+            }
+        })
+    }
+
+    /// Positions of all values needed to compute the quantile.
+    pub(crate) fn positions(&self) -> [usize; 2] {
+        match *self {
+            QuantileRank::At(i) => [i, i],
+            QuantileRank::Between(lower, upper, _) => [lower, upper],
+        }
+    }
+
+    /// Compute the quantile using a function that returns the value at a
+    /// position in the sorted sequence.
+    pub(crate) fn value<F: Fn(usize) -> f64>(&self, get: F) -> f64 {
+        match *self {
+            QuantileRank::At(i) => get(i),
+            QuantileRank::Between(lower, upper, frac) => {
+                let (lower, upper) = (get(lower), get(upper));
+                lower + (upper - lower) * frac
+            }
+        }
+    }
+}
+
+/// Value at a particular quantile of a sequence of values, expressed as a
+/// percent from 0 to 100.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quantile {
+    percent: f64,
+    value: f64,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    name: Option<String>,
+}
+
+impl Quantile {
+    /// Compute the value at the given percent (from 0 to 100, inclusive) of
+    /// a **sorted** sequence of values.
+    ///
+    /// This method returns `None` if the sequence of values is empty or if
+    /// the percent is not between 0 and 100.
+    pub fn from<V: Value>(vals: &[V], percent: f64, method: QuantileMethod) -> Option<Quantile> {
+        quantile(vals, percent / 100f64, method).map(|value| Quantile::new(percent, value))
+    }
+
+    /// Create a quantile from a value that has already been computed (or
+    /// estimated) for the given percent.
+    pub(crate) fn new(percent: f64, value: f64) -> Quantile {
+        Quantile {
+            percent,
+            value,
+            name: None,
+        }
+    }
+
+    /// This quantile with the value changed, keeping its percent and name.
+    pub(crate) fn map_value<F: Fn(f64) -> f64>(&self, f: F) -> Quantile {
+        Quantile {
+            value: f(self.value),
+            ..self.clone()
+        }
+    }
+
+    /// Set the name this quantile is displayed with instead of its percent,
+    /// e.g. `q1` for the 25th percentile.
+    pub fn with_name(mut self, name: &str) -> Quantile {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn percent(&self) -> f64 {
+        self.percent
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Name this quantile is displayed with, if it was given one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Key of this quantile when displayed: its name if it was given one and
+    /// its percent otherwise, e.g. `p99` or `p99.9`.
+    pub fn key(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("p{}", self.percent),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quantile, Quantile, QuantileMethod};
+
+    const ODD: &[f64] = &[1f64, 2f64, 5f64, 7f64, 9f64];
+
+    const EVEN: &[f64] = &[1f64, 2f64, 5f64, 7f64, 9f64, 12f64];
+
+    #[test]
+    fn test_quantile_empty() {
+        assert_eq!(None, quantile::<f64>(&[], 0.5, QuantileMethod::Linear));
+        assert_eq!(None, quantile::<f64>(&[], 0.5, QuantileMethod::Nearest));
+    }
+
+    #[test]
+    fn test_quantile_out_of_range() {
+        assert_eq!(None, quantile(ODD, -0.1, QuantileMethod::Linear));
+        assert_eq!(None, quantile(ODD, 1.1, QuantileMethod::Linear));
+        assert_eq!(None, quantile(ODD, f64::NAN, QuantileMethod::Linear));
+    }
+
+    #[test]
+    fn test_quantile_linear_bounds() {
+        assert_eq!(Some(1f64), quantile(EVEN, 0f64, QuantileMethod::Linear));
+        assert_eq!(Some(12f64), quantile(EVEN, 1f64, QuantileMethod::Linear));
+    }
+
+    #[test]
+    fn test_quantile_linear_median() {
+        assert_eq!(Some(5f64), quantile(ODD, 0.5, QuantileMethod::Linear));
+        assert_eq!(Some(6f64), quantile(EVEN, 0.5, QuantileMethod::Linear));
+    }
+
+    #[test]
+    fn test_quantile_linear_interpolated() {
+        // Rank of 0.25 * 5 = 1.25, a quarter of the way from 2 to 5
+        assert_eq!(Some(2.75), quantile(EVEN, 0.25, QuantileMethod::Linear));
+    }
+
+    #[test]
+    fn test_quantile_nearest() {
+        assert_eq!(Some(1f64), quantile(EVEN, 0f64, QuantileMethod::Nearest));
+        assert_eq!(Some(2f64), quantile(EVEN, 0.25, QuantileMethod::Nearest));
+        assert_eq!(Some(5f64), quantile(EVEN, 0.5, QuantileMethod::Nearest));
+        assert_eq!(Some(12f64), quantile(EVEN, 1f64, QuantileMethod::Nearest));
+    }
+
+    #[test]
+    fn test_quantile_single_value() {
+        assert_eq!(Some(13f64), quantile(&[13f64], 0.9, QuantileMethod::Linear));
+        assert_eq!(Some(13f64), quantile(&[13f64], 0.9, QuantileMethod::Nearest));
+    }
+
+    #[test]
+    fn test_quantile_method_from_str() {
+        assert_eq!(QuantileMethod::Linear, "linear".parse::<QuantileMethod>().unwrap());
+        assert_eq!(QuantileMethod::Nearest, "nearest".parse::<QuantileMethod>().unwrap());
+        assert!("banana".parse::<QuantileMethod>().is_err());
+    }
+
+    #[test]
+    fn test_quantile_key() {
+        assert_eq!("p25", Quantile::from(ODD, 25f64, QuantileMethod::Linear).unwrap().key());
+        assert_eq!(
+            "p99.9",
+            Quantile::from(ODD, 99.9, QuantileMethod::Linear).unwrap().key()
+        );
+
+        let q1 = Quantile::from(ODD, 25f64, QuantileMethod::Linear)
+            .unwrap()
+            .with_name("q1");
+        assert_eq!("q1", q1.key());
+        assert_eq!(Some("q1"), q1.name());
+        assert_eq!(25f64, q1.percent());
+    }
+}
@@ -0,0 +1,116 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use std::io::{self, Read};
+
+/// Reader of records separated by NUL bytes instead of newlines, e.g. from
+/// `find -print0`, so that records can contain newlines.
+///
+/// Each NUL byte is read as a newline and each newline is read as a space,
+/// so that anything that reads lines (such as `get_values`) reads each record
+/// as a single line. Newlines within a record are whitespace the same way as
+/// spaces, so they're removed from the start and end of a value and separate
+/// fields. A final record without a NUL byte after it is read the same way
+/// as a final line without a newline.
+///
+/// ```
+/// use staccato::{get_values, NullDelimited, SortingPolicy};
+///
+/// let mut reader = NullDelimited::new(&b"1.5\n\x00\n2\x003"[..]);
+/// let vals = get_values(&mut reader, SortingPolicy::Unsorted).unwrap();
+/// assert_eq!(vec![1.5, 2.0, 3.0], vals);
+/// ```
+#[derive(Debug)]
+pub struct NullDelimited<R> {
+    inner: R,
+}
+
+impl<R: Read> NullDelimited<R> {
+    pub fn new(inner: R) -> NullDelimited<R> {
+        NullDelimited { inner }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for NullDelimited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        for b in &mut buf[..read] {
+            *b = match *b {
+                b'\0' => b'\n',
+                b'\n' => b' ',
+                other => other,
+            };
+        }
+
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NullDelimited;
+    use crate::{get_parsed_values, get_values_with, FieldExtractor, SortingPolicy};
+    use std::io::Read;
+
+    fn records(bytes: &[u8]) -> String {
+        let mut out = String::new();
+        NullDelimited::new(bytes).read_to_string(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_records_become_lines() {
+        assert_eq!("1\n2\n3\n", records(b"1\x002\x003\x00"));
+        assert_eq!("a b\n c \n", records(b"a\nb\x00\nc\n\x00"));
+    }
+
+    #[test]
+    fn test_trailing_record_without_terminator() {
+        let bytes: Vec<u8> = b"1\x00\n2\n\x003".to_vec();
+        let parsed = get_parsed_values(&mut NullDelimited::new(&bytes[..]), SortingPolicy::Unsorted).unwrap();
+
+        assert_eq!(&[1.0, 2.0, 3.0], parsed.values());
+        assert_eq!(3, parsed.total_lines());
+    }
+
+    #[test]
+    fn test_embedded_newlines_in_records() {
+        // Each record is counted once, no matter how many newlines it has.
+        let bytes: Vec<u8> = b"1\x00\n\n\x00foo\nbar\x004\n".to_vec();
+        let parsed = get_parsed_values(&mut NullDelimited::new(&bytes[..]), SortingPolicy::Unsorted).unwrap();
+
+        assert_eq!(&[1.0, 4.0], parsed.values());
+        assert_eq!(4, parsed.total_lines());
+        assert_eq!(1, parsed.blank());
+        assert_eq!(3, parsed.first_errors()[0].line());
+        assert_eq!("foo bar", parsed.first_errors()[0].content());
+    }
+
+    #[test]
+    fn test_fields_split_by_embedded_newlines() {
+        let bytes: Vec<u8> = b"GET\n12.5\x00POST\n40\x00".to_vec();
+        let extractor = FieldExtractor::new(2).unwrap();
+        let vals = get_values_with(&mut NullDelimited::new(&bytes[..]), SortingPolicy::Unsorted, extractor).unwrap();
+
+        assert_eq!(vec![12.5, 40.0], vals);
+    }
+}
@@ -0,0 +1,305 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{
+    for_each_line, sort_values, QuantileMethod, Rng, SortedValues, StaccatoError, StatisticsBundle, StreamingStatistics,
+};
+use std::io::BufRead;
+
+/// Exact streaming statistics of a sequence of values along with a fixed
+/// size uniform random sample of them, from which the median, quantiles,
+/// and percentile statistics are estimated.
+///
+/// The sample is chosen using "Algorithm R": the first `capacity` values
+/// are kept and after that, the Nth value replaces a randomly chosen kept
+/// value with probability `capacity / N`. Every value has the same chance
+/// of being in the sample no matter where in the sequence it appears, so
+/// quantiles of the sample are unbiased estimates of quantiles of all the
+/// values. The rank of a quantile `q` estimated from a sample of `n` values
+/// has a standard error of about `sqrt(q * (1 - q) / n)`, e.g. about 0.16%
+/// for the median with a sample of 100,000 values.
+///
+/// When no more than `capacity` values have been pushed the sample contains
+/// all of them and the estimates are exact.
+#[derive(Debug, Clone)]
+pub struct Reservoir {
+    capacity: usize,
+    seen: usize,
+    sample: Vec<f64>,
+    sorted: bool,
+    rng: Rng,
+    stats: StreamingStatistics,
+}
+
+impl Reservoir {
+    /// Create a reservoir that keeps a sample of at most `capacity` values,
+    /// chosen using a pseudo-random generator seeded with `seed`. The same
+    /// seed and sequence of values always result in the same sample.
+    pub fn new(capacity: usize, seed: u64) -> Reservoir {
+        Self::with_rng(capacity, Rng::new(seed))
+    }
+
+    /// Create a reservoir that keeps a sample of at most `capacity` values,
+    /// chosen using the given generator.
+    pub fn with_rng(capacity: usize, rng: Rng) -> Reservoir {
+        Reservoir {
+            capacity,
+            seen: 0,
+            sample: Vec::new(),
+            sorted: true,
+            rng,
+            stats: StreamingStatistics::new(),
+        }
+    }
+
+    pub fn push(&mut self, val: f64) {
+        self.seen += 1;
+        self.stats.push(val);
+
+        if self.sample.len() < self.capacity {
+            self.sample.push(val);
+            self.sorted = false;
+            return;
+        }
+
+        let index = self.rng.below(self.seen);
+        if index < self.capacity {
+            self.sample[index] = val;
+            self.sorted = false;
+        }
+    }
+
+    /// Update the statistics and sample with values read one line at a time,
+    /// parsed the same way `get_values` parses them.
+    pub fn push_reader<T: BufRead>(&mut self, reader: &mut T) -> Result<(), StaccatoError> {
+        for_each_line(reader, |line| {
+            if let Ok(v) = line.trim().parse::<f64>() {
+                self.push(v);
+            }
+        })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of values pushed so far.
+    pub fn seen(&self) -> usize {
+        self.seen
+    }
+
+    /// Number of values in the sample.
+    pub fn len(&self) -> usize {
+        self.sample.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sample.is_empty()
+    }
+
+    /// Exact statistics of all values pushed so far.
+    pub fn stats(&self) -> &StreamingStatistics {
+        &self.stats
+    }
+
+    /// Values in the sample, sorted the same way as `sort_values`.
+    pub fn as_sorted_slice(&mut self) -> &[f64] {
+        if !self.sorted {
+            sort_values(&mut self.sample);
+            self.sorted = true;
+        }
+
+        &self.sample
+    }
+
+    /// Create a statistics bundle with exact statistics of all the values
+    /// pushed so far except for the median, quantiles, and percentile slices
+    /// which are computed from the sample.
+    ///
+    /// This method returns `None` if no values have been pushed or the
+    /// sample is empty. The bundle has a sample size if the sample doesn't
+    /// contain every value pushed.
+    pub fn bundle(
+        &mut self,
+        percentiles: &[u8],
+        quantiles: &[f64],
+        method: QuantileMethod,
+    ) -> Option<StatisticsBundle> {
+        let mut global = self.stats.finalize()?;
+        let (seen, len) = (self.seen, self.len());
+        let sample = SortedValues::new_unchecked(self.as_sorted_slice());
+        let estimated = StatisticsBundle::from_sorted(sample, percentiles, quantiles, method)?;
+
+        global.median = estimated.global.median;
+        Some(StatisticsBundle {
+            global,
+            quantiles: estimated.quantiles,
+            percentiles: estimated.percentiles,
+            sample_size: if len < seen { Some(len) } else { None },
+            omitted: estimated.omitted,
+            omitted_quantiles: estimated.omitted_quantiles,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Reservoir;
+    use crate::{QuantileMethod, Rng, SortedValues, StatisticsBundle, StatisticsFormatter};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_reservoir_fewer_values_than_capacity() {
+        let mut reservoir = Reservoir::new(10, 1);
+        for &v in &[5f64, 1f64, 4f64, 2f64, 3f64] {
+            reservoir.push(v);
+        }
+
+        assert_eq!(5, reservoir.seen());
+        assert_eq!(5, reservoir.len());
+        assert_eq!(&[1f64, 2f64, 3f64, 4f64, 5f64], reservoir.as_sorted_slice());
+    }
+
+    #[test]
+    fn test_reservoir_bounded() {
+        let mut reservoir = Reservoir::new(100, 1);
+        for i in 0..10_000 {
+            reservoir.push(i as f64);
+        }
+
+        assert_eq!(10_000, reservoir.seen());
+        assert_eq!(100, reservoir.len());
+        assert_eq!(10_000, reservoir.stats().count());
+    }
+
+    #[test]
+    fn test_reservoir_with_rng_same_as_seed() {
+        let mut a = Reservoir::new(20, 9);
+        let mut b = Reservoir::with_rng(20, Rng::new(9));
+        for i in 0..500 {
+            a.push(i as f64);
+            b.push(i as f64);
+        }
+
+        assert_eq!(a.as_sorted_slice(), b.as_sorted_slice());
+    }
+
+    #[test]
+    fn test_reservoir_same_seed_same_sample() {
+        let mut a = Reservoir::new(50, 42);
+        let mut b = Reservoir::new(50, 42);
+        let mut c = Reservoir::new(50, 43);
+        for i in 0..1000 {
+            a.push(i as f64);
+            b.push(i as f64);
+            c.push(i as f64);
+        }
+
+        assert_eq!(a.as_sorted_slice(), b.as_sorted_slice());
+        assert_ne!(a.as_sorted_slice(), c.as_sorted_slice());
+    }
+
+    #[test]
+    fn test_reservoir_push_reader() {
+        let mut reservoir = Reservoir::new(10, 1);
+        reservoir.push_reader(&mut Cursor::new("3\nfoo\n1\n2\n")).unwrap();
+
+        assert_eq!(3, reservoir.seen());
+        assert_eq!(&[1f64, 2f64, 3f64], reservoir.as_sorted_slice());
+    }
+
+    #[test]
+    fn test_reservoir_rank_error_skewed() {
+        // Exponentially distributed values, most of them small with a long
+        // tail of large ones, in increasing order so that a sample biased
+        // towards the start or end of the input would be obvious.
+        let mut state = 7u64;
+        let mut vals: Vec<f64> = (0..1_000_000)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let u = ((state >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+                -u.ln()
+            })
+            .collect();
+        vals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let size = 10_000;
+        let mut reservoir = Reservoir::new(size, 1234);
+        for &v in &vals {
+            reservoir.push(v);
+        }
+
+        let sample = reservoir.as_sorted_slice();
+        for &q in &[0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99] {
+            let estimate = sample[(q * size as f64) as usize];
+            let rank = vals.partition_point(|&v| v < estimate) as f64 / vals.len() as f64;
+            let bound = 5f64 * (q * (1f64 - q) / size as f64).sqrt();
+
+            assert!((rank - q).abs() < bound, "q {}: rank {} error over {}", q, rank, bound);
+        }
+    }
+
+    #[test]
+    fn test_reservoir_bundle_exact() {
+        let vals = &[1f64, 2f64, 5f64, 7f64, 9f64, 12f64];
+        let mut reservoir = Reservoir::new(10, 1);
+        for &v in vals {
+            reservoir.push(v);
+        }
+
+        let bundle = reservoir.bundle(&[50], &[90f64], QuantileMethod::Linear).unwrap();
+        let expected = StatisticsBundle::from_sorted(
+            SortedValues::new(vals).unwrap(),
+            &[50],
+            &[90f64],
+            QuantileMethod::Linear,
+        )
+        .unwrap();
+
+        assert_eq!(None, bundle.sample_size());
+        assert_eq!(
+            format!("{}", StatisticsFormatter::new(&expected).precision(3)),
+            format!("{}", StatisticsFormatter::new(&bundle).precision(3))
+        );
+    }
+
+    #[test]
+    fn test_reservoir_bundle_sampled() {
+        let mut reservoir = Reservoir::new(100, 1);
+        for i in 1..=1000 {
+            reservoir.push(i as f64);
+        }
+
+        let bundle = reservoir.bundle(&[50], &[], QuantileMethod::Linear).unwrap();
+        let global = bundle.global_stats();
+
+        assert_eq!(Some(100), bundle.sample_size());
+        assert_eq!(1000, global.count());
+        assert_eq!(500500f64, global.sum());
+        assert_eq!(1000f64, global.upper());
+        assert_eq!(1f64, global.lower());
+        assert_eq!(50, bundle.percentile_stats()[0].count());
+    }
+
+    #[test]
+    fn test_reservoir_bundle_empty() {
+        assert!(Reservoir::new(10, 1).bundle(&[], &[], QuantileMethod::Linear).is_none());
+    }
+}
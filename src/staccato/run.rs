@@ -0,0 +1,519 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{
+    get_parsed_values_sampled, sort_values, EmptyPolicy, ErrorPolicy, LineExtractor, Percentiles, Phase,
+    PlainExtractor, QuantileMethod, SortedValues, SortingPolicy, StaccatoError, StatisticsBundle, StatisticsConfig,
+    TimedReader, Timings, Value, DEFAULT_MALFORMED_SAMPLES,
+};
+use std::io::Read;
+use std::time::Instant;
+
+/// Options for computing a statistics bundle, shared by the command line
+/// tool and library users so that both get identical results from the same
+/// values. Converting statistics to another unit is done when displaying
+/// them instead, see `KeyValueFormatter::unit`.
+///
+/// ```
+/// use staccato::{run, Percentiles, StaccatoConfig};
+///
+/// // The same as `st -p 90,99`
+/// let config = StaccatoConfig::new().with_percentiles("90,99".parse::<Percentiles>().unwrap());
+/// let bundle = run(&config, &mut "4\n1\n3\n2\n".as_bytes()).unwrap();
+/// assert_eq!(4, bundle.global_stats().count());
+/// assert_eq!(2, bundle.percentile_stats().len());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StaccatoConfig {
+    percentiles: Percentiles,
+    quantiles: Vec<f64>,
+    quantile_names: Vec<(f64, String)>,
+    method: QuantileMethod,
+    statistics: StatisticsConfig,
+    sorting: SortingPolicy,
+    empty: EmptyPolicy,
+    errors: ErrorPolicy,
+    malformed_samples: Option<usize>,
+    min_samples: Option<usize>,
+}
+
+impl StaccatoConfig {
+    /// Create a config for every statistic of all values, without any
+    /// percentile slices or quantiles.
+    pub fn new() -> StaccatoConfig {
+        Self::default()
+    }
+
+    /// Set the percentiles to compute slices of statistics for.
+    pub fn with_percentiles(mut self, percentiles: Percentiles) -> Self {
+        self.percentiles = percentiles;
+        self
+    }
+
+    /// Set the quantiles (expressed as percents from 0 to 100) to compute
+    /// values for. Quantiles outside of this range are omitted.
+    pub fn with_quantiles(mut self, quantiles: &[f64]) -> Self {
+        self.quantiles = quantiles.to_vec();
+        self
+    }
+
+    /// Display the quantile of the given percent with a name instead of its
+    /// percent, e.g. `q1` instead of `p25`, see `Quantile::key`.
+    pub fn with_quantile_name(mut self, percent: f64, name: &str) -> Self {
+        self.quantile_names.push((percent, name.to_string()));
+        self
+    }
+
+    /// Set the method used to compute quantiles between two values.
+    pub fn with_quantile_method(mut self, method: QuantileMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Set which statistics are computed, for all values and each percentile
+    /// slice. Statistics that aren't selected are `NaN` and aren't computed,
+    /// e.g. the standard deviation isn't accumulated unless it's selected.
+    /// This also affects whether values are sorted with the `Auto` policy.
+    pub fn with_statistics(mut self, statistics: StatisticsConfig) -> Self {
+        self.statistics = statistics;
+        self
+    }
+
+    /// Set whether values are sorted after they're read. Values must be
+    /// sorted to compute percentile slices or quantiles, so `Unsorted` should
+    /// only be used without them. Default is `Auto`.
+    pub fn with_sorting(mut self, sorting: SortingPolicy) -> Self {
+        self.sorting = sorting;
+        self
+    }
+
+    /// Set what's computed when there aren't any values. Default is `None`,
+    /// which makes `run` return an error.
+    pub fn with_empty_policy(mut self, empty: EmptyPolicy) -> Self {
+        self.empty = empty;
+        self
+    }
+
+    /// Set what's done with lines that aren't values. Default is `Skip`,
+    /// which ignores them the same way as blank lines.
+    pub fn with_error_policy(mut self, errors: ErrorPolicy) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Set the number of lines that aren't values to include in the error
+    /// when the error policy is `Fail`. Default is `None`, which includes
+    /// `DEFAULT_MALFORMED_SAMPLES` of them.
+    pub fn with_malformed_samples(mut self, malformed_samples: Option<usize>) -> Self {
+        self.malformed_samples = malformed_samples;
+        self
+    }
+
+    /// Set the minimum number of values a percentile slice must have to be
+    /// kept, and that there must be in total to compute quantiles, see
+    /// `StatisticsBundle::with_min_samples`. Default is `None`, which keeps
+    /// every slice with any values.
+    pub fn with_min_samples(mut self, min_samples: Option<usize>) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    pub fn percentiles(&self) -> &Percentiles {
+        &self.percentiles
+    }
+
+    pub fn quantiles(&self) -> &[f64] {
+        &self.quantiles
+    }
+
+    pub fn quantile_names(&self) -> &[(f64, String)] {
+        &self.quantile_names
+    }
+
+    pub fn quantile_method(&self) -> QuantileMethod {
+        self.method
+    }
+
+    pub fn statistics(&self) -> StatisticsConfig {
+        self.statistics
+    }
+
+    pub fn sorting(&self) -> SortingPolicy {
+        self.sorting
+    }
+
+    pub fn empty_policy(&self) -> EmptyPolicy {
+        self.empty
+    }
+
+    pub fn error_policy(&self) -> ErrorPolicy {
+        self.errors
+    }
+
+    pub fn malformed_samples(&self) -> usize {
+        self.malformed_samples.unwrap_or(DEFAULT_MALFORMED_SAMPLES)
+    }
+
+    pub fn min_samples(&self) -> Option<usize> {
+        self.min_samples
+    }
+
+    /// Policy used to read values, with `Auto` resolved to `Sorted` if there
+    /// are percentile slices or quantiles to compute or the median is selected
+    /// and `Unsorted` otherwise.
+    pub fn sorting_policy(&self) -> SortingPolicy {
+        if self.sorting == SortingPolicy::Auto && !(self.percentiles.is_empty() && self.quantiles.is_empty()) {
+            SortingPolicy::Sorted
+        } else {
+            self.sorting.resolve(self.statistics)
+        }
+    }
+
+    /// Compute a statistics bundle of values, which must be sorted if the
+    /// sorting policy of this config is `Sorted` or `AlreadySorted`.
+    ///
+    /// This method returns `None` if there are no values, unless the empty
+    /// policy of this config is `Zeroed`.
+    pub fn bundle<V: Value>(&self, vals: &[V]) -> Option<StatisticsBundle> {
+        let bundle = if self.sorting_policy().is_sorted() {
+            StatisticsBundle::from_sorted_with_config(
+                SortedValues::new_unchecked(vals),
+                self.percentiles.as_slice(),
+                &self.quantiles,
+                self.method,
+                self.statistics,
+            )
+        } else {
+            StatisticsBundle::with_config(
+                vals,
+                self.percentiles.as_slice(),
+                &self.quantiles,
+                self.method,
+                self.statistics,
+            )
+        };
+
+        self.empty.apply(bundle.map(|b| self.finish(b)))
+    }
+
+    /// Apply the parts of this config that don't depend on how statistics
+    /// were computed to a bundle: omitting percentile slices with too few
+    /// values and naming quantiles. This is done by `bundle`, and is for
+    /// bundles computed some other way, e.g. by `StreamingStatistics`.
+    pub fn finish(&self, bundle: StatisticsBundle) -> StatisticsBundle {
+        let bundle = match self.min_samples {
+            Some(min) => bundle.with_min_samples(min),
+            None => bundle,
+        };
+
+        self.quantile_names
+            .iter()
+            .fold(bundle, |b, (percent, name)| b.with_quantile_name(*percent, name))
+    }
+}
+
+/// Read values from each line of a reader and compute a statistics bundle
+/// of them using a config, the same way as the `st` command line tool.
+///
+/// An error is returned if the values can't be read, if any lines aren't
+/// values and the error policy of the config is `Fail`, or if there aren't
+/// any values unless the empty policy of the config is `Zeroed`.
+pub fn run<T: Read>(config: &StaccatoConfig, reader: &mut T) -> Result<StatisticsBundle, StaccatoError> {
+    run_with(config, reader, PlainExtractor)
+}
+
+/// Compute a statistics bundle the same way as `run` using an extractor to
+/// produce values from each line, e.g. to read a single field of each line.
+pub fn run_with<T: Read, E: LineExtractor>(
+    config: &StaccatoConfig,
+    reader: &mut T,
+    extractor: E,
+) -> Result<StatisticsBundle, StaccatoError> {
+    run_from(config, |sorting| read_values(config, reader, sorting, extractor))
+}
+
+/// Compute a statistics bundle the same way as `run` using a function to
+/// read the values instead of a reader, e.g. to read them from a memory
+/// mapped file or from several files at once.
+///
+/// The function is called with the sorting policy required by the config
+/// and must return values sorted (or checked) according to it.
+///
+/// ```
+/// use staccato::{get_values_from_slice, run_from, StaccatoConfig};
+///
+/// let config = StaccatoConfig::new();
+/// let bundle = run_from(&config, |sorting| get_values_from_slice(b"4\n1\n3\n2\n", sorting)).unwrap();
+/// assert_eq!(2.5, bundle.global_stats().median());
+/// ```
+pub fn run_from<F>(config: &StaccatoConfig, read: F) -> Result<StatisticsBundle, StaccatoError>
+where
+    F: FnOnce(SortingPolicy) -> Result<Vec<f64>, StaccatoError>,
+{
+    let vals = read(config.sorting_policy())?;
+    config.bundle(&vals).ok_or(StaccatoError::EmptyInput)
+}
+
+/// Compute a statistics bundle the same way as `run_with`, adding the time
+/// spent reading, parsing, and sorting values and computing statistics of
+/// them to `timings` along with the number of values.
+///
+/// Time spent reading is measured separately from parsing by timing each
+/// read from the reader, so `run_with` should be used when timings aren't
+/// needed.
+pub fn run_with_timings<T: Read, E: LineExtractor>(
+    config: &StaccatoConfig,
+    reader: &mut T,
+    extractor: E,
+    timings: &mut Timings,
+) -> Result<StatisticsBundle, StaccatoError> {
+    let start = Instant::now();
+    let mut timed = TimedReader::new(reader);
+    let mut vals = read_values(config, &mut timed, SortingPolicy::Unsorted, extractor)?;
+    timings.add(Phase::Read, timed.elapsed());
+    timings.add(Phase::Parse, start.elapsed().saturating_sub(timed.elapsed()));
+
+    timings.time(Phase::Sort, || {
+        config.sorting_policy().sort_with(&mut vals, sort_values)
+    })?;
+    timings.set_values(vals.len() as u64);
+
+    timings
+        .time(Phase::Stats, || config.bundle(&vals))
+        .ok_or(StaccatoError::EmptyInput)
+}
+
+/// Read values from each line of a reader, applying the error policy of
+/// the config to lines that aren't values.
+fn read_values<T: Read, E: LineExtractor>(
+    config: &StaccatoConfig,
+    reader: &mut T,
+    sorting: SortingPolicy,
+    extractor: E,
+) -> Result<Vec<f64>, StaccatoError> {
+    let parsed = get_parsed_values_sampled(reader, sorting, extractor, 0, config.malformed_samples())?;
+    config.error_policy().apply(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, run_from, run_with, run_with_timings, StaccatoConfig};
+    use crate::{
+        get_values, EmptyPolicy, ErrorPolicy, FieldExtractor, Percentiles, Phase, PlainExtractor, QuantileMethod,
+        SortingPolicy, StaccatoError, StatisticsBundle, StatisticsConfig, Timings,
+    };
+    use std::time::Duration;
+
+    const INPUT: &str = "5\n3\nfoo\n9\n1\n\n7\n2\n8\n4\n6\n10\n";
+
+    #[test]
+    fn test_sorting_policy_auto() {
+        let config = StaccatoConfig::new();
+        assert_eq!(SortingPolicy::Auto, config.sorting());
+        assert_eq!(SortingPolicy::Sorted, config.sorting_policy());
+
+        let without_median = config.with_statistics(StatisticsConfig::new().with_median(false));
+        assert_eq!(SortingPolicy::Unsorted, without_median.sorting_policy());
+        assert_eq!(
+            SortingPolicy::Sorted,
+            without_median.clone().with_quantiles(&[50f64]).sorting_policy()
+        );
+
+        let percentiles = Percentiles::new(&[90]).unwrap();
+        assert_eq!(
+            SortingPolicy::Sorted,
+            without_median.with_percentiles(percentiles).sorting_policy()
+        );
+    }
+
+    #[test]
+    fn test_sorting_policy_explicit() {
+        let config = StaccatoConfig::new().with_percentiles(Percentiles::new(&[90]).unwrap());
+        for &policy in &[
+            SortingPolicy::Sorted,
+            SortingPolicy::Unsorted,
+            SortingPolicy::AlreadySorted,
+        ] {
+            assert_eq!(policy, config.clone().with_sorting(policy).sorting_policy());
+        }
+    }
+
+    #[test]
+    fn test_run_already_sorted() {
+        let config = StaccatoConfig::new()
+            .with_percentiles(Percentiles::new(&[50]).unwrap())
+            .with_sorting(SortingPolicy::AlreadySorted);
+        let bundle = run(&config, &mut "1\n2\n3\n4\n".as_bytes()).unwrap();
+
+        assert_eq!(2.5, bundle.global_stats().median());
+        assert_eq!(3f64, bundle.percentile_stats()[0].sum());
+    }
+
+    #[test]
+    fn test_run_same_as_bundle_of_values() {
+        let config = StaccatoConfig::new()
+            .with_percentiles(Percentiles::new(&[50, 90]).unwrap())
+            .with_quantiles(&[25f64, 99.9])
+            .with_quantile_method(QuantileMethod::Nearest);
+
+        let vals = get_values(&mut INPUT.as_bytes(), SortingPolicy::Sorted).unwrap();
+        let expected = StatisticsBundle::with_quantiles(&vals, &[50, 90], &[25f64, 99.9], QuantileMethod::Nearest);
+
+        let bundle = run(&config, &mut INPUT.as_bytes()).unwrap();
+        assert_eq!(expected.unwrap(), bundle);
+    }
+
+    #[test]
+    fn test_run_unsorted_uses_statistics_config() {
+        let statistics = StatisticsConfig::new().with_stddev(false).with_median(false);
+        let config = StaccatoConfig::new().with_statistics(statistics);
+        let bundle = run(&config, &mut INPUT.as_bytes()).unwrap();
+
+        assert_eq!(10, bundle.global_stats().count());
+        assert_eq!(55f64, bundle.global_stats().sum());
+        assert!(bundle.global_stats().stddev().is_nan());
+    }
+
+    #[test]
+    fn test_run_sorted_uses_statistics_config() {
+        let statistics = StatisticsConfig::none().with_mean(true);
+        let config = StaccatoConfig::new()
+            .with_statistics(statistics)
+            .with_percentiles(Percentiles::new(&[90]).unwrap());
+        assert_eq!(SortingPolicy::Sorted, config.sorting_policy());
+
+        let bundle = run(&config, &mut INPUT.as_bytes()).unwrap();
+        for stats in [bundle.global_stats(), &bundle.percentile_stats()[0]] {
+            assert!(!stats.mean().is_nan());
+            assert!(stats.sum().is_nan());
+            assert!(stats.median().is_nan());
+            assert!(stats.stddev().is_nan());
+        }
+
+        assert_eq!(5.5, bundle.global_stats().mean());
+        assert_eq!(5f64, bundle.percentile_stats()[0].mean());
+    }
+
+    #[test]
+    fn test_run_quantile_names() {
+        let config = StaccatoConfig::new()
+            .with_quantiles(&[25f64, 90f64])
+            .with_quantile_name(25f64, "q1")
+            .with_quantile_name(50f64, "median");
+        let bundle = run(&config, &mut INPUT.as_bytes()).unwrap();
+        let keys: Vec<String> = bundle.quantile_values().iter().map(|q| q.key()).collect();
+
+        assert_eq!(vec!["q1", "p90"], keys);
+        assert_eq!(Some("q1"), bundle.quantile_values()[0].name());
+    }
+
+    #[test]
+    fn test_run_with_extractor() {
+        let config = StaccatoConfig::new();
+        let extractor = FieldExtractor::new(2).unwrap();
+        let bundle = run_with(&config, &mut "a 1\nb 2\nc\n".as_bytes(), extractor).unwrap();
+
+        assert_eq!(2, bundle.global_stats().count());
+        assert_eq!(3f64, bundle.global_stats().sum());
+    }
+
+    #[test]
+    fn test_run_from_same_as_run() {
+        let config = StaccatoConfig::new()
+            .with_percentiles(Percentiles::new(&[50, 90]).unwrap())
+            .with_quantiles(&[25f64]);
+        let bundle = run_from(&config, |sorting| {
+            assert_eq!(SortingPolicy::Sorted, sorting);
+            get_values(&mut INPUT.as_bytes(), sorting)
+        })
+        .unwrap();
+
+        assert_eq!(run(&config, &mut INPUT.as_bytes()).unwrap(), bundle);
+        let res = run_from(&config, |_| Ok(Vec::new()));
+        assert!(matches!(res, Err(StaccatoError::EmptyInput)));
+    }
+
+    #[test]
+    fn test_run_with_timings_same_as_run() {
+        let config = StaccatoConfig::new()
+            .with_percentiles(Percentiles::new(&[50, 90]).unwrap())
+            .with_quantiles(&[25f64]);
+        let mut timings = Timings::new();
+        let bundle = run_with_timings(&config, &mut INPUT.as_bytes(), PlainExtractor, &mut timings).unwrap();
+
+        assert_eq!(run(&config, &mut INPUT.as_bytes()).unwrap(), bundle);
+        assert_eq!(10, timings.values());
+        assert_eq!(Duration::default(), timings.get(Phase::Format));
+    }
+
+    #[test]
+    fn test_run_with_timings_already_sorted() {
+        let config = StaccatoConfig::new().with_sorting(SortingPolicy::AlreadySorted);
+        let mut timings = Timings::new();
+        let res = run_with_timings(&config, &mut "1\n3\n2\n".as_bytes(), PlainExtractor, &mut timings);
+        assert!(matches!(res, Err(StaccatoError::NotSorted { position: 3 })));
+    }
+
+    #[test]
+    fn test_run_min_samples() {
+        let config = StaccatoConfig::new()
+            .with_percentiles(Percentiles::new(&[50, 90]).unwrap())
+            .with_quantiles(&[50f64]);
+
+        let bundle = run(&config.clone().with_min_samples(Some(9)), &mut INPUT.as_bytes()).unwrap();
+        assert_eq!(vec![90], bundle.percentiles().collect::<Vec<u8>>());
+        assert_eq!(1, bundle.quantile_values().len());
+
+        let bundle = run(&config.with_min_samples(Some(11)), &mut INPUT.as_bytes()).unwrap();
+        assert_eq!(0, bundle.percentile_stats().len());
+        assert!(bundle.quantile_values().is_empty());
+        assert_eq!(10, bundle.global_stats().count());
+    }
+
+    #[test]
+    fn test_run_error_policy() {
+        let config = StaccatoConfig::new().with_error_policy(ErrorPolicy::Fail);
+        let res = run(&config, &mut INPUT.as_bytes());
+        assert!(matches!(res, Err(StaccatoError::Malformed { count: 1, ref samples }) if samples.len() == 1));
+
+        let res = run(&config.clone().with_malformed_samples(Some(0)), &mut INPUT.as_bytes());
+        assert!(matches!(res, Err(StaccatoError::Malformed { count: 1, ref samples }) if samples.is_empty()));
+
+        let mut timings = Timings::new();
+        let res = run_with_timings(&config, &mut INPUT.as_bytes(), PlainExtractor, &mut timings);
+        assert!(matches!(res, Err(StaccatoError::Malformed { count: 1, .. })));
+
+        let bundle = run(&config, &mut "3\n\n1\n2\n".as_bytes()).unwrap();
+        assert_eq!(3, bundle.global_stats().count());
+    }
+
+    #[test]
+    fn test_run_empty_input() {
+        let res = run(&StaccatoConfig::new(), &mut "\nfoo\n".as_bytes());
+        assert!(matches!(res, Err(StaccatoError::EmptyInput)));
+    }
+
+    #[test]
+    fn test_run_empty_input_zeroed() {
+        let config = StaccatoConfig::new()
+            .with_percentiles(Percentiles::new(&[50]).unwrap())
+            .with_empty_policy(EmptyPolicy::Zeroed);
+        let bundle = run(&config, &mut "\nfoo\nnan\n".as_bytes()).unwrap();
+        assert!(bundle.approx_eq(&StatisticsBundle::empty(), 0f64));
+    }
+}
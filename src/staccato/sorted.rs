@@ -0,0 +1,279 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{quantile, sort_values, QuantileMethod, Value};
+
+/// Sequence of values that is known to be sorted in ascending order.
+///
+/// Methods that require sorted values accept this instead of a slice so that
+/// unsorted values can't be passed by mistake, and so they can take advantage
+/// of the values being sorted (e.g. the lowest value is always the first).
+#[derive(Debug, Clone, Copy)]
+pub struct SortedValues<'a, V> {
+    vals: &'a [V],
+}
+
+impl<'a, V: Value> SortedValues<'a, V> {
+    /// Sort a sequence of values in place, the same way as `sort_values`.
+    pub fn sort(vals: &'a mut [V]) -> SortedValues<'a, V> {
+        sort_values(vals);
+        SortedValues { vals }
+    }
+
+    /// Use a sequence of values only if it is sorted in ascending order,
+    /// checking each value. This method returns `None` if the values are
+    /// not sorted or any of them is `NaN`.
+    pub fn new(vals: &'a [V]) -> Option<SortedValues<'a, V>> {
+        if vals.windows(2).all(|w| w[0] <= w[1]) && vals.iter().all(|&v| !Into::<f64>::into(v).is_nan()) {
+            Some(SortedValues { vals })
+        } else {
+            None
+        }
+    }
+
+    /// Use a sequence of values that has already been sorted (e.g. by
+    /// `get_values` or `sort_values`) without checking it.
+    ///
+    /// The values must be sorted or statistics computed from them will be
+    /// incorrect. This is only checked in debug builds.
+    pub fn new_unchecked(vals: &'a [V]) -> SortedValues<'a, V> {
+        debug_assert!(vals
+            .windows(2)
+            .all(|w| w[0].partial_cmp(&w[1]) != Some(std::cmp::Ordering::Greater)));
+        SortedValues { vals }
+    }
+
+    pub fn as_slice(&self) -> &'a [V] {
+        self.vals
+    }
+
+    pub fn len(&self) -> usize {
+        self.vals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vals.is_empty()
+    }
+
+    /// Value at quantile `q` (from 0 to 1, inclusive) of the values, e.g. to
+    /// look up quantiles that weren't known when statistics were computed
+    /// without sorting the values again.
+    ///
+    /// This method returns `None` if there are no values or if the quantile
+    /// is not between 0 and 1.
+    ///
+    /// ```
+    /// use staccato::{QuantileMethod, SortedValues};
+    ///
+    /// let mut vals = vec![4.0, 1.0, 3.0, 2.0];
+    /// let sorted = SortedValues::sort(&mut vals);
+    /// assert_eq!(Some(2.5), sorted.quantile(0.5, QuantileMethod::Linear));
+    /// assert_eq!(Some(2.0), sorted.quantile(0.5, QuantileMethod::Nearest));
+    /// assert_eq!(None, sorted.quantile(1.5, QuantileMethod::Linear));
+    /// ```
+    pub fn quantile(&self, q: f64, method: QuantileMethod) -> Option<f64> {
+        quantile(self.vals, q, method)
+    }
+
+    /// Percentile rank (from 0 to 100) of a value, the percent of values less
+    /// than it plus half of the percent of values equal to it. The value
+    /// doesn't need to be one of the values.
+    ///
+    /// This method returns `None` if there are no values.
+    ///
+    /// ```
+    /// use staccato::SortedValues;
+    ///
+    /// let sorted = SortedValues::new(&[1.0, 2.0, 3.0, 4.0]).unwrap();
+    /// assert_eq!(Some(62.5), sorted.rank_of(3.0));
+    /// assert_eq!(Some(100.0), sorted.rank_of(10.0));
+    /// ```
+    pub fn rank_of(&self, val: f64) -> Option<f64> {
+        percentile_rank(self.vals, val)
+    }
+}
+
+/// Percentile rank of a value in a **sorted** sequence of values, see
+/// `SortedValues::rank_of`.
+pub(crate) fn percentile_rank<V: Value>(sorted: &[V], val: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let below = sorted.partition_point(|&v| v.into() < val);
+    let equal = sorted.partition_point(|&v| v.into() <= val) - below;
+    Some((below as f64 + equal as f64 / 2f64) / sorted.len() as f64 * 100f64)
+}
+
+/// Owned sequence of values that is known to be sorted in ascending order,
+/// e.g. to keep the values used to compute a bundle for later queries.
+///
+/// `NaN` values are removed when sorting, since they're ignored when
+/// computing statistics.
+///
+/// ```
+/// use staccato::{QuantileMethod, SortedVec};
+///
+/// let sorted = SortedVec::sort(vec![4.0, 1.0, f64::NAN, 3.0, 2.0]);
+/// assert_eq!(&[1.0, 2.0, 3.0, 4.0], sorted.as_slice());
+/// assert_eq!(Some(2.5), sorted.quantile(0.5, QuantileMethod::Linear));
+/// assert_eq!(Some(12.5), sorted.rank_of(1.0));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SortedVec<V> {
+    vals: Vec<V>,
+}
+
+impl<V: Value> SortedVec<V> {
+    /// Remove any `NaN` values and sort the rest in place, the same way as
+    /// `sort_values`, without copying them.
+    pub fn sort(mut vals: Vec<V>) -> SortedVec<V> {
+        vals.retain(|&v| !v.into().is_nan());
+        sort_values(&mut vals);
+        SortedVec { vals }
+    }
+
+    /// Borrow the values, e.g. to compute statistics from them.
+    pub fn as_sorted(&self) -> SortedValues<'_, V> {
+        SortedValues { vals: &self.vals }
+    }
+
+    pub fn as_slice(&self) -> &[V] {
+        &self.vals
+    }
+
+    pub fn into_vec(self) -> Vec<V> {
+        self.vals
+    }
+
+    pub fn len(&self) -> usize {
+        self.vals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vals.is_empty()
+    }
+
+    /// Value at quantile `q` (from 0 to 1, inclusive) of the values, see
+    /// `SortedValues::quantile`.
+    pub fn quantile(&self, q: f64, method: QuantileMethod) -> Option<f64> {
+        self.as_sorted().quantile(q, method)
+    }
+
+    /// Percentile rank (from 0 to 100) of a value, see `SortedValues::rank_of`.
+    pub fn rank_of(&self, val: f64) -> Option<f64> {
+        self.as_sorted().rank_of(val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SortedValues, SortedVec};
+    use crate::QuantileMethod;
+
+    #[test]
+    fn test_sorted_values_sort() {
+        let mut vals = vec![3f64, 1f64, 2f64];
+        let sorted = SortedValues::sort(&mut vals);
+
+        assert_eq!(&[1f64, 2f64, 3f64], sorted.as_slice());
+    }
+
+    #[test]
+    fn test_sorted_values_new() {
+        assert!(SortedValues::new(&[1f64, 2f64, 2f64, 3f64]).is_some());
+        assert!(SortedValues::<f64>::new(&[]).is_some());
+        assert!(SortedValues::new(&[1f32]).is_some());
+    }
+
+    #[test]
+    fn test_sorted_values_new_unsorted() {
+        assert!(SortedValues::new(&[1f64, 3f64, 2f64]).is_none());
+        assert!(SortedValues::new(&[1f64, f64::NAN]).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn test_sorted_values_new_unchecked_unsorted() {
+        SortedValues::new_unchecked(&[3f64, 1f64]);
+    }
+
+    #[test]
+    fn test_sorted_values_quantile_bounds() {
+        let sorted = SortedValues::new(&[1f64, 2f64, 5f64, 7f64, 9f64]).unwrap();
+        for &method in &[QuantileMethod::Linear, QuantileMethod::Nearest] {
+            assert_eq!(Some(1f64), sorted.quantile(0f64, method));
+            assert_eq!(Some(9f64), sorted.quantile(1f64, method));
+        }
+    }
+
+    #[test]
+    fn test_sorted_values_quantile_out_of_range() {
+        let sorted = SortedValues::new(&[1f64, 2f64]).unwrap();
+        assert_eq!(None, sorted.quantile(-0.1, QuantileMethod::Linear));
+        assert_eq!(None, sorted.quantile(1.01, QuantileMethod::Linear));
+        assert_eq!(None, sorted.quantile(f64::NAN, QuantileMethod::Linear));
+    }
+
+    #[test]
+    fn test_sorted_values_quantile_tiny() {
+        let empty = SortedValues::<f64>::new(&[]).unwrap();
+        assert_eq!(None, empty.quantile(0.5, QuantileMethod::Linear));
+
+        let one = SortedValues::new(&[3f32]).unwrap();
+        assert_eq!(Some(3f64), one.quantile(0f64, QuantileMethod::Linear));
+        assert_eq!(Some(3f64), one.quantile(0.999, QuantileMethod::Nearest));
+
+        let two = SortedValues::new(&[1f64, 2f64]).unwrap();
+        assert_eq!(Some(1.999), two.quantile(0.999, QuantileMethod::Linear));
+        assert_eq!(Some(2f64), two.quantile(0.999, QuantileMethod::Nearest));
+        assert_eq!(Some(1f64), two.quantile(0.5, QuantileMethod::Nearest));
+    }
+
+    #[test]
+    fn test_sorted_values_rank_of() {
+        let sorted = SortedValues::new(&[1f64, 2f64, 2f64, 3f64]).unwrap();
+        assert_eq!(Some(0f64), sorted.rank_of(0f64));
+        assert_eq!(Some(12.5), sorted.rank_of(1f64));
+        assert_eq!(Some(50f64), sorted.rank_of(2f64));
+        assert_eq!(Some(75f64), sorted.rank_of(2.5));
+        assert_eq!(Some(100f64), sorted.rank_of(4f64));
+        assert_eq!(None, SortedValues::<f64>::new(&[]).unwrap().rank_of(1f64));
+    }
+
+    #[test]
+    fn test_sorted_vec_sort_in_place() {
+        let vals = vec![3f64, f64::NAN, 1f64, 2f64];
+        let ptr = vals.as_ptr();
+        let sorted = SortedVec::sort(vals);
+
+        assert_eq!(&[1f64, 2f64, 3f64], sorted.as_slice());
+        assert_eq!(ptr, sorted.as_slice().as_ptr());
+        assert!(SortedValues::new(sorted.as_slice()).is_some());
+    }
+
+    #[test]
+    fn test_sorted_vec_queries() {
+        let sorted = SortedVec::sort(vec![4f32, 1f32, 3f32, 2f32]);
+        assert_eq!(4, sorted.len());
+        assert_eq!(Some(2.5), sorted.quantile(0.5, QuantileMethod::Linear));
+        assert_eq!(Some(62.5), sorted.rank_of(3f64));
+        assert_eq!(vec![1f32, 2f32, 3f32, 4f32], sorted.into_vec());
+    }
+}
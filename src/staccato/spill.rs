@@ -0,0 +1,494 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::quantile::{Quantile, QuantileRank};
+use crate::{
+    percentile_len, sort_values, try_for_each_line, MergeHead, QuantileMethod, RunningMoments, SortedValues,
+    StaccatoError, Statistics, StatisticsBundle,
+};
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Default number of values kept in memory before being written to a
+/// temporary file, about 512MB worth of values.
+pub const DEFAULT_RUN_SIZE: usize = 64 * 1024 * 1024;
+
+static NEXT_RUN: AtomicUsize = AtomicUsize::new(0);
+
+/// Computes exact statistics of a sequence of values too large to fit in
+/// memory using an external sort.
+///
+/// Values are kept in memory until there are `run_size` of them, at which
+/// point they are sorted and written to a temporary file as a "run". Once
+/// all values have been pushed, the runs are merged into a single sorted
+/// sequence that is read once to compute statistics, so at most `run_size`
+/// values are ever held in memory.
+///
+/// Temporary files are removed as soon as they are created on platforms
+/// that allow using a file after it has been removed (e.g. Linux and macOS)
+/// so they are cleaned up even if the process is interrupted or killed.
+/// Otherwise they are removed when no longer needed, including when an
+/// error occurs.
+#[derive(Debug)]
+pub struct ExternalSorter {
+    dir: PathBuf,
+    run_size: usize,
+    count: usize,
+    buffer: Vec<f64>,
+    runs: Vec<Run>,
+}
+
+impl ExternalSorter {
+    /// Create a sorter that writes runs of at most `run_size` values (at
+    /// least one) to temporary files in the given directory.
+    pub fn new<P: Into<PathBuf>>(dir: P, run_size: usize) -> ExternalSorter {
+        ExternalSorter {
+            dir: dir.into(),
+            run_size: run_size.max(1),
+            count: 0,
+            buffer: Vec::new(),
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, val: f64) -> Result<(), StaccatoError> {
+        self.buffer.push(val);
+        self.count += 1;
+
+        if self.buffer.len() >= self.run_size {
+            self.spill()?;
+        }
+
+        Ok(())
+    }
+
+    /// Push values read one line at a time, parsed the same way `get_values`
+    /// parses them.
+    pub fn push_reader<T: BufRead>(&mut self, reader: &mut T) -> Result<(), StaccatoError> {
+        try_for_each_line(reader, |line| match line.trim().parse::<f64>() {
+            Ok(v) => self.push(v),
+            Err(_) => Ok(()),
+        })
+    }
+
+    /// Number of values pushed so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Number of runs written to temporary files so far.
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Compute a statistics bundle from all values pushed, the same as the
+    /// `StatisticsBundle::from_sorted` method other than rounding error in
+    /// the standard deviation.
+    ///
+    /// This method returns `Ok(None)` if no values have been pushed and an
+    /// error if the temporary files couldn't be written or read.
+    pub fn bundle(
+        mut self,
+        percentiles: &[u8],
+        quantiles: &[f64],
+        method: QuantileMethod,
+    ) -> Result<Option<StatisticsBundle>, StaccatoError> {
+        if self.runs.is_empty() {
+            sort_values(&mut self.buffer);
+            let sorted = SortedValues::new_unchecked(&self.buffer);
+            return Ok(StatisticsBundle::from_sorted(sorted, percentiles, quantiles, method));
+        }
+
+        self.spill()?;
+
+        let count = self.count;
+        let ends: Vec<usize> = percentiles.iter().map(|&p| percentile_len(count, p)).collect();
+        let ranks: Vec<(f64, QuantileRank)> = quantiles
+            .iter()
+            .flat_map(|&q| QuantileRank::from(count, q / 100f64, method).map(|r| (q, r)))
+            .collect();
+
+        // Only the values at these positions in the merged sequence are
+        // kept: the lowest, highest, and median values of each slice and
+        // the values needed for each quantile.
+        let mut kept: BTreeMap<usize, f64> = BTreeMap::new();
+        for &end in ends.iter().chain(Some(&count)).filter(|&&e| e > 0) {
+            for i in [0, end - 1, (end - 1) / 2, end / 2] {
+                kept.insert(i, f64::NAN);
+            }
+        }
+
+        for (_, rank) in &ranks {
+            for i in rank.positions() {
+                kept.insert(i, f64::NAN);
+            }
+        }
+
+        let mut slices: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for (i, &end) in ends.iter().enumerate().filter(|(_, &e)| e > 0) {
+            slices.entry(end).or_default().push(i);
+        }
+
+        // Shifting by the median of the medians of each run keeps the shift
+        // close to the mean, see `RunningMoments`.
+        let mut medians: Vec<f64> = self.runs.iter().map(|r| r.median).collect();
+        sort_values(&mut medians);
+
+        let mut moments = RunningMoments::new(Statistics::compute_median(&medians));
+        let mut percentile_stats: Vec<Option<Statistics>> = vec![None; percentiles.len()];
+        let mut merge = RunMerge::new(&self.runs)?;
+        let mut index = 0;
+
+        while let Some(val) = merge.next_value()? {
+            moments.push_value(val);
+            if let Some(v) = kept.get_mut(&index) {
+                *v = val;
+            }
+
+            index += 1;
+            if let Some(idx) = slices.get(&index) {
+                for &i in idx {
+                    percentile_stats[i] = Some(snapshot(&moments, &kept, Some(percentiles[i])));
+                }
+            }
+        }
+
+        if index != count {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "temporary file did not contain all values",
+            )
+            .into());
+        }
+
+        Ok(Some(
+            StatisticsBundle {
+                global: snapshot(&moments, &kept, None),
+                quantiles: ranks
+                    .iter()
+                    .map(|(q, r)| Quantile::new(*q, r.value(|i| kept[&i])))
+                    .collect(),
+                percentiles: percentile_stats.into_iter().flatten().collect(),
+                sample_size: None,
+                omitted: Vec::new(),
+                omitted_quantiles: Vec::new(),
+            }
+            .with_omitted(percentiles),
+        ))
+    }
+
+    fn spill(&mut self) -> Result<(), io::Error> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        sort_values(&mut self.buffer);
+        self.runs.push(Run::create(&self.dir, &self.buffer)?);
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+/// Statistics of the prefix of values pushed so far, using the same method
+/// to compute the median as `Statistics`.
+fn snapshot(moments: &RunningMoments, kept: &BTreeMap<usize, f64>, percentile: Option<u8>) -> Statistics {
+    let len = moments.count;
+    let median = if len % 2 == 1 {
+        kept[&(len / 2)]
+    } else {
+        (kept[&(len / 2)] + kept[&(len / 2 - 1)]) / 2f64
+    };
+
+    moments.statistics_with(percentile, kept[&0], kept[&(len - 1)], median)
+}
+
+/// Sorted values written to a temporary file.
+#[derive(Debug)]
+struct Run {
+    file: Option<File>,
+    path: Option<PathBuf>,
+    median: f64,
+}
+
+impl Run {
+    fn create(dir: &Path, sorted: &[f64]) -> Result<Run, io::Error> {
+        let path = dir.join(format!(
+            "staccato-{}-{}.run",
+            process::id(),
+            NEXT_RUN.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+
+        let run = Run {
+            file: Some(file),
+            path: fs::remove_file(&path).err().map(|_| path),
+            median: Statistics::compute_median(sorted),
+        };
+
+        let mut writer = BufWriter::new(run.file());
+        for v in sorted {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+
+        writer.flush()?;
+        drop(writer);
+
+        run.file().seek(SeekFrom::Start(0))?;
+        Ok(run)
+    }
+
+    fn file(&self) -> &File {
+        self.file.as_ref().unwrap()
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        // Close the file before removing it since some platforms don't
+        // allow removing a file that is still open.
+        self.file.take();
+        if let Some(path) = &self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Merges the values of several runs into a single sorted sequence.
+struct RunMerge<'a> {
+    readers: Vec<BufReader<&'a File>>,
+    heads: BinaryHeap<MergeHead>,
+}
+
+impl<'a> RunMerge<'a> {
+    fn new(runs: &'a [Run]) -> Result<RunMerge<'a>, io::Error> {
+        let mut merge = RunMerge {
+            readers: runs.iter().map(|r| BufReader::new(r.file())).collect(),
+            heads: BinaryHeap::with_capacity(runs.len()),
+        };
+
+        for seq in 0..runs.len() {
+            merge.advance(seq, 0)?;
+        }
+
+        Ok(merge)
+    }
+
+    fn next_value(&mut self) -> Result<Option<f64>, io::Error> {
+        match self.heads.pop() {
+            Some(head) => {
+                self.advance(head.seq, head.index + 1)?;
+                Ok(Some(head.val))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn advance(&mut self, seq: usize, index: usize) -> Result<(), io::Error> {
+        let mut buf = [0u8; 8];
+        match self.readers[seq].read_exact(&mut buf) {
+            Ok(()) => {
+                self.heads.push(MergeHead {
+                    val: f64::from_le_bytes(buf),
+                    seq,
+                    index,
+                });
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExternalSorter;
+    use crate::{QuantileMethod, SortedValues, StatisticsBundle};
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use std::process;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("staccato-test-{}-{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn random_values(n: usize) -> Vec<f64> {
+        let mut state = 5u64;
+        (0..n)
+            .map(|i| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                // Some repeated values to make sure ties are merged correctly
+                if i % 10 == 0 {
+                    3f64
+                } else {
+                    (state >> 40) as f64 / 1000f64
+                }
+            })
+            .collect()
+    }
+
+    fn assert_same(expected: &StatisticsBundle, actual: &StatisticsBundle) {
+        let fields = |b: &StatisticsBundle| {
+            let mut out = vec![];
+            for s in Some(b.global_stats()).into_iter().chain(b.percentile_stats()) {
+                out.push((
+                    s.percentile(),
+                    s.count(),
+                    s.sum().to_bits(),
+                    s.upper().to_bits(),
+                    s.lower().to_bits(),
+                    s.median().to_bits(),
+                ));
+            }
+            out
+        };
+
+        assert_eq!(fields(expected), fields(actual));
+        assert_eq!(expected.quantile_values(), actual.quantile_values());
+
+        let stddevs = |b: &StatisticsBundle| {
+            Some(b.global_stats())
+                .into_iter()
+                .chain(b.percentile_stats())
+                .map(|s| s.stddev())
+                .collect::<Vec<f64>>()
+        };
+        for (a, b) in stddevs(expected).iter().zip(stddevs(actual)) {
+            assert!((a - b).abs() <= 1e-12 * a.abs(), "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_external_sorter_same_as_in_memory() {
+        let dir = temp_dir("same");
+        let vals = random_values(5003);
+        let mut sorted = vals.clone();
+        let expected = StatisticsBundle::from_sorted(
+            SortedValues::sort(&mut sorted),
+            &[90, 50, 0, 99, 1],
+            &[0f64, 25f64, 99.9, 100f64],
+            QuantileMethod::Linear,
+        )
+        .unwrap();
+
+        let mut sorter = ExternalSorter::new(&dir, 100);
+        for &v in &vals {
+            sorter.push(v).unwrap();
+        }
+
+        assert_eq!(50, sorter.runs());
+        let actual = sorter
+            .bundle(
+                &[90, 50, 0, 99, 1],
+                &[0f64, 25f64, 99.9, 100f64],
+                QuantileMethod::Linear,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_same(&expected, &actual);
+        assert_eq!(expected.omitted_percentiles(), actual.omitted_percentiles());
+        assert_eq!(0, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_external_sorter_nearest_quantiles() {
+        let dir = temp_dir("nearest");
+        let vals = random_values(1000);
+        let mut sorted = vals.clone();
+        let expected = StatisticsBundle::from_sorted(
+            SortedValues::sort(&mut sorted),
+            &[],
+            &[10f64, 50f64, 95f64],
+            QuantileMethod::Nearest,
+        )
+        .unwrap();
+
+        let mut sorter = ExternalSorter::new(&dir, 7);
+        for &v in &vals {
+            sorter.push(v).unwrap();
+        }
+
+        let actual = sorter
+            .bundle(&[], &[10f64, 50f64, 95f64], QuantileMethod::Nearest)
+            .unwrap()
+            .unwrap();
+
+        assert_same(&expected, &actual);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_external_sorter_fits_in_memory() {
+        let dir = temp_dir("memory");
+        let mut sorter = ExternalSorter::new(&dir, 100);
+        sorter.push_reader(&mut Cursor::new("3\n1\nfoo\n2\n")).unwrap();
+
+        assert_eq!(3, sorter.count());
+        assert_eq!(0, sorter.runs());
+
+        let bundle = sorter.bundle(&[], &[], QuantileMethod::Linear).unwrap().unwrap();
+        assert_eq!(2f64, bundle.global_stats().median());
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_external_sorter_empty() {
+        let dir = temp_dir("empty");
+        let sorter = ExternalSorter::new(&dir, 100);
+
+        assert!(sorter.bundle(&[50], &[], QuantileMethod::Linear).unwrap().is_none());
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_external_sorter_removes_files_when_dropped() {
+        let dir = temp_dir("dropped");
+        let mut sorter = ExternalSorter::new(&dir, 10);
+        for v in 0..100 {
+            sorter.push(v as f64).unwrap();
+        }
+
+        assert_eq!(10, sorter.runs());
+        drop(sorter);
+
+        assert_eq!(0, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_external_sorter_missing_dir() {
+        let mut sorter = ExternalSorter::new(temp_dir("missing").join("does-not-exist"), 2);
+        sorter.push(1f64).unwrap();
+
+        assert!(sorter.push(2f64).is_err());
+        fs::remove_dir(temp_dir("missing")).unwrap();
+    }
+}
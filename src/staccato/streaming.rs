@@ -0,0 +1,504 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::digest::TDigest;
+use crate::quantile::Quantile;
+use crate::{for_each_line, sum_overflowed, CompensatedSum, StaccatoError, Statistics, StatisticsBundle};
+use std::io::BufRead;
+
+/// Statistics computed one value at a time, in a single pass, without
+/// keeping any of the values around.
+///
+/// The mean and standard deviation are computed using Welford's online
+/// algorithm so they remain accurate over long sequences of values. Since
+/// the values aren't kept, the median and other quantiles are estimated
+/// using a `TDigest` instead of being computed exactly.
+///
+/// Statistics of separate sequences of values, e.g. pushed on different
+/// threads, can be combined with `merge` into statistics of all of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamingStatistics {
+    count: usize,
+    sum: CompensatedSum,
+    shift: f64,
+    mean: f64,
+    m2: f64,
+    upper: f64,
+    lower: f64,
+    digest: Option<TDigest>,
+}
+
+impl StreamingStatistics {
+    pub fn new() -> StreamingStatistics {
+        StreamingStatistics {
+            count: 0,
+            sum: CompensatedSum::default(),
+            shift: 0f64,
+            mean: 0f64,
+            m2: 0f64,
+            upper: f64::MIN,
+            lower: f64::MAX,
+            digest: Some(TDigest::new()),
+        }
+    }
+
+    /// Create statistics that don't estimate the median or quantiles, which
+    /// makes pushing each value cheaper when they aren't needed. The median
+    /// of snapshots is `NaN` and there are never any quantiles.
+    pub fn without_digest() -> StreamingStatistics {
+        StreamingStatistics {
+            digest: None,
+            ..Self::new()
+        }
+    }
+
+    /// Update the statistics with a single value. `NaN` values are ignored,
+    /// the same as by `Statistics`.
+    pub fn push(&mut self, val: f64) {
+        if val.is_nan() {
+            return;
+        }
+
+        self.count += 1;
+        self.sum.add(val);
+
+        // Values are shifted by the first value so that rounding error in
+        // the running mean stays small relative to the deviation even when
+        // the mean is large (e.g. timestamps).
+        if self.count == 1 {
+            self.shift = val;
+        }
+
+        let shifted = val - self.shift;
+        let delta = shifted - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (shifted - self.mean);
+
+        if val > self.upper {
+            self.upper = val;
+        }
+
+        if val < self.lower {
+            self.lower = val;
+        }
+
+        if let Some(d) = self.digest.as_mut() {
+            d.push(val);
+        }
+    }
+
+    /// Combine the statistics of values pushed to another instance with the
+    /// values pushed to this one, as if they had all been pushed here.
+    ///
+    /// The count, sum, upper, and lower values are combined exactly and the
+    /// mean and standard deviation are combined using the parallel variance
+    /// formula of Chan et al. The median and quantiles are only estimated
+    /// afterwards if both instances estimate them.
+    pub fn merge(&mut self, other: &StreamingStatistics) {
+        match (self.digest.as_mut(), other.digest.as_ref()) {
+            (Some(d), Some(o)) => d.merge(o),
+            _ => self.digest = None,
+        }
+
+        if other.count == 0 {
+            return;
+        }
+
+        if self.count == 0 {
+            self.shift = other.shift;
+            self.mean = other.mean;
+            self.m2 = other.m2;
+        } else {
+            // The mean of the other values is shifted by their own first value
+            // so it needs to be shifted by ours instead before combining them.
+            let count = self.count + other.count;
+            let delta = (other.shift - self.shift) + other.mean - self.mean;
+            let weight = other.count as f64 / count as f64;
+
+            self.m2 += other.m2 + delta * delta * self.count as f64 * weight;
+            self.mean += delta * weight;
+        }
+
+        self.count += other.count;
+        self.sum.merge(&other.sum);
+        self.upper = self.upper.max(other.upper);
+        self.lower = self.lower.min(other.lower);
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Number of values pushed so far, the same as `count`.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Update the statistics with values read one line at a time, parsed
+    /// the same way `get_values` parses them.
+    ///
+    /// Only a single line is held in memory at any time, regardless of the
+    /// size of the input.
+    pub fn push_reader<T: BufRead>(&mut self, reader: &mut T) -> Result<(), StaccatoError> {
+        for_each_line(reader, |line| {
+            if let Ok(v) = line.trim().parse::<f64>() {
+                self.push(v);
+            }
+        })
+    }
+
+    /// Estimate the value at quantile `q` (from 0 to 1, inclusive) of all
+    /// the values pushed so far.
+    ///
+    /// This method returns `None` if no values have been pushed, if the
+    /// quantile is not between 0 and 1, or if quantiles aren't estimated.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        self.digest.as_ref().and_then(|d| d.quantile(q))
+    }
+
+    /// Compute statistics of all the values pushed so far, which can be done
+    /// any number of times while values are still being pushed.
+    ///
+    /// The median of the resulting statistics is an estimate, or `NaN` if
+    /// quantiles aren't estimated. This method returns `None` if no values
+    /// have been pushed.
+    pub fn snapshot(&self) -> Option<Statistics> {
+        if self.count == 0 {
+            return None;
+        }
+
+        Some(Statistics {
+            percentile: None,
+            count: self.count,
+            sum: self.sum.total(),
+            mean: self.shift + self.mean,
+            upper: self.upper,
+            lower: self.lower,
+            median: self.quantile(0.5).unwrap_or(f64::NAN),
+            stddev: (self.m2 / self.count as f64).sqrt(),
+            merged: false,
+            overflowed: sum_overflowed(self.sum.total(), self.lower, self.upper),
+        })
+    }
+
+    /// Compute statistics of all the values pushed so far, the same as
+    /// `snapshot`.
+    pub fn finalize(&self) -> Option<Statistics> {
+        self.snapshot()
+    }
+
+    /// Create a statistics bundle from all the values pushed so far along
+    /// with estimates of the values at each quantile (expressed as percents
+    /// from 0 to 100).
+    ///
+    /// This method returns `None` if no values have been pushed. Quantiles
+    /// outside of the range 0 to 100 are omitted. Percentile slices can't
+    /// be computed without all the values so the bundle never has any.
+    pub fn bundle(&self, quantiles: &[f64]) -> Option<StatisticsBundle> {
+        let quantile_vals = quantiles
+            .iter()
+            .flat_map(|&p| self.quantile(p / 100f64).map(|v| Quantile::new(p, v)))
+            .collect();
+
+        self.snapshot().map(|global| StatisticsBundle {
+            global,
+            quantiles: quantile_vals,
+            percentiles: Vec::new(),
+            sample_size: None,
+            omitted: Vec::new(),
+            omitted_quantiles: Vec::new(),
+        })
+    }
+}
+
+impl Default for StreamingStatistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read values one line at a time, the same way `get_values` parses them,
+/// and compute streaming statistics of them.
+///
+/// Only a single line is held in memory at any time, regardless of the
+/// size of the input.
+pub fn get_streaming_statistics<T: BufRead>(reader: &mut T) -> Result<StreamingStatistics, StaccatoError> {
+    let mut stats = StreamingStatistics::new();
+    stats.push_reader(reader)?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_streaming_statistics, StreamingStatistics};
+    use crate::{sort_values, Statistics, StatisticsFormatter};
+    use std::thread;
+
+    const VALUES: &[f64] = &[3f64, -1.5, 4f64, 1f64, 5.25, 9f64, 2f64, 6f64, 5f64, 3.5];
+
+    fn random_values(n: usize, seed: u64) -> Vec<f64> {
+        let mut state = seed;
+        (0..n)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                1e6 + (state >> 33) as f64 / 1000f64 - (state >> 60) as f64 * 1e3
+            })
+            .collect()
+    }
+
+    fn pushed(vals: &[f64]) -> StreamingStatistics {
+        let mut stats = StreamingStatistics::new();
+        for &v in vals {
+            stats.push(v);
+        }
+
+        stats
+    }
+
+    fn assert_close(expected: f64, actual: f64) {
+        let tolerance = 1e-7 * expected.abs().max(1f64);
+        assert!((expected - actual).abs() < tolerance, "{} != {}", expected, actual);
+    }
+
+    fn streamed(vals: &[f64]) -> Statistics {
+        let mut stats = StreamingStatistics::new();
+        for &v in vals {
+            stats.push(v);
+        }
+
+        stats.finalize().unwrap()
+    }
+
+    #[test]
+    fn test_streaming_statistics_empty() {
+        assert!(StreamingStatistics::new().finalize().is_none());
+    }
+
+    #[test]
+    fn test_streaming_statistics_matches_batch() {
+        let mut sorted = VALUES.to_vec();
+        sort_values(&mut sorted);
+        let batch = Statistics::from(&sorted, None).unwrap();
+        let stream = streamed(VALUES);
+
+        assert_eq!(batch.count(), stream.count());
+        assert_close(batch.sum(), stream.sum());
+        assert_close(batch.mean(), stream.mean());
+        assert_eq!(batch.upper(), stream.upper());
+        assert_eq!(batch.lower(), stream.lower());
+        assert_close(batch.stddev(), stream.stddev());
+        assert_eq!(batch.median(), stream.median());
+    }
+
+    #[test]
+    fn test_streaming_statistics_matches_batch_large_offset() {
+        let vals: Vec<f64> = (0..10_000).map(|i| 1e9 + (i % 7) as f64 * 0.25).collect();
+        let batch = Statistics::from(&vals, None).unwrap();
+        let stream = streamed(&vals);
+
+        assert_close(batch.mean(), stream.mean());
+        assert_close(batch.stddev(), stream.stddev());
+    }
+
+    #[test]
+    fn test_streaming_statistics_sum_overflowed() {
+        let stream = streamed(&[f64::MAX / 2f64, f64::MAX, f64::MAX]);
+
+        assert!(stream.overflowed());
+        assert_eq!(f64::INFINITY, stream.sum());
+        assert_close(f64::MAX / 6f64 * 5f64, stream.mean());
+        assert!(!streamed(VALUES).overflowed());
+    }
+
+    #[test]
+    fn test_streaming_statistics_single_value() {
+        let stream = streamed(&[42f64]);
+
+        assert_eq!(42f64, stream.mean());
+        assert_eq!(0f64, stream.stddev());
+        assert_eq!(42f64, stream.upper());
+        assert_eq!(42f64, stream.lower());
+    }
+
+    #[test]
+    fn test_streaming_statistics_ignores_nan() {
+        let stream = streamed(&[f64::NAN, 3f64, -f64::NAN, 1f64, f64::NAN]);
+        let expected = streamed(&[3f64, 1f64]);
+
+        assert_eq!(2, stream.count());
+        assert_eq!(expected.mean(), stream.mean());
+        assert_eq!(expected.stddev(), stream.stddev());
+        assert_eq!(3f64, stream.upper());
+        assert_eq!(1f64, stream.lower());
+    }
+
+    #[test]
+    fn test_get_streaming_statistics() {
+        let input = "3\n  4.5 \nbanana\n\n-1\n";
+        let stats = get_streaming_statistics(&mut input.as_bytes()).unwrap();
+        let out = stats.finalize().unwrap();
+
+        assert_eq!(3, out.count());
+        assert_eq!(6.5, out.sum());
+        assert_eq!(4.5, out.upper());
+        assert_eq!(-1f64, out.lower());
+    }
+
+    #[test]
+    fn test_streaming_statistics_push_reader_multiple() {
+        let mut stats = StreamingStatistics::new();
+        stats.push_reader(&mut "1\n2".as_bytes()).unwrap();
+        stats.push_reader(&mut "3\n".as_bytes()).unwrap();
+
+        assert_eq!(3, stats.count());
+        assert_eq!(6f64, stats.finalize().unwrap().sum());
+    }
+
+    #[test]
+    fn test_streaming_statistics_bundle() {
+        let mut stats = StreamingStatistics::new();
+        (1..=1000).for_each(|v| stats.push(v as f64));
+        let bundle = stats.bundle(&[90f64, 99f64, 150f64]).unwrap();
+
+        assert_eq!(2, bundle.quantile_values().len());
+        assert!((bundle.quantile_values()[0].value() - 900f64).abs() < 10f64);
+        assert!((bundle.quantile_values()[1].value() - 990f64).abs() < 10f64);
+        assert!(bundle.percentile_stats().is_empty());
+    }
+
+    #[test]
+    fn test_streaming_statistics_bundle_formatted() {
+        let mut stats = StreamingStatistics::new();
+        (1..=3).for_each(|v| stats.push(v as f64));
+        let bundle = stats.bundle(&[]).unwrap();
+        let out = format!("{}", StatisticsFormatter::new(&bundle).precision(1));
+
+        assert_eq!(
+            "count: 3\nsum: 6.0\nmean: 2.0\nupper: 3.0\nlower: 1.0\nmedian: 2.0\nstddev: 0.8\n",
+            out
+        );
+    }
+
+    #[test]
+    fn test_streaming_statistics_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<StreamingStatistics>();
+    }
+
+    #[test]
+    fn test_streaming_statistics_len() {
+        let mut stats = StreamingStatistics::new();
+        assert!(stats.is_empty());
+
+        stats.push(1f64);
+        stats.push(2f64);
+        assert_eq!(2, stats.len());
+        assert!(!stats.is_empty());
+    }
+
+    #[test]
+    fn test_streaming_statistics_snapshot_matches_batch_random() {
+        for seed in 1..=20 {
+            let vals = random_values(1 + seed as usize * 37, seed);
+            let batch = Statistics::from(&vals, None).unwrap();
+            let stream = pushed(&vals).snapshot().unwrap();
+
+            assert_eq!(batch.count(), stream.count());
+            assert_close(batch.sum(), stream.sum());
+            assert_close(batch.mean(), stream.mean());
+            assert_eq!(batch.upper(), stream.upper());
+            assert_eq!(batch.lower(), stream.lower());
+            assert_close(batch.stddev(), stream.stddev());
+        }
+    }
+
+    #[test]
+    fn test_streaming_statistics_merge_matches_batch_random() {
+        for seed in 1..=20 {
+            let vals = random_values(500 + seed as usize * 13, seed);
+            let batch = Statistics::from(&vals, None).unwrap();
+
+            // Shards of different sizes, including an empty one
+            let bounds = [0, 0, seed as usize * 7, vals.len() / 2, vals.len()];
+            let mut merged = StreamingStatistics::new();
+            for w in bounds.windows(2) {
+                merged.merge(&pushed(&vals[w[0]..w[1]]));
+            }
+
+            let stream = merged.snapshot().unwrap();
+            assert_eq!(batch.count(), stream.count());
+            assert_close(batch.sum(), stream.sum());
+            assert_close(batch.mean(), stream.mean());
+            assert_eq!(batch.upper(), stream.upper());
+            assert_eq!(batch.lower(), stream.lower());
+            assert_close(batch.stddev(), stream.stddev());
+            assert!((batch.median() - stream.median()).abs() < (batch.upper() - batch.lower()) * 0.02);
+        }
+    }
+
+    #[test]
+    fn test_streaming_statistics_merge_across_threads() {
+        let vals = random_values(10_000, 7);
+        let shards: Vec<StreamingStatistics> = vals
+            .chunks(2500)
+            .map(|chunk| {
+                let chunk = chunk.to_vec();
+                thread::spawn(move || pushed(&chunk))
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect();
+
+        let mut merged = StreamingStatistics::new();
+        shards.iter().for_each(|s| merged.merge(s));
+
+        assert_eq!(pushed(&vals).snapshot().unwrap().count(), merged.len());
+        assert_close(
+            Statistics::from(&vals, None).unwrap().stddev(),
+            merged.snapshot().unwrap().stddev(),
+        );
+    }
+
+    #[test]
+    fn test_streaming_statistics_without_digest() {
+        let mut stats = StreamingStatistics::without_digest();
+        VALUES.iter().for_each(|&v| stats.push(v));
+        let snapshot = stats.snapshot().unwrap();
+
+        assert_eq!(VALUES.len(), snapshot.count());
+        assert!(snapshot.median().is_nan());
+        assert_eq!(None, stats.quantile(0.5));
+        assert!(stats.bundle(&[50f64]).unwrap().quantile_values().is_empty());
+    }
+
+    #[test]
+    fn test_streaming_statistics_merge_without_digest() {
+        let mut stats = pushed(VALUES);
+        stats.merge(&StreamingStatistics::without_digest());
+
+        assert_eq!(None, stats.quantile(0.5));
+        assert_eq!(VALUES.len(), stats.len());
+    }
+}
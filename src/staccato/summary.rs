@@ -0,0 +1,127 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::quantile::{quantile, QuantileMethod};
+
+/// Classic five-number summary (lower, first quartile, median, third
+/// quartile, and upper) of a sequence of values, along with the count.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FiveNumberSummary {
+    count: usize,
+    lower: f64,
+    q1: f64,
+    median: f64,
+    q3: f64,
+    upper: f64,
+}
+
+impl FiveNumberSummary {
+    /// Create a five-number summary from a **sorted** sequence of values.
+    ///
+    /// The quartiles and median are computed with the same method used for
+    /// arbitrary quantiles so they are consistent with each other.
+    ///
+    /// This method returns `None` if the sequence of values is empty.
+    pub fn from(vals: &[f64], method: QuantileMethod) -> Option<FiveNumberSummary> {
+        Some(FiveNumberSummary {
+            count: vals.len(),
+            lower: quantile(vals, 0f64, method)?,
+            q1: quantile(vals, 0.25, method)?,
+            median: quantile(vals, 0.5, method)?,
+            q3: quantile(vals, 0.75, method)?,
+            upper: quantile(vals, 1f64, method)?,
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn lower(&self) -> f64 {
+        self.lower
+    }
+
+    pub fn q1(&self) -> f64 {
+        self.q1
+    }
+
+    pub fn median(&self) -> f64 {
+        self.median
+    }
+
+    pub fn q3(&self) -> f64 {
+        self.q3
+    }
+
+    pub fn upper(&self) -> f64 {
+        self.upper
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FiveNumberSummary;
+    use crate::quantile::{Quantile, QuantileMethod};
+    use crate::StatisticsBundle;
+
+    const VALUES: &[f64] = &[1f64, 2f64, 5f64, 7f64, 9f64, 12f64];
+
+    const ODD: &[f64] = &[1f64, 2f64, 5f64, 7f64, 9f64, 12f64, 20f64];
+
+    #[test]
+    fn test_five_number_summary_empty() {
+        assert!(FiveNumberSummary::from(&[], QuantileMethod::Linear).is_none());
+    }
+
+    #[test]
+    fn test_five_number_summary_values() {
+        let summary = FiveNumberSummary::from(VALUES, QuantileMethod::Linear).unwrap();
+
+        assert_eq!(6, summary.count());
+        assert_eq!(1f64, summary.lower());
+        assert_eq!(2.75, summary.q1());
+        assert_eq!(6f64, summary.median());
+        assert_eq!(8.5, summary.q3());
+        assert_eq!(12f64, summary.upper());
+    }
+
+    #[test]
+    fn test_five_number_summary_consistent_with_quantiles() {
+        for &method in &[QuantileMethod::Linear, QuantileMethod::Nearest] {
+            for &vals in &[VALUES, ODD] {
+                let summary = FiveNumberSummary::from(vals, method).unwrap();
+                let bundle = StatisticsBundle::with_quantiles(vals, &[], &[25f64, 50f64, 75f64], method).unwrap();
+                let quantiles: Vec<f64> = bundle.quantile_values().iter().map(Quantile::value).collect();
+
+                assert_eq!(vec![summary.q1(), summary.median(), summary.q3()], quantiles);
+                assert_eq!(bundle.global_stats().lower(), summary.lower());
+                assert_eq!(bundle.global_stats().upper(), summary.upper());
+            }
+        }
+    }
+
+    #[test]
+    fn test_five_number_summary_median_matches_statistics() {
+        for &vals in &[VALUES, ODD] {
+            let summary = FiveNumberSummary::from(vals, QuantileMethod::Linear).unwrap();
+            let bundle = StatisticsBundle::from(vals).unwrap();
+
+            assert_eq!(bundle.global_stats().median(), summary.median());
+        }
+    }
+}
@@ -0,0 +1,248 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::KeyValueSep;
+use std::fmt;
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+/// Phase of computing statistics that time spent in is measured for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Reading bytes from the input.
+    Read,
+    /// Splitting the input into lines and parsing values from them.
+    Parse,
+    /// Sorting values, or checking that they're already sorted.
+    Sort,
+    /// Computing statistics of the values.
+    Stats,
+    /// Formatting the statistics for display.
+    Format,
+}
+
+impl Phase {
+    /// Every phase, in the order they happen.
+    pub const ALL: [Phase; 5] = [Phase::Read, Phase::Parse, Phase::Sort, Phase::Stats, Phase::Format];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Read => "read",
+            Phase::Parse => "parse",
+            Phase::Sort => "sort",
+            Phase::Stats => "stats",
+            Phase::Format => "format",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Wall-clock time spent in each phase of computing statistics along with
+/// the number of values they were computed from.
+///
+/// ```
+/// use staccato::{Phase, Timings};
+/// use std::time::Duration;
+///
+/// let mut timings = Timings::new();
+/// timings.add(Phase::Read, Duration::from_millis(3));
+/// let sum = timings.time(Phase::Stats, || 1 + 2);
+///
+/// assert_eq!(3, sum);
+/// assert_eq!(Duration::from_millis(3), timings.get(Phase::Read));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timings {
+    phases: [Duration; 5],
+    values: u64,
+}
+
+impl Timings {
+    pub fn new() -> Timings {
+        Self::default()
+    }
+
+    /// Add time spent in a phase.
+    pub fn add(&mut self, phase: Phase, elapsed: Duration) {
+        self.phases[phase.index()] += elapsed;
+    }
+
+    /// Call a function, adding the time it takes to a phase.
+    pub fn time<T, F: FnOnce() -> T>(&mut self, phase: Phase, f: F) -> T {
+        let start = Instant::now();
+        let res = f();
+        self.add(phase, start.elapsed());
+        res
+    }
+
+    /// Time spent in a phase.
+    pub fn get(&self, phase: Phase) -> Duration {
+        self.phases[phase.index()]
+    }
+
+    /// Time spent in every phase.
+    pub fn total(&self) -> Duration {
+        self.phases.iter().sum()
+    }
+
+    /// Set the number of values that statistics were computed from.
+    pub fn set_values(&mut self, values: u64) {
+        self.values = values;
+    }
+
+    pub fn values(&self) -> u64 {
+        self.values
+    }
+
+    /// Number of values per second of time spent in every phase, or zero
+    /// if no time was spent.
+    pub fn values_per_second(&self) -> f64 {
+        let secs = self.total().as_secs_f64();
+        if secs > 0f64 {
+            self.values as f64 / secs
+        } else {
+            0f64
+        }
+    }
+}
+
+/// Reader that measures the time spent reading from another reader, to
+/// separate it from the time spent parsing what's read.
+pub struct TimedReader<R> {
+    inner: R,
+    elapsed: Duration,
+}
+
+impl<R: Read> TimedReader<R> {
+    pub fn new(inner: R) -> TimedReader<R> {
+        TimedReader {
+            inner,
+            elapsed: Duration::default(),
+        }
+    }
+
+    /// Time spent reading so far.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+impl<R: Read> Read for TimedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = Instant::now();
+        let res = self.inner.read(buf);
+        self.elapsed += start.elapsed();
+        res
+    }
+}
+
+/// Display the time spent in each phase as `phase<SEP>millis` lines,
+/// followed by the total time and the number of values per second.
+pub struct TimingsFormatter<'a> {
+    timings: &'a Timings,
+    sep: KeyValueSep,
+}
+
+impl<'a> TimingsFormatter<'a> {
+    pub fn new(timings: &'a Timings) -> TimingsFormatter<'a> {
+        Self::with_sep(timings, KeyValueSep::Colon)
+    }
+
+    pub fn with_sep(timings: &'a Timings, sep: KeyValueSep) -> TimingsFormatter<'a> {
+        TimingsFormatter { timings, sep }
+    }
+}
+
+impl<'a> fmt::Display for TimingsFormatter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (t, sep) = (self.timings, &self.sep);
+
+        for &phase in Phase::ALL.iter() {
+            writeln!(f, "{}{}{:.3}", phase, sep, millis(t.get(phase)))?;
+        }
+
+        writeln!(f, "total{}{:.3}", sep, millis(t.total()))?;
+        writeln!(f, "values_per_second{}{:.0}", sep, t.values_per_second())
+    }
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Phase, TimedReader, Timings, TimingsFormatter};
+    use crate::KeyValueSep;
+    use std::io::Read;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timings_add() {
+        let mut timings = Timings::new();
+        timings.add(Phase::Sort, Duration::from_millis(2));
+        timings.add(Phase::Sort, Duration::from_millis(3));
+        timings.add(Phase::Format, Duration::from_millis(1));
+
+        assert_eq!(Duration::from_millis(5), timings.get(Phase::Sort));
+        assert_eq!(Duration::default(), timings.get(Phase::Read));
+        assert_eq!(Duration::from_millis(6), timings.total());
+    }
+
+    #[test]
+    fn test_timings_values_per_second() {
+        let mut timings = Timings::new();
+        timings.set_values(100);
+        assert_eq!(0f64, timings.values_per_second());
+
+        timings.add(Phase::Parse, Duration::from_millis(250));
+        timings.add(Phase::Stats, Duration::from_millis(250));
+        assert_eq!(200f64, timings.values_per_second());
+    }
+
+    #[test]
+    fn test_timed_reader() {
+        let mut reader = TimedReader::new("1\n2\n".as_bytes());
+        let mut out = String::new();
+        reader.read_to_string(&mut out).unwrap();
+        assert_eq!("1\n2\n", out);
+        assert!(reader.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_timings_formatter() {
+        let mut timings = Timings::new();
+        timings.add(Phase::Read, Duration::from_micros(1500));
+        timings.add(Phase::Stats, Duration::from_micros(500));
+        timings.set_values(10);
+
+        assert_eq!(
+            "read\t1.500\nparse\t0.000\nsort\t0.000\nstats\t0.500\nformat\t0.000\ntotal\t2.000\nvalues_per_second\t5000\n",
+            TimingsFormatter::with_sep(&timings, KeyValueSep::Tab).to_string()
+        );
+    }
+}
@@ -0,0 +1,296 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{sum_overflowed, Statistics, StatisticsBundle};
+use std::fmt;
+use std::iter;
+use std::str::FromStr;
+
+/// Kind of quantity measured by a `Unit`. Only units of the same kind can
+/// be converted to each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnitKind {
+    Time,
+    Size,
+}
+
+/// Unit of time or size that values can be converted between, e.g. to
+/// display values read in seconds as milliseconds.
+///
+/// Units are parsed from and displayed as their symbols, e.g. "ms" or "MiB".
+/// Sizes with a 'K', 'M', or 'G' prefix are powers of 1000 and those with
+/// 'Ki', 'Mi', or 'Gi' are powers of 1024.
+///
+/// ```
+/// use staccato::{Unit, UnitKind};
+///
+/// let unit: Unit = "MiB".parse().unwrap();
+/// assert_eq!(Unit::Mebibytes, unit);
+/// assert_eq!(UnitKind::Size, unit.kind());
+/// assert_eq!("MiB", unit.to_string());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+    Minutes,
+    Hours,
+    Bytes,
+    Kilobytes,
+    Kibibytes,
+    Megabytes,
+    Mebibytes,
+    Gigabytes,
+    Gibibytes,
+}
+
+impl Unit {
+    const ALL: [Unit; 13] = [
+        Unit::Nanoseconds,
+        Unit::Microseconds,
+        Unit::Milliseconds,
+        Unit::Seconds,
+        Unit::Minutes,
+        Unit::Hours,
+        Unit::Bytes,
+        Unit::Kilobytes,
+        Unit::Kibibytes,
+        Unit::Megabytes,
+        Unit::Mebibytes,
+        Unit::Gigabytes,
+        Unit::Gibibytes,
+    ];
+
+    pub fn kind(self) -> UnitKind {
+        match self {
+            Unit::Nanoseconds
+            | Unit::Microseconds
+            | Unit::Milliseconds
+            | Unit::Seconds
+            | Unit::Minutes
+            | Unit::Hours => UnitKind::Time,
+            _ => UnitKind::Size,
+        }
+    }
+
+    /// Symbol the unit is parsed from and displayed as, e.g. "ms".
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Nanoseconds => "ns",
+            Unit::Microseconds => "us",
+            Unit::Milliseconds => "ms",
+            Unit::Seconds => "s",
+            Unit::Minutes => "m",
+            Unit::Hours => "h",
+            Unit::Bytes => "B",
+            Unit::Kilobytes => "KB",
+            Unit::Kibibytes => "KiB",
+            Unit::Megabytes => "MB",
+            Unit::Mebibytes => "MiB",
+            Unit::Gigabytes => "GB",
+            Unit::Gibibytes => "GiB",
+        }
+    }
+
+    /// Size of the unit in seconds for times and bytes for sizes.
+    fn size(self) -> f64 {
+        match self {
+            Unit::Nanoseconds => 1e-9,
+            Unit::Microseconds => 1e-6,
+            Unit::Milliseconds => 1e-3,
+            Unit::Seconds => 1.0,
+            Unit::Minutes => 60.0,
+            Unit::Hours => 3600.0,
+            Unit::Bytes => 1.0,
+            Unit::Kilobytes => 1e3,
+            Unit::Kibibytes => 1024.0,
+            Unit::Megabytes => 1e6,
+            Unit::Mebibytes => 1024.0 * 1024.0,
+            Unit::Gigabytes => 1e9,
+            Unit::Gibibytes => 1024.0 * 1024.0 * 1024.0,
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl FromStr for Unit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Unit, Self::Err> {
+        match s {
+            "µs" | "μs" => Ok(Unit::Microseconds),
+            _ => Unit::ALL.iter().copied().find(|u| u.symbol() == s).ok_or_else(|| {
+                let symbols: Vec<&str> = Unit::ALL.iter().map(|u| u.symbol()).collect();
+                format!("Invalid unit {}, expected one of {}", s, symbols.join(", "))
+            }),
+        }
+    }
+}
+
+/// Conversion of values from one unit to another of the same kind.
+///
+/// ```
+/// use staccato::{Unit, UnitConversion};
+///
+/// let conversion = UnitConversion::new(Unit::Seconds, Unit::Milliseconds).unwrap();
+/// assert_eq!(1000.0, conversion.factor());
+/// assert!(UnitConversion::new(Unit::Seconds, Unit::Bytes).is_none());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UnitConversion {
+    from: Unit,
+    to: Unit,
+}
+
+impl UnitConversion {
+    /// Create a conversion between two units. This method returns `None` if
+    /// the units are different kinds, e.g. a time and a size.
+    pub fn new(from: Unit, to: Unit) -> Option<UnitConversion> {
+        if from.kind() == to.kind() {
+            Some(UnitConversion { from, to })
+        } else {
+            None
+        }
+    }
+
+    pub fn from(&self) -> Unit {
+        self.from
+    }
+
+    pub fn to(&self) -> Unit {
+        self.to
+    }
+
+    /// Number that values in the original unit are multiplied by to convert
+    /// them to the new unit.
+    pub fn factor(&self) -> f64 {
+        self.from.size() / self.to.size()
+    }
+}
+
+impl Statistics {
+    /// Statistics of the same values multiplied by a positive factor, e.g.
+    /// to convert them to another unit.
+    ///
+    /// Every statistic other than the count is in the same unit as the
+    /// values so they're all multiplied by the factor, including the
+    /// standard deviation. The variance (the square of the standard
+    /// deviation) is multiplied by the square of the factor as a result.
+    pub fn scaled(&self, factor: f64) -> Statistics {
+        debug_assert!(factor > 0.0, "scale factor must be positive");
+        let mut stats = self.map_values(|v| v * factor);
+        stats.overflowed |= sum_overflowed(stats.sum, stats.lower, stats.upper);
+        stats
+    }
+}
+
+impl StatisticsBundle {
+    /// Bundle of statistics of the same values multiplied by a positive
+    /// factor, scaling the global statistics, each percentile slice (with
+    /// `Statistics::scaled`), and each quantile.
+    ///
+    /// ```
+    /// use staccato::{StatisticsBundle, Unit, UnitConversion};
+    ///
+    /// let bundle = StatisticsBundle::with_percentiles(&[0.25, 0.5, 1.5], &[]).unwrap();
+    /// let conversion = UnitConversion::new(Unit::Seconds, Unit::Milliseconds).unwrap();
+    /// let scaled = bundle.scaled(conversion.factor());
+    /// assert_eq!(1500.0, scaled.global_stats().upper());
+    /// assert_eq!(3, scaled.global_stats().count());
+    /// ```
+    pub fn scaled(&self, factor: f64) -> StatisticsBundle {
+        debug_assert!(factor > 0.0, "scale factor must be positive");
+        let mut bundle = self.map_values(|v| v * factor);
+        for stats in iter::once(&mut bundle.global).chain(bundle.percentiles.iter_mut()) {
+            stats.overflowed |= sum_overflowed(stats.sum, stats.lower, stats.upper);
+        }
+
+        bundle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Unit, UnitConversion, UnitKind};
+    use crate::{QuantileMethod, StatisticsBundle};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_unit_from_str() {
+        assert_eq!(Unit::Microseconds, Unit::from_str("us").unwrap());
+        assert_eq!(Unit::Microseconds, Unit::from_str("µs").unwrap());
+        assert_eq!(Unit::Kibibytes, Unit::from_str("KiB").unwrap());
+        assert_eq!(UnitKind::Time, Unit::from_str("h").unwrap().kind());
+        assert!(Unit::from_str("parsecs").is_err());
+        assert!(Unit::from_str("").is_err());
+    }
+
+    #[test]
+    fn test_unit_conversion_factor() {
+        let factor = |from, to| UnitConversion::new(from, to).unwrap().factor();
+        assert_eq!(1000.0, factor(Unit::Seconds, Unit::Milliseconds));
+        assert_eq!(0.001, factor(Unit::Milliseconds, Unit::Seconds));
+        assert_eq!(1.0 / 60.0, factor(Unit::Seconds, Unit::Minutes));
+        assert_eq!(1.0 / 1_048_576.0, factor(Unit::Bytes, Unit::Mebibytes));
+        assert_eq!(1000.0, factor(Unit::Megabytes, Unit::Kilobytes));
+        assert_eq!(1.0, factor(Unit::Hours, Unit::Hours));
+        assert!(UnitConversion::new(Unit::Bytes, Unit::Seconds).is_none());
+    }
+
+    #[test]
+    fn test_scaled_matches_scaled_values() {
+        let vals = vec![1.0, 2.0, 4.0, 7.0, 11.0];
+        let scaled_vals: Vec<f64> = vals.iter().map(|v| v * 1000.0).collect();
+        let bundle = StatisticsBundle::with_percentiles(&vals, &[60]).unwrap();
+        let expected = StatisticsBundle::with_percentiles(&scaled_vals, &[60]).unwrap();
+        assert!(bundle.scaled(1000.0).approx_eq(&expected, 1e-12));
+    }
+
+    #[test]
+    fn test_scaled_stddev_and_variance() {
+        let bundle = StatisticsBundle::from(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]).unwrap();
+        let stats = bundle.global_stats();
+        assert_eq!(2.0, stats.stddev());
+
+        let scaled = bundle.scaled(1000.0);
+        let scaled_stats = scaled.global_stats();
+        // The standard deviation is in the same unit as the values but the
+        // variance is in the square of it.
+        assert_eq!(2000.0, scaled_stats.stddev());
+        assert_eq!(4.0, stats.stddev().powi(2));
+        assert_eq!(4_000_000.0, scaled_stats.stddev().powi(2));
+        assert_eq!(8, scaled_stats.count());
+        assert_eq!(40_000.0, scaled_stats.sum());
+        assert_eq!(5000.0, scaled_stats.mean());
+    }
+
+    #[test]
+    fn test_scaled_quantiles() {
+        let bundle = StatisticsBundle::with_quantiles(&[1.0, 2.0, 3.0], &[], &[50.0], QuantileMethod::Linear).unwrap();
+        let scaled = bundle.scaled(0.5);
+        assert_eq!(50.0, scaled.quantile_values()[0].percent());
+        assert_eq!(1.0, scaled.quantile_values()[0].value());
+    }
+}
@@ -0,0 +1,43 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{Percentiles, QuantileMethod, SortedValues, StatisticsBundle};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+/// Compute a statistics bundle of values and the lowest percent of them for
+/// each percentile (from 1 to 99), returned as an object with the same
+/// fields as the JSON output of `st`, or `null` if there aren't any values.
+///
+/// An error is thrown if any of the percentiles are invalid.
+#[wasm_bindgen]
+pub fn compute_stats(values: &[f64], percentiles: &[u8]) -> Result<JsValue, JsError> {
+    let percentiles = Percentiles::new(percentiles)?;
+    let mut vals = values.to_vec();
+    let bundle = StatisticsBundle::from_sorted(
+        SortedValues::sort(&mut vals),
+        percentiles.as_slice(),
+        &[],
+        QuantileMethod::default(),
+    );
+
+    match bundle {
+        Some(b) => Ok(b.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?),
+        None => Ok(JsValue::NULL),
+    }
+}
@@ -0,0 +1,239 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::{Percentiles, QuantileMethod, SortedValues, StatisticsBundle};
+
+/// Statistics of consecutive windows of a fixed number of values, for
+/// rolling summaries of a sequence of values that never ends.
+///
+/// Values are kept in a ring buffer allocated up front. When it fills, a
+/// statistics bundle of the window is emitted and the oldest `step` values
+/// are dropped. By default the step is the whole window so windows don't
+/// overlap, while a smaller step emits overlapping windows more often.
+/// Values are sorted in scratch space that's reused for every window.
+///
+/// ```
+/// use staccato::WindowedStatistics;
+///
+/// let mut windows = WindowedStatistics::new(3);
+/// assert!(windows.push(1.0).is_none());
+/// assert!(windows.push(2.0).is_none());
+///
+/// let bundle = windows.push(3.0).unwrap();
+/// assert_eq!(6f64, bundle.global_stats().sum());
+///
+/// windows.push(10.0);
+/// let partial = windows.stats_now().unwrap();
+/// assert_eq!(1, partial.global_stats().count());
+/// ```
+#[derive(Debug, Clone)]
+pub struct WindowedStatistics {
+    buf: Vec<f64>,
+    head: usize,
+    len: usize,
+    step: usize,
+    percentiles: Percentiles,
+    scratch: Vec<f64>,
+}
+
+impl WindowedStatistics {
+    /// Create windows of `capacity` values (at least one) that don't overlap.
+    pub fn new(capacity: usize) -> WindowedStatistics {
+        let capacity = capacity.max(1);
+        WindowedStatistics {
+            buf: vec![0f64; capacity],
+            head: 0,
+            len: 0,
+            step: capacity,
+            percentiles: Percentiles::default(),
+            scratch: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Set the number of values to drop from the start of each window after
+    /// it's emitted, from one up to the capacity of the window (the default).
+    /// Any values currently in the window are kept.
+    pub fn step(mut self, step: usize) -> Self {
+        self.step = step.clamp(1, self.buf.len());
+        self
+    }
+
+    /// Set the percentiles to include slices for in each bundle.
+    pub fn percentiles(mut self, percentiles: Percentiles) -> Self {
+        self.percentiles = percentiles;
+        self
+    }
+
+    /// Add a value to the window, returning statistics of the window if it's
+    /// now full.
+    pub fn push(&mut self, val: f64) -> Option<StatisticsBundle> {
+        let capacity = self.buf.len();
+        self.buf[(self.head + self.len) % capacity] = val;
+        self.len += 1;
+
+        if self.len < capacity {
+            return None;
+        }
+
+        let bundle = self.stats_now();
+        self.head = (self.head + self.step) % capacity;
+        self.len -= self.step;
+        bundle
+    }
+
+    /// Compute statistics of the values currently in the window, e.g. the
+    /// final partial window once there are no more values.
+    ///
+    /// This method returns `None` if the window is empty.
+    pub fn stats_now(&mut self) -> Option<StatisticsBundle> {
+        let (first, second) = contents(&self.buf, self.head, self.len);
+        self.scratch.clear();
+        self.scratch.extend_from_slice(first);
+        self.scratch.extend_from_slice(second);
+
+        StatisticsBundle::from_sorted(
+            SortedValues::sort(&mut self.scratch),
+            self.percentiles.as_slice(),
+            &[],
+            QuantileMethod::default(),
+        )
+    }
+
+    /// Number of values in a full window.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Number of values currently in the window.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Values in a ring buffer from oldest to newest, which wrap around the end of
+/// the buffer into a second slice.
+fn contents(buf: &[f64], head: usize, len: usize) -> (&[f64], &[f64]) {
+    let end = head + len;
+    if end <= buf.len() {
+        (&buf[head..end], &[])
+    } else {
+        (&buf[head..], &buf[..end - buf.len()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WindowedStatistics;
+    use crate::{Percentiles, StatisticsBundle};
+
+    #[test]
+    fn test_emits_at_window_boundaries() {
+        let mut windows = WindowedStatistics::new(4);
+        let emitted: Vec<usize> = (1..=12).filter(|&i| windows.push(i as f64).is_some()).collect();
+
+        assert_eq!(vec![4, 8, 12], emitted);
+        assert!(windows.is_empty());
+        assert!(windows.stats_now().is_none());
+    }
+
+    #[test]
+    fn test_windows_do_not_overlap() {
+        let mut windows = WindowedStatistics::new(3);
+        let bundles: Vec<StatisticsBundle> = (1..=6).flat_map(|i| windows.push(i as f64)).collect();
+
+        assert_eq!(2, bundles.len());
+        assert_eq!(1f64, bundles[0].global_stats().lower());
+        assert_eq!(3f64, bundles[0].global_stats().upper());
+        assert_eq!(4f64, bundles[1].global_stats().lower());
+        assert_eq!(6f64, bundles[1].global_stats().upper());
+    }
+
+    #[test]
+    fn test_final_partial_window() {
+        let mut windows = WindowedStatistics::new(3);
+        for i in 1..=5 {
+            windows.push(i as f64);
+        }
+
+        assert_eq!(2, windows.len());
+        let partial = windows.stats_now().unwrap();
+        assert_eq!(2, partial.global_stats().count());
+        assert_eq!(9f64, partial.global_stats().sum());
+
+        // Computing statistics of the partial window doesn't change it.
+        assert_eq!(2, windows.len());
+        assert!(windows.push(6.0).is_some());
+    }
+
+    #[test]
+    fn test_sliding_windows() {
+        let mut windows = WindowedStatistics::new(4).step(2);
+        let bundles: Vec<StatisticsBundle> = (1..=8).flat_map(|i| windows.push(i as f64)).collect();
+
+        let bounds: Vec<(f64, f64)> = bundles
+            .iter()
+            .map(|b| (b.global_stats().lower(), b.global_stats().upper()))
+            .collect();
+        assert_eq!(vec![(1.0, 4.0), (3.0, 6.0), (5.0, 8.0)], bounds);
+        assert_eq!(2, windows.len());
+    }
+
+    #[test]
+    fn test_window_matches_bundle_of_values() {
+        let mut windows = WindowedStatistics::new(10).percentiles(Percentiles::new(&[50, 90]).unwrap());
+        let vals: Vec<f64> = (0..25).map(|i| ((i * 7) % 13) as f64).collect();
+        let bundles: Vec<StatisticsBundle> = vals.iter().flat_map(|&v| windows.push(v)).collect();
+
+        for (bundle, chunk) in bundles.iter().zip(vals.chunks(10)) {
+            let mut chunk = chunk.to_vec();
+            crate::sort_values(&mut chunk);
+            let expected = StatisticsBundle::with_percentiles(&chunk, &[50, 90]).unwrap();
+            assert!(
+                expected.approx_eq(bundle, 1e-12),
+                "{:?}",
+                expected.differences(bundle, 1e-12)
+            );
+        }
+    }
+
+    #[test]
+    fn test_reuses_scratch_space() {
+        let mut windows = WindowedStatistics::new(100);
+        let capacity = windows.scratch.capacity();
+        for i in 0..1000 {
+            windows.push(i as f64);
+        }
+
+        assert_eq!(capacity, windows.scratch.capacity());
+        assert_eq!(100, windows.buf.len());
+    }
+
+    #[test]
+    fn test_capacity_and_step_limits() {
+        let windows = WindowedStatistics::new(0);
+        assert_eq!(1, windows.capacity());
+
+        let mut windows = WindowedStatistics::new(3).step(10);
+        let emitted = (1..=6).filter(|&i| windows.push(i as f64).is_some()).count();
+        assert_eq!(2, emitted);
+    }
+}
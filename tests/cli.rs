@@ -0,0 +1,1547 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use staccato::{run, Percentiles, StaccatoConfig, StatisticsFormatter, VERSION_INFO};
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+#[test]
+fn test_output_to_closed_pipe_exits_cleanly() {
+    let mut child = st()
+        .arg("--cdf=all")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Enough distinct values that the output is much larger than a pipe buffer
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || {
+        let input: String = (0..100_000).map(|i| format!("{}\n", i)).collect();
+        stdin.write_all(input.as_bytes()).unwrap();
+    });
+
+    // Read a single line and close the pipe like `st --cdf=all | head -1`
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    writer.join().unwrap();
+
+    let mut err = String::new();
+    child.stderr.take().unwrap().read_to_string(&mut err).unwrap();
+    let status = child.wait().unwrap();
+
+    assert!(!line.is_empty());
+    assert!(!err.contains("panicked"), "{}", err);
+    assert!(status.success(), "{}", status);
+}
+
+/// Command to run st, without reading the config file or environment
+/// variables of whoever is running the tests.
+fn st() -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_st"));
+    cmd.env(
+        "XDG_CONFIG_HOME",
+        Path::new(env!("CARGO_TARGET_TMPDIR")).join("no-config"),
+    );
+    for (key, _) in env::vars_os().filter(|(k, _)| k.to_string_lossy().starts_with("ST_")) {
+        cmd.env_remove(key);
+    }
+
+    cmd
+}
+
+/// Run st with the given arguments and input, returning standard output
+/// if it exits successfully.
+fn run_st(args: &[&str], input: &str) -> String {
+    run_command(st().args(args), input)
+}
+
+/// Run a command with the given input, returning standard output if it
+/// exits successfully.
+fn run_command(cmd: &mut Command, input: &str) -> String {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_sum_overflow_warning() {
+    let mut child = st()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let input = format!("{:e}\n{:e}\n", f64::MAX, f64::MAX);
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let out = String::from_utf8(output.stdout).unwrap();
+    let err = String::from_utf8(output.stderr).unwrap();
+
+    assert!(output.status.success(), "{}", err);
+    assert!(out.contains("sum: inf\n"), "{}", out);
+    assert!(err.contains("warning: sum of values overflowed"), "{}", err);
+    assert!(!run_st(&[], "1\n2\n").is_empty());
+}
+
+#[test]
+fn test_version() {
+    assert_eq!(format!("st {}\n", VERSION_INFO), run_st(&["--version"], ""));
+}
+
+#[test]
+fn test_format_text_is_default() {
+    let input = "1\n2\n3\n4\n";
+    assert_eq!(
+        run_st(&["-p", "50"], input),
+        run_st(&["-p", "50", "--format=text"], input)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_format_json() {
+    let out = run_st(&["-p", "50", "--format=json"], "1\n2\n3\n4\n");
+    let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+
+    assert_eq!(4, json["global"]["count"]);
+    assert!(json["global"]["count"].is_u64());
+    assert_eq!(2.5, json["global"]["mean"]);
+    assert_eq!(50, json["percentiles"][0]["percentile"]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_merge_shards() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("merge-shards");
+    fs::create_dir_all(&dir).unwrap();
+    let values = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sorted.log")).unwrap();
+    let lines: Vec<&str> = values.lines().collect();
+
+    // Every fourth value in each shard, with one shard that also has a 99th
+    // percentile slice, which isn't in every shard so it's left out.
+    let mut shards = Vec::new();
+    for i in 0..4 {
+        let input: String = lines.iter().skip(i).step_by(4).map(|l| format!("{}\n", l)).collect();
+        let percentiles = if i == 0 { "50,90,99" } else { "50,90" };
+        let path = dir.join(format!("shard-{}.json", i));
+        fs::write(&path, run_st(&["--format=json", "-p", percentiles], &input)).unwrap();
+        shards.push(path.to_str().unwrap().to_string());
+    }
+
+    let mut args = vec!["merge", "--format=json"];
+    args.extend(shards.iter().map(String::as_str));
+    let merged: serde_json::Value = serde_json::from_str(&run_st(&args, "")).unwrap();
+    let full: serde_json::Value = serde_json::from_str(&run_st(&["--format=json", "-p", "50,90"], &values)).unwrap();
+
+    assert_eq!(full["global"]["count"], merged["global"]["count"]);
+    assert_eq!(full["global"]["upper"], merged["global"]["upper"]);
+    assert_eq!(full["global"]["lower"], merged["global"]["lower"]);
+    for key in ["sum", "mean", "stddev"] {
+        let (expected, actual) = (
+            full["global"][key].as_f64().unwrap(),
+            merged["global"][key].as_f64().unwrap(),
+        );
+        assert!(
+            (expected - actual).abs() < 1e-9 * expected.abs(),
+            "{}: {} != {}",
+            key,
+            expected,
+            actual
+        );
+    }
+
+    let slices = merged["percentiles"].as_array().unwrap();
+    let percentiles: Vec<u64> = slices.iter().flat_map(|s| s["percentile"].as_u64()).collect();
+    assert_eq!(vec![50, 90], percentiles);
+    assert_eq!(true, merged["global"]["merged"]);
+
+    let err = run_st_stderr(&args, "");
+    assert!(
+        err.contains("warning: omitted percentiles 99 (not in every file"),
+        "{}",
+        err
+    );
+    assert!(
+        err.contains("notice: exact: count, sum, mean, upper, lower\n"),
+        "{}",
+        err
+    );
+    assert!(err.contains("notice: formula: stddev\n"), "{}", err);
+    assert!(
+        err.contains("notice: estimate: median, percentile slices 50,90\n"),
+        "{}",
+        err
+    );
+
+    args.push("--quiet");
+    assert_eq!("", run_st_stderr(&args, ""));
+
+    let output = st().arg("merge").arg("--strict").args(&shards).output().unwrap();
+    assert_eq!(Some(65), output.status.code());
+}
+
+#[test]
+fn test_key_case_text() {
+    let input = "1\n2\n3\n4\n";
+    let args = |key_case| ["-p", "50", "--keys", "count,mean", "--key-case", key_case];
+
+    assert_eq!(
+        "count: 4\nmean: 2.50000\ncount_50: 2\nmean_50: 1.50000\n",
+        run_st(&args("snake"), input)
+    );
+    assert_eq!(
+        "count: 4\nmean: 2.50000\ncount50: 2\nmean50: 1.50000\n",
+        run_st(&args("camel"), input)
+    );
+    assert_eq!(
+        "count: 4\nmean: 2.50000\ncount-50: 2\nmean-50: 1.50000\n",
+        run_st(&args("kebab"), input)
+    );
+    assert_eq!(
+        run_st(&["-p", "50"], input),
+        run_st(&["-p", "50", "--key-case", "snake"], input)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_key_case_json() {
+    let input = "1\n2\n3\n4\n";
+    let expected = concat!(
+        r#"{"global":{"count":4,"sum":10.0,"mean":2.5,"upper":4.0,"lower":1.0,"median":2.0,"#,
+        r#""stddev":1.118033988749895},"quantiles":[],"percentiles":[],"sample_count":2}"#,
+        "\n"
+    );
+    let args = |key_case| {
+        [
+            "--format=json",
+            "--reservoir",
+            "2",
+            "--seed",
+            "1",
+            "--key-case",
+            key_case,
+        ]
+    };
+
+    assert_eq!(expected, run_st(&args("snake"), input));
+    assert_eq!(
+        expected.replace("sample_count", "sampleCount"),
+        run_st(&args("camel"), input)
+    );
+    assert_eq!(
+        expected.replace("sample_count", "sample-count"),
+        run_st(&args("kebab"), input)
+    );
+}
+
+#[test]
+fn test_piped_stdin() {
+    let expected = "count: 2\nsum: 3.00000\n";
+    assert_eq!(expected, run_st(&["--keys", "count,sum"], "1\n2\n"));
+    assert_eq!(expected, run_st(&["--stdin", "--keys", "count,sum"], "1\n2\n"));
+
+    let output = st()
+        .args(["--stdin", "values.txt"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(Some(2), output.status.code());
+}
+
+// Standard input is made a terminal by running st with `script`, and the
+// test is skipped if it isn't installed.
+#[cfg(target_os = "linux")]
+#[test]
+fn test_terminal_stdin_is_usage_error() {
+    let cmd = format!("{} --no-config --keys count", env!("CARGO_BIN_EXE_st"));
+    let output = match Command::new("script")
+        .args(["-qec", &cmd, "/dev/null"])
+        .stdin(Stdio::null())
+        .output()
+    {
+        Ok(o) => o,
+        Err(_) => return,
+    };
+
+    let out = String::from_utf8_lossy(&output.stdout);
+    assert!(out.contains("standard input is a terminal"), "{}", out);
+    assert!(!out.contains("waiting for input"), "{}", out);
+    assert_eq!(Some(64), output.status.code());
+}
+
+#[test]
+fn test_field_same_as_plain_values() {
+    let plain = run_st(&["-p", "50"], "1\n2\n3\n4\n");
+    let field = run_st(
+        &["-p", "50", "--field", "2", "--delimiter", ","],
+        "a,1\nb,2\nc\nd,3\ne,4\n",
+    );
+    assert_eq!(plain, field);
+}
+
+#[test]
+fn test_split_and_after_extract_every_value() {
+    let plain = run_st(&[], "1\n2\n3\n4\n");
+    assert_eq!(plain, run_st(&["--split"], "1 2\n3   4\n"));
+    assert_eq!(
+        plain,
+        run_st(&["--after", "time="], "time=1ms time=2ms\nnope\ntime=3 time=4\n")
+    );
+}
+
+#[test]
+fn test_integers_beyond_i64_range() {
+    let input = "18446744073709551615\n18446744073709551615\n-9223372036854775808\n";
+    assert_eq!(
+        "count: 3\nsum: 27670116110564327422\nupper: 18446744073709551615\nlower: -9223372036854775808\n",
+        run_st(&["--integers", "--keys", "count,sum,upper,lower"], input)
+    );
+
+    let output = st()
+        .arg("--integers")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .and_then(|mut c| {
+            c.stdin.take().unwrap().write_all(b"18446744073709551616\n")?;
+            c.wait()
+        })
+        .unwrap();
+    assert_eq!(Some(65), output.code());
+}
+
+#[test]
+fn test_null_data_records() {
+    let plain = run_st(&["-p", "50"], "1\n2\n3\n4\n");
+    let records = "1\n\x00\n2\x00\x003\n\n\x004";
+
+    assert_eq!(plain, run_st(&["-p", "50", "-0"], records));
+    assert_eq!(
+        run_st(&["-p", "50", "--integers"], "1\n2\n3\n4\n"),
+        run_st(&["-p", "50", "--null-data", "--integers"], records)
+    );
+    assert_eq!(
+        run_st(&["--field", "2"], "a 1\nb 2\n"),
+        run_st(&["--null-data", "--field", "2"], "a\n1\x00b\n2\x00")
+    );
+    assert_eq!(
+        run_st(&["--measure", "words"], "a b\nc\n"),
+        run_st(&["-0", "--measure", "words"], "a\nb\x00c")
+    );
+}
+
+#[test]
+fn test_field_zero_is_usage_error() {
+    let output = st().args(["--field", "0"]).stdin(Stdio::null()).output().unwrap();
+
+    assert_eq!(Some(64), output.status.code());
+}
+
+#[test]
+fn test_no_subcommand_same_as_summary() {
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/values-small.log");
+    let out = run_st(&["-p", "90", fixture], "");
+
+    assert!(out.contains("count_90: "), "{}", out);
+    assert_eq!(out, run_st(&["summary", "-p", "90", fixture], ""));
+}
+
+#[test]
+fn test_hist_subcommand() {
+    let input = "1\n2\n3\n4\n";
+
+    assert_eq!(
+        "1.0..2.0: 1\n2.0..3.0: 1\n3.0..4.0: 2\n",
+        run_st(&["hist", "--buckets", "3", "--precision", "1"], input)
+    );
+    assert_eq!(
+        "underflow\t1\n2..3\t1\n3..4\t2\n",
+        run_st(&["-s", "tab", "hist", "--edges", "2,3,4", "--precision", "0"], input)
+    );
+}
+
+#[test]
+fn test_compare_subcommand_same_as_compare_option() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("compare-subcommand");
+    fs::create_dir_all(&dir).unwrap();
+    let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+    fs::write(&a, "1\n2\n3\n4\n").unwrap();
+    fs::write(&b, "2\n3\n4\n9\n").unwrap();
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+    let out = run_st(&["compare", "-p", "50", a, "--to", b], "");
+
+    assert!(out.contains("pearson_r"), "{}", out);
+    assert_eq!(out, run_st(&["-p", "50", "--compare", b, a], ""));
+    assert_eq!(
+        run_st(&["compare", "--boxplot", a, "--to", b], ""),
+        run_st(&["--boxplot", "--compare", b, a], "")
+    );
+
+    let err = run_st_stderr(&["--compare", b, a], "");
+    assert!(err.contains("--compare is deprecated"), "{}", err);
+
+    let output = st()
+        .args(["--threshold", "5", a])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+/// Read lines of output from a child process until one is equal to `line`.
+fn read_until_line<R: BufRead>(reader: &mut R, line: &str) -> String {
+    let mut out = String::new();
+    loop {
+        let start = out.len();
+        assert_ne!(0, reader.read_line(&mut out).unwrap(), "{}", out);
+        if out[start..].trim_end() == line {
+            return out;
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_watch_replaced_file() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("watch");
+    fs::create_dir_all(&dir).unwrap();
+    let (path, replacement) = (dir.join("values.txt"), dir.join("values.txt.new"));
+    fs::write(&path, "1\n2\n").unwrap();
+
+    let mut child = st()
+        .args(["--watch", "--keys", "count,sum", path.to_str().unwrap()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let first = read_until_line(&mut stdout, "sum: 3.00000");
+
+    // Replace the file the same way editors and log rotation do
+    fs::write(&replacement, "4\n5\n6\n").unwrap();
+    fs::rename(&replacement, &path).unwrap();
+    let second = read_until_line(&mut stdout, "sum: 15.00000");
+
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!("count: 2\nsum: 3.00000\n", first);
+    assert_eq!("---\ncount: 3\nsum: 15.00000\n", second);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_watch_strict() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("watch-strict");
+    fs::create_dir_all(&dir).unwrap();
+    let (path, replacement) = (dir.join("values.txt"), dir.join("values.txt.new"));
+    fs::write(&path, "1\nfoo\n2\n").unwrap();
+
+    let mut child = st()
+        .args(["--watch", "--strict", "--keys", "count,sum", path.to_str().unwrap()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Malformed lines are reported instead of displaying statistics of the
+    // other values, and watching continues until the file is fixed.
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let warning = format!(
+        "warning: Could not parse values from {}: 1 line is not a valid value",
+        path.display()
+    );
+    read_until_line(&mut stderr, &warning);
+    let mut sample = String::new();
+    stderr.read_line(&mut sample).unwrap();
+    fs::write(&replacement, "1\n2\n").unwrap();
+    fs::rename(&replacement, &path).unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let fixed = read_until_line(&mut stdout, "sum: 3.00000");
+
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!("  line 2 (byte 2): foo\n", sample);
+    assert_eq!("---\ncount: 2\nsum: 3.00000\n", fixed);
+    assert!(output.status.success());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_interrupt_displays_partial_statistics() {
+    let mut child = st()
+        .args(["--keys", "count,sum"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Standard input is kept open, so values are only displayed because
+    // reading stops when interrupted.
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"1\n2\n3\n").unwrap();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut notice = String::new();
+    stderr.read_line(&mut notice).unwrap();
+    assert!(notice.starts_with("notice: waiting for input"), "{}", notice);
+    thread::sleep(std::time::Duration::from_millis(200));
+
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().unwrap();
+    let mut err = String::new();
+    stderr.read_to_string(&mut err).unwrap();
+    drop(stdin);
+
+    assert_eq!(Some(130), output.status.code());
+    assert_eq!("count: 3\nsum: 6.00000\n", String::from_utf8(output.stdout).unwrap());
+    assert!(err.contains("warning: input was interrupted"), "{}", err);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_terminate_exits_without_statistics() {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut child = st()
+        .args(["--keys", "count,sum"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"1\n2\n3\n").unwrap();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut notice = String::new();
+    stderr.read_line(&mut notice).unwrap();
+    assert!(notice.starts_with("notice: waiting for input"), "{}", notice);
+    thread::sleep(std::time::Duration::from_millis(200));
+
+    let status = Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().unwrap();
+    drop(stdin);
+
+    assert_eq!(Some(15), output.status.signal());
+    assert_eq!("", String::from_utf8(output.stdout).unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_stream_report_on_signal() {
+    let mut child = st()
+        .args(["--stream", "--keys", "count,sum"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"1\n2\n").unwrap();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut notice = String::new();
+    stderr.read_line(&mut notice).unwrap();
+    assert!(notice.starts_with("notice: waiting for input"), "{}", notice);
+    thread::sleep(std::time::Duration::from_millis(200));
+
+    let status = Command::new("kill")
+        .args(["-USR1", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // The report is displayed once the next line is read, with statistics
+    // of the values before it.
+    stdin.write_all(b"3\n").unwrap();
+    let report = read_until_line(&mut stderr, "sum: 3.00000");
+    stdin.write_all(b"4\n").unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!("count: 2\nsum: 3.00000\n", report);
+    assert!(output.status.success());
+    assert_eq!("count: 4\nsum: 10.00000\n", String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn test_watch_requires_file() {
+    let output = st().args(["--watch"]).stdin(Stdio::null()).output().unwrap();
+
+    assert_eq!(Some(64), output.status.code());
+}
+
+#[test]
+fn test_per_file() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("per-file");
+    fs::create_dir_all(dir.join("other")).unwrap();
+    let (a, b, c) = (dir.join("a.log"), dir.join("b.log"), dir.join("other").join("a.log"));
+    fs::write(&a, "3\n1\n2\n").unwrap();
+    fs::write(&b, "20\n10\n").unwrap();
+    fs::write(&c, "5\n").unwrap();
+    let (a, b, c) = (a.to_str().unwrap(), b.to_str().unwrap(), c.to_str().unwrap());
+
+    let out = run_st(&["--per-file", "-p", "50", a, b], "");
+    let expected = format!(
+        "label: {}\n{}label: {}\n{}label: total\n{}",
+        a,
+        run_st(&["-p", "50", a], ""),
+        b,
+        run_st(&["-p", "50", b], ""),
+        run_st(&["-p", "50", a, b], "")
+    );
+    assert_eq!(expected, out);
+
+    let short = run_st(&["--per-file", "--short-labels", "--keys", "count", a, b], "");
+    assert_eq!(
+        "label: a.log\ncount: 3\nlabel: b.log\ncount: 2\nlabel: total\ncount: 5\n",
+        short
+    );
+
+    let output = st()
+        .args(["--per-file", "--short-labels", a, c])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(Some(64), output.status.code());
+}
+
+#[test]
+fn test_per_file_output_dir() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("output-dir");
+    let (inputs, out) = (dir.join("in"), dir.join("out"));
+    let _ = fs::remove_dir_all(&out);
+    fs::create_dir_all(&inputs).unwrap();
+    let files = [
+        (inputs.join(".hidden.log"), "1\n2\n"),
+        (inputs.join("Total"), "3\n"),
+        (inputs.join("index"), "4\n"),
+    ];
+    for (path, contents) in &files {
+        fs::write(path, contents).unwrap();
+    }
+
+    let mut args = vec!["--per-file", "--short-labels", "--keys", "count,sum", "--output-dir"];
+    args.push(out.to_str().unwrap());
+    args.extend(files.iter().map(|(p, _)| p.to_str().unwrap()));
+    assert_eq!("", run_st(&args, ""));
+
+    let read = |name: &str| fs::read_to_string(out.join(name)).unwrap();
+    assert_eq!(
+        ".hidden.log: _hidden.log.txt\nTotal: Total.txt\nindex: index-2.txt\ntotal: total-2.txt\n",
+        read("index.txt")
+    );
+    assert_eq!("count: 2\nsum: 3.00000\n", read("_hidden.log.txt"));
+    assert_eq!("count: 1\nsum: 3.00000\n", read("Total.txt"));
+    assert_eq!("count: 1\nsum: 4.00000\n", read("index-2.txt"));
+    assert_eq!("count: 4\nsum: 10.00000\n", read("total-2.txt"));
+    assert_eq!(5, fs::read_dir(&out).unwrap().count());
+}
+
+/// Assert that output is the same as the golden file with the given name
+/// in `tests/fixtures/golden`, which locks down the order that statistics
+/// are displayed in. Set `UPDATE_GOLDEN=1` to write the output to the file
+/// instead, after checking that any change to it is intended.
+fn assert_golden(name: &str, out: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("golden")
+        .join(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, out).unwrap();
+    }
+
+    let expected = fs::read_to_string(&path).unwrap();
+    assert_eq!(expected, out, "output differs from {}", path.display());
+}
+
+#[test]
+fn test_golden_percentiles_ascending() {
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sorted.log");
+    let out = run_st(&["-p", "90,50,75", "--quantiles", "99,50", fixture], "");
+
+    assert_golden("percentiles.txt", &out);
+    assert_eq!(out, run_st(&["-p", "75,90,50", "--quantiles", "99,50", fixture], ""));
+}
+
+#[test]
+fn test_golden_sort_keys() {
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sorted.log");
+    let out = run_st(&["--sort-keys", "-p", "90,50,75", "--quantiles", "99,50", fixture], "");
+
+    assert_golden("sort-keys.txt", &out);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_golden_sort_keys_json() {
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sorted.log");
+    let args = [
+        "--format=json",
+        "--sort-keys",
+        "-p",
+        "90,50",
+        "--quantiles",
+        "50",
+        fixture,
+    ];
+
+    assert_golden("sort-keys.json", &run_st(&args, ""));
+}
+
+#[test]
+fn test_golden_per_file() {
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+    let (valid, sorted) = (format!("{}/valid.log", fixtures), format!("{}/sorted.log", fixtures));
+    let out = run_st(
+        &[
+            "--per-file",
+            "--short-labels",
+            "--sort-keys",
+            "-p",
+            "90,50",
+            &valid,
+            &sorted,
+        ],
+        "",
+    );
+
+    assert_golden("per-file.txt", &out);
+}
+
+#[test]
+fn test_label() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("label");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("latency-api.log");
+    fs::write(&path, "1\n2\n3\n").unwrap();
+    let path = path.to_str().unwrap();
+
+    let labeled = run_st(&["--label", "--keys", "count,mean", "-p", "50", path], "");
+    assert_eq!(
+        "latency-api.count: 3\nlatency-api.mean: 2.00000\nlatency-api.count_50: 1\nlatency-api.mean_50: 1.00000\n",
+        labeled
+    );
+    assert_eq!(
+        "db_1.count: 3\n",
+        run_st(&["--label=db 1", "--keys", "count"], "4\n5\n6\n")
+    );
+    assert_eq!(
+        "api.count: 3\n",
+        run_st(&["--label", "api", "--keys", "count", path], "")
+    );
+    assert_eq!(
+        "latency-api.count: 3\n",
+        run_st(&["--keys", "count", path, "--label"], "")
+    );
+
+    let output = st()
+        .args([path, path, "--label"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(Some(64), output.status.code());
+}
+
+#[test]
+fn test_timer() {
+    let timings = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/timings.log")).unwrap();
+    let millis: String = timings
+        .lines()
+        .map(|line| match line.strip_suffix("ms") {
+            Some(ms) => format!("{}\n", ms),
+            None => format!(
+                "{}\n",
+                (line.trim_end_matches('s').parse::<f64>().unwrap() * 1000.0).round()
+            ),
+        })
+        .collect();
+
+    let expected = run_st(&["-p", "90", "--quantiles", "90,95,99"], &millis);
+    assert!(expected.contains("upper_90: "));
+    assert!(expected.contains("p99: "));
+    assert_eq!(expected, run_st(&["--timer"], &timings));
+
+    let overridden = run_st(&["--timer", "-p", "50", "--keys", "count,mean"], &timings);
+    assert_eq!(run_st(&["-p", "50", "--keys", "count,mean"], &millis), overridden);
+    assert!(!overridden.contains("p95"));
+}
+
+#[test]
+fn test_unit_conversion() {
+    let seconds = "0.25\n1.5\n0.5\n";
+    let converted = run_st(&["--unit-in", "s", "--unit-out", "ms", "--quantiles", "50"], seconds);
+    let millis = run_st(&["--quantiles", "50"], "250\n1500\n500\n");
+    assert_eq!(format!("unit: ms\n{}", millis), converted);
+
+    assert_eq!(
+        "unit: KiB\ncount: 2\nmean: 1.50000\nstddev: 0.50000\n",
+        run_st(
+            &["--unit-in", "B", "--unit-out", "KiB", "--keys", "count,mean,stddev"],
+            "1024\n2048\n"
+        )
+    );
+    assert_eq!(
+        "unit: s\nmean: 0.87500\np50: 0.87500\n",
+        run_st(
+            &["--timer", "--unit-out", "s", "--keys", "mean", "--quantiles", "50"],
+            "250ms\n1.5s\n"
+        )
+    );
+
+    for args in [&["--unit-in", "s", "--unit-out", "MB"][..], &["--unit-out", "ms"][..]] {
+        let output = st().args(args).stdin(Stdio::null()).output().unwrap();
+        assert_eq!(Some(64), output.status.code());
+    }
+}
+
+#[test]
+fn test_round() {
+    assert_eq!(
+        "count: 2\nmean: 2\n",
+        run_st(&["--round", "--precision", "3", "--keys", "count,mean"], "2\n3\n")
+    );
+    assert_eq!("mean: 4\n", run_st(&["--round", "--keys", "mean"], "3\n4\n"));
+    assert_eq!("mean: 3\n", run_st(&["--round=ceil", "--keys", "mean"], "2\n2.1\n"));
+    assert_eq!("mean: 2\n", run_st(&["--round=floor", "--keys", "mean"], "2.9\n2.9\n"));
+    assert_eq!(
+        "mean: 3\np50: 3\n",
+        run_st(&["--round", "--keys", "mean", "--quantiles", "50"], "2.5\n3.5\n")
+    );
+}
+
+#[test]
+fn test_list_keys() {
+    let list = run_st(&["--list-keys", "-p", "90,99"], "");
+    let keys: Vec<&str> = list
+        .lines()
+        .skip(1)
+        .filter_map(|l| l.split_whitespace().next())
+        .collect();
+    assert!(keys.starts_with(&["count", "sum", "mean", "upper", "lower", "median", "stddev"]));
+    assert!(keys.contains(&"stddev_90"));
+    assert!(keys.contains(&"upper_99"));
+    assert!(list.contains("p99.upper"));
+    assert!(!keys.contains(&"STAT_P"));
+
+    // Every key listed for percentiles is displayed with them.
+    let stats = run_st(&["-p", "90,99"], "1\n2\n3\n");
+    for key in keys.iter().filter(|k| k.ends_with("_90") || k.ends_with("_99")) {
+        assert!(stats.contains(&format!("{}: ", key)), "{}", key);
+    }
+}
+
+#[test]
+fn test_omitted_percentiles_warning() {
+    let input = "1\n2\n3\n4\n5\n";
+    let err = run_st_stderr(&["-p", "10,99"], input);
+    assert!(
+        err.contains("warning: omitted percentile slices 10 (not enough values)"),
+        "{}",
+        err
+    );
+
+    let out = run_st(&["-p", "10,99"], input);
+    assert!(out.contains("count_99: 4"));
+    assert!(!out.contains("count_10"));
+
+    let quiet = run_st_stderr(&["-p", "10,99", "--quiet"], input);
+    assert!(!quiet.contains("warning"), "{}", quiet);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_omitted_percentiles_null() {
+    let input = "1\n2\n3\n4\n5\n";
+    let out = run_st(&["-p", "10,99", "--format", "json", "--null-omitted"], input);
+    let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+    let slices = json["percentiles"].as_array().unwrap();
+
+    assert_eq!(2, slices.len());
+    assert_eq!(10, slices[0]["percentile"]);
+    assert!(slices[0]["count"].is_null());
+    assert!(slices[0]["mean"].is_null());
+    assert_eq!(99, slices[1]["percentile"]);
+    assert_eq!(4, slices[1]["count"]);
+
+    let out = run_st(&["-p", "10,99", "--format", "json"], input);
+    let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(1, json["percentiles"].as_array().unwrap().len());
+}
+
+#[test]
+fn test_min_samples() {
+    let input = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+    let args = |min: &'static str| vec!["-p", "50", "--quantiles", "90", "--min-samples", min];
+
+    // The 50th percentile slice has 5 values.
+    let at = run_st(&args("5"), input);
+    assert_eq!(run_st(&["-p", "50", "--quantiles", "90"], input), at);
+    assert!(!run_st_stderr(&args("5"), input).contains("warning"));
+
+    let below = run_st(&args("6"), input);
+    assert!(!below.contains("count_50"), "{}", below);
+    assert!(below.contains("p90: "));
+    assert!(run_st_stderr(&args("6"), input).contains("omitted percentile slices 50 (fewer than 6 values)"));
+
+    // Quantiles need the minimum number of values in total.
+    assert!(run_st(&args("10"), input).contains("p90: "));
+    let total_below = run_st(&args("11"), input);
+    assert!(!total_below.contains("p90: "));
+    assert!(total_below.contains("count: 10"));
+    assert!(run_st_stderr(&args("11"), input).contains("omitted quantiles p90 (fewer than 11 values)"));
+}
+
+#[test]
+fn test_quartiles() {
+    let input = shuffled_input();
+    let keys = ["--keys", "count,mean"];
+    let explicit = run_st(&[&keys[..], &["--quantiles", "25,50,75"]].concat(), &input);
+    let quartiles = run_st(&[&keys[..], &["-q"]].concat(), &input);
+    assert_eq!(
+        explicit
+            .replace("p25", "q1")
+            .replace("p50", "median")
+            .replace("p75", "q3"),
+        quartiles
+    );
+
+    let lines: Vec<&str> = quartiles.lines().collect();
+    assert!(lines[2].starts_with("q1: "), "{}", quartiles);
+    assert!(lines[3].starts_with("median: "), "{}", quartiles);
+    assert!(lines[4].starts_with("q3: "), "{}", quartiles);
+    assert_eq!(5, lines.len());
+
+    // Composes with percentiles and other quantiles, replacing the median
+    // of all values with the median quartile.
+    let explicit = run_st(
+        &["-p", "90", "--quantiles", "99,25,50,75", "--quantile-method", "nearest"],
+        &input,
+    );
+    let quartiles = run_st(
+        &[
+            "-p",
+            "90",
+            "--quantiles",
+            "99",
+            "--quartiles",
+            "--quantile-method",
+            "nearest",
+        ],
+        &input,
+    );
+    let expected: String = explicit
+        .lines()
+        .filter(|l| !l.starts_with("median: "))
+        .map(|l| {
+            format!(
+                "{}\n",
+                l.replace("p25", "q1").replace("p50", "median").replace("p75", "q3")
+            )
+        })
+        .collect();
+    assert_eq!(expected, quartiles);
+    assert!(quartiles.contains("median_90: "));
+}
+
+#[test]
+fn test_summary() {
+    let input = "5\n1\n9\n2\n7\n12\n";
+    assert_eq!(
+        "count: 6\nlower: 1.00000\nq1: 2.75000\nmedian: 6.00000\nq3: 8.50000\nupper: 12.00000\n",
+        run_st(&["--summary"], input)
+    );
+    assert_eq!(
+        "count\t6\nlower\t1\nq1\t3\nmedian\t6\nq3\t8\nupper\t12\n",
+        run_st(&["--summary", "--round", "-s", "tab"], input)
+    );
+    assert_eq!(
+        "count: 6\nmean: 6.0\nlower: 1.0\nq1: 2.8\nmedian: 6.0\nq3: 8.5\nupper: 12.0\n",
+        run_st(&["--summary", "--keys", "count,mean", "--precision", "1"], input)
+    );
+    assert_eq!(
+        "count: 6\nlower: 1.00000\nmedian: 6.00000\nq1: 2.75000\nq3: 8.50000\nupper: 12.00000\n",
+        run_st(&["--summary", "--sort-keys"], input)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_summary_format_json() {
+    let input = "5\n1\n9\n2\n7\n12\n";
+    let out = run_st(&["--summary", "--format", "json", "--round"], input);
+    let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+
+    assert_eq!(6, json["global"]["count"]);
+    assert!(json["global"]["mean"].is_null());
+    let quantiles: Vec<(&str, f64)> = json["quantiles"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|q| (q["name"].as_str().unwrap(), q["value"].as_f64().unwrap()))
+        .collect();
+    assert_eq!(
+        vec![
+            ("lower", 1.0),
+            ("q1", 3.0),
+            ("median", 6.0),
+            ("q3", 8.0),
+            ("upper", 12.0)
+        ],
+        quantiles
+    );
+
+    let out = run_st(
+        &["--summary", "--format", "json", "--key-case", "kebab", "--label=api"],
+        input,
+    );
+    let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(6, json["api"]["global"]["count"]);
+    assert_eq!("median", json["api"]["quantiles"][2]["name"]);
+}
+
+#[test]
+fn test_repeated_percentiles() {
+    let input = shuffled_input();
+    let expected = run_st(&["-p", "25,90,93,96,99"], &input);
+
+    assert_eq!(expected, run_st(&["-p", "99", "-p", "90-99:3", "-p", "25,99"], &input));
+    assert_eq!(
+        expected,
+        run_st(&["--percentiles=96", "-p", "25", "-p", "90-99:3"], &input)
+    );
+}
+
+#[test]
+fn test_timing() {
+    let input = shuffled_input();
+    let args = ["-p", "50,90", "--separator", "tab"];
+    let mut timed_args = args.to_vec();
+    timed_args.push("--timing");
+
+    // Statistics are the same with or without timings.
+    assert_eq!(run_st(&args, &input), run_st(&timed_args, &input));
+
+    let err = run_st_stderr(&timed_args, &input);
+    let phases: Vec<(&str, f64)> = err
+        .lines()
+        .filter_map(|l| l.split_once('\t'))
+        .map(|(k, v)| (k, v.parse().unwrap()))
+        .collect();
+    let keys: Vec<&str> = phases.iter().map(|&(k, _)| k).collect();
+
+    assert_eq!(
+        vec!["read", "parse", "sort", "stats", "format", "total", "values_per_second"],
+        keys
+    );
+    assert!(phases.iter().all(|&(_, v)| v >= 0f64));
+}
+
+#[test]
+fn test_seed_reproducible() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("seed");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("values.txt");
+    fs::write(&path, shuffled_input()).unwrap();
+    let path = path.to_str().unwrap();
+
+    let output = |args: &[&str], seed: &str| {
+        let out = st()
+            .args(args)
+            .args(["--seed", seed, "-p", "50,90", "--quantiles", "25,75", path])
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        out.stdout
+    };
+
+    for args in [&["--reservoir", "100"][..], &["--max-memory", "1K"][..]] {
+        let first = output(args, "7");
+        assert_eq!(first, output(args, "7"), "{:?}", args);
+        assert_ne!(first, output(args, "8"), "{:?}", args);
+    }
+}
+
+#[test]
+fn test_sorted_input() {
+    let sorted = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sorted.log");
+    let expected = run_st(
+        &["-p", "50,90", "--quantiles", "99"],
+        &fs::read_to_string(sorted).unwrap(),
+    );
+
+    assert_eq!(
+        expected,
+        run_st(&["-p", "50,90", "--quantiles", "99", "--sorted", sorted], "")
+    );
+    assert_eq!(
+        expected,
+        run_st(&["-p", "50,90", "--quantiles", "99", "--sorted=verify", sorted], "")
+    );
+}
+
+#[test]
+fn test_sorted_input_out_of_order() {
+    let nearly = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/nearly-sorted.log");
+    let output = st()
+        .args(["-p", "50", "--sorted", nearly])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let err = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(Some(65), output.status.code());
+    assert!(output.stdout.is_empty());
+    assert!(err.contains("value 185 is less than the value before it"), "{}", err);
+
+    let output = st()
+        .args(["-p", "50", "--sorted=trust", nearly])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let err = String::from_utf8(output.stderr).unwrap();
+
+    assert!(output.status.success(), "{}", err);
+    assert!(err.contains("warning: Sorting values"), "{}", err);
+    assert_eq!(
+        run_st(&["-p", "50", nearly], ""),
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+fn test_fail_if() {
+    let run = |args: &[&str]| {
+        let mut child = st()
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        child.stdin.take().unwrap().write_all(b"1\n2\n3\n4\n").unwrap();
+        let output = child.wait_with_output().unwrap();
+        (output.status.code(), String::from_utf8(output.stderr).unwrap())
+    };
+
+    let (code, err) = run(&["-p", "50", "--fail-if", "p50.upper > 3", "--fail-if", "mean < 100"]);
+    assert_eq!(Some(0), run(&["-p", "50", "--fail-if", "p50.upper > 2"]).0);
+    assert_eq!(Some(1), code, "{}", err);
+    assert!(err.contains("failed: mean < 100 (mean is 2.50000)"), "{}", err);
+    assert!(!err.contains("p50.upper"), "{}", err);
+
+    let (code, err) = run(&["--fail-if", "p99.upper > 250"]);
+    assert_eq!(Some(64), code, "{}", err);
+    assert!(err.contains("Statistic p99.upper was not computed"), "{}", err);
+}
+
+#[test]
+fn test_validate_clean_input() {
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/valid.log");
+
+    assert_eq!(
+        "lines: 6\nvalid: 5\nblank: 1\nmalformed: 0\n",
+        run_st(&["--validate", fixture], "")
+    );
+}
+
+#[test]
+fn test_validate_malformed_input() {
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/malformed.log");
+    let output = st()
+        .args(["--validate", fixture])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert_eq!(Some(65), output.status.code());
+    assert_eq!(
+        "lines: 8\nvalid: 3\nblank: 2\nmalformed: 3\nline 2 (byte 5): three\nline 5 (byte 18): 1e3 ms\nline 8 (byte 28): n/a\n",
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+fn test_validate_with_field() {
+    let out = run_st(&["--validate", "--field", "2"], "a 1\nb 2\n\nc 3\n");
+
+    assert_eq!("lines: 4\nvalid: 3\nblank: 1\nmalformed: 0\n", out);
+}
+
+#[test]
+fn test_validate_malformed_samples() {
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/malformed.log");
+    let output = st()
+        .args(["--validate", "--malformed-samples", "1", fixture])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert_eq!(Some(65), output.status.code());
+    assert_eq!(
+        "lines: 8\nvalid: 3\nblank: 2\nmalformed: 3\nline 2 (byte 5): three\n",
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+fn test_strict() {
+    assert_eq!(
+        run_st(&["--keys", "count"], "1\n\n2\n"),
+        run_st(&["--strict", "--keys", "count"], "1\n\n2\n")
+    );
+
+    let mut child = st()
+        .args(["--strict", "--malformed-samples", "2"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let input = "1\nfoo\tbar\n2\nx\ny\n";
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let err = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(Some(65), output.status.code());
+    assert!(output.stdout.is_empty());
+    assert!(
+        err.contains(
+            "error: Could not parse values from stdin: 3 lines are not valid values\n  \
+             line 2 (byte 2): foo\\tbar\n  line 4 (byte 12): x\n"
+        ),
+        "{}",
+        err
+    );
+}
+
+/// Run st with the given arguments and input, returning standard error if
+/// it exits successfully.
+fn run_st_stderr(args: &[&str], input: &str) -> String {
+    let mut child = st()
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let err = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", err);
+
+    err
+}
+
+#[test]
+fn test_verbose_diagnostics() {
+    let input = "3\n\nfoo\n1\n2\n";
+
+    let quiet = run_st_stderr(&["-p", "50"], input);
+    assert!(!quiet.contains("info:"), "{}", quiet);
+
+    let info = run_st_stderr(&["-p", "50", "-v"], input);
+    assert!(info.contains("info: parsing each line as a number\n"), "{}", info);
+    assert!(info.contains("info: reading values from stdin\n"), "{}", info);
+    assert!(
+        info.contains("info: read 5 lines from stdin: 3 parsed, 1 blank, 1 malformed\n"),
+        "{}",
+        info
+    );
+    assert!(info.contains("info: sorted 3 values\n"), "{}", info);
+    assert!(
+        info.contains("info: percentile 50 slice contains 1 values\n"),
+        "{}",
+        info
+    );
+    assert!(info.contains("info: finished in "), "{}", info);
+    assert!(!info.contains("debug:"), "{}", info);
+
+    let debug = run_st_stderr(&["-p", "50", "-vv"], input);
+    assert!(
+        debug.contains("debug: malformed line 3 of stdin (byte 3): foo\n"),
+        "{}",
+        debug
+    );
+    assert!(debug.contains("debug: read 3 values in "), "{}", debug);
+    assert!(debug.contains("info: sorted 3 values\n"), "{}", debug);
+}
+
+#[test]
+fn test_verbose_diagnostics_extractor() {
+    let field = run_st_stderr(&["--field", "2", "--delimiter", ",", "--verbose"], "a,1\nb,2\n");
+    assert!(
+        field.contains("info: parsing field 2 of each line separated by ,\n"),
+        "{}",
+        field
+    );
+
+    let unsorted = run_st_stderr(&["--keys", "count,mean", "-v"], "1\n2\n");
+    assert!(
+        unsorted.contains("info: did not sort 2 values, no statistics required them to be sorted\n"),
+        "{}",
+        unsorted
+    );
+
+    let measured = run_st_stderr(&["hist", "--measure", "words", "-v"], "a b\nc\n");
+    assert!(
+        measured.contains("info: measuring the number of words of each line\n"),
+        "{}",
+        measured
+    );
+    assert!(measured.contains("info: measured 2 lines from stdin\n"), "{}", measured);
+}
+
+/// Write a config file in the XDG config directory `dir` (under the
+/// temporary directory for tests), returning the path of the file.
+fn write_config(dir: &str, contents: &str) -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(dir).join("staccato");
+    fs::create_dir_all(&path).unwrap();
+    let path = path.join("config.toml");
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+const CONFIG: &str = "percentiles = [50]\nseparator = \"tab\"\nprecision = 2\nkeys = \"count,mean\"\n";
+
+#[test]
+fn test_config_file_defaults() {
+    let path = write_config("config-defaults", CONFIG);
+    let xdg = path.parent().unwrap().parent().unwrap();
+    let input = "1\n2\n3\n4\n";
+    let expected = "count\t4\nmean\t2.50\ncount_50\t2\nmean_50\t1.50\n";
+
+    assert_eq!(expected, run_command(st().env("XDG_CONFIG_HOME", xdg), input));
+    assert_eq!(expected, run_st(&["--config", path.to_str().unwrap()], input));
+}
+
+#[test]
+fn test_config_file_precedence() {
+    let path = write_config("config-precedence", CONFIG);
+    let xdg = path.parent().unwrap().parent().unwrap();
+    let input = "1\n2\n3\n4\n";
+
+    // Command line options take precedence over the config file, which
+    // takes precedence over the built in defaults
+    assert_eq!(
+        "count: 4\nmean: 2.5\ncount_50: 2\nmean_50: 1.5\n",
+        run_command(
+            st().env("XDG_CONFIG_HOME", xdg)
+                .args(["--precision", "1", "-s", "colon"]),
+            input
+        )
+    );
+    assert_eq!(
+        run_st(&[], input),
+        run_command(st().env("XDG_CONFIG_HOME", xdg).arg("--no-config"), input)
+    );
+}
+
+#[test]
+fn test_env_var_precedence() {
+    let path = write_config("config-env", CONFIG);
+    let xdg = path.parent().unwrap().parent().unwrap();
+    let input = "1\n2\n3\n4\n";
+
+    // Environment variables take precedence over the config file
+    assert_eq!(
+        "count\t4\nmean\t2.5\ncount_50\t2\nmean_50\t1.5\n",
+        run_command(st().env("XDG_CONFIG_HOME", xdg).env("ST_PRECISION", "1"), input)
+    );
+
+    // Command line options take precedence over environment variables
+    assert_eq!(
+        "count\t4\nmean\t2.500\ncount_50\t2\nmean_50\t1.500\n",
+        run_command(
+            st().env("XDG_CONFIG_HOME", xdg)
+                .env("ST_PRECISION", "1")
+                .args(["--precision", "3"]),
+            input
+        )
+    );
+
+    // Without a config file, environment variables take precedence over
+    // the built in defaults
+    assert_eq!(
+        "count: 4\nmean: 2.5\n",
+        run_command(
+            st().env("ST_PRECISION", "1")
+                .env("ST_KEYS", "count,mean")
+                .env("ST_INTEGERS", "false"),
+            input
+        )
+    );
+}
+
+#[test]
+fn test_config_file_subcommand_options() {
+    let path = write_config("config-subcommand", "precision = 1\nbuckets = 2\n");
+    let xdg = path.parent().unwrap().parent().unwrap();
+    let input = "1\n2\n3\n4\n";
+
+    // Options only apply to the subcommands that have them
+    assert_eq!(
+        "1.0..2.5: 2\n2.5..4.0: 2\n",
+        run_command(st().env("XDG_CONFIG_HOME", xdg).arg("hist"), input)
+    );
+    assert_eq!(
+        "1.0..2.0: 1\n2.0..3.0: 1\n3.0..4.0: 2\n",
+        run_command(
+            st().env("XDG_CONFIG_HOME", xdg)
+                .env("ST_BUCKETS", "4")
+                .args(["hist", "--buckets", "3"]),
+            input
+        )
+    );
+    assert!(run_command(st().env("XDG_CONFIG_HOME", xdg), input).contains("mean: 2.5\n"));
+}
+
+#[test]
+fn test_env_var_invalid() {
+    for (key, value) in [("ST_PRECISION", "two"), ("ST_INTEGERS", "yes")] {
+        let output = st().env(key, value).stdin(Stdio::null()).output().unwrap();
+        let err = String::from_utf8(output.stderr).unwrap();
+
+        assert_eq!(Some(64), output.status.code(), "{}", err);
+        assert!(err.contains(key), "{}", err);
+    }
+}
+
+#[test]
+fn test_config_file_invalid_option() {
+    for (contents, message) in [
+        ("precison = 2\n", "Invalid option 'precison'"),
+        ("precision = \"two\"\n", "Invalid option 'precision'"),
+        ("integers = 1\n", "Invalid option 'integers'"),
+        ("precision = \n", "line 1"),
+    ] {
+        let path = write_config("config-invalid", contents);
+        let output = st().arg("--config").arg(&path).stdin(Stdio::null()).output().unwrap();
+        let err = String::from_utf8(output.stderr).unwrap();
+
+        assert_eq!(Some(78), output.status.code(), "{}", err);
+        assert!(err.contains(message), "{}", err);
+    }
+}
+
+#[test]
+fn test_output_same_as_library_run() {
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/values-small.log");
+    let out = run_st(&["-p", "90,99", fixture], "");
+
+    let config = StaccatoConfig::new().with_percentiles("90,99".parse::<Percentiles>().unwrap());
+    let bundle = run(&config, &mut File::open(fixture).unwrap()).unwrap();
+    assert_eq!(StatisticsFormatter::new(&bundle).to_string(), out);
+}
+
+/// Values from 1 to 101 in an order that's far from sorted, so a median
+/// taken from the middle of the input as given would be wrong.
+fn shuffled_input() -> String {
+    (0..101).map(|i| format!("{}\n", (i * 37) % 101 + 1)).collect()
+}
+
+#[test]
+fn test_median_of_shuffled_input_without_percentiles() {
+    let input = shuffled_input();
+    assert_ne!("51", input.lines().nth(50).unwrap());
+
+    for args in [&[][..], &["--keys", "median"], &["--integers"]] {
+        let out = run_st(args, &input);
+        assert!(out.contains("median: 51.00000\n"), "{:?}: {}", args, out);
+    }
+}
+
+#[test]
+fn test_percentile_without_enough_values_omitted() {
+    let out = run_st(&["-p", "99"], "5\n");
+    assert!(out.contains("count: 1\n"), "{}", out);
+    assert!(!out.contains("_99"), "{}", out);
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mmap_waits_for_exclusive_lock() {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mmap-locked.log");
+    fs::write(&path, "1\n2\n3\n4\n").unwrap();
+    let writer = File::open(&path).unwrap();
+    writer.lock().unwrap();
+
+    let child = st()
+        .args(["--mmap", "--keys", "count,sum"])
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(std::time::Duration::from_millis(200));
+
+    // The file is only mapped once the writer holding the exclusive lock
+    // is done truncating it.
+    fs::write(&path, "1\n2\n").unwrap();
+    writer.unlock().unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!("count: 2\nsum: 3.00000\n", String::from_utf8(output.stdout).unwrap());
+}
@@ -0,0 +1,83 @@
+# Tests for the Python bindings built with the `python` feature. These run
+# with `cargo test --features python` or with pytest after `maturin develop`.
+
+import math
+
+import staccato
+
+
+def assert_raises_value_error(func, *args, **kwargs):
+    try:
+        func(*args, **kwargs)
+    except ValueError:
+        return
+    raise AssertionError("ValueError not raised")
+
+
+def test_stats_global():
+    stats = staccato.stats([1.0, 2.0, 3.0, 4.0])
+    assert stats["global"] == {
+        "count": 4,
+        "sum": 10.0,
+        "mean": 2.5,
+        "upper": 4.0,
+        "lower": 1.0,
+        "median": 2.5,
+        "stddev": math.sqrt(1.25),
+    }
+    assert isinstance(stats["global"]["count"], int)
+    assert stats["quantiles"] == []
+    assert stats["percentiles"] == []
+    assert "sample_count" not in stats
+
+
+def test_stats_percentiles():
+    stats = staccato.stats([4.0, 1.0, 3.0, 2.0], percentiles=[99, 50])
+    assert [p["percentile"] for p in stats["percentiles"]] == [50, 99]
+    assert stats["percentiles"][0]["count"] == 2
+    assert isinstance(stats["percentiles"][0]["count"], int)
+    assert isinstance(stats["percentiles"][0]["sum"], float)
+    assert stats["percentiles"][0]["sum"] == 3.0
+
+
+def test_stats_any_iterable():
+    expected = staccato.stats([1.0, 2.0, 3.0])
+    assert staccato.stats((1.0, 2.0, 3.0)) == expected
+    assert staccato.stats(float(i) for i in range(1, 4)) == expected
+    assert staccato.stats(range(1, 4)) == expected
+
+
+def test_stats_empty():
+    assert staccato.stats([]) is None
+
+
+def test_stats_bad_percentiles():
+    assert_raises_value_error(staccato.stats, [1.0], percentiles=[0])
+    assert_raises_value_error(staccato.stats, [1.0], percentiles=[100])
+    assert_raises_value_error(staccato.stats, [1.0], percentiles=[-1])
+    assert_raises_value_error(staccato.stats, [1.0], percentiles=[1000])
+
+
+def test_streaming_stats():
+    streaming = staccato.StreamingStats()
+    assert len(streaming) == 0
+    assert streaming.stats() is None
+
+    streaming.push(1.0)
+    streaming.extend(float(i) for i in range(2, 5))
+    assert len(streaming) == 4
+
+    stats = streaming.stats(quantiles=[50])
+    assert stats["global"]["count"] == 4
+    assert stats["global"]["sum"] == 10.0
+    assert stats["percentiles"] == []
+    assert [q["percent"] for q in stats["quantiles"]] == [50.0]
+
+
+def test_streaming_stats_merge():
+    a = staccato.StreamingStats()
+    a.extend([1.0, 2.0])
+    b = staccato.StreamingStats()
+    b.extend([3.0, 4.0])
+    a.merge(b)
+    assert a.stats()["global"]["sum"] == 10.0
@@ -0,0 +1,56 @@
+// Staccato - Statistics from the command line
+//
+// Copyright 2016-2017 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// Run with `wasm-pack test --node -- --no-default-features --features wasm`,
+// or headless in a browser with e.g. `--headless --firefox` instead of `--node`.
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use staccato::{compute_stats, get_values, SortingPolicy, StatisticsBundle};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+/// Output of `st -p 50,90,99 --format json benches/values-small.log` built
+/// for the native target.
+const NATIVE_VALUES_SMALL: &str = r#"{"global":{"count":100,"sum":29.154730999999998,"mean":0.29154731,"upper":3.59286,"lower":0.002877,"median":0.035653500000000005,"stddev":0.5824617098876063},"quantiles":[],"percentiles":[{"percentile":50,"count":50,"sum":0.631346,"mean":0.01262692,"upper":0.035551,"lower":0.002877,"median":0.00929,"stddev":0.0073957041310209306},{"percentile":90,"count":90,"sum":11.581976000000001,"mean":0.12868862222222224,"upper":1.043369,"lower":0.002877,"median":0.025363,"stddev":0.22947003080632836},{"percentile":99,"count":99,"sum":25.561871,"mean":0.25820071717171716,"upper":2.461055,"lower":0.002877,"median":0.035551,"stddev":0.48113304131746637}]}"#;
+
+fn fixture_values() -> Vec<f64> {
+    let fixture = include_str!("../benches/values-small.log");
+    get_values(&mut fixture.as_bytes(), SortingPolicy::Unsorted).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn test_compute_stats_same_as_native() {
+    let res = compute_stats(&fixture_values(), &[90, 50, 99]).unwrap();
+    let bundle: StatisticsBundle = serde_wasm_bindgen::from_value(res).unwrap();
+    let native: StatisticsBundle = serde_json::from_str(NATIVE_VALUES_SMALL).unwrap();
+
+    assert!(
+        bundle.approx_eq(&native, 1e-12),
+        "{:?}",
+        bundle.differences(&native, 1e-12)
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_compute_stats_empty() {
+    assert!(compute_stats(&[], &[50]).unwrap().is_null());
+}
+
+#[wasm_bindgen_test]
+fn test_compute_stats_invalid_percentile() {
+    assert!(compute_stats(&[1.0], &[100]).is_err());
+}